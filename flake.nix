@@ -29,6 +29,8 @@
           ra-bridge = craneLib.buildPackage {
             inherit src;
             strictDeps = true;
+            # No .git in the sandbox, so build.rs takes the hash from here
+            RA_BRIDGE_GIT_HASH = self.shortRev or self.dirtyShortRev or "unknown";
           };
         in
         {
@@ -1,36 +1,403 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use anyhow::Result;
-use tokio::sync::{broadcast, mpsc, watch, RwLock};
-use tracing::{info, warn};
+use serde::Serialize;
+use tokio::sync::{broadcast, mpsc, watch};
+use tracing::{debug, info, warn};
 
+use crate::config::LevelLimits;
 use crate::id_map::IdMap;
-use crate::leap_client::{LeapEvent, LeapRequest};
+use crate::leap_client::{LeapEvent, LeapHeader, LeapRequest};
 use crate::ra2_protocol::{Ra2Command, Ra2Event};
-use crate::savant_client::{SavantEvent, SavantRequest};
+use crate::savant_client::{SavantEvent, SavantHostMetrics, SavantMetrics, SavantRequest};
 use crate::savant_id_map::SavantIdMap;
+use crate::state::{BackendHealth, BackendStatus, ZoneLevels};
 use crate::{savant_translator, translator};
 
 /// Handles returned from `start()` to control the bridge externally.
 pub struct BridgeHandle {
     pub leap_req_tx: Option<mpsc::Sender<LeapRequest>>,
-    pub savant_req_tx: Option<mpsc::Sender<SavantRequest>>,
+    /// Every message the processor sends, for raw passthrough responses.
+    pub leap_events: Option<broadcast::Sender<LeapEvent>>,
+    /// One per `[[savant]]` host, in config order; empty without Savant.
+    pub savant_req_tx: Vec<mpsc::Sender<SavantRequest>>,
+    /// Every message each Savant host sends, for raw passthrough captures.
+    pub savant_events: Vec<broadcast::Sender<SavantEvent>>,
     pub shutdown_tx: mpsc::Sender<()>,
+    /// Publish a rebuilt `BridgeMaps` here to apply zone edits live.
+    pub maps_tx: watch::Sender<Arc<BridgeMaps>>,
+}
+
+/// Where an RA2 command came from, so the logs can say who sent it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandSource {
+    /// A telnet client, by peer address (or socket path).
+    Telnet(String),
+    Mqtt,
+    /// A set-level from the web UI or API.
+    Web,
+}
+
+impl std::fmt::Display for CommandSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandSource::Telnet(peer) => write!(f, "telnet {}", peer),
+            CommandSource::Mqtt => write!(f, "mqtt"),
+            CommandSource::Web => write!(f, "web"),
+        }
+    }
+}
+
+/// An RA2 command on its way into the bridge loop.
+#[derive(Debug)]
+pub struct BridgeCommand {
+    pub source: CommandSource,
+    pub cmd: Ra2Command,
+}
+
+/// Commands waiting for the bridge loop before new ones are dropped.
+pub const COMMAND_QUEUE_CAPACITY: usize = 256;
+
+/// Command intake counters, kept across bridge restarts.
+#[derive(Debug, Default)]
+pub struct CommandMetrics {
+    dropped: AtomicU64,
+    peak_depth: AtomicUsize,
+    /// Set while the queue is full, so a storm logs once rather than per
+    /// dropped command.
+    saturated: AtomicBool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandMetricsSnapshot {
+    pub capacity: usize,
+    /// Most commands ever waiting at once.
+    pub peak_depth: usize,
+    pub dropped: u64,
+}
+
+impl CommandMetrics {
+    pub fn snapshot(&self) -> CommandMetricsSnapshot {
+        CommandMetricsSnapshot {
+            capacity: COMMAND_QUEUE_CAPACITY,
+            peak_depth: self.peak_depth.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Sending side of the telnet/MQTT → bridge command queue.
+///
+/// Backpressure policy: `submit` never waits. If the bridge loop falls
+/// `COMMAND_QUEUE_CAPACITY` commands behind (e.g. a backend that has stopped
+/// accepting requests), further commands are dropped and counted instead of
+/// blocking the submitting client, so one slow backend can't stall command
+/// intake for every telnet client and MQTT. Controllers resend on the next
+/// button press, and a dropped set-level is less harmful than a frozen
+/// session.
+#[derive(Clone)]
+pub struct CommandQueue {
+    tx: mpsc::Sender<BridgeCommand>,
+    metrics: Arc<CommandMetrics>,
+}
+
+impl CommandQueue {
+    pub fn new(capacity: usize, metrics: Arc<CommandMetrics>) -> (Self, mpsc::Receiver<BridgeCommand>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (Self { tx, metrics }, rx)
+    }
+
+    /// Queue `cmd` without waiting. A full queue drops it (see above);
+    /// the only error is the bridge having stopped.
+    pub fn submit(&self, cmd: BridgeCommand) -> Result<()> {
+        match self.tx.try_send(cmd) {
+            Ok(()) => {
+                let depth = self.tx.max_capacity() - self.tx.capacity();
+                self.metrics.peak_depth.fetch_max(depth, Ordering::Relaxed);
+                if depth <= self.tx.max_capacity() / 2 {
+                    self.metrics.saturated.store(false, Ordering::Relaxed);
+                }
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Full(dropped)) => {
+                self.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+                if !self.metrics.saturated.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        "Command queue full ({} waiting), dropping commands until the bridge catches up; first dropped: {:?} from {}",
+                        self.tx.max_capacity(),
+                        dropped.cmd,
+                        dropped.source
+                    );
+                }
+                Ok(())
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => anyhow::bail!("Bridge stopped"),
+        }
+    }
+
+    /// Resolves once the bridge loop has stopped taking commands.
+    pub async fn closed(&self) {
+        self.tx.closed().await
+    }
+}
+
+/// The id maps the running bridge routes by, derived from the zone config.
+pub struct BridgeMaps {
+    pub leap: IdMap,
+    pub savant: SavantIdMap,
+    pub known_ids: HashSet<u32>,
+    /// `[bridge] default_fade_secs`, for set-level commands without a fade.
+    pub default_fade: Option<f64>,
+    /// `[bridge] level_deadband`, for dropping jittery level reports.
+    pub level_deadband: f64,
+    /// `[bridge] skip_redundant_sets`, for dropping sets to the current level.
+    pub skip_redundant_sets: bool,
+    /// Per-zone `min_level` / `max_level`, for zones that set them.
+    pub limits: HashMap<u32, LevelLimits>,
+    /// DbXmlInfo.xml for the current zones, served to telnet `GETDBXML`.
+    pub db_xml: String,
+    /// Zone names by ra2_id, for MQTT discovery.
+    pub zone_names: BTreeMap<u32, String>,
+}
+
+impl BridgeMaps {
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self {
+            leap: IdMap::from_zones(&config.zones, &config.level_curve)
+                .with_buttons(&config.buttons),
+            savant: SavantIdMap::from_zones(&config.savant_zones, &config.level_curve),
+            known_ids: config.ra2_ids(),
+            default_fade: config.bridge.default_fade(),
+            level_deadband: config.bridge.level_deadband,
+            skip_redundant_sets: config.bridge.skip_redundant_sets,
+            limits: config
+                .zones
+                .iter()
+                .map(|z| (z.ra2_id, z.limits()))
+                .chain(config.savant_zones.iter().map(|z| (z.ra2_id, z.limits())))
+                .filter(|(_, l)| *l != LevelLimits::default())
+                .collect(),
+            db_xml: crate::web::xml_export::generate_xml(
+                &config.web.project_name,
+                config.web.area_separator,
+                &config.zones,
+                &config.savant_zones,
+                &config.keypads,
+            ),
+            zone_names: config.zone_names(),
+        }
+    }
+
+    /// Clamp a set-level command into its zone's limits.
+    pub fn clamp(&self, cmd: Ra2Command) -> Ra2Command {
+        match cmd {
+            Ra2Command::SetOutput { id, level, fade } => Ra2Command::SetOutput {
+                id,
+                level: self.limits.get(&id).map_or(level, |l| l.apply(level)),
+                fade,
+            },
+            cmd => cmd,
+        }
+    }
+}
+
+/// Log the first event for `id` from `backend` when the other backend owns
+/// the same id too. `validate()` rejects such configs, so this only fires for
+/// one that slipped past it; both forwarders then write the same
+/// `zone_levels` entry and whichever event lands last wins. Returns whether
+/// it logged.
+fn warn_shared_id(warned: &mut HashSet<u32>, backend: &str, id: u32) -> bool {
+    if !warned.insert(id) {
+        return false;
+    }
+    warn!(
+        "{} event for ra2_id {}, which the other backend also owns; levels for it will flip between backends (duplicate id in config?)",
+        backend, id
+    );
+    true
+}
+
+/// The request for a Savant-owned command, and the channel of the host that
+/// owns its zone.
+fn savant_route<'a>(
+    txs: &'a [mpsc::Sender<SavantRequest>],
+    map: &SavantIdMap,
+    cmd: &Ra2Command,
+    default_fade: Option<f64>,
+) -> Option<(&'a mpsc::Sender<SavantRequest>, SavantRequest)> {
+    let tx = txs.get(map.host(cmd.id()?)?)?;
+    Some((tx, savant_translator::ra2_to_savant(cmd, map, default_fade)?))
+}
+
+/// Route one command from the telnet/MQTT/web queue to the backend that owns
+/// its zone. Shared by `start` and the CLI `run` loop.
+async fn handle_command(
+    BridgeCommand { source, cmd }: BridgeCommand,
+    maps: &BridgeMaps,
+    zone_levels: &ZoneLevels,
+    leap_req_tx: Option<&mpsc::Sender<LeapRequest>>,
+    savant_req_tx: &[mpsc::Sender<SavantRequest>],
+    ra2_event_tx: &broadcast::Sender<Ra2Event>,
+) {
+    let cmd = maps.clamp(cmd);
+    let id = cmd.id();
+
+    if let Some(id) = id {
+        // Update zone level cache for web UI (don't echo to
+        // telnet — pylutron updates its own cache immediately,
+        // and the Savant set-echo + polls provide the real
+        // hardware confirmation. Echoing here caused HA to
+        // think the light was already at the target level,
+        // swallowing subsequent commands.)
+        if let Ra2Command::SetOutput { id: set_id, level, .. } = &cmd {
+            if maps.skip_redundant_sets && zone_levels.is_at(*set_id, *level).await {
+                debug!("bridge: ra2_id={} already at {} (from {}), not sent", id, level, source);
+                return;
+            }
+            zone_levels.set_level(*set_id, *level).await;
+        }
+
+        // Route to the correct backend based on ra2_id ownership
+        if maps.leap.ra2_to_leap(id).is_some() {
+            if let Some(tx) = leap_req_tx {
+                if let Some(req) = translator::ra2_to_leap(&cmd, &maps.leap, maps.default_fade) {
+                    info!("bridge → LEAP: ra2_id={} {:?} (from {})", id, cmd, source);
+                    if let Err(e) = tx.send(req).await {
+                        warn!("Failed to send LEAP request: {}", e);
+                    }
+                }
+            }
+        } else if maps.savant.ra2_to_savant(id).is_some() {
+            if let Some((tx, req)) = savant_route(savant_req_tx, &maps.savant, &cmd, maps.default_fade) {
+                info!("bridge → Savant: ra2_id={} → {:?} (from {})", id, req, source);
+                if let Err(e) = tx.send(req).await {
+                    warn!("Failed to send Savant request: {}", e);
+                }
+            }
+        } else {
+            warn!("bridge: no backend for ra2_id {} (from {})", id, source);
+        }
+    } else if let Ra2Command::Monitoring { mon_type, enable } = &cmd {
+        info!("bridge: monitoring type {} {} by {}",
+            mon_type, if *enable { "enabled" } else { "disabled" }, source);
+        // Sync current state to HA on monitoring enable
+        if *mon_type == 5 && *enable {
+            let levels = zone_levels.snapshot().await;
+            for (id, level) in &levels {
+                let _ = ra2_event_tx.send(Ra2Event::OutputLevel {
+                    id: *id, level: *level,
+                });
+            }
+            info!("bridge: synced {} zone levels to telnet", levels.len());
+        }
+    }
+}
+
+/// Fold the per-host statuses of several Savant hosts into `savant`, the one
+/// status the UI and notifications watch: any host down makes Savant down.
+fn spawn_savant_health(hosts: &[watch::Sender<BackendStatus>], savant: watch::Sender<BackendStatus>) {
+    let mut rxs: Vec<_> = hosts.iter().map(|tx| tx.subscribe()).collect();
+    tokio::spawn(async move {
+        let mut statuses: Vec<_> = rxs.iter_mut().map(|rx| rx.borrow_and_update().clone()).collect();
+        // A host whose client has exited keeps its last status
+        let mut open = vec![true; rxs.len()];
+        while open.contains(&true) {
+            savant.send_replace(combined_status(statuses.clone()));
+            let changed = rxs
+                .iter_mut()
+                .enumerate()
+                .filter(|(i, _)| open[*i])
+                .map(|(i, rx)| Box::pin(async move { (i, rx.changed().await) }));
+            let ((host, result), _, _) = futures_util::future::select_all(changed).await;
+            match result {
+                Ok(()) => statuses[host] = rxs[host].borrow_and_update().clone(),
+                Err(_) => open[host] = false,
+            }
+        }
+        savant.send_replace(combined_status(statuses));
+    });
+}
+
+/// The worst of several backend statuses.
+fn combined_status(statuses: Vec<BackendStatus>) -> BackendStatus {
+    let rank = |s: &BackendStatus| match s {
+        BackendStatus::Failed { .. } => 0,
+        BackendStatus::Error { .. } => 1,
+        BackendStatus::Stopped => 2,
+        BackendStatus::Connecting => 3,
+        BackendStatus::NotConfigured => 4,
+        BackendStatus::Connected => 5,
+    };
+    statuses
+        .into_iter()
+        .min_by_key(rank)
+        .unwrap_or(BackendStatus::NotConfigured)
+}
+
+/// Whether moving a running bridge from `old` to `new` needs a restart:
+/// zone/id edits are applied live through `BridgeMaps`, but the backend
+/// clients and telnet listener keep the settings they were started with.
+pub fn restart_required(old: &crate::config::Config, new: &crate::config::Config) -> bool {
+    let savant_key = |c: &crate::config::Config| {
+        c.savant
+            .iter()
+            .map(|s| (s.host.clone(), s.port, s.load_key(), s.device.clone()))
+            .collect::<Vec<_>>()
+    };
+    let savant_loads = |c: &crate::config::Config| {
+        c.savant_zones
+            .iter()
+            .map(|z| (z.host, z.address.clone(), z.load_offset))
+            .collect::<HashSet<_>>()
+    };
+
+    old.telnet.port != new.telnet.port
+        || old.telnet.unix_socket != new.telnet.unix_socket
+        || old.telnet.level_decimals != new.telnet.level_decimals
+        || old.telnet.banner != new.telnet.banner
+        || old.has_leap() != new.has_leap()
+        || old.has_savant() != new.has_savant()
+        || old.processor.host != new.processor.host
+        || old.processor.leap_port != new.processor.leap_port
+        || old.processor.ca_files != new.processor.ca_files
+        || old.processor.ping_interval_secs != new.processor.ping_interval_secs
+        || old.processor.use_polling != new.processor.use_polling
+        || old.processor.poll_interval_secs != new.processor.poll_interval_secs
+        || old.bridge.max_reconnect_attempts != new.bridge.max_reconnect_attempts
+        || old.led_subscriptions() != new.led_subscriptions()
+        || savant_key(old) != savant_key(new)
+        || savant_loads(old) != savant_loads(new)
+        || old.mqtt != new.mqtt
+        || old.logging != new.logging
 }
 
 /// Start the bridge as a background task. Returns a handle for external control.
+/// Refuses an invalid config: duplicate ids would silently collapse in the
+/// id maps and route commands to the wrong zone.
 pub async fn start(
     config: crate::config::Config,
     certs_dir: std::path::PathBuf,
-    zone_levels: Arc<RwLock<HashMap<u32, f64>>>,
+    zone_levels: ZoneLevels,
     bridge_status_tx: watch::Sender<crate::state::BridgeStatus>,
+    savant_metrics: Arc<SavantHostMetrics>,
+    command_metrics: Arc<CommandMetrics>,
+    backend_health: Arc<BackendHealth>,
 ) -> Result<BridgeHandle> {
-    let leap_id_map = Arc::new(IdMap::from_zones(&config.zones));
-    let savant_id_map = Arc::new(SavantIdMap::from_zones(&config.savant_zones));
+    config
+        .validate()
+        .map_err(|e| anyhow::anyhow!("Invalid config: {}", e))?;
+    crate::protocol_log::set_enabled(config.logging.trace_protocol);
+
+    // Bound up front so a port conflict fails the start rather than leaving
+    // a Running bridge with no telnet server
+    let telnet_listener = crate::telnet_server::bind(&config.telnet).await?;
+
+    let (maps_tx, maps_rx) = watch::channel(Arc::new(BridgeMaps::from_config(&config)));
 
     // Channels: telnet → bridge (RA2 commands)
-    let (ra2_cmd_tx, mut ra2_cmd_rx) = mpsc::channel::<Ra2Command>(256);
+    let (ra2_cmd_tx, mut ra2_cmd_rx) = CommandQueue::new(COMMAND_QUEUE_CAPACITY, command_metrics);
 
     // Channels: bridge → telnet (RA2 events, broadcast to all clients)
     let (ra2_event_tx, _) = broadcast::channel::<Ra2Event>(256);
@@ -38,28 +405,50 @@ pub async fn start(
     // Shutdown signal
     let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
 
+    if let Some(mqtt) = &config.mqtt {
+        crate::mqtt::spawn(
+            mqtt.clone(),
+            config.web.project_name.clone(),
+            maps_rx.clone(),
+            zone_levels.clone(),
+            ra2_cmd_tx.clone(),
+        );
+    }
+
     // Start telnet server
     let telnet_event_tx = ra2_event_tx.clone();
-    let telnet_port = config.telnet.port;
+    let telnet = config.telnet.clone();
+    let telnet_maps = maps_rx.clone();
     tokio::spawn(async move {
         if let Err(e) =
-            crate::telnet_server::run(telnet_port, ra2_cmd_tx, telnet_event_tx).await
+            crate::telnet_server::serve(telnet_listener, telnet, ra2_cmd_tx, telnet_event_tx, telnet_maps).await
         {
             tracing::error!("Telnet server error: {}", e);
         }
     });
 
     // Conditionally start LEAP client
-    let leap_req_tx = if config.has_leap() {
+    let (leap_req_tx, leap_events) = if config.has_leap() {
         let (tx, leap_req_rx) = mpsc::channel::<LeapRequest>(256);
         let (leap_event_tx, mut leap_event_rx) = broadcast::channel::<LeapEvent>(256);
+        let events = leap_event_tx.clone();
 
-        let leap_host = config.processor.host.clone();
-        let leap_port = config.processor.leap_port;
+        let processor = config.processor.clone();
+        let led_subscriptions = config.led_subscriptions();
+        let max_reconnect_attempts = config.bridge.max_reconnect_attempts;
+        let leap_status_tx = backend_health.leap.clone();
         tokio::spawn(async move {
             if let Err(e) =
-                crate::leap_client::run(leap_host, leap_port, certs_dir, leap_req_rx, leap_event_tx)
-                    .await
+                crate::leap_client::run(
+                    processor,
+                    certs_dir,
+                    led_subscriptions,
+                    leap_req_rx,
+                    leap_event_tx,
+                    leap_status_tx,
+                    max_reconnect_attempts,
+                )
+                .await
             {
                 tracing::error!("LEAP client error: {}", e);
             }
@@ -68,15 +457,23 @@ pub async fn start(
         // LEAP event forwarder
         let ra2_event_tx_leap = ra2_event_tx.clone();
         let zone_levels_leap = zone_levels.clone();
-        let leap_id_map_clone = leap_id_map.clone();
+        let leap_maps = maps_rx.clone();
         tokio::spawn(async move {
+            let mut shared_ids = HashSet::new();
             loop {
                 match leap_event_rx.recv().await {
                     Ok(event) => {
-                        if let Some(ra2_event) = translator::leap_to_ra2(&event, &leap_id_map_clone)
+                        let maps = leap_maps.borrow().clone();
+                        if let Some(ra2_event) = translator::leap_to_ra2(&event, &maps.leap)
                         {
-                            let Ra2Event::OutputLevel { id, level } = &ra2_event;
-                            zone_levels_leap.write().await.insert(*id, *level);
+                            if let Ra2Event::OutputLevel { id, level } = &ra2_event {
+                                if maps.savant.ra2_to_savant(*id).is_some() {
+                                    warn_shared_id(&mut shared_ids, "LEAP", *id);
+                                }
+                                if !zone_levels_leap.update(*id, *level, maps.level_deadband).await {
+                                    continue;
+                                }
+                            }
                             let _ = ra2_event_tx_leap.send(ra2_event);
                         }
                     }
@@ -88,26 +485,90 @@ pub async fn start(
             }
         });
 
-        info!("LEAP backend started ({} zones)", config.zones.len());
-        Some(tx)
+        // The processor only pushes changes, so anything that moved while
+        // the link was down would stay stale for telnet clients. Re-read
+        // every zone and LED after each reconnect; the responses flow out
+        // as ordinary ~OUTPUT / ~DEVICE events.
+        let mut leap_status_rx = backend_health.leap.subscribe();
+        let resync_tx = tx.clone();
+        let resync_maps = maps_rx.clone();
+        tokio::spawn(async move {
+            let mut connected_before = false;
+            while leap_status_rx.changed().await.is_ok() && !resync_tx.is_closed() {
+                if *leap_status_rx.borrow_and_update() != BackendStatus::Connected {
+                    continue;
+                }
+                if connected_before {
+                    let urls = resync_maps.borrow().leap.status_urls();
+                    info!("LEAP reconnected, re-reading {} statuses", urls.len());
+                    for url in urls {
+                        let req = LeapRequest {
+                            communique_type: "ReadRequest".to_string(),
+                            header: LeapHeader {
+                                url,
+                                client_tag: None,
+                                extra: Default::default(),
+                            },
+                            body: None,
+                        };
+                        if resync_tx.send(req).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                connected_before = true;
+            }
+        });
+
+        info!(
+            "LEAP backend started ({} zones, {} keypad LEDs)",
+            config.zones.len(),
+            config.buttons.len()
+        );
+        (Some(tx), Some(events))
     } else {
         info!("LEAP backend skipped (no zones configured)");
-        None
+        let _ = backend_health.leap.send(BackendStatus::NotConfigured);
+        (None, None)
     };
 
-    // Conditionally start Savant client
-    let savant_req_tx = if config.has_savant() {
-        let savant_cfg = config.savant.as_ref().unwrap();
+    // Conditionally start one Savant client per host
+    let mut savant_req_tx = Vec::new();
+    let mut savant_events = Vec::new();
+    let host_metrics = savant_metrics.hosts(if config.has_savant() { config.savant.len() } else { 0 });
+    let savant_statuses = if config.has_savant() && config.savant.len() > 1 {
+        let statuses: Vec<_> = config
+            .savant
+            .iter()
+            .map(|_| watch::channel(BackendStatus::NotConfigured).0)
+            .collect();
+        spawn_savant_health(&statuses, backend_health.savant.clone());
+        statuses
+    } else {
+        vec![backend_health.savant.clone()]
+    };
+    for (host, savant_cfg) in config.savant.iter().enumerate().filter(|_| config.has_savant()) {
         let (tx, savant_req_rx) = mpsc::channel::<SavantRequest>(256);
         let (savant_event_tx, mut savant_event_rx) = broadcast::channel::<SavantEvent>(256);
+        savant_events.push(savant_event_tx.clone());
 
-        let savant_host = savant_cfg.host.clone();
-        let savant_port = savant_cfg.port;
-        let savant_zones = config.savant_zones.clone();
+        let savant_cfg = savant_cfg.clone();
+        let savant_zones = config.savant_zones_for(host);
+        let max_reconnect_attempts = config.bridge.max_reconnect_attempts;
+        let savant_status_tx = savant_statuses[host].clone();
+        let savant_metrics = host_metrics[host].clone();
         tokio::spawn(async move {
             if let Err(e) =
-                crate::savant_client::run(savant_host, savant_port, savant_zones, savant_req_rx, savant_event_tx)
-                    .await
+                crate::savant_client::run(
+                    savant_cfg,
+                    savant_zones,
+                    savant_req_rx,
+                    savant_event_tx,
+                    savant_metrics,
+                    savant_status_tx,
+                    max_reconnect_attempts,
+                )
+                .await
             {
                 tracing::error!("Savant client error: {}", e);
             }
@@ -116,16 +577,24 @@ pub async fn start(
         // Savant event forwarder
         let ra2_event_tx_savant = ra2_event_tx.clone();
         let zone_levels_savant = zone_levels.clone();
-        let savant_id_map_clone = savant_id_map.clone();
+        let savant_maps = maps_rx.clone();
         tokio::spawn(async move {
+            let mut shared_ids = HashSet::new();
             loop {
                 match savant_event_rx.recv().await {
                     Ok(event) => {
+                        let maps = savant_maps.borrow().clone();
                         if let Some(ra2_event) =
-                            savant_translator::savant_to_ra2(&event, &savant_id_map_clone)
+                            savant_translator::savant_to_ra2(&event, &maps.savant, host)
                         {
-                            let Ra2Event::OutputLevel { id, level } = &ra2_event;
-                            zone_levels_savant.write().await.insert(*id, *level);
+                            if let Ra2Event::OutputLevel { id, level } = &ra2_event {
+                                if maps.leap.ra2_to_leap(*id).is_some() {
+                                    warn_shared_id(&mut shared_ids, "Savant", *id);
+                                }
+                                if !zone_levels_savant.update(*id, *level, maps.level_deadband).await {
+                                    continue;
+                                }
+                            }
                             let _ = ra2_event_tx_savant.send(ra2_event);
                         }
                     }
@@ -138,14 +607,16 @@ pub async fn start(
         });
 
         info!(
-            "Savant backend started ({} zones)",
-            config.savant_zones.len()
+            "Savant backend started for {} ({} zones)",
+            config.savant[host].host,
+            config.savant_zones.iter().filter(|z| z.host == host).count()
         );
-        Some(tx)
-    } else {
+        savant_req_tx.push(tx);
+    }
+    if savant_req_tx.is_empty() {
         info!("Savant backend skipped (not configured)");
-        None
-    };
+        let _ = backend_health.savant.send(BackendStatus::NotConfigured);
+    }
 
     let _ = bridge_status_tx.send(crate::state::BridgeStatus::Running);
     info!("Bridge status: Running");
@@ -158,62 +629,9 @@ pub async fn start(
     tokio::spawn(async move {
         loop {
             tokio::select! {
-                Some(cmd) = ra2_cmd_rx.recv() => {
-                    let id = match &cmd {
-                        Ra2Command::SetOutput { id, .. } => Some(*id),
-                        Ra2Command::QueryOutput { id } => Some(*id),
-                        Ra2Command::Monitoring { .. } => None,
-                    };
-
-                    if let Some(id) = id {
-                        // Update zone level cache for web UI (don't echo to
-                        // telnet — pylutron updates its own cache immediately,
-                        // and the Savant set-echo + polls provide the real
-                        // hardware confirmation. Echoing here caused HA to
-                        // think the light was already at the target level,
-                        // swallowing subsequent commands.)
-                        if let Ra2Command::SetOutput { id: set_id, level, .. } = &cmd {
-                            zone_levels.write().await.insert(*set_id, *level);
-                        }
-
-                        // Route to the correct backend based on ra2_id ownership
-                        if leap_id_map.ra2_to_leap(id).is_some() {
-                            if let Some(ref tx) = leap_req_tx {
-                                if let Some(req) = translator::ra2_to_leap(&cmd, &leap_id_map) {
-                                    if let Err(e) = tx.send(req).await {
-                                        warn!("Failed to send LEAP request: {}", e);
-                                    }
-                                }
-                            }
-                        } else if savant_id_map.ra2_to_savant(id).is_some() {
-                            if let Some(ref tx) = savant_req_tx {
-                                if let Some(req) = savant_translator::ra2_to_savant(&cmd, &savant_id_map) {
-                                    info!("bridge → Savant: ra2_id={} → {:?}", id, req);
-                                    if let Err(e) = tx.send(req).await {
-                                        warn!("Failed to send Savant request: {}", e);
-                                    }
-                                }
-                            }
-                        } else {
-                            warn!("bridge: no backend for ra2_id {}", id);
-                        }
-                    } else {
-                        // Monitoring commands
-                        if let Ra2Command::Monitoring { mon_type, enable } = &cmd {
-                            info!("bridge: monitoring type {} {}",
-                                mon_type, if *enable { "enabled" } else { "disabled" });
-                            // Sync current state to HA on monitoring enable
-                            if *mon_type == 5 && *enable {
-                                let levels = zone_levels.read().await.clone();
-                                for (id, level) in &levels {
-                                    let _ = ra2_event_tx.send(Ra2Event::OutputLevel {
-                                        id: *id, level: *level,
-                                    });
-                                }
-                                info!("bridge: synced {} zone levels to telnet", levels.len());
-                            }
-                        }
-                    }
+                Some(command) = ra2_cmd_rx.recv() => {
+                    let maps = maps_rx.borrow().clone();
+                    handle_command(command, &maps, &zone_levels, leap_req_tx.as_ref(), &savant_req_tx, &ra2_event_tx).await;
                 }
                 _ = shutdown_rx.recv() => {
                     info!("Bridge shutting down");
@@ -226,31 +644,48 @@ pub async fn start(
 
     Ok(BridgeHandle {
         leap_req_tx: handle_leap_req_tx,
+        leap_events,
         savant_req_tx: handle_savant_req_tx,
+        savant_events,
         shutdown_tx,
+        maps_tx,
     })
 }
 
 /// Run the bridge (blocking). Used by the `run` CLI command for backward compatibility.
+/// Refuses an invalid config, as `start` does.
 pub async fn run(
     config: crate::config::Config,
     certs_dir: std::path::PathBuf,
 ) -> Result<()> {
-    let leap_id_map = Arc::new(IdMap::from_zones(&config.zones));
-    let savant_id_map = Arc::new(SavantIdMap::from_zones(&config.savant_zones));
+    config
+        .validate()
+        .map_err(|e| anyhow::anyhow!("Invalid config: {}", e))?;
+    let leap_id_map = Arc::new(
+        IdMap::from_zones(&config.zones, &config.level_curve).with_buttons(&config.buttons),
+    );
+    let savant_id_map = Arc::new(SavantIdMap::from_zones(&config.savant_zones, &config.level_curve));
+    crate::protocol_log::set_enabled(config.logging.trace_protocol);
+    let level_deadband = config.bridge.level_deadband;
+    // Only the deadband, skip_redundant_sets and the monitoring resync read
+    // this; there's no web UI to serve levels to
+    let zone_levels = ZoneLevels::default();
 
     // Channels: telnet → bridge (RA2 commands)
-    let (ra2_cmd_tx, mut ra2_cmd_rx) = mpsc::channel::<Ra2Command>(256);
+    let (ra2_cmd_tx, mut ra2_cmd_rx) = CommandQueue::new(COMMAND_QUEUE_CAPACITY, Arc::default());
 
     // Channels: bridge → telnet (RA2 events, broadcast to all clients)
     let (ra2_event_tx, _) = broadcast::channel::<Ra2Event>(256);
 
     // Start telnet server
     let telnet_event_tx = ra2_event_tx.clone();
-    let telnet_port = config.telnet.port;
+    let telnet = config.telnet.clone();
+    let (_maps_tx, telnet_maps) = watch::channel(Arc::new(BridgeMaps::from_config(&config)));
+    let telnet_maps_clamp = telnet_maps.clone();
+    let telnet_listener = crate::telnet_server::bind(&telnet).await?;
     tokio::spawn(async move {
         if let Err(e) =
-            crate::telnet_server::run(telnet_port, ra2_cmd_tx, telnet_event_tx).await
+            crate::telnet_server::serve(telnet_listener, telnet, ra2_cmd_tx, telnet_event_tx, telnet_maps).await
         {
             tracing::error!("Telnet server error: {}", e);
         }
@@ -261,12 +696,21 @@ pub async fn run(
         let (tx, leap_req_rx) = mpsc::channel::<LeapRequest>(256);
         let (leap_event_tx, mut leap_event_rx) = broadcast::channel::<LeapEvent>(256);
 
-        let leap_host = config.processor.host.clone();
-        let leap_port = config.processor.leap_port;
+        let processor = config.processor.clone();
+        let led_subscriptions = config.led_subscriptions();
+        let max_reconnect_attempts = config.bridge.max_reconnect_attempts;
         tokio::spawn(async move {
             if let Err(e) =
-                crate::leap_client::run(leap_host, leap_port, certs_dir, leap_req_rx, leap_event_tx)
-                    .await
+                crate::leap_client::run(
+                    processor,
+                    certs_dir,
+                    led_subscriptions,
+                    leap_req_rx,
+                    leap_event_tx,
+                    watch::channel(BackendStatus::NotConfigured).0,
+                    max_reconnect_attempts,
+                )
+                .await
             {
                 tracing::error!("LEAP client error: {}", e);
             }
@@ -275,11 +719,22 @@ pub async fn run(
         // LEAP event forwarder
         let ra2_event_tx_leap = ra2_event_tx.clone();
         let leap_id_map_clone = leap_id_map.clone();
+        let savant_id_map_other = savant_id_map.clone();
+        let zone_levels_leap = zone_levels.clone();
         tokio::spawn(async move {
+            let mut shared_ids = HashSet::new();
             loop {
                 match leap_event_rx.recv().await {
                     Ok(event) => {
                         if let Some(ra2_event) = translator::leap_to_ra2(&event, &leap_id_map_clone) {
+                            if let Ra2Event::OutputLevel { id, level } = &ra2_event {
+                                if savant_id_map_other.ra2_to_savant(*id).is_some() {
+                                    warn_shared_id(&mut shared_ids, "LEAP", *id);
+                                }
+                                if !zone_levels_leap.update(*id, *level, level_deadband).await {
+                                    continue;
+                                }
+                            }
                             let _ = ra2_event_tx_leap.send(ra2_event);
                         }
                     }
@@ -291,25 +746,38 @@ pub async fn run(
             }
         });
 
-        info!("LEAP backend started ({} zones)", config.zones.len());
+        info!(
+            "LEAP backend started ({} zones, {} keypad LEDs)",
+            config.zones.len(),
+            config.buttons.len()
+        );
         Some(tx)
     } else {
         None
     };
 
-    // Conditionally start Savant client
-    let savant_req_tx = if config.has_savant() {
-        let savant_cfg = config.savant.as_ref().unwrap();
+    // Conditionally start one Savant client per host
+    let mut savant_req_tx = Vec::new();
+    for (host, savant_cfg) in config.savant.iter().enumerate().filter(|_| config.has_savant()) {
         let (tx, savant_req_rx) = mpsc::channel::<SavantRequest>(256);
         let (savant_event_tx, mut savant_event_rx) = broadcast::channel::<SavantEvent>(256);
 
-        let savant_host = savant_cfg.host.clone();
-        let savant_port = savant_cfg.port;
-        let savant_zones = config.savant_zones.clone();
+        let savant_cfg = savant_cfg.clone();
+        let savant_zones = config.savant_zones_for(host);
+        let savant_metrics = Arc::new(SavantMetrics::default());
+        let max_reconnect_attempts = config.bridge.max_reconnect_attempts;
         tokio::spawn(async move {
             if let Err(e) =
-                crate::savant_client::run(savant_host, savant_port, savant_zones, savant_req_rx, savant_event_tx)
-                    .await
+                crate::savant_client::run(
+                    savant_cfg,
+                    savant_zones,
+                    savant_req_rx,
+                    savant_event_tx,
+                    savant_metrics,
+                    watch::channel(BackendStatus::NotConfigured).0,
+                    max_reconnect_attempts,
+                )
+                .await
             {
                 tracing::error!("Savant client error: {}", e);
             }
@@ -318,11 +786,22 @@ pub async fn run(
         // Savant event forwarder
         let ra2_event_tx_savant = ra2_event_tx.clone();
         let savant_id_map_clone = savant_id_map.clone();
+        let leap_id_map_other = leap_id_map.clone();
+        let zone_levels_savant = zone_levels.clone();
         tokio::spawn(async move {
+            let mut shared_ids = HashSet::new();
             loop {
                 match savant_event_rx.recv().await {
                     Ok(event) => {
-                        if let Some(ra2_event) = savant_translator::savant_to_ra2(&event, &savant_id_map_clone) {
+                        if let Some(ra2_event) = savant_translator::savant_to_ra2(&event, &savant_id_map_clone, host) {
+                            if let Ra2Event::OutputLevel { id, level } = &ra2_event {
+                                if leap_id_map_other.ra2_to_leap(*id).is_some() {
+                                    warn_shared_id(&mut shared_ids, "Savant", *id);
+                                }
+                                if !zone_levels_savant.update(*id, *level, level_deadband).await {
+                                    continue;
+                                }
+                            }
                             let _ = ra2_event_tx_savant.send(ra2_event);
                         }
                     }
@@ -335,53 +814,399 @@ pub async fn run(
         });
 
         info!(
-            "Savant backend started ({} zones)",
-            config.savant_zones.len()
+            "Savant backend started for {} ({} zones)",
+            config.savant[host].host,
+            config.savant_zones.iter().filter(|z| z.host == host).count()
         );
-        Some(tx)
-    } else {
-        None
-    };
+        savant_req_tx.push(tx);
+    }
 
     info!("Bridge running");
 
     // Translation loop
     loop {
         tokio::select! {
-            Some(cmd) = ra2_cmd_rx.recv() => {
-                let id = match &cmd {
-                    Ra2Command::SetOutput { id, .. } => Some(*id),
-                    Ra2Command::QueryOutput { id } => Some(*id),
-                    Ra2Command::Monitoring { .. } => None,
-                };
-
-                if let Some(id) = id {
-                    if leap_id_map.ra2_to_leap(id).is_some() {
-                        if let Some(ref tx) = leap_req_tx {
-                            if let Some(req) = translator::ra2_to_leap(&cmd, &leap_id_map) {
-                                if let Err(e) = tx.send(req).await {
-                                    warn!("Failed to send LEAP request: {}", e);
-                                }
-                            }
-                        }
-                    } else if savant_id_map.ra2_to_savant(id).is_some() {
-                        if let Some(ref tx) = savant_req_tx {
-                            if let Some(req) = savant_translator::ra2_to_savant(&cmd, &savant_id_map) {
-                                if let Err(e) = tx.send(req).await {
-                                    warn!("Failed to send Savant request: {}", e);
-                                }
-                            }
-                        }
-                    } else {
-                        warn!("No backend for ra2_id {}", id);
-                    }
-                } else {
-                    if let Ra2Command::Monitoring { mon_type, enable } = &cmd {
-                        info!("Monitoring type {} {}",
-                            mon_type, if *enable { "enabled" } else { "disabled" });
-                    }
-                }
+            Some(command) = ra2_cmd_rx.recv() => {
+                let maps = telnet_maps_clamp.borrow().clone();
+                handle_command(command, &maps, &zone_levels, leap_req_tx.as_ref(), &savant_req_tx, &ra2_event_tx).await;
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, ZoneMapping};
+
+    fn config(zones: &[(u32, &str)]) -> Config {
+        let mut config: Config = toml::from_str("[processor]\nhost = \"10.0.0.5\"").unwrap();
+        config.zones = zones
+            .iter()
+            .map(|(id, href)| ZoneMapping {
+                ra2_id: *id,
+                leap_href: href.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        config
+    }
+
+    #[test]
+    fn set_levels_are_clamped_to_zone_limits() {
+        let mut config = config(&[(1, "/zone/5"), (2, "/zone/6")]);
+        config.zones[0].min_level = Some(10.0);
+        let maps = BridgeMaps::from_config(&config);
+
+        let set = |id, level| Ra2Command::SetOutput { id, level, fade: None };
+        let level = |cmd| match cmd {
+            Ra2Command::SetOutput { level, .. } => level,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(level(maps.clamp(set(1, 4.0))), 10.0);
+        assert_eq!(level(maps.clamp(set(1, 0.0))), 0.0);
+        assert_eq!(level(maps.clamp(set(2, 4.0))), 4.0);
+    }
+
+    #[tokio::test]
+    async fn full_command_queue_drops_instead_of_blocking() {
+        let metrics = Arc::new(CommandMetrics::default());
+        let (queue, mut rx) = CommandQueue::new(2, metrics.clone());
+        let command = |id| BridgeCommand {
+            source: CommandSource::Telnet("10.0.0.9:5000".to_string()),
+            cmd: Ra2Command::SetOutput { id, level: 50.0, fade: None },
+        };
+
+        for id in 1..=3 {
+            queue.submit(command(id)).unwrap();
+        }
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.peak_depth, snapshot.dropped), (2, 1));
+        assert_eq!(rx.recv().await.unwrap().cmd.id(), Some(1));
+        assert_eq!(rx.recv().await.unwrap().cmd.id(), Some(2));
+
+        // Room again: commands flow, and only a stopped bridge is an error
+        queue.submit(command(4)).unwrap();
+        assert_eq!(rx.recv().await.unwrap().cmd.id(), Some(4));
+        assert_eq!(metrics.snapshot().dropped, 1);
+        drop(rx);
+        assert!(queue.submit(command(5)).is_err());
+    }
+
+    #[tokio::test]
+    async fn leap_reconnect_rereads_zone_status() {
+        use crate::leap_mock::{MockLeapServer, MockZone};
+
+        let server = MockLeapServer::start(vec![MockZone {
+            area: "Kitchen",
+            href: "/zone/5",
+            name: "Pendants",
+            control_type: "Dimmed",
+            category: "Light",
+            level: 40.0,
+        }])
+        .await
+        .unwrap();
+        let mut config = config(&[(1, "/zone/5")]);
+        config.processor.host = "127.0.0.1".to_string();
+        config.processor.leap_port = server.port;
+        config.telnet.port = 0;
+
+        let reads = || {
+            server
+                .received()
+                .iter()
+                .filter(|r| r["CommuniqueType"] == "ReadRequest" && r["Header"]["Url"] == "/zone/5/status")
+                .count()
+        };
+        let health = Arc::new(BackendHealth::new());
+        let handle = start(
+            config,
+            server.certs_dir.clone(),
+            ZoneLevels::default(),
+            watch::channel(crate::state::BridgeStatus::Stopped).0,
+            Arc::new(SavantHostMetrics::default()),
+            Arc::new(CommandMetrics::default()),
+            health.clone(),
+        )
+        .await
+        .unwrap();
+
+        let mut status = health.leap.subscribe();
+        status.wait_for(|s| *s == BackendStatus::Connected).await.unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(reads(), 0);
+
+        server.kick();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while reads() == 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("zone status not re-read after reconnect");
+        let _ = handle.shutdown_tx.send(()).await;
+    }
+
+    #[tokio::test]
+    async fn forwarded_sets_are_recorded() {
+        let mut config = config(&[(1, "/zone/5")]);
+        config.bridge.skip_redundant_sets = true;
+        let maps = BridgeMaps::from_config(&config);
+        let zone_levels = ZoneLevels::default();
+        let (leap_tx, mut leap_rx) = mpsc::channel(4);
+        let (event_tx, _) = broadcast::channel(4);
+        let set = |level| BridgeCommand {
+            source: CommandSource::Telnet("10.0.0.9:5000".to_string()),
+            cmd: Ra2Command::SetOutput { id: 1, level, fade: None },
+        };
+
+        handle_command(set(60.0), &maps, &zone_levels, Some(&leap_tx), &[], &event_tx).await;
+        assert_eq!(zone_levels.get_level(1).await, Some(60.0));
+        handle_command(set(60.0), &maps, &zone_levels, Some(&leap_tx), &[], &event_tx).await;
+        assert!(leap_rx.try_recv().is_ok());
+        assert!(leap_rx.try_recv().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn redundant_sets_are_not_sent_when_enabled() {
+        use crate::leap_mock::{MockLeapServer, MockZone};
+        use tokio::io::AsyncWriteExt;
+
+        let server = MockLeapServer::start(vec![MockZone {
+            area: "Kitchen",
+            href: "/zone/5",
+            name: "Pendants",
+            control_type: "Dimmed",
+            category: "Light",
+            level: 40.0,
+        }])
+        .await
+        .unwrap();
+        let path = std::env::temp_dir().join(format!("ra-bridge-{}.sock", uuid::Uuid::new_v4()));
+        let mut config = config(&[(1, "/zone/5")]);
+        config.processor.host = "127.0.0.1".to_string();
+        config.processor.leap_port = server.port;
+        config.telnet.port = 0;
+        config.telnet.unix_socket = Some(path.clone());
+        config.bridge.skip_redundant_sets = true;
+
+        let zone_levels = ZoneLevels::default();
+        zone_levels.set_level(1, 40.0).await;
+        let health = Arc::new(BackendHealth::new());
+        let handle = start(
+            config,
+            server.certs_dir.clone(),
+            zone_levels,
+            watch::channel(crate::state::BridgeStatus::Stopped).0,
+            Arc::new(SavantHostMetrics::default()),
+            Arc::new(CommandMetrics::default()),
+            health.clone(),
+        )
+        .await
+        .unwrap();
+        let mut status = health.leap.subscribe();
+        status.wait_for(|s| *s == BackendStatus::Connected).await.unwrap();
+
+        let mut telnet = tokio::net::UnixStream::connect(&path).await.unwrap();
+        telnet
+            .write_all(b"lutron\r\nintegration\r\n#OUTPUT,1,1,40\r\n#OUTPUT,1,1,40.001\r\n#OUTPUT,1,1,60\r\n")
+            .await
+            .unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while server.level("/zone/5") != Some(60.0) {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("set-level never reached the processor");
+
+        let commands = server
+            .received()
+            .iter()
+            .filter(|r| r["Header"]["Url"] == "/zone/5/commandprocessor")
+            .count();
+        assert_eq!(commands, 1);
+        let _ = handle.shutdown_tx.send(()).await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn start_refuses_duplicate_ids() {
+        let mut config = config(&[(1, "/zone/5"), (1, "/zone/6")]);
+        config.telnet.port = 0;
+        let err = start(
+            config,
+            std::env::temp_dir(),
+            ZoneLevels::default(),
+            watch::channel(crate::state::BridgeStatus::Stopped).0,
+            Arc::new(SavantHostMetrics::default()),
+            Arc::new(CommandMetrics::default()),
+            Arc::new(BackendHealth::new()),
+        )
+        .await
+        .err()
+        .expect("duplicate ids must not start");
+        assert!(err.to_string().contains("Duplicate ra2_id 1"), "{}", err);
+
+        let mut duplicated = self::config(&[(1, "/zone/5"), (1, "/zone/6")]);
+        duplicated.telnet.port = 0;
+        let err = tokio::time::timeout(std::time::Duration::from_secs(5), run(duplicated, std::env::temp_dir()))
+            .await
+            .expect("run started with duplicate ids")
+            .expect_err("duplicate ids must not run");
+        assert!(err.to_string().contains("Duplicate ra2_id 1"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn start_fails_when_telnet_port_is_taken() {
+        let taken = tokio::net::TcpListener::bind("0.0.0.0:0").await.unwrap();
+        let mut config = config(&[(1, "/zone/5")]);
+        config.telnet.port = taken.local_addr().unwrap().port();
+        let err = start(
+            config,
+            std::env::temp_dir(),
+            ZoneLevels::default(),
+            watch::channel(crate::state::BridgeStatus::Stopped).0,
+            Arc::new(SavantHostMetrics::default()),
+            Arc::new(CommandMetrics::default()),
+            Arc::new(BackendHealth::new()),
+        )
+        .await
+        .err()
+        .expect("a taken telnet port must fail the start");
+        assert!(
+            err.to_string().contains(&format!("Failed to bind telnet port {}", taken.local_addr().unwrap().port())),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn restart_rebinds_the_telnet_port() {
+        let port = {
+            let probe = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+            probe.local_addr().unwrap().port()
+        };
+        let mut config = config(&[]);
+        config.telnet.port = port;
+        let start = || {
+            start(
+                config.clone(),
+                std::env::temp_dir(),
+                ZoneLevels::default(),
+                watch::channel(crate::state::BridgeStatus::Stopped).0,
+                Arc::new(SavantHostMetrics::default()),
+                Arc::new(CommandMetrics::default()),
+                Arc::new(BackendHealth::new()),
+            )
+        };
+
+        let first = start().await.unwrap();
+        first.shutdown_tx.send(()).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let second = start().await.expect("the stopped bridge still holds the telnet port");
+        let _ = second.shutdown_tx.send(()).await;
+    }
+
+    #[test]
+    fn shared_ids_are_reported_once() {
+        let mut warned = HashSet::new();
+        assert!(warn_shared_id(&mut warned, "Savant", 7));
+        assert!(!warn_shared_id(&mut warned, "Savant", 7));
+        assert!(warn_shared_id(&mut warned, "Savant", 8));
+    }
+
+    #[tokio::test]
+    async fn savant_commands_go_to_the_owning_host() {
+        let config: Config = toml::from_str(
+            r#"
+            [processor]
+            host = ""
+
+            [[savant]]
+            host = "10.0.0.20"
+
+            [[savant]]
+            host = "10.0.0.21"
+
+            [[savant_zones]]
+            ra2_id = 200
+            address = "001"
+            load_offset = 0
+
+            [[savant_zones]]
+            ra2_id = 300
+            host = 1
+            address = "001"
+            load_offset = 0
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let maps = BridgeMaps::from_config(&config);
+        let (tx0, mut rx0) = mpsc::channel(4);
+        let (tx1, mut rx1) = mpsc::channel(4);
+        let txs = [tx0, tx1];
+
+        for (id, level) in [(200, 25.0), (300, 75.0)] {
+            let cmd = Ra2Command::SetOutput { id, level, fade: None };
+            let (tx, req) = savant_route(&txs, &maps.savant, &cmd, None).unwrap();
+            tx.send(req).await.unwrap();
+        }
+        let query = Ra2Command::QueryOutput { id: 300 };
+        let (tx, req) = savant_route(&txs, &maps.savant, &query, None).unwrap();
+        tx.send(req).await.unwrap();
+
+        let level = |req| match req {
+            Some(SavantRequest::SetLoad { address, level, .. }) if address == "001" => level,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(level(rx0.try_recv().ok()), 25.0);
+        assert!(rx0.try_recv().is_err());
+        assert_eq!(level(rx1.try_recv().ok()), 75.0);
+        assert!(matches!(rx1.try_recv(), Ok(SavantRequest::QueryLoad { .. })));
+
+        // Only one host running: the other host's zones have nowhere to go
+        let cmd = Ra2Command::SetOutput { id: 300, level: 50.0, fade: None };
+        assert!(savant_route(&txs[..1], &maps.savant, &cmd, None).is_none());
+
+        let error = BackendStatus::Error { message: "refused".to_string(), attempts: 1 };
+        assert_eq!(combined_status(vec![BackendStatus::Connected, error.clone()]), error);
+        assert_eq!(
+            combined_status(vec![BackendStatus::Connected, BackendStatus::Connected]),
+            BackendStatus::Connected
+        );
+    }
+
+    #[test]
+    fn zone_edits_apply_live() {
+        let old = config(&[(1, "/zone/5")]);
+        let new = config(&[(1, "/zone/5"), (2, "/zone/6")]);
+        assert!(!restart_required(&old, &new));
+
+        let maps = BridgeMaps::from_config(&new);
+        assert_eq!(maps.leap.ra2_to_leap(2), Some("/zone/6"));
+        assert!(maps.known_ids.contains(&2));
+    }
+
+    #[test]
+    fn backend_changes_need_restart() {
+        let old = config(&[(1, "/zone/5")]);
+        let mut moved = old.clone();
+        moved.processor.host = "10.0.0.6".to_string();
+        assert!(restart_required(&old, &moved));
+
+        let mut retelnet = old.clone();
+        retelnet.telnet.port += 1;
+        assert!(restart_required(&old, &retelnet));
+
+        let mut unix = old.clone();
+        unix.telnet.unix_socket = Some("/run/ra-bridge.sock".into());
+        assert!(restart_required(&old, &unix));
+
+        // Removing the last LEAP zone stops the LEAP backend
+        assert!(restart_required(&old, &config(&[])));
+    }
+}
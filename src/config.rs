@@ -1,9 +1,22 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::level_curve::LevelCurve;
+use crate::savant_client::LoadKey;
+
+/// Current config schema version. Files without a `version` key predate
+/// versioning and are treated as version 0.
+pub const CONFIG_VERSION: u32 = 1;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default = "default_config_version")]
+    pub version: u32,
+    /// Default RA2↔backend level curve; zones may override it.
+    #[serde(default, skip_serializing_if = "LevelCurve::is_linear")]
+    pub level_curve: LevelCurve,
     pub processor: ProcessorConfig,
     #[serde(default)]
     pub telnet: TelnetConfig,
@@ -11,10 +24,118 @@ pub struct Config {
     pub web: WebConfig,
     #[serde(default)]
     pub zones: Vec<ZoneMapping>,
-    #[serde(default)]
-    pub savant: Option<SavantConfig>,
+    /// Savant hosts: `[savant]` for one, `[[savant]]` for several. Zones
+    /// pick theirs with `host`, an index into this list.
+    #[serde(default, with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+    pub savant: Vec<SavantConfig>,
     #[serde(default)]
     pub savant_zones: Vec<SavantZoneMapping>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buttons: Vec<ButtonMapping>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keypads: Vec<VirtualKeypad>,
+    #[serde(default)]
+    pub bridge: BridgeConfig,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<MqttConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_ranges: Option<IdRanges>,
+}
+
+/// ra2_ids reserved for each backend. Discovery numbers zones inside their
+/// backend's range and fails once it's full; without this section LEAP
+/// zones count up from 1 and Savant zones from the requested start id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IdRanges {
+    pub leap: IdRange,
+    pub savant: IdRange,
+}
+
+/// An inclusive ra2_id range, written `{ start = 1, end = 199 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IdRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl IdRange {
+    /// Every id from `start` up.
+    pub fn starting_at(start: u32) -> Self {
+        Self { start, end: u32::MAX }
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        (self.start..=self.end).contains(&id)
+    }
+
+    fn overlaps(&self, other: &IdRange) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+impl std::fmt::Display for IdRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// Where to report bridge and backend state changes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    /// `http://` URL that gets a JSON POST each time the bridge or a backend
+    /// changes state, e.g. when the processor connection drops.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
+}
+
+/// Log output beyond what `-v` / `RUST_LOG` select.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Log every message sent to and received from the processor and Savant
+    /// host at info. Off, those lines are only logged at debug/trace.
+    #[serde(default)]
+    pub trace_protocol: bool,
+}
+
+/// Behavior shared by every backend.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BridgeConfig {
+    /// Fade applied to set-level commands that don't give their own;
+    /// 0 snaps instantly.
+    #[serde(default)]
+    pub default_fade_secs: f64,
+    /// Seconds `serve` waits before auto-starting, for boots where the
+    /// network comes up late.
+    #[serde(default)]
+    pub autostart_delay_secs: u64,
+    /// After the delay, wait up to this many seconds for the processor (or
+    /// Savant host) to accept TCP connections. 0 starts without checking.
+    #[serde(default)]
+    pub autostart_wait_secs: u64,
+    /// Consecutive failed connection attempts after which a backend gives up
+    /// and reports `Failed` until the bridge is restarted. 0 retries forever.
+    #[serde(default)]
+    pub max_reconnect_attempts: u32,
+    /// Level reports within this many percent of the last one forwarded are
+    /// dropped, to quiet backends that jitter (49.8 → 50.1 → 49.9). 0 keeps
+    /// every report.
+    #[serde(default)]
+    pub level_deadband: f64,
+    /// Answer a set-level for the level a zone is already at without
+    /// sending it on. Off by default, so every set reaches the backend.
+    #[serde(default)]
+    pub skip_redundant_sets: bool,
+}
+
+impl BridgeConfig {
+    /// The configured default fade, or `None` for instant.
+    pub fn default_fade(&self) -> Option<f64> {
+        (self.default_fade_secs > 0.0).then_some(self.default_fade_secs)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,27 +143,272 @@ pub struct ProcessorConfig {
     pub host: String,
     #[serde(default = "default_leap_port")]
     pub leap_port: u16,
+    /// Extra PEM CA files to trust for this processor, on top of the paired
+    /// `ca.crt` and the built-in Lutron CAs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ca_files: Vec<PathBuf>,
+    /// Seconds between LEAP keepalive pings. A ping still unanswered when
+    /// the next one is due drops the connection and reconnects.
+    #[serde(default = "default_ping_interval_secs")]
+    pub ping_interval_secs: u64,
+    /// Read `/zone/status` every `poll_interval_secs` instead of
+    /// subscribing to it, for firmware whose subscription sends duplicate
+    /// or malformed events.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub use_polling: bool,
+    #[serde(default = "default_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+}
+
+impl ProcessorConfig {
+    pub fn new(host: String, leap_port: u16) -> Self {
+        Self {
+            host,
+            leap_port,
+            ca_files: Vec::new(),
+            ping_interval_secs: default_ping_interval_secs(),
+            use_polling: false,
+            poll_interval_secs: default_poll_interval_secs(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TelnetConfig {
     #[serde(default = "default_telnet_port")]
     pub port: u16,
+    /// Listen on this Unix domain socket instead of the TCP port, for
+    /// controllers running on the same host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unix_socket: Option<PathBuf>,
+    /// Decimal places in `~OUTPUT` levels (0–2); some controllers only
+    /// accept whole numbers.
+    #[serde(default = "default_level_decimals")]
+    pub level_decimals: usize,
+    /// Text sent on connect, before `login:`, for controllers that look for
+    /// a particular greeting. Empty by default, as on a real RA2 main
+    /// repeater, which opens straight with the login prompt.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub banner: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WebConfig {
     #[serde(default = "default_web_port")]
     pub port: u16,
+    /// `ProjectName` written into the exported DbXmlInfo.xml.
+    #[serde(default = "default_project_name")]
+    pub project_name: String,
+    /// Bearer token required by the raw LEAP/Savant passthrough endpoints,
+    /// which are disabled while this is unset. The web UI only ever sees it
+    /// as `REDACTED`; set it to "" there to turn the endpoints off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_token: Option<String>,
+    /// Goes between area and load in discovered zone names ("Kitchen ─ Main").
+    /// Must be one of `AREA_SEPARATORS`. Names are split into area and load
+    /// at this one first, and at any of the others when it's absent.
+    #[serde(default = "default_area_separator")]
+    pub area_separator: char,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Write `contents` to a temp file next to `path` and rename it over `path`,
+/// so readers, and whatever loads the file after a crash mid-write, see the
+/// old contents or the new, never a truncated mix. An existing file keeps
+/// its permissions, and a symlink is followed rather than replaced.
+pub async fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    write_atomic_with(path, contents.as_ref(), false).await
+}
+
+/// `write_atomic` for private keys: the file is always left owner-only.
+pub async fn write_private(path: &Path, contents: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    write_atomic_with(path, contents.as_ref(), true).await
+}
+
+async fn write_atomic_with(path: &Path, contents: &[u8], private: bool) -> anyhow::Result<()> {
+    use anyhow::Context;
+    use tokio::io::AsyncWriteExt;
+
+    // Rename over the file a symlink points at, not the link itself
+    let path = match tokio::fs::canonicalize(path).await {
+        Ok(target) => target,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to resolve {}", path.display())),
+    };
+    let existing = tokio::fs::metadata(&path).await.ok().map(|m| m.permissions());
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("{} is not a file path", path.display()))?;
+    let tmp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        uuid::Uuid::new_v4().simple()
+    ));
+    let result = async {
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        // Owner-only while being written whenever the result should be
+        // restricted; an existing file's own permissions go on afterwards
+        #[cfg(unix)]
+        if private || existing.is_some() {
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp).await?;
+        file.write_all(contents).await?;
+        file.sync_all().await?;
+        drop(file);
+        if let (Some(perms), false) = (existing, private) {
+            tokio::fs::set_permissions(&tmp, perms).await?;
+        }
+        tokio::fs::rename(&tmp, &path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+    }
+    result.with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Separators recognised between area and load in zone names, when
+/// surrounded by spaces.
+pub const AREA_SEPARATORS: [char; 4] = ['\u{2500}', '-', '/', '|'];
+
+/// "Area ─ Load", with `separator` in place of the ─.
+pub fn join_area_name(area: &str, load: &str, separator: char) -> String {
+    format!("{} {} {}", area, separator, load)
+}
+
+/// Split "Area ─ Load" (or "Area - Load", ...) into its trimmed halves, at
+/// the first spaced `separator`. Names without it are split at the first
+/// spaced separator of the other kinds, so "Den ─ Wall - Left" keeps
+/// "Wall - Left" as the load when `separator` is ─.
+pub fn split_area_name(name: &str, separator: char) -> Option<(String, String)> {
+    let find = |c: &char| {
+        let sep = format!(" {} ", c);
+        name.find(&sep).map(|pos| (pos, sep))
+    };
+    let (pos, sep) = find(&separator).or_else(|| {
+        AREA_SEPARATORS
+            .iter()
+            .filter_map(find)
+            .min_by_key(|(pos, _)| *pos)
+    })?;
+    Some((
+        name[..pos].trim().to_string(),
+        name[pos + sep.len()..].trim().to_string(),
+    ))
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct ZoneMapping {
     pub ra2_id: u32,
     pub leap_href: String,
     #[serde(default)]
     pub name: String,
+    /// The name discovery gave this zone, so rediscovery can tell a name the
+    /// user chose from one it should refresh.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovered_name: Option<String>,
+    /// LEAP `ControlType` seen at discovery ("Dimmed", "Switched", "Shade", ...).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control_type: Option<String>,
+    /// LEAP `Category.Type` seen at discovery ("Light", "ExhaustFan", ...),
+    /// used for the exported OutputType when the control type doesn't settle it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level_curve: Option<LevelCurve>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_level: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_level: Option<f64>,
+}
+
+impl ZoneMapping {
+    pub fn limits(&self) -> LevelLimits {
+        LevelLimits {
+            min: self.min_level,
+            max: self.max_level,
+        }
+    }
+
+    /// Whether the zone takes a fade; switched loads reject `GoToDimmedLevel`.
+    pub fn accepts_fade(&self) -> bool {
+        !self
+            .control_type
+            .as_deref()
+            .is_some_and(|t| t.eq_ignore_ascii_case("switched"))
+    }
+}
+
+/// A zone's `min_level` / `max_level`, in RA2 percent, for fixtures that
+/// flicker when dimmed too low or shouldn't run at full output.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LevelLimits {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl LevelLimits {
+    /// Clamp a requested level into the limits. Off stays off, so a
+    /// minimum never keeps a load lit.
+    pub fn apply(&self, level: f64) -> f64 {
+        if level <= 0.0 {
+            return level;
+        }
+        let level = self.min.map_or(level, |min| level.max(min));
+        self.max.map_or(level, |max| level.min(max))
+    }
+
+    fn validate(&self, ra2_id: u32) -> Result<(), String> {
+        let in_range = |l: Option<f64>| l.is_none_or(|l| (0.0..=100.0).contains(&l));
+        if !in_range(self.min) || !in_range(self.max) {
+            return Err(format!("Zone {}: min_level/max_level must be 0-100", ra2_id));
+        }
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if min > max {
+                return Err(format!("Zone {}: min_level {} is above max_level {}", ra2_id, min, max));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A phantom keypad listed under its area's `<DeviceGroups>` in the XML
+/// export, so RA2 controllers have a device integration ID and buttons to
+/// program against. The bridge doesn't act on presses of these buttons.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VirtualKeypad {
+    pub ra2_id: u32,
+    /// "Area ─ Keypad", split into area and device name like zone names.
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub buttons: Vec<VirtualButton>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VirtualButton {
+    /// RA2 button component number (1–7 on a seeTouch keypad).
+    pub component: u32,
+    /// Engraving shown by the controller.
+    #[serde(default)]
+    pub name: String,
+}
+
+/// A keypad button LED on the LEAP processor, mirrored to RA2 clients as
+/// `~DEVICE,<ra2_id>,<component>,9,<0|1>`. Read-only: RA2 clients can't
+/// press buttons through the bridge.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ButtonMapping {
+    /// RA2 integration ID of the keypad device.
+    pub ra2_id: u32,
+    /// RA2 LED component number (81 = LED of button 1 on a seeTouch keypad).
+    pub component: u32,
+    /// LEAP LED href, e.g. "/led/412".
+    pub led_href: String,
+    #[serde(default)]
+    pub name: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,31 +416,212 @@ pub struct SavantConfig {
     pub host: String,
     #[serde(default = "default_savant_port")]
     pub port: u16,
+    /// Savant addresses a load in `load.<hex>` state names by packing the
+    /// module address and load offset into one key:
+    ///
+    ///   key = (address << load_key_shift) | (load_offset & load_offset_mask)
+    ///
+    /// The defaults (16 / 1023) match the host web UI's getSetStateValue();
+    /// hosts provisioned with a different offset width need other values.
+    #[serde(default = "default_load_key_shift")]
+    pub load_key_shift: u32,
+    #[serde(default = "default_load_offset_mask")]
+    pub load_offset_mask: u32,
+    /// How the bridge identifies itself in `session/devicePresent`.
+    #[serde(default)]
+    pub device: SavantDevice,
+    /// Most loads discovery adds for this host. Any beyond it are dropped
+    /// with a warning, so a host answering oddly can't fill the config with
+    /// phantom zones.
+    #[serde(default = "default_max_discovered_zones")]
+    pub max_discovered_zones: usize,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl SavantConfig {
+    pub fn new(host: String, port: u16) -> Self {
+        Self {
+            host,
+            port,
+            load_key_shift: default_load_key_shift(),
+            load_offset_mask: default_load_offset_mask(),
+            device: SavantDevice::default(),
+            max_discovered_zones: default_max_discovered_zones(),
+        }
+    }
+
+    pub fn load_key(&self) -> LoadKey {
+        LoadKey {
+            shift: self.load_key_shift,
+            mask: self.load_offset_mask,
+        }
+    }
+}
+
+/// The `device` block sent to the Savant host on connect. Some hosts key
+/// authorization or logging on the reported name or IP, and a few reject
+/// the default `0.0.0.0`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavantDevice {
+    #[serde(default = "default_savant_device_name")]
+    pub name: String,
+    /// Discovery connects as `<app>-discover`.
+    #[serde(default = "default_savant_device_app")]
+    pub app: String,
+    #[serde(default = "default_savant_device_ip")]
+    pub ip: String,
+}
+
+/// MQTT broker for Home Assistant: every zone is announced as a light via
+/// MQTT discovery, with its level published on change and commands taken
+/// from its command topic.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MqttConfig {
+    pub broker: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Home Assistant's discovery prefix.
+    #[serde(default = "default_mqtt_discovery_prefix")]
+    pub discovery_prefix: String,
+    /// Root of the state, command and availability topics; also the client id.
+    #[serde(default = "default_mqtt_topic_prefix")]
+    pub topic_prefix: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SavantZoneMapping {
     pub ra2_id: u32,
+    /// Index of the owning host in `[[savant]]`; 0 with a single host.
+    #[serde(default, skip_serializing_if = "is_first_host")]
+    pub host: usize,
     pub address: String,
     pub load_offset: usize,
     #[serde(default)]
     pub name: String,
+    /// The name discovery gave this load; see `ZoneMapping::discovered_name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovered_name: Option<String>,
     #[serde(default)]
     pub room: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level_curve: Option<LevelCurve>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_level: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_level: Option<f64>,
+}
+
+impl SavantZoneMapping {
+    pub fn limits(&self) -> LevelLimits {
+        LevelLimits {
+            min: self.min_level,
+            max: self.max_level,
+        }
+    }
+}
+
+fn is_first_host(host: &usize) -> bool {
+    *host == 0
+}
+
+/// (De)serialize a list as a single table when it has one entry, so a
+/// one-host config keeps its plain `[savant]` section.
+mod one_or_many {
+    use serde::de::DeserializeOwned;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T: Serialize, S: Serializer>(items: &[T], serializer: S) -> Result<S::Ok, S::Error> {
+        match items {
+            [one] => one.serialize(serializer),
+            _ => items.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, T: DeserializeOwned, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<T>, D::Error> {
+        let value = toml::Value::deserialize(deserializer)?;
+        let items = match value {
+            toml::Value::Array(_) => value.try_into(),
+            _ => value.try_into().map(|one| vec![one]),
+        };
+        items.map_err(serde::de::Error::custom)
+    }
+}
+
+fn default_load_key_shift() -> u32 {
+    LoadKey::default().shift
+}
+
+fn default_load_offset_mask() -> u32 {
+    LoadKey::default().mask
+}
+
+pub fn default_max_discovered_zones() -> usize {
+    256
 }
 
 fn default_leap_port() -> u16 {
     8081
 }
 
+fn default_ping_interval_secs() -> u64 {
+    15
+}
+
+fn default_poll_interval_secs() -> u64 {
+    2
+}
+
+fn default_config_version() -> u32 {
+    CONFIG_VERSION
+}
+
 fn default_telnet_port() -> u16 {
     6023
 }
 
+fn default_level_decimals() -> usize {
+    crate::ra2_protocol::DEFAULT_LEVEL_DECIMALS
+}
+
 fn default_web_port() -> u16 {
     8080
 }
 
+fn default_savant_device_name() -> String {
+    "Linux".to_string()
+}
+
+fn default_savant_device_app() -> String {
+    "ra-bridge".to_string()
+}
+
+fn default_savant_device_ip() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_project_name() -> String {
+    "RA3 Bridge Import".to_string()
+}
+
+pub fn default_area_separator() -> char {
+    AREA_SEPARATORS[0]
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+fn default_mqtt_topic_prefix() -> String {
+    "ra-bridge".to_string()
+}
+
 fn default_savant_port() -> u16 {
     8480
 }
@@ -83,41 +630,342 @@ impl Default for TelnetConfig {
     fn default() -> Self {
         Self {
             port: default_telnet_port(),
+            unix_socket: None,
+            level_decimals: default_level_decimals(),
+            banner: String::new(),
         }
     }
 }
 
+impl Default for SavantDevice {
+    fn default() -> Self {
+        Self {
+            name: default_savant_device_name(),
+            app: default_savant_device_app(),
+            ip: default_savant_device_ip(),
+        }
+    }
+}
+
+/// Stands in for `[web] api_token` in the config the web UI is shown.
+pub const REDACTED: &str = "********";
+
 impl Default for WebConfig {
     fn default() -> Self {
         Self {
             port: default_web_port(),
+            project_name: default_project_name(),
+            api_token: None,
+            area_separator: default_area_separator(),
         }
     }
 }
 
 impl Config {
-    pub fn load(path: &Path) -> anyhow::Result<Self> {
-        let contents = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&contents)?;
+    /// Load a config file, migrating it to `CONFIG_VERSION` first if it is
+    /// older. The migration is in memory only: the file is left alone until
+    /// it's saved or `migrate_file` rewrites it.
+    pub async fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = tokio::fs::read_to_string(path).await?;
+        let (config, migrated) = Self::parse_migrated(path, &contents)?;
+        if let Some((from, _)) = migrated {
+            info!(
+                "{} is config version {}, upgraded to {} in memory; run `ra-bridge migrate-config` to rewrite it",
+                path.display(),
+                from,
+                CONFIG_VERSION
+            );
+        }
         Ok(config)
     }
 
-    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+    /// Rewrite an older config file as `CONFIG_VERSION`, keeping the original
+    /// as `.v<N>.bak`. Returns the backup path, or None if the file was
+    /// already current and left alone.
+    pub async fn migrate_file(path: &Path) -> anyhow::Result<Option<PathBuf>> {
+        let contents = tokio::fs::read_to_string(path).await?;
+        let (config, migrated) = Self::parse_migrated(path, &contents)?;
+        let Some((from, original)) = migrated else {
+            return Ok(None);
+        };
+
+        let bak = path.with_extension(format!("toml.v{}.bak", from));
+        tokio::fs::copy(path, &bak).await?;
+        config.save(path).await?;
+
+        let upgraded = toml::Table::try_from(&config)?;
+        let mut added = Vec::new();
+        added_keys(&original, &upgraded, "", &mut added);
+        info!(
+            "Migrated {} from config version {} to {} (backup at {}); filled in: {}",
+            path.display(),
+            from,
+            CONFIG_VERSION,
+            bak.display(),
+            if added.is_empty() { "nothing".to_string() } else { added.join(", ") }
+        );
+        Ok(Some(bak))
+    }
+
+    /// Like `load`, but blocking, for callers already off the runtime
+    /// (site previews).
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Self::parse_migrated(path, &contents)?.0)
+    }
+
+    /// Parse the contents of `path`, migrating it in memory if it's an older
+    /// version. The second value is the version it was migrated from and its
+    /// raw table.
+    fn parse_migrated(path: &Path, contents: &str) -> anyhow::Result<(Self, Option<(i64, toml::Table)>)> {
+        let mut raw: toml::Table = toml::from_str(contents)?;
+        if raw.is_empty() {
+            return Err(EmptyConfig(path.to_path_buf()).into());
+        }
+        let from = raw
+            .get("version")
+            .and_then(|v| v.as_integer())
+            .unwrap_or(0);
+        if from > CONFIG_VERSION as i64 {
+            anyhow::bail!(
+                "{} is config version {}, but this build only understands up to {}",
+                path.display(),
+                from,
+                CONFIG_VERSION
+            );
+        }
+        if from == CONFIG_VERSION as i64 {
+            return Ok((toml::Value::Table(raw).try_into()?, None));
+        }
+
+        let original = raw.clone();
+        migrate(&mut raw, from);
+        let config: Config = toml::Value::Table(raw).try_into()?;
+        Ok((config, Some((from, original))))
+    }
+
+    pub async fn save(&self, path: &Path) -> anyhow::Result<()> {
         let toml_str = toml::to_string_pretty(self)?;
-        std::fs::write(path, &toml_str)?;
-        Ok(())
+        write_atomic(path, &toml_str).await
+    }
+
+    /// This config with secrets replaced by `REDACTED`, for showing to
+    /// clients that aren't trusted with them.
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        if config.web.api_token.is_some() {
+            config.web.api_token = Some(REDACTED.to_string());
+        }
+        config
+    }
+
+    /// Undo `redacted` on an edited copy: a secret that's still `REDACTED`,
+    /// or was left out, keeps its value from `stored`.
+    pub fn keep_secrets(&mut self, stored: Option<&Config>) {
+        let Some(stored) = stored else { return };
+        if self.web.api_token.as_deref().is_none_or(|t| t == REDACTED) {
+            self.web.api_token.clone_from(&stored.web.api_token);
+        }
     }
 
     pub fn has_leap(&self) -> bool {
-        !self.zones.is_empty()
+        !self.zones.is_empty() || !self.buttons.is_empty()
     }
 
     pub fn has_savant(&self) -> bool {
-        self.savant.is_some() && !self.savant_zones.is_empty()
+        !self.savant.is_empty() && !self.savant_zones.is_empty()
+    }
+
+    /// Savant zones owned by the `host`th `[[savant]]` entry.
+    pub fn savant_zones_for(&self, host: usize) -> Vec<SavantZoneMapping> {
+        self.savant_zones.iter().filter(|z| z.host == host).cloned().collect()
+    }
+
+    /// All ra2_ids owned by either backend.
+    pub fn ra2_ids(&self) -> HashSet<u32> {
+        self.zones
+            .iter()
+            .map(|z| z.ra2_id)
+            .chain(self.savant_zones.iter().map(|z| z.ra2_id))
+            .collect()
+    }
+
+    /// Where discovery numbers LEAP zones.
+    pub fn leap_ids(&self) -> IdRange {
+        self.id_ranges.as_ref().map_or(IdRange::starting_at(1), |r| r.leap)
+    }
+
+    /// Where discovery numbers Savant zones; `start_id` applies only when
+    /// no `[id_ranges]` are configured.
+    pub fn savant_ids(&self, start_id: u32) -> IdRange {
+        self.id_ranges.as_ref().map_or(IdRange::starting_at(start_id), |r| r.savant)
+    }
+
+    /// Every zone's name by ra2_id, LEAP and Savant alike.
+    pub fn zone_names(&self) -> BTreeMap<u32, String> {
+        self.zones
+            .iter()
+            .map(|z| (z.ra2_id, z.name.clone()))
+            .chain(self.savant_zones.iter().map(|z| (z.ra2_id, z.name.clone())))
+            .collect()
+    }
+
+    /// LEAP status URLs to subscribe to for keypad LED feedback.
+    pub fn led_subscriptions(&self) -> Vec<String> {
+        self.buttons
+            .iter()
+            .map(|b| format!("{}/status", b.led_href))
+            .collect()
+    }
+
+    /// Level curve for a zone: its own override, else the global default.
+    pub fn level_curve_for(&self, ra2_id: u32) -> &LevelCurve {
+        let zone_curve = self
+            .zones
+            .iter()
+            .find(|z| z.ra2_id == ra2_id)
+            .map(|z| &z.level_curve)
+            .or_else(|| {
+                self.savant_zones
+                    .iter()
+                    .find(|z| z.ra2_id == ra2_id)
+                    .map(|z| &z.level_curve)
+            });
+        zone_curve
+            .and_then(|c| c.as_ref())
+            .unwrap_or(&self.level_curve)
+    }
+
+    /// Zones (ra2_id, name) whose name matches `query`, ignoring case, extra
+    /// spaces and the "─" (or other `AREA_SEPARATORS`) between area and load. A full-name match wins;
+    /// otherwise every word of the query must appear in the name, so
+    /// "kitchen ceiling" finds "KITCHEN ─ CEILING LIGHTS".
+    pub fn zones_matching_name(&self, query: &str) -> Vec<(u32, &str)> {
+        fn words(s: &str) -> Vec<String> {
+            s.split(|c: char| c.is_whitespace() || c == '\u{2500}')
+                .filter(|w| !w.is_empty() && !w.chars().all(|c| AREA_SEPARATORS.contains(&c)))
+                .map(|w| w.to_lowercase())
+                .collect()
+        }
+        let query = words(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let names: Vec<(u32, &str)> = self
+            .zones
+            .iter()
+            .map(|z| (z.ra2_id, z.name.as_str()))
+            .chain(self.savant_zones.iter().map(|z| (z.ra2_id, z.name.as_str())))
+            .collect();
+
+        let exact: Vec<_> = names.iter().copied().filter(|(_, n)| words(n) == query).collect();
+        if !exact.is_empty() {
+            return exact;
+        }
+        names
+            .into_iter()
+            .filter(|(_, n)| {
+                let name = words(n).join(" ");
+                query.iter().all(|w| name.contains(w.as_str()))
+            })
+            .collect()
+    }
+
+    /// Level limits for a zone; unbounded if it has none or isn't configured.
+    pub fn level_limits_for(&self, ra2_id: u32) -> LevelLimits {
+        self.zones
+            .iter()
+            .find(|z| z.ra2_id == ra2_id)
+            .map(|z| z.limits())
+            .or_else(|| {
+                self.savant_zones
+                    .iter()
+                    .find(|z| z.ra2_id == ra2_id)
+                    .map(|z| z.limits())
+            })
+            .unwrap_or_default()
     }
 
-    /// Check for duplicate ra2_ids across both zone lists.
+    /// Check for duplicate ra2_ids across both zone lists, keypads that reuse a
+    /// zone's or another keypad's ra2_id, and malformed level curves and limits.
     pub fn validate(&self) -> Result<(), String> {
+        self.level_curve.validate()?;
+        if !(self.bridge.default_fade_secs.is_finite() && self.bridge.default_fade_secs >= 0.0) {
+            return Err("bridge.default_fade_secs must be a non-negative number".to_string());
+        }
+        if !AREA_SEPARATORS.contains(&self.web.area_separator) {
+            return Err(format!(
+                "web.area_separator must be one of {}",
+                AREA_SEPARATORS.iter().map(|c| format!("'{}'", c)).collect::<Vec<_>>().join(", ")
+            ));
+        }
+        if !(0.0..=100.0).contains(&self.bridge.level_deadband) {
+            return Err("bridge.level_deadband must be 0-100".to_string());
+        }
+        if self.processor.ping_interval_secs == 0 {
+            return Err("processor.ping_interval_secs must be at least 1".to_string());
+        }
+        if self.telnet.level_decimals > crate::ra2_protocol::MAX_LEVEL_DECIMALS {
+            return Err(format!(
+                "telnet.level_decimals must be at most {}",
+                crate::ra2_protocol::MAX_LEVEL_DECIMALS
+            ));
+        }
+        for savant in &self.savant {
+            savant.load_key().validate()?;
+        }
+        if let Some(z) = self.savant_zones.iter().find(|z| z.host > 0 && z.host >= self.savant.len()) {
+            return Err(format!(
+                "Savant zone {} ('{}') is on host {}, but only {} [[savant]] host(s) are configured",
+                z.ra2_id,
+                z.name,
+                z.host,
+                self.savant.len()
+            ));
+        }
+        if let Some(mqtt) = &self.mqtt {
+            if mqtt.broker.is_empty() {
+                return Err("mqtt.broker must not be empty".to_string());
+            }
+            if mqtt.topic_prefix.is_empty() || mqtt.topic_prefix.contains(['+', '#']) {
+                return Err("mqtt.topic_prefix must be non-empty and free of MQTT wildcards".to_string());
+            }
+        }
+        if let Some(ranges) = &self.id_ranges {
+            for (name, range) in [("leap", &ranges.leap), ("savant", &ranges.savant)] {
+                if range.start == 0 || range.start > range.end {
+                    return Err(format!("id_ranges.{} must satisfy 1 <= start <= end", name));
+                }
+            }
+            if ranges.leap.overlaps(&ranges.savant) {
+                return Err(format!(
+                    "id_ranges.leap ({}) and id_ranges.savant ({}) overlap",
+                    ranges.leap, ranges.savant
+                ));
+            }
+        }
+        if let Some(url) = &self.notifications.webhook_url {
+            if !url.starts_with("http://") {
+                return Err("notifications.webhook_url must be an http:// URL".to_string());
+            }
+        }
+        for curve in self
+            .zones
+            .iter()
+            .filter_map(|z| z.level_curve.as_ref())
+            .chain(self.savant_zones.iter().filter_map(|z| z.level_curve.as_ref()))
+        {
+            curve.validate()?;
+        }
+        for (ra2_id, limits) in self
+            .zones
+            .iter()
+            .map(|z| (z.ra2_id, z.limits()))
+            .chain(self.savant_zones.iter().map(|z| (z.ra2_id, z.limits())))
+        {
+            limits.validate(ra2_id)?;
+        }
+
         let mut seen = HashSet::new();
         for z in &self.zones {
             if !seen.insert(z.ra2_id) {
@@ -132,6 +980,393 @@ impl Config {
                 ));
             }
         }
+
+        // Several buttons share one keypad ID, but each LED component is unique
+        let mut leds = HashSet::new();
+        for b in &self.buttons {
+            if seen.contains(&b.ra2_id) {
+                return Err(format!(
+                    "Keypad ra2_id {} (button '{}') conflicts with a zone",
+                    b.ra2_id, b.name
+                ));
+            }
+            if !leds.insert((b.ra2_id, b.component)) {
+                return Err(format!(
+                    "Duplicate LED component {} on keypad {}",
+                    b.component, b.ra2_id
+                ));
+            }
+        }
+
+        let mut keypads = HashSet::new();
+        for k in &self.keypads {
+            if seen.contains(&k.ra2_id) {
+                return Err(format!(
+                    "Virtual keypad ra2_id {} ('{}') conflicts with a zone",
+                    k.ra2_id, k.name
+                ));
+            }
+            if self.buttons.iter().any(|b| b.ra2_id == k.ra2_id) {
+                return Err(format!(
+                    "Virtual keypad ra2_id {} ('{}') conflicts with a [[buttons]] keypad",
+                    k.ra2_id, k.name
+                ));
+            }
+            if !keypads.insert(k.ra2_id) {
+                return Err(format!("Duplicate virtual keypad ra2_id {}", k.ra2_id));
+            }
+            let mut components = HashSet::new();
+            for b in &k.buttons {
+                if b.component == 0 || !components.insert(b.component) {
+                    return Err(format!(
+                        "Virtual keypad {} has a zero or duplicate button component {}",
+                        k.ra2_id, b.component
+                    ));
+                }
+            }
+        }
         Ok(())
     }
 }
+
+/// A config file with no settings at all: zero-length, whitespace or only
+/// comments. Usually left behind by an interrupted setup; callers that can
+/// run the setup wizard treat it like a missing file.
+#[derive(Debug)]
+pub struct EmptyConfig(pub PathBuf);
+
+impl std::fmt::Display for EmptyConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is empty — run pairing/discovery to create it", self.0.display())
+    }
+}
+
+impl std::error::Error for EmptyConfig {}
+
+/// Upgrade a raw config table one version at a time.
+fn migrate(raw: &mut toml::Table, from: i64) {
+    if from < 1 {
+        // v0 → v1: the layout is unchanged; versioning starts here and
+        // missing sections pick up their serde defaults on re-serialization.
+        raw.insert("version".to_string(), toml::Value::Integer(1));
+    }
+}
+
+/// Dotted paths of keys in `new` that `old` didn't have (tables only).
+fn added_keys(old: &toml::Table, new: &toml::Table, prefix: &str, out: &mut Vec<String>) {
+    for (key, value) in new {
+        let path = format!("{}{}", prefix, key);
+        match (old.get(key), value) {
+            (None, _) => out.push(path),
+            (Some(toml::Value::Table(old_t)), toml::Value::Table(new_t)) => {
+                added_keys(old_t, new_t, &format!("{}.", path), out);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_token_is_redacted_and_kept_on_save() {
+        let stored: Config =
+            toml::from_str("[processor]\nhost = \"10.0.0.5\"\n[web]\napi_token = \"s3cret\"").unwrap();
+        let shown = toml::to_string_pretty(&stored.redacted()).unwrap();
+        assert!(!shown.contains("s3cret"));
+
+        let mut edited: Config = toml::from_str(&shown).unwrap();
+        edited.keep_secrets(Some(&stored));
+        assert_eq!(edited.web.api_token.as_deref(), Some("s3cret"));
+
+        let mut dropped = stored.clone();
+        dropped.web.api_token = None;
+        dropped.keep_secrets(Some(&stored));
+        assert_eq!(dropped.web.api_token.as_deref(), Some("s3cret"));
+
+        let mut changed = stored.clone();
+        changed.web.api_token = Some(String::new());
+        changed.keep_secrets(Some(&stored));
+        assert_eq!(changed.web.api_token.as_deref(), Some(""));
+    }
+
+    #[tokio::test]
+    async fn unversioned_config_migrates_to_v1() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let v0 = r#"
+[processor]
+host = "10.0.0.5"
+
+[[zones]]
+ra2_id = 1
+leap_href = "/zone/5"
+name = "Kitchen"
+"#;
+        std::fs::write(&path, v0).unwrap();
+
+        let config = Config::load(&path).await.unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.processor.leap_port, 8081);
+        assert_eq!(config.telnet.port, 6023);
+        assert_eq!(config.web.project_name, "RA3 Bridge Import");
+        assert_eq!(config.zones.len(), 1);
+
+        // Loading never touches the file
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), v0);
+        assert!(!dir.join("config.toml.v0.bak").exists());
+
+        // Migrating rewrites it in place, original kept
+        let bak = Config::migrate_file(&path).await.unwrap();
+        assert_eq!(bak, Some(dir.join("config.toml.v0.bak")));
+        let rewritten = std::fs::read_to_string(&path).unwrap();
+        assert!(rewritten.contains("version = 1"));
+        assert_eq!(
+            std::fs::read_to_string(dir.join("config.toml.v0.bak")).unwrap(),
+            v0
+        );
+        assert_eq!(Config::migrate_file(&path).await.unwrap(), None);
+        assert_eq!(Config::load(&path).await.unwrap().version, CONFIG_VERSION);
+
+        std::fs::write(&path, "version = 99\n[processor]\nhost = \"x\"\n").unwrap();
+        assert!(Config::load(&path).await.is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn empty_config_is_reported_as_such() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+
+        for contents in ["", "  \n\t\n", "# written by setup\n\n# [processor]\n"] {
+            std::fs::write(&path, contents).unwrap();
+            let err = Config::load(&path).await.unwrap_err();
+            assert!(err.is::<EmptyConfig>(), "{:?}: {}", contents, err);
+            assert!(err.to_string().contains("is empty"));
+            assert!(Config::read(&path).unwrap_err().is::<EmptyConfig>());
+        }
+
+        // A file with settings but no [processor] is still a parse error
+        std::fs::write(&path, "version = 1\n").unwrap();
+        assert!(!Config::load(&path).await.unwrap_err().is::<EmptyConfig>());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn save_then_load_round_trips() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-config-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("config.toml");
+
+        let mut config: Config = toml::from_str("[processor]\nhost = \"10.0.0.5\"").unwrap();
+        config.zones.push(ZoneMapping {
+            ra2_id: 3,
+            leap_href: "/zone/5".to_string(),
+            name: "Kitchen".to_string(),
+            max_level: Some(80.0),
+            ..Default::default()
+        });
+        config.save(&path).await.unwrap();
+
+        let loaded = Config::load(&path).await.unwrap();
+        assert_eq!(
+            toml::to_string_pretty(&loaded).unwrap(),
+            toml::to_string_pretty(&config).unwrap()
+        );
+        assert_eq!(Config::read(&path).unwrap().zones[0].max_level, Some(80.0));
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn writes_keep_permissions_and_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ra-bridge-config-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        // A config the user locked down stays locked down
+        let config = dir.join("config.toml");
+        std::fs::write(&config, "").unwrap();
+        std::fs::set_permissions(&config, std::fs::Permissions::from_mode(0o600)).unwrap();
+        write_atomic(&config, "[processor]\n").await.unwrap();
+        assert_eq!(mode(&config), 0o600);
+
+        // Saving through a symlink updates its target and leaves the link
+        let link = dir.join("linked.toml");
+        std::os::unix::fs::symlink(&config, &link).unwrap();
+        write_atomic(&link, "[web]\n").await.unwrap();
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_to_string(&config).unwrap(), "[web]\n");
+        assert_eq!(mode(&config), 0o600);
+
+        // Keys are owner-only, new or not
+        let key = dir.join("ra-bridge.key");
+        write_private(&key, "key").await.unwrap();
+        assert_eq!(mode(&key), 0o600);
+        std::fs::set_permissions(&key, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write_private(&key, "key").await.unwrap();
+        assert_eq!(mode(&key), 0o600);
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn saves_never_expose_a_partial_file() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-config-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("config.toml");
+
+        let mut config: Config = toml::from_str("[processor]\nhost = \"10.0.0.5\"").unwrap();
+        for id in 1..=300 {
+            config.zones.push(ZoneMapping {
+                ra2_id: id,
+                leap_href: format!("/zone/{}", id),
+                name: format!("Area {} \u{2500} Load {}", id / 10, id),
+                ..Default::default()
+            });
+        }
+        config.save(&path).await.unwrap();
+
+        // A save that died mid-write leaves only its temp file behind
+        let full = toml::to_string_pretty(&config).unwrap();
+        std::fs::write(dir.join(".config.toml.crashed.tmp"), &full[..full.len() / 2]).unwrap();
+        assert_eq!(Config::load(&path).await.unwrap().zones.len(), 300);
+
+        // Readers racing a stream of saves always get a whole file
+        let writer = {
+            let (path, config) = (path.clone(), config.clone());
+            tokio::spawn(async move {
+                for _ in 0..50 {
+                    config.save(&path).await.unwrap();
+                }
+            })
+        };
+        while !writer.is_finished() {
+            assert_eq!(Config::read(&path).unwrap().zones.len(), 300);
+        }
+        writer.await.unwrap();
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[test]
+    fn level_limits_clamp_set_levels() {
+        let limits = LevelLimits {
+            min: Some(10.0),
+            max: Some(90.0),
+        };
+        assert_eq!(limits.apply(3.0), 10.0);
+        assert_eq!(limits.apply(50.0), 50.0);
+        assert_eq!(limits.apply(100.0), 90.0);
+        assert_eq!(limits.apply(0.0), 0.0);
+        assert_eq!(LevelLimits::default().apply(3.0), 3.0);
+
+        let mut config: Config = toml::from_str(
+            "[processor]\nhost = \"x\"\n[[zones]]\nra2_id = 1\nleap_href = \"/zone/5\"\nmin_level = 10.0",
+        )
+        .unwrap();
+        assert_eq!(config.level_limits_for(1).apply(3.0), 10.0);
+        assert_eq!(config.level_limits_for(2), LevelLimits::default());
+        assert!(config.validate().is_ok());
+        config.zones[0].max_level = Some(5.0);
+        assert!(config.validate().unwrap_err().contains("above max_level"));
+    }
+
+    #[test]
+    fn zones_match_by_name() {
+        let mut config: Config = toml::from_str("[processor]\nhost = \"x\"").unwrap();
+        for (id, name) in [
+            (1, "KITCHEN \u{2500} CEILING LIGHTS"),
+            (2, "KITCHEN \u{2500} CEILING LIGHTS 2"),
+            (3, "HALL \u{2500} CANS"),
+        ] {
+            config.zones.push(ZoneMapping {
+                ra2_id: id,
+                leap_href: format!("/zone/{}", id),
+                name: name.to_string(),
+                ..Default::default()
+            });
+        }
+        let ids = |q: &str| config.zones_matching_name(q).iter().map(|(id, _)| *id).collect::<Vec<_>>();
+
+        assert_eq!(ids("  kitchen \u{2500} ceiling lights "), vec![1]);
+        assert_eq!(ids("kitchen ceiling"), vec![1, 2]);
+        assert_eq!(ids("hall cans"), vec![3]);
+        assert_eq!(ids("cans"), vec![3]);
+        assert!(ids("garage").is_empty());
+        assert!(ids("  ").is_empty());
+        assert_eq!(ids("kitchen - ceiling lights"), vec![1]);
+    }
+
+    #[test]
+    fn savant_hosts_are_a_table_or_an_array() {
+        let mut config: Config =
+            toml::from_str("[processor]\nhost = \"x\"\n[savant]\nhost = \"10.0.0.20\"").unwrap();
+        assert_eq!(config.savant.len(), 1);
+        let saved = toml::to_string(&config).unwrap();
+        assert!(saved.contains("[savant]\n") && !saved.contains("[[savant]]"));
+
+        config.savant.push(config.savant[0].clone());
+        config.savant[1].host = "10.0.0.21".to_string();
+        config.savant_zones.push(SavantZoneMapping {
+            ra2_id: 300,
+            host: 1,
+            address: "001".to_string(),
+            ..Default::default()
+        });
+        assert!(config.validate().is_ok());
+        let reloaded: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(reloaded.savant[1].host, "10.0.0.21");
+        assert_eq!(reloaded.savant_zones_for(1).len(), 1);
+
+        config.savant.pop();
+        assert!(config.validate().unwrap_err().contains("host 1"));
+    }
+
+    #[test]
+    fn area_separator_must_be_recognised() {
+        let mut config: Config = toml::from_str("[processor]\nhost = \"x\"\n[web]\narea_separator = \"/\"").unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(join_area_name("Hall", "Cans", config.web.area_separator), "Hall / Cans");
+        config.web.area_separator = ':';
+        assert!(config.validate().unwrap_err().contains("area_separator"));
+    }
+
+    #[test]
+    fn id_ranges_must_not_overlap() {
+        let toml = "[processor]\nhost = \"x\"\n[id_ranges]\nleap = { start = 1, end = 199 }\nsavant = { start = 200, end = 299 }";
+        let mut config: Config = toml::from_str(toml).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.leap_ids(), IdRange { start: 1, end: 199 });
+        assert_eq!(config.savant_ids(500).start, 200);
+
+        config.id_ranges.as_mut().unwrap().savant.start = 150;
+        assert!(config.validate().unwrap_err().contains("overlap"));
+        config.id_ranges.as_mut().unwrap().savant = IdRange { start: 300, end: 250 };
+        assert!(config.validate().unwrap_err().contains("id_ranges.savant"));
+
+        config.id_ranges = None;
+        assert_eq!(config.savant_ids(500), IdRange::starting_at(500));
+    }
+
+    #[test]
+    fn virtual_keypads_need_their_own_ids() {
+        let toml = "[processor]\nhost = \"x\"\n\
+                    [[buttons]]\nra2_id = 40\ncomponent = 81\nled_href = \"/led/412\"\n\
+                    [[keypads]]\nra2_id = 41\nname = \"Hall \u{2500} Scenes\"";
+        let mut config: Config = toml::from_str(toml).unwrap();
+        assert!(config.validate().is_ok());
+
+        config.keypads[0].ra2_id = 40;
+        assert!(config.validate().unwrap_err().contains("[[buttons]] keypad"));
+    }
+}
@@ -0,0 +1,247 @@
+//! Health audit of a loaded config: static validation plus optional live
+//! checks that every LEAP href and Savant load still exists.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::config::{Config, IdRange};
+use crate::leap_client::{LeapHeader, LeapRequest};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    /// `config`, `leap` or `savant`.
+    pub source: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ra2_id: Option<u32>,
+    pub message: String,
+}
+
+impl Issue {
+    fn new(severity: Severity, source: &'static str, ra2_id: Option<u32>, message: String) -> Self {
+        Self {
+            severity,
+            source,
+            ra2_id,
+            message,
+        }
+    }
+}
+
+/// Problems detectable from the config alone.
+pub fn static_issues(config: &Config) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if let Err(e) = config.validate() {
+        issues.push(Issue::new(Severity::Error, "config", None, e));
+    }
+    if config.zones.is_empty() && config.savant_zones.is_empty() {
+        issues.push(Issue::new(
+            Severity::Warning,
+            "config",
+            None,
+            "No zones configured".to_string(),
+        ));
+    }
+    issues
+}
+
+/// Read every configured zone and LED href from the processor. Stops at the
+/// first transport error, since every later read would fail the same way.
+pub async fn leap_issues(config: &Config, certs_dir: &Path) -> Vec<Issue> {
+    let processor = &config.processor;
+    let hrefs = config
+        .zones
+        .iter()
+        .map(|z| (z.ra2_id, z.leap_href.as_str(), z.name.as_str()))
+        .chain(config.buttons.iter().map(|b| (b.ra2_id, b.led_href.as_str(), b.name.as_str())));
+
+    let mut issues = Vec::new();
+    let unreachable = |e: anyhow::Error| {
+        Issue::new(
+            Severity::Error,
+            "leap",
+            None,
+            format!("Processor {}:{} unreachable: {}", processor.host, processor.leap_port, e),
+        )
+    };
+    let mut session = match crate::leap_client::LeapSession::connect(
+        &processor.host,
+        processor.leap_port,
+        certs_dir,
+        &processor.ca_files,
+        crate::leap_client::DEFAULT_REQUEST_TIMEOUT,
+    )
+    .await
+    {
+        Ok(session) => session,
+        Err(e) => return vec![unreachable(e)],
+    };
+    for (ra2_id, href, name) in hrefs {
+        let req = LeapRequest {
+            communique_type: "ReadRequest".into(),
+            header: LeapHeader {
+                url: href.to_string(),
+                client_tag: None,
+                extra: Default::default(),
+            },
+            body: None,
+        };
+        match session.request(&req, crate::leap_client::DEFAULT_REQUEST_TIMEOUT).await {
+            Ok(resp) => {
+                let status = resp.header.status_code.unwrap_or_default();
+                if !status.starts_with("200") {
+                    issues.push(Issue::new(
+                        Severity::Error,
+                        "leap",
+                        Some(ra2_id),
+                        format!("{} ('{}') could not be read: {}", href, name, status),
+                    ));
+                }
+            }
+            Err(e) => {
+                issues.push(unreachable(e));
+                break;
+            }
+        }
+    }
+    info!("LEAP audit finished with {} issue(s)", issues.len());
+    issues
+}
+
+/// Enumerate each Savant host's loads and flag configured loads it no
+/// longer reports.
+pub async fn savant_issues(config: &Config) -> Vec<Issue> {
+    if config.savant_zones.is_empty() {
+        return Vec::new();
+    }
+
+    let mut issues = Vec::new();
+    for (host, savant) in config.savant.iter().enumerate() {
+        match crate::savant_discover::discover_zones(&savant.host, savant.port, IdRange::starting_at(1), &savant.device, savant.max_discovered_zones, config.web.area_separator).await {
+            Ok((_, found)) => {
+                let found = found
+                    .into_iter()
+                    .map(|z| (z.address, z.load_offset))
+                    .collect::<HashSet<_>>();
+                issues.extend(missing_savant_loads(config, host, &found));
+            }
+            Err(e) => issues.push(Issue::new(
+                Severity::Error,
+                "savant",
+                None,
+                format!("Savant host {}:{} unreachable: {:#}", savant.host, savant.port, e),
+            )),
+        }
+    }
+    issues
+}
+
+fn missing_savant_loads(config: &Config, host: usize, found: &HashSet<(String, usize)>) -> Vec<Issue> {
+    config
+        .savant_zones
+        .iter()
+        .filter(|z| z.host == host && !found.contains(&(z.address.clone(), z.load_offset)))
+        .map(|z| {
+            Issue::new(
+                Severity::Warning,
+                "savant",
+                Some(z.ra2_id),
+                format!(
+                    "Load {}:{} ('{}') is not reported by the Savant host",
+                    z.address, z.load_offset, z.name
+                ),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SavantZoneMapping, ZoneMapping};
+    use crate::leap_mock::{MockLeapServer, MockZone};
+
+    fn config(zones: &[(u32, &str)]) -> Config {
+        let mut config: Config = toml::from_str("[processor]\nhost = \"127.0.0.1\"").unwrap();
+        config.zones = zones
+            .iter()
+            .map(|(id, href)| ZoneMapping {
+                ra2_id: *id,
+                leap_href: href.to_string(),
+                name: format!("Zone {}", id),
+                ..Default::default()
+            })
+            .collect();
+        config
+    }
+
+    #[test]
+    fn static_issues_report_validation_errors() {
+        assert!(static_issues(&config(&[(1, "/zone/5")])).is_empty());
+
+        let issues = static_issues(&config(&[(1, "/zone/5"), (1, "/zone/6")]));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert!(issues[0].message.contains("Duplicate ra2_id 1"));
+    }
+
+    #[tokio::test]
+    async fn leap_audit_flags_unknown_hrefs() {
+        let server = MockLeapServer::start(vec![MockZone {
+            href: "/zone/5",
+            name: "Pendants",
+            area: "Kitchen",
+            control_type: "Dimmed",
+            category: "Light",
+            level: 0.0,
+        }])
+        .await
+        .unwrap();
+        let mut config = config(&[(1, "/zone/5"), (2, "/zone/99")]);
+        config.processor.leap_port = server.port;
+
+        let issues = leap_issues(&config, &server.certs_dir).await;
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].ra2_id, Some(2));
+        assert!(issues[0].message.contains("404"));
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[test]
+    fn missing_savant_loads_are_warnings() {
+        let mut config = config(&[]);
+        config.savant_zones = vec![
+            SavantZoneMapping {
+                ra2_id: 10,
+                address: "001".to_string(),
+                load_offset: 1,
+                name: "Den".to_string(),
+                room: String::new(),
+                ..Default::default()
+            },
+            SavantZoneMapping {
+                ra2_id: 11,
+                address: "002".to_string(),
+                load_offset: 3,
+                name: "Hall".to_string(),
+                room: String::new(),
+                ..Default::default()
+            },
+        ];
+        let found = HashSet::from([("001".to_string(), 1)]);
+        let issues = missing_savant_loads(&config, 0, &found);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].ra2_id, Some(11));
+        assert_eq!(issues[0].severity, Severity::Warning);
+    }
+}
@@ -0,0 +1,202 @@
+//! What a config edit did to the zone list, matched by `ZoneIdentity` so an
+//! ra2_id remap shows up as a change rather than a remove and an add.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::zone_attributes::ZoneIdentity;
+
+#[derive(Debug, Default, Serialize)]
+pub struct ZoneDiff {
+    pub added: Vec<ZoneRef>,
+    pub removed: Vec<ZoneRef>,
+    pub changed: Vec<ZoneChange>,
+}
+
+/// A zone in the diff. `zone` is the LEAP href or Savant `address:offset`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ZoneRef {
+    pub backend: &'static str,
+    pub zone: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<usize>,
+    pub ra2_id: u32,
+    pub name: String,
+}
+
+/// A zone present before and after, with each field that differs. `zone`
+/// describes it as it is now.
+#[derive(Debug, Serialize)]
+pub struct ZoneChange {
+    #[serde(flatten)]
+    pub zone: ZoneRef,
+    pub changes: BTreeMap<String, FieldChange>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub from: Value,
+    pub to: Value,
+}
+
+impl ZoneDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// "2 added, 1 removed, 3 changed", or "no zone changes".
+    pub fn summary(&self) -> String {
+        if self.is_empty() {
+            return "no zone changes".to_string();
+        }
+        format!(
+            "{} added, {} removed, {} changed",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        )
+    }
+}
+
+/// Compare the LEAP and Savant zones of two configs. `old` is None when
+/// there was no config, so every zone counts as added.
+pub fn diff_zones(old: Option<&Config>, new: &Config) -> ZoneDiff {
+    let before = old.map(zones).unwrap_or_default();
+    let after = zones(new);
+
+    let mut diff = ZoneDiff::default();
+    for (key, (zone, fields)) in &after {
+        match before.get(key) {
+            None => diff.added.push(zone.clone()),
+            Some((_, old_fields)) => {
+                let changes = field_changes(old_fields, fields);
+                if !changes.is_empty() {
+                    diff.changed.push(ZoneChange {
+                        zone: zone.clone(),
+                        changes,
+                    });
+                }
+            }
+        }
+    }
+    diff.removed = before
+        .into_iter()
+        .filter(|(key, _)| !after.contains_key(key))
+        .map(|(_, (zone, _))| zone)
+        .collect();
+    diff
+}
+
+/// Every zone by identity, with its serialized fields to compare.
+fn zones(config: &Config) -> BTreeMap<ZoneIdentity, (ZoneRef, serde_json::Map<String, Value>)> {
+    let fields = |zone: Value| match zone {
+        Value::Object(fields) => fields,
+        _ => serde_json::Map::new(),
+    };
+    let leap = config.zones.iter().map(|z| {
+        let zone = ZoneRef {
+            backend: "leap",
+            zone: z.leap_href.clone(),
+            host: None,
+            ra2_id: z.ra2_id,
+            name: z.name.clone(),
+        };
+        (ZoneIdentity::leap(z), (zone, fields(serde_json::to_value(z).unwrap_or_default())))
+    });
+    let savant = config.savant_zones.iter().map(|z| {
+        let zone = ZoneRef {
+            backend: "savant",
+            zone: format!("{}:{}", z.address, z.load_offset),
+            host: (z.host > 0).then_some(z.host),
+            ra2_id: z.ra2_id,
+            name: z.name.clone(),
+        };
+        (ZoneIdentity::savant(z), (zone, fields(serde_json::to_value(z).unwrap_or_default())))
+    });
+    leap.chain(savant).collect()
+}
+
+fn field_changes(
+    old: &serde_json::Map<String, Value>,
+    new: &serde_json::Map<String, Value>,
+) -> BTreeMap<String, FieldChange> {
+    old.keys()
+        .chain(new.keys())
+        .filter_map(|field| {
+            let (from, to) = (old.get(field), new.get(field));
+            (from != to).then(|| {
+                let change = FieldChange {
+                    from: from.cloned().unwrap_or(Value::Null),
+                    to: to.cloned().unwrap_or(Value::Null),
+                };
+                (field.clone(), change)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SavantZoneMapping, ZoneMapping};
+
+    fn config(zones: Vec<ZoneMapping>, savant_zones: Vec<SavantZoneMapping>) -> Config {
+        let mut config: Config = toml::from_str("[processor]\nhost = \"10.0.0.5\"").unwrap();
+        config.zones = zones;
+        config.savant_zones = savant_zones;
+        config
+    }
+
+    fn zone(ra2_id: u32, href: &str, name: &str) -> ZoneMapping {
+        ZoneMapping {
+            ra2_id,
+            leap_href: href.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn zones_are_matched_by_identity() {
+        let lamp = SavantZoneMapping {
+            ra2_id: 200,
+            address: "01A".to_string(),
+            load_offset: 3,
+            name: "Lamp".to_string(),
+            ..Default::default()
+        };
+        let old = config(
+            vec![zone(1, "/zone/5", "Kitchen"), zone(2, "/zone/6", "Hall"), zone(3, "/zone/7", "Den")],
+            vec![lamp.clone()],
+        );
+        let mut dimmed = zone(2, "/zone/6", "Hallway");
+        dimmed.min_level = Some(10.0);
+        let new = config(
+            vec![zone(7, "/zone/5", "Kitchen"), dimmed, zone(4, "/zone/8", "Porch")],
+            vec![lamp],
+        );
+
+        let diff = diff_zones(Some(&old), &new);
+        assert_eq!(diff.summary(), "1 added, 1 removed, 2 changed");
+        assert_eq!((diff.added[0].zone.as_str(), diff.added[0].ra2_id), ("/zone/8", 4));
+        assert_eq!((diff.removed[0].zone.as_str(), diff.removed[0].name.as_str()), ("/zone/7", "Den"));
+
+        let kitchen = &diff.changed[0];
+        assert_eq!(kitchen.zone.ra2_id, 7);
+        assert_eq!(kitchen.changes.keys().collect::<Vec<_>>(), ["ra2_id"]);
+        assert_eq!(kitchen.changes["ra2_id"], FieldChange { from: 1.into(), to: 7.into() });
+        let hall = &diff.changed[1];
+        assert_eq!(hall.changes.keys().collect::<Vec<_>>(), ["min_level", "name"]);
+        assert_eq!(hall.changes["min_level"], FieldChange { from: Value::Null, to: 10.0.into() });
+
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(json["changed"][0]["zone"], "/zone/5");
+        assert_eq!(json["changed"][0]["changes"]["ra2_id"]["to"], 7);
+
+        assert!(diff_zones(Some(&new), &new).is_empty());
+        assert_eq!(diff_zones(None, &new).added.len(), 4);
+    }
+}
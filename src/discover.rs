@@ -1,16 +1,47 @@
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use tracing::{info, warn};
 
-use crate::config::{Config, ProcessorConfig, TelnetConfig, ZoneMapping};
+use crate::config::{join_area_name, Config, IdRange, ProcessorConfig, CONFIG_VERSION, TelnetConfig, ZoneMapping};
+use crate::id_map::IdMap;
 use crate::leap_client::{LeapHeader, LeapRequest};
+use crate::level_curve::LevelCurve;
+use crate::zone_attributes::ZoneAttributeStore;
 
-/// Query the processor for all areas and their zones, returning mappings with sequential RA2 IDs.
+/// LEAP zone `ControlType`s kept when the user doesn't choose.
+pub const DEFAULT_ZONE_TYPES: &str = "dimmed,switched";
+
+/// Split a comma-separated `ControlType` list such as `"dimmed,shade"`.
+pub fn parse_zone_types(types: &str) -> Vec<String> {
+    types
+        .split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Whether a zone with this `ControlType` passes the `types` filter
+/// (case-insensitive). Zones from firmware that doesn't report a type are kept.
+fn type_selected(control_type: Option<&str>, types: &[String]) -> bool {
+    match control_type {
+        Some(ct) => types.iter().any(|t| t.eq_ignore_ascii_case(ct)),
+        None => true,
+    }
+}
+
+/// Query the processor for all areas and their zones, returning mappings with sequential RA2 IDs
+/// from the start of `ids`, named "Area ─ Zone" with `separator` as the ─. Only zones whose
+/// `ControlType` is listed in `types` are returned. Fails if `ids` can't hold every zone.
 pub async fn discover_zones(
     host: &str,
     port: u16,
     certs_dir: &Path,
+    ca_files: &[PathBuf],
+    types: &[String],
+    ids: IdRange,
+    separator: char,
 ) -> Result<Vec<ZoneMapping>> {
     // Fetch all areas
     let area_req = LeapRequest {
@@ -23,7 +54,17 @@ pub async fn discover_zones(
         body: None,
     };
 
-    let area_resp = crate::leap_client::one_shot_request(host, port, certs_dir, &area_req)
+    // One connection for the whole walk, rather than a handshake per area
+    let mut session = crate::leap_client::LeapSession::connect(
+        host,
+        port,
+        certs_dir,
+        ca_files,
+        crate::leap_client::DEFAULT_REQUEST_TIMEOUT,
+    )
+    .await?;
+    let area_resp = session
+        .request(&area_req, crate::leap_client::DEFAULT_REQUEST_TIMEOUT)
         .await
         .context("Failed to read /area")?;
 
@@ -32,7 +73,7 @@ pub async fn discover_zones(
         .context("Response body missing 'Areas' array")?;
 
     let mut zones = Vec::new();
-    let mut ra2_id: u32 = 1;
+    let mut ra2_id = ids.start;
 
     for area in areas {
         let area_href = area["href"].as_str().unwrap_or_default();
@@ -54,13 +95,12 @@ pub async fn discover_zones(
             body: None,
         };
 
-        let zone_resp = match crate::leap_client::one_shot_request(host, port, certs_dir, &zone_req).await {
-            Ok(resp) => resp,
-            Err(e) => {
-                warn!("Failed to read {}: {}", zone_url, e);
-                continue;
-            }
-        };
+        // An area the processor refuses still answers; an error here means
+        // the connection is gone
+        let zone_resp = session
+            .request(&zone_req, crate::leap_client::DEFAULT_REQUEST_TIMEOUT)
+            .await
+            .with_context(|| format!("Failed to read {}", zone_url))?;
 
         let zone_array = match zone_resp.body["Zones"].as_array() {
             Some(arr) => arr,
@@ -70,18 +110,31 @@ pub async fn discover_zones(
         for zone in zone_array {
             let zone_href = zone["href"].as_str().unwrap_or_default();
             let zone_name = zone["Name"].as_str().unwrap_or("Unknown Zone");
+            let control_type = zone["ControlType"].as_str();
+            let category = zone["Category"]["Type"].as_str();
 
             if zone_href.is_empty() {
                 continue;
             }
 
-            let name = format!("{} \u{2500} {}", area_name, zone_name);
+            let name = join_area_name(area_name, zone_name, separator);
+            if !type_selected(control_type, types) {
+                info!("  skipping {} ({})", name, control_type.unwrap_or_default());
+                continue;
+            }
+            if !ids.contains(ra2_id) {
+                anyhow::bail!("LEAP id range {} is full; widen id_ranges.leap", ids);
+            }
             info!("  [{}] {} → {}", ra2_id, name, zone_href);
 
             zones.push(ZoneMapping {
                 ra2_id,
                 leap_href: zone_href.to_string(),
+                discovered_name: Some(name.clone()),
                 name,
+                control_type: control_type.map(|s| s.to_string()),
+                category: category.map(|s| s.to_string()),
+                ..Default::default()
             });
             ra2_id += 1;
         }
@@ -90,8 +143,162 @@ pub async fn discover_zones(
     Ok(zones)
 }
 
+/// Read each zone's current level so the dashboard is accurate right after
+/// discovery. Returns RA2 levels (curves applied) by ra2_id; zones whose
+/// status can't be read, or that come after a failed read, are left out.
+pub async fn read_levels(
+    host: &str,
+    port: u16,
+    certs_dir: &Path,
+    ca_files: &[PathBuf],
+    zones: &[ZoneMapping],
+    default_curve: &LevelCurve,
+) -> HashMap<u32, f64> {
+    let map = IdMap::from_zones(zones, default_curve);
+    let mut levels = HashMap::new();
+    let mut session = match crate::leap_client::LeapSession::connect(
+        host,
+        port,
+        certs_dir,
+        ca_files,
+        crate::leap_client::DEFAULT_REQUEST_TIMEOUT,
+    )
+    .await
+    {
+        Ok(session) => session,
+        Err(e) => {
+            warn!("Failed to connect to read initial levels: {}", e);
+            return levels;
+        }
+    };
+    for z in zones {
+        let status_url = format!("{}/status", z.leap_href);
+        let req = LeapRequest {
+            communique_type: "ReadRequest".into(),
+            header: LeapHeader {
+                url: status_url.clone(),
+                client_tag: None,
+                extra: Default::default(),
+            },
+            body: None,
+        };
+        let resp = match session.request(&req, crate::leap_client::DEFAULT_REQUEST_TIMEOUT).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                // The connection is gone; the rest would only time out too
+                warn!("Failed to read {}: {}", status_url, e);
+                break;
+            }
+        };
+        if let Some(level) = resp.body["ZoneStatus"]["Level"].as_f64() {
+            levels.insert(z.ra2_id, map.curve(z.ra2_id).to_ra2(level));
+        }
+    }
+    info!("Read initial levels for {}/{} zones", levels.len(), zones.len());
+    levels
+}
+
+/// Result of merging freshly discovered zones into an existing zone list.
+pub struct MergedZones {
+    pub zones: Vec<ZoneMapping>,
+    /// ra2_ids of zones that were not in the existing list.
+    pub added: Vec<u32>,
+    /// Existing zones the processor no longer reports. They are kept so their
+    /// ids and names survive; the user can delete them by hand.
+    pub missing: Vec<u32>,
+}
+
+/// Merge `discovered` into `existing` by `leap_href`. Matched zones keep their
+/// ra2_id, custom name and overrides; new zones get ids in `ids` after the
+/// highest one in use there (including `reserved`, e.g. Savant ids). Fails if
+/// the range runs out.
+pub fn merge_zones(
+    existing: &[ZoneMapping],
+    discovered: &[ZoneMapping],
+    reserved: &HashSet<u32>,
+    ids: IdRange,
+) -> Result<MergedZones> {
+    let discovered_hrefs: HashSet<&str> =
+        discovered.iter().map(|z| z.leap_href.as_str()).collect();
+    let mut next_id = existing
+        .iter()
+        .map(|z| z.ra2_id)
+        .chain(reserved.iter().copied())
+        .filter(|id| ids.contains(*id))
+        .max()
+        .map_or(ids.start, |id| id.saturating_add(1));
+
+    let mut zones = existing.to_vec();
+    let mut added = Vec::new();
+    for z in discovered {
+        if let Some(e) = zones.iter_mut().find(|e| e.leap_href == z.leap_href) {
+            // The type comes from the processor, so refresh it
+            if z.control_type.is_some() {
+                e.control_type = z.control_type.clone();
+            }
+            if z.category.is_some() {
+                e.category = z.category.clone();
+            }
+            continue;
+        }
+        if !ids.contains(next_id) {
+            anyhow::bail!("LEAP id range {} is full; widen id_ranges.leap", ids);
+        }
+        info!("  new zone [{}] {} → {}", next_id, z.name, z.leap_href);
+        zones.push(ZoneMapping {
+            ra2_id: next_id,
+            ..z.clone()
+        });
+        added.push(next_id);
+        next_id += 1;
+    }
+
+    let missing: Vec<u32> = existing
+        .iter()
+        .filter(|e| !discovered_hrefs.contains(e.leap_href.as_str()))
+        .map(|e| e.ra2_id)
+        .collect();
+    for id in &missing {
+        warn!("Zone {} no longer reported by processor — kept in config", id);
+    }
+
+    Ok(MergedZones {
+        zones,
+        added,
+        missing,
+    })
+}
+
+/// What pairing and discovery take from an existing config.
+pub struct DiscoverySettings {
+    pub ids: IdRange,
+    pub separator: char,
+    /// `[processor] ca_files`, trusted when connecting to LEAP.
+    pub ca_files: Vec<PathBuf>,
+}
+
+/// The LEAP id range, area separator and extra CAs configured at `path`,
+/// or the defaults when there's no config yet.
+pub async fn discovery_settings(path: &Path) -> Result<DiscoverySettings> {
+    if path.exists() {
+        let config = Config::load(path).await?;
+        Ok(DiscoverySettings {
+            ids: config.leap_ids(),
+            separator: config.web.area_separator,
+            ca_files: config.processor.ca_files,
+        })
+    } else {
+        Ok(DiscoverySettings {
+            ids: IdRange::starting_at(1),
+            separator: crate::config::default_area_separator(),
+            ca_files: Vec::new(),
+        })
+    }
+}
+
 /// Write the config file. Backs up existing file to `.bak` if present.
-pub fn write_config(
+/// Sections other than `[processor]` and `zones` are preserved.
+pub async fn write_config(
     path: &Path,
     host: &str,
     port: u16,
@@ -99,36 +306,171 @@ pub fn write_config(
 ) -> Result<()> {
     if path.exists() {
         let bak = path.with_extension("toml.bak");
-        std::fs::copy(path, &bak)
+        tokio::fs::copy(path, &bak)
+            .await
             .with_context(|| format!("Failed to back up {} to {}", path.display(), bak.display()))?;
         warn!("Backed up existing config to {}", bak.display());
     }
 
-    // Preserve existing Savant config if present
-    let (existing_savant, existing_savant_zones) = if path.exists() {
-        match Config::load(path) {
-            Ok(old) => (old.savant, old.savant_zones),
-            Err(_) => (None, vec![]),
-        }
+    let existing = if path.exists() {
+        Config::load(path).await.ok()
     } else {
-        (None, vec![])
+        None
     };
+    // Discovery names zones as the processor does; keep what the user set
+    let attributes = existing.as_ref().map(ZoneAttributeStore::capture);
 
-    let config = Config {
-        processor: ProcessorConfig {
-            host: host.to_string(),
-            leap_port: port,
+    let mut config = match existing {
+        Some(old) => Config {
+            processor: ProcessorConfig {
+                host: host.to_string(),
+                leap_port: port,
+                ..old.processor
+            },
+            zones: zones.to_vec(),
+            ..old
+        },
+        None => Config {
+            version: CONFIG_VERSION,
+            level_curve: Default::default(),
+            processor: ProcessorConfig::new(host.to_string(), port),
+            telnet: TelnetConfig::default(),
+            web: Default::default(),
+            zones: zones.to_vec(),
+            savant: vec![],
+            savant_zones: vec![],
+            buttons: vec![],
+            keypads: vec![],
+            bridge: Default::default(),
+            notifications: Default::default(),
+            logging: Default::default(),
+            mqtt: None,
+            id_ranges: None,
         },
-        telnet: TelnetConfig::default(),
-        web: Default::default(),
-        zones: zones.to_vec(),
-        savant: existing_savant,
-        savant_zones: existing_savant_zones,
     };
+    if let Some(attributes) = attributes {
+        let restored = attributes.restore(&mut config);
+        if restored > 0 {
+            info!("Kept custom names and settings for {} zones", restored);
+        }
+    }
 
+    config
+        .validate()
+        .map_err(|e| anyhow::anyhow!("Discovered config is invalid: {}", e))?;
     let toml_str = toml::to_string_pretty(&config).context("Failed to serialize config")?;
-    std::fs::write(path, &toml_str)
-        .with_context(|| format!("Failed to write {}", path.display()))?;
+    crate::config::write_atomic(path, &toml_str).await
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leap_mock::{MockLeapServer, MockZone};
+
+    fn zone(ra2_id: u32, href: &str, name: &str) -> ZoneMapping {
+        ZoneMapping {
+            ra2_id,
+            leap_href: href.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merge_keeps_existing_ids_and_names() {
+        let existing = vec![zone(10, "/zone/5", "My Kitchen"), zone(11, "/zone/6", "Gone")];
+        let discovered = vec![
+            zone(1, "/zone/5", "KITCHEN \u{2500} MAIN"),
+            zone(2, "/zone/7", "HALL \u{2500} CANS"),
+        ];
+        let reserved: HashSet<u32> = [200].into_iter().collect();
+
+        let merged = merge_zones(&existing, &discovered, &reserved, IdRange::starting_at(1)).unwrap();
+
+        assert_eq!(merged.zones.len(), 3);
+        let kitchen = merged.zones.iter().find(|z| z.leap_href == "/zone/5").unwrap();
+        assert_eq!(kitchen.ra2_id, 10);
+        assert_eq!(kitchen.name, "My Kitchen");
+        let hall = merged.zones.iter().find(|z| z.leap_href == "/zone/7").unwrap();
+        assert_eq!(hall.ra2_id, 201);
+        assert_eq!(merged.added, vec![201]);
+        assert_eq!(merged.missing, vec![11]);
+
+        // Within a range, ids outside it don't push new zones past its end
+        let merged = merge_zones(&existing, &discovered, &reserved, IdRange { start: 1, end: 12 }).unwrap();
+        assert_eq!(merged.added, vec![12]);
+        let err = merge_zones(&existing, &discovered, &reserved, IdRange { start: 1, end: 11 })
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("1..11 is full"));
+    }
+
+    #[tokio::test]
+    async fn rediscovery_keeps_zone_settings() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-discover-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("config.toml");
+
+        let mut kitchen = zone(10, "/zone/5", "Pendants");
+        kitchen.min_level = Some(25.0);
+        write_config(&path, "10.0.0.5", 8081, &[kitchen]).await.unwrap();
+
+        // The processor's own name and a new id, as a fresh discovery gives
+        let discovered = [zone(1, "/zone/5", "KITCHEN \u{2500} MAIN"), zone(2, "/zone/6", "HALL")];
+        write_config(&path, "10.0.0.5", 8081, &discovered).await.unwrap();
+
+        let config = Config::load(&path).await.unwrap();
+        assert_eq!(config.zones[0].ra2_id, 1);
+        assert_eq!(config.zones[0].name, "Pendants");
+        assert_eq!(config.zones[0].min_level, Some(25.0));
+        assert_eq!((config.zones[1].name.as_str(), config.zones[1].min_level), ("HALL", None));
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn discover_zones_from_mock() {
+        let server = MockLeapServer::start(vec![
+            MockZone { area: "Kitchen", href: "/zone/5", name: "Main", control_type: "Dimmed", category: "Light", level: 40.0 },
+            MockZone { area: "Kitchen", href: "/zone/6", name: "Island", control_type: "Switched", category: "ExhaustFan", level: 0.0 },
+            MockZone { area: "Kitchen", href: "/zone/7", name: "Blinds", control_type: "Shade", category: "Shade", level: 0.0 },
+            MockZone { area: "Hall", href: "/zone/9", name: "Cans", control_type: "Dimmed", category: "Light", level: 0.0 },
+        ])
+        .await
+        .unwrap();
+
+        let types = parse_zone_types(DEFAULT_ZONE_TYPES);
+        let zones = discover_zones("127.0.0.1", server.port, &server.certs_dir, &[], &types, IdRange::starting_at(1), '\u{2500}')
+            .await
+            .unwrap();
+
+        let got: Vec<(u32, &str, &str, Option<&str>)> = zones
+            .iter()
+            .map(|z| (z.ra2_id, z.leap_href.as_str(), z.name.as_str(), z.control_type.as_deref()))
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                (1, "/zone/5", "Kitchen \u{2500} Main", Some("Dimmed")),
+                (2, "/zone/6", "Kitchen \u{2500} Island", Some("Switched")),
+                (3, "/zone/9", "Hall \u{2500} Cans", Some("Dimmed")),
+            ]
+        );
+        let categories: Vec<Option<&str>> = zones.iter().map(|z| z.category.as_deref()).collect();
+        assert_eq!(categories, vec![Some("Light"), Some("ExhaustFan"), Some("Light")]);
+
+        let shade = ["shade".to_string()];
+        let shades = discover_zones("127.0.0.1", server.port, &server.certs_dir, &[], &shade, IdRange::starting_at(1), '/')
+            .await
+            .unwrap();
+        assert_eq!(shades.len(), 1);
+        assert_eq!(shades[0].leap_href, "/zone/7");
+        assert_eq!(shades[0].name, "Kitchen / Blinds");
+
+        let levels = read_levels("127.0.0.1", server.port, &server.certs_dir, &[], &zones, &LevelCurve::Linear).await;
+        assert_eq!(levels.len(), 3);
+        assert_eq!(levels[&1], 40.0);
+        assert_eq!(levels[&3], 0.0);
+        // One connection per walk, not one per area or zone
+        assert_eq!(server.connections(), 3);
+    }
 }
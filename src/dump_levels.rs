@@ -0,0 +1,68 @@
+use anyhow::{bail, Context, Result};
+
+/// Fetch `/api/zones` from a running bridge and print it as a table (or raw JSON).
+pub async fn run(web: &str, json: bool) -> Result<()> {
+    let resp = crate::http_client::get(&crate::http_client::join(web, "/api/zones")).await?;
+    if resp.status != 200 {
+        bail!("{} returned HTTP {}: {}", web, resp.status, resp.body.trim());
+    }
+    let body: serde_json::Value =
+        serde_json::from_str(&resp.body).context("Invalid JSON from /api/zones")?;
+    let zones = body["zones"].as_array().cloned().unwrap_or_default();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&zones)?);
+    } else {
+        print!("{}", format_table(&zones));
+    }
+    Ok(())
+}
+
+fn format_table(zones: &[serde_json::Value]) -> String {
+    let name_width = zones
+        .iter()
+        .filter_map(|z| z["name"].as_str())
+        .map(|n| n.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("NAME".len());
+
+    let mut out = format!(
+        "{:>6}  {:<width$}  {:>7}  {}\n",
+        "ID",
+        "NAME",
+        "LEVEL",
+        "BACKEND",
+        width = name_width
+    );
+    for z in zones {
+        out.push_str(&format!(
+            "{:>6}  {:<width$}  {:>6.1}%  {}\n",
+            z["ra2_id"].as_u64().unwrap_or_default(),
+            z["name"].as_str().unwrap_or(""),
+            z["level"].as_f64().unwrap_or(0.0),
+            z["backend"].as_str().unwrap_or("?"),
+            width = name_width
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_aligns_columns() {
+        let zones = vec![
+            serde_json::json!({"ra2_id": 1, "name": "Kitchen", "level": 100.0, "backend": "leap"}),
+            serde_json::json!({"ra2_id": 200, "name": "Patio", "level": 0.0, "backend": "savant"}),
+        ];
+        assert_eq!(
+            format_table(&zones),
+            "    ID  NAME       LEVEL  BACKEND\n\
+             \x20    1  Kitchen   100.0%  leap\n\
+             \x20  200  Patio       0.0%  savant\n"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{bail, Context, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Minimal plain-HTTP/1.1 client for talking to a running bridge's web API
+/// and posting webhooks. Only `http://host[:port][/path]` URLs are supported.
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+struct ParsedUrl {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+fn parse_url(url: &str) -> Result<ParsedUrl> {
+    let rest = url
+        .strip_prefix("http://")
+        .with_context(|| format!("Only http:// URLs are supported: {}", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((h, p)) => (h, p.parse().with_context(|| format!("Invalid port in {}", url))?),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        bail!("Missing host in {}", url);
+    }
+    Ok(ParsedUrl {
+        host: host.to_string(),
+        port,
+        path: path.to_string(),
+    })
+}
+
+/// Join a base URL (e.g. `http://localhost:8080`) and an API path.
+pub fn join(base: &str, path: &str) -> String {
+    format!("{}{}", base.trim_end_matches('/'), path)
+}
+
+pub async fn get(url: &str) -> Result<HttpResponse> {
+    request("GET", url, None).await
+}
+
+pub async fn post_json(url: &str, body: &serde_json::Value) -> Result<HttpResponse> {
+    let bytes = serde_json::to_vec(body)?;
+    request("POST", url, Some(("application/json", &bytes))).await
+}
+
+async fn request(method: &str, url: &str, body: Option<(&str, &[u8])>) -> Result<HttpResponse> {
+    let u = parse_url(url)?;
+    let mut stream = TcpStream::connect((u.host.as_str(), u.port))
+        .await
+        .with_context(|| format!("Failed to connect to {}:{}", u.host, u.port))?;
+
+    let mut req = format!(
+        "{} {} HTTP/1.1\r\nHost: {}:{}\r\nConnection: close\r\n",
+        method, u.path, u.host, u.port
+    );
+    if let Some((content_type, bytes)) = body {
+        req.push_str(&format!(
+            "Content-Type: {}\r\nContent-Length: {}\r\n",
+            content_type,
+            bytes.len()
+        ));
+    }
+    req.push_str("\r\n");
+    stream.write_all(req.as_bytes()).await?;
+    if let Some((_, bytes)) = body {
+        stream.write_all(bytes).await?;
+    }
+
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).await?;
+    parse_response(&raw)
+}
+
+fn parse_response(raw: &[u8]) -> Result<HttpResponse> {
+    let text = String::from_utf8_lossy(raw);
+    let (head, body) = text
+        .split_once("\r\n\r\n")
+        .context("Malformed HTTP response")?;
+    let status = head
+        .lines()
+        .next()
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|s| s.parse().ok())
+        .context("Malformed HTTP status line")?;
+    let chunked = head.lines().any(|l| {
+        let l = l.to_ascii_lowercase();
+        l.starts_with("transfer-encoding:") && l.contains("chunked")
+    });
+    let body = if chunked {
+        decode_chunked(body)?
+    } else {
+        body.to_string()
+    };
+    Ok(HttpResponse { status, body })
+}
+
+fn decode_chunked(mut body: &str) -> Result<String> {
+    let mut out = String::new();
+    loop {
+        let (size_line, rest) = body.split_once("\r\n").context("Truncated chunk")?;
+        let size = usize::from_str_radix(size_line.split(';').next().unwrap_or("").trim(), 16)
+            .context("Invalid chunk size")?;
+        if size == 0 {
+            return Ok(out);
+        }
+        out.push_str(rest.get(..size).context("Truncated chunk")?);
+        body = rest.get(size + 2..).unwrap_or("");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_urls() {
+        let u = parse_url("http://10.0.0.5:8080/api/zones").unwrap();
+        assert_eq!((u.host.as_str(), u.port, u.path.as_str()), ("10.0.0.5", 8080, "/api/zones"));
+        let u = parse_url("http://bridge.local").unwrap();
+        assert_eq!((u.host.as_str(), u.port, u.path.as_str()), ("bridge.local", 80, "/"));
+        assert!(parse_url("https://x").is_err());
+    }
+
+    #[test]
+    fn parse_chunked_response() {
+        let raw = b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n4\r\n{\"a\"\r\n3\r\n:1}\r\n0\r\n\r\n";
+        let resp = parse_response(raw).unwrap();
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body, "{\"a\":1}");
+    }
+}
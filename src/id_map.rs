@@ -1,27 +1,61 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::config::ZoneMapping;
+use crate::config::{ButtonMapping, ZoneMapping};
+use crate::level_curve::LevelCurve;
 
-/// Bidirectional map between RA2 integer IDs and LEAP zone hrefs.
+/// Bidirectional map between RA2 integer IDs and LEAP zone hrefs, plus the
+/// one-way LEAP LED → RA2 keypad component map.
 pub struct IdMap {
     ra2_to_leap: HashMap<u32, String>,
     leap_to_ra2: HashMap<String, u32>,
+    leds: HashMap<String, (u32, u32)>,
+    curves: HashMap<u32, LevelCurve>,
+    default_curve: LevelCurve,
+    no_fade: HashSet<u32>,
 }
 
 impl IdMap {
-    pub fn from_zones(zones: &[ZoneMapping]) -> Self {
+    pub fn from_zones(zones: &[ZoneMapping], default_curve: &LevelCurve) -> Self {
         let mut ra2_to_leap = HashMap::new();
         let mut leap_to_ra2 = HashMap::new();
+        let mut curves = HashMap::new();
+        let mut no_fade = HashSet::new();
         for z in zones {
             ra2_to_leap.insert(z.ra2_id, z.leap_href.clone());
             leap_to_ra2.insert(z.leap_href.clone(), z.ra2_id);
+            if let Some(curve) = &z.level_curve {
+                curves.insert(z.ra2_id, curve.clone());
+            }
+            if !z.accepts_fade() {
+                no_fade.insert(z.ra2_id);
+            }
         }
         Self {
             ra2_to_leap,
             leap_to_ra2,
+            leds: HashMap::new(),
+            curves,
+            default_curve: default_curve.clone(),
+            no_fade,
         }
     }
 
+    pub fn with_buttons(mut self, buttons: &[ButtonMapping]) -> Self {
+        for b in buttons {
+            self.leds.insert(b.led_href.clone(), (b.ra2_id, b.component));
+        }
+        self
+    }
+
+    pub fn curve(&self, id: u32) -> &LevelCurve {
+        self.curves.get(&id).unwrap_or(&self.default_curve)
+    }
+
+    /// See `ZoneMapping::accepts_fade`.
+    pub fn accepts_fade(&self, id: u32) -> bool {
+        !self.no_fade.contains(&id)
+    }
+
     pub fn ra2_to_leap(&self, id: u32) -> Option<&str> {
         self.ra2_to_leap.get(&id).map(|s| s.as_str())
     }
@@ -29,4 +63,19 @@ impl IdMap {
     pub fn leap_to_ra2(&self, href: &str) -> Option<u32> {
         self.leap_to_ra2.get(href).copied()
     }
+
+    /// Status URLs for every mapped zone and LED, for re-reading current
+    /// state after a reconnect.
+    pub fn status_urls(&self) -> Vec<String> {
+        self.leap_to_ra2
+            .keys()
+            .chain(self.leds.keys())
+            .map(|href| format!("{}/status", href))
+            .collect()
+    }
+
+    /// RA2 (device, component) for a LEAP LED href.
+    pub fn led_to_ra2(&self, href: &str) -> Option<(u32, u32)> {
+        self.leds.get(href).copied()
+    }
 }
@@ -0,0 +1,167 @@
+//! `init-config`: a commented sample config.toml for setting up by hand.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use toml_edit::{DocumentMut, Table};
+
+use crate::config::{Config, ZoneMapping};
+
+/// Commented out at the end of the sample, since a Savant host is optional.
+const SAVANT_EXAMPLE: &str = "
+# Savant Smart Host, for lighting loads that aren't on the RA3 processor.
+# `ra-bridge savant-discover --host <ip>` fills this in, zones included.
+# Use [[savant]] once per host to bridge several; zones then pick theirs
+# with `host = <index>`.
+#
+# [savant]
+# host = \"192.168.1.60\"
+# port = 8480
+#
+# [[savant_zones]]
+# ra2_id = 200
+# address = \"01A\"
+# load_offset = 1
+# name = \"Den ─ Lamp\"
+# room = \"Den\"
+";
+
+/// Write the sample config to `output`. An existing file is only replaced
+/// with `force`.
+pub async fn run(output: &Path, force: bool) -> Result<()> {
+    if output.exists() && !force {
+        bail!("{} already exists; pass --force to overwrite it", output.display());
+    }
+    crate::config::write_atomic(output, sample_config()?).await
+}
+
+/// A small but complete config: processor, telnet and web settings at their
+/// defaults, two example zones, and a commented-out Savant section. It's
+/// built from a real `Config` so the values never drift from the defaults.
+fn sample_config() -> Result<String> {
+    let mut config: Config = toml::from_str("[processor]\nhost = \"192.168.1.50\"")?;
+    config.zones = vec![
+        ZoneMapping {
+            ra2_id: 1,
+            leap_href: "/zone/1".to_string(),
+            name: "Kitchen ─ Main".to_string(),
+            ..Default::default()
+        },
+        ZoneMapping {
+            ra2_id: 2,
+            leap_href: "/zone/2".to_string(),
+            name: "Hall ─ Cans".to_string(),
+            min_level: Some(10.0),
+            ..Default::default()
+        },
+    ];
+
+    let mut doc: DocumentMut = toml::to_string_pretty(&config)
+        .context("Failed to serialize sample config")?
+        .parse()
+        .context("Failed to parse sample config")?;
+    // Sections left out stay at their defaults
+    for key in ["savant_zones", "bridge", "notifications", "logging"] {
+        doc.remove(key);
+    }
+
+    comment_key(
+        doc.as_table_mut(),
+        "version",
+        "# ra-bridge configuration. Pairing (`ra-bridge pair --host <ip>`) writes\n\
+         # this file for you; edit it by hand or from the web UI.\n\n",
+    );
+    comment_table(
+        &mut doc,
+        "processor",
+        "\n# The RadioRA 3 processor. Pairing certificates live in --certs-dir.\n",
+    );
+    comment_table_key(&mut doc, "processor", "ping_interval_secs", "# Seconds between LEAP keepalive pings\n");
+    comment_table(
+        &mut doc,
+        "telnet",
+        "\n# The RadioRA 2 style integration port controllers connect to.\n\
+         # Log in as lutron / integration.\n",
+    );
+    comment_table_key(&mut doc, "telnet", "level_decimals", "# Decimal places in ~OUTPUT levels (0-2)\n");
+    comment_table(&mut doc, "web", "\n# The management UI and HTTP API.\n");
+    comment_table_key(&mut doc, "web", "project_name", "# ProjectName in the exported DbXmlInfo.xml\n");
+    comment_table_key(
+        &mut doc,
+        "web",
+        "area_separator",
+        "# Goes between area and load in discovered zone names\n",
+    );
+    if let Some(zones) = doc["zones"].as_array_of_tables_mut() {
+        if let Some(first) = zones.get_mut(0) {
+            first.decor_mut().set_prefix(
+                "\n# One entry per RA3 zone. ra2_id is the integration id controllers\n\
+                 # use; leap_href is the processor's zone. min_level / max_level\n\
+                 # (percent) and level_curve (\"linear\", \"square\") are optional.\n",
+            );
+        }
+    }
+    doc.set_trailing(SAVANT_EXAMPLE);
+    Ok(doc.to_string())
+}
+
+fn comment_table(doc: &mut DocumentMut, table: &str, comment: &str) {
+    if let Some(table) = doc[table].as_table_mut() {
+        table.decor_mut().set_prefix(comment);
+    }
+}
+
+fn comment_key(table: &mut Table, key: &str, comment: &str) {
+    if let Some(mut key) = table.key_mut(key) {
+        key.leaf_decor_mut().set_prefix(comment);
+    }
+}
+
+/// `comment_key` for a key inside one of the document's tables.
+fn comment_table_key(doc: &mut DocumentMut, table: &str, key: &str, comment: &str) {
+    if let Some(table) = doc[table].as_table_mut() {
+        comment_key(table, key, comment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_is_a_valid_config() {
+        let sample = sample_config().unwrap();
+        let config: Config = toml::from_str(&sample).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.processor.host, "192.168.1.50");
+        assert_eq!(config.zones.len(), 2);
+        assert!(config.savant.is_empty());
+        assert!(sample.contains("# The RadioRA 3 processor"));
+        assert!(sample.contains("# [savant]\n# host = "));
+
+        // Uncommenting the Savant example gives a working Savant section
+        let uncommented = sample.replace("\n# [", "\n[").replace("\n# host", "\nhost").replace("\n# port", "\nport");
+        let uncommented = ["ra2_id", "address", "load_offset", "name", "room"]
+            .iter()
+            .fold(uncommented, |s, key| s.replace(&format!("\n# {} =", key), &format!("\n{} =", key)));
+        let config: Config = toml::from_str(&uncommented).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.savant_zones[0].address, "01A");
+    }
+
+    #[tokio::test]
+    async fn existing_files_need_force() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-init-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("config.toml");
+        tokio::fs::write(&path, "mine").await.unwrap();
+
+        let err = run(&path, false).await.unwrap_err();
+        assert!(err.to_string().contains("--force"));
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), "mine");
+
+        run(&path, true).await.unwrap();
+        assert!(Config::load(&path).await.is_ok());
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+}
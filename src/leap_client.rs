@@ -1,18 +1,24 @@
+use std::collections::HashMap;
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
 use rustls::client::WebPkiServerVerifier;
 use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
 use serde::{Deserialize, Serialize};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::{broadcast, mpsc, watch};
 use tokio_rustls::TlsConnector;
 use tracing::{error, info, warn};
 
+use crate::config::ProcessorConfig;
+use crate::protocol_log::protocol_log;
+use crate::state::{reconnect_delay, BackendStatus};
+
 /// TLS certificate verifier that validates the chain but skips hostname checking.
 /// Lutron processors use DNS-based names in their certs (e.g. "radiora3-xxxx-server")
 /// but are connected to by IP address.
@@ -72,7 +78,7 @@ impl ServerCertVerifier for NoHostnameVerification {
 }
 
 /// A LEAP request to send to the processor.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LeapRequest {
     #[serde(rename = "CommuniqueType")]
     pub communique_type: String,
@@ -93,7 +99,7 @@ pub struct LeapHeader {
 }
 
 /// A LEAP event received from the processor.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct LeapEvent {
     #[serde(rename = "CommuniqueType")]
@@ -104,19 +110,23 @@ pub struct LeapEvent {
     pub body: serde_json::Value,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct LeapEventHeader {
     #[serde(rename = "Url", default)]
     pub url: String,
     #[serde(rename = "StatusCode", default)]
     pub status_code: Option<String>,
+    /// Echoed from the request, so responses can be matched to it.
+    #[serde(rename = "ClientTag", default, skip_serializing_if = "Option::is_none")]
+    pub client_tag: Option<String>,
     #[serde(flatten)]
     pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
-/// Build a TLS connector using certificates from the certs directory.
-pub fn build_leap_tls_connector(certs_dir: &Path) -> Result<TlsConnector> {
+/// Build a TLS connector using certificates from the certs directory, also
+/// trusting any extra CA PEM files from `[processor] ca_files`.
+pub fn build_leap_tls_connector(certs_dir: &Path, ca_files: &[PathBuf]) -> Result<TlsConnector> {
     let ca_path = certs_dir.join("ca.crt");
     let cert_path = certs_dir.join("ra-bridge.crt");
     let key_path = certs_dir.join("ra-bridge.key");
@@ -130,6 +140,22 @@ pub fn build_leap_tls_connector(certs_dir: &Path) -> Result<TlsConnector> {
         root_store.add(cert)?;
     }
 
+    // User-supplied CAs for processors chaining to something else
+    for path in ca_files {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read CA file: {}", path.display()))?;
+        let mut r = BufReader::new(pem.as_slice());
+        let certs = rustls_pemfile::certs(&mut r)
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Failed to parse CA file: {}", path.display()))?;
+        if certs.is_empty() {
+            anyhow::bail!("No certificates found in CA file: {}", path.display());
+        }
+        for cert in certs {
+            root_store.add(cert)?;
+        }
+    }
+
     // Also add well-known Lutron CAs as fallback trust anchors
     let extra_cas = [
         crate::leap_pairing::LUTRON_ROOT_CA_PEM,
@@ -184,61 +210,212 @@ pub fn build_leap_tls_connector(certs_dir: &Path) -> Result<TlsConnector> {
     Ok(TlsConnector::from(Arc::new(config)))
 }
 
-/// Send a single LEAP request and return the response. Connects, sends, reads one line, disconnects.
-pub async fn one_shot_request(
-    host: &str,
+/// A message still incomplete past this size is handed over as-is so a
+/// garbled stream can't grow the buffer forever.
+const MAX_MESSAGE_LEN: usize = 1 << 20;
+
+/// Splits the LEAP stream into JSON messages. Processors send one object per
+/// line, but an object pretty-printed over several lines is accumulated
+/// until it parses. Lines are read straight into the buffer, so a read
+/// cancelled by `select!` loses nothing.
+#[derive(Default)]
+struct MessageReader {
+    buf: String,
+}
+
+impl MessageReader {
+    /// The next complete message, or None at end of stream. Text that can
+    /// never parse is returned too, for the caller to report.
+    async fn next<R: AsyncBufRead + Unpin>(&mut self, reader: &mut R) -> std::io::Result<Option<String>> {
+        loop {
+            if reader.read_line(&mut self.buf).await? == 0 {
+                return Ok(None);
+            }
+            let text = self.buf.trim();
+            if text.is_empty() {
+                self.buf.clear();
+                continue;
+            }
+            match serde_json::from_str::<serde::de::IgnoredAny>(text) {
+                Err(e) if e.is_eof() && text.len() < MAX_MESSAGE_LEN => continue,
+                _ => {
+                    let message = text.to_string();
+                    self.buf.clear();
+                    return Ok(Some(message));
+                }
+            }
+        }
+    }
+}
+
+/// How long discovery waits for a `LeapSession` request before giving up.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+type TlsHalves = (
+    tokio::io::BufReader<tokio::io::ReadHalf<tokio_rustls::client::TlsStream<TcpStream>>>,
+    tokio::io::WriteHalf<tokio_rustls::client::TlsStream<TcpStream>>,
+);
+
+/// A connection for a run of requests outside the bridge, such as walking
+/// every area or zone, so they share one TLS handshake. Any error means the
+/// connection is gone; LEAP error statuses come back as ordinary responses.
+pub struct LeapSession {
+    host: String,
     port: u16,
-    certs_dir: &Path,
-    request: &LeapRequest,
-) -> Result<LeapEvent> {
-    let connector = build_leap_tls_connector(certs_dir)?;
-    let tcp = TcpStream::connect((host, port)).await?;
-    let server_name = rustls::pki_types::ServerName::try_from(host.to_string())
-        .unwrap_or_else(|_| {
-            rustls::pki_types::ServerName::IpAddress(
-                host.parse::<std::net::IpAddr>()
-                    .expect("Invalid host address")
-                    .into(),
-            )
-        });
-    let tls = connector.connect(server_name, tcp).await?;
+    stream: TlsHalves,
+    messages: MessageReader,
+    next_tag: u64,
+}
 
-    let (reader, mut writer) = tokio::io::split(tls);
-    let mut reader = tokio::io::BufReader::new(reader);
+impl LeapSession {
+    /// Connect, giving up after `timeout`.
+    pub async fn connect(
+        host: &str,
+        port: u16,
+        certs_dir: &Path,
+        ca_files: &[PathBuf],
+        timeout: Duration,
+    ) -> Result<Self> {
+        tokio::time::timeout(timeout, Self::open(host, port, certs_dir, ca_files))
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!("Connecting to {}:{} timed out after {:.1}s", host, port, timeout.as_secs_f64())
+            })?
+    }
 
-    let mut msg = serde_json::to_string(request)?;
-    msg.push_str("\r\n");
-    writer.write_all(msg.as_bytes()).await?;
+    async fn open(host: &str, port: u16, certs_dir: &Path, ca_files: &[PathBuf]) -> Result<Self> {
+        let connector = build_leap_tls_connector(certs_dir, ca_files)?;
+        let tcp = TcpStream::connect((host, port)).await?;
+        let server_name = rustls::pki_types::ServerName::try_from(host.to_string())
+            .unwrap_or_else(|_| {
+                rustls::pki_types::ServerName::IpAddress(
+                    host.parse::<std::net::IpAddr>()
+                        .expect("Invalid host address")
+                        .into(),
+                )
+            });
+        let tls = connector.connect(server_name, tcp).await?;
 
-    let mut line = String::new();
-    reader.read_line(&mut line).await?;
-    let event: LeapEvent = serde_json::from_str(line.trim())
-        .with_context(|| format!("Failed to parse LEAP response: {}", line.trim()))?;
+        let (reader, writer) = tokio::io::split(tls);
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            stream: (tokio::io::BufReader::new(reader), writer),
+            messages: MessageReader::default(),
+            next_tag: 0,
+        })
+    }
 
-    Ok(event)
+    /// Send `request` and wait up to `timeout` for the response to it.
+    pub async fn request(&mut self, request: &LeapRequest, timeout: Duration) -> Result<LeapEvent> {
+        let (host, port) = (self.host.clone(), self.port);
+        tokio::time::timeout(timeout, self.exchange(request)).await.map_err(|_| {
+            anyhow::anyhow!(
+                "LEAP request {} to {}:{} timed out after {:.1}s",
+                request.header.url,
+                host,
+                port,
+                timeout.as_secs_f64()
+            )
+        })?
+    }
+
+    /// Responses are matched by ClientTag, so one that arrives after its
+    /// request timed out isn't taken for the next request's.
+    async fn exchange(&mut self, request: &LeapRequest) -> Result<LeapEvent> {
+        let mut request = request.clone();
+        let tag = request
+            .header
+            .client_tag
+            .get_or_insert_with(|| {
+                self.next_tag += 1;
+                format!("session-{}", self.next_tag)
+            })
+            .clone();
+        let (reader, writer) = &mut self.stream;
+
+        let mut msg = serde_json::to_string(&request)?;
+        msg.push_str("\r\n");
+        writer.write_all(msg.as_bytes()).await?;
+
+        loop {
+            let message = self
+                .messages
+                .next(reader)
+                .await?
+                .context("Processor closed the connection without responding")?;
+            let event: LeapEvent = serde_json::from_str(&message)
+                .with_context(|| format!("Failed to parse LEAP response: {}", message))?;
+            if event.header.client_tag.as_deref() == Some(tag.as_str()) {
+                return Ok(event);
+            }
+        }
+    }
 }
 
 /// Run the LEAP client. Sends requests from `req_rx`, publishes events on `event_tx`.
-/// Reconnects with exponential backoff on disconnect.
+/// Besides zone status, subscribes to each URL in `subscriptions` (e.g. LED
+/// status) on every connect.
+/// Reconnects with exponential backoff on disconnect; exits once `req_rx` is
+/// closed, or after `max_attempts` consecutive failures (0 = never).
 pub async fn run(
-    host: String,
-    port: u16,
-    certs_dir: std::path::PathBuf,
+    processor: ProcessorConfig,
+    certs_dir: PathBuf,
+    subscriptions: Vec<String>,
     mut req_rx: mpsc::Receiver<LeapRequest>,
     event_tx: broadcast::Sender<LeapEvent>,
+    status_tx: watch::Sender<BackendStatus>,
+    max_attempts: u32,
 ) -> Result<()> {
     let mut backoff = 1u64;
     let max_backoff = 60u64;
+    let mut attempts = 0u32;
 
     loop {
-        match connect_and_run(&host, port, &certs_dir, &mut req_rx, &event_tx).await {
-            Ok(()) => {
-                info!("LEAP connection closed gracefully");
+        let _ = status_tx.send(BackendStatus::Connecting);
+        let session = connect_and_run(&processor, &certs_dir, &subscriptions, &mut req_rx, &event_tx, &status_tx).await;
+        // A session that got as far as Connected starts the count over,
+        // however it ended
+        if *status_tx.borrow() == BackendStatus::Connected {
+            attempts = 0;
+            backoff = 1;
+        }
+        match session {
+            Ok(SessionEnd::Stopped) => {
+                info!("LEAP client stopped");
+                let _ = status_tx.send(BackendStatus::Stopped);
                 break;
             }
+            Ok(SessionEnd::RemoteClosed) => {
+                // Not a failure, so retry after a short delay rather than
+                // backing off
+                info!("Processor closed the LEAP connection. Reconnecting in 1s...");
+                if !reconnect_delay(Duration::from_secs(1), &req_rx).await {
+                    info!("LEAP client stopped");
+                    let _ = status_tx.send(BackendStatus::Stopped);
+                    break;
+                }
+            }
             Err(e) => {
+                attempts += 1;
+                if max_attempts > 0 && attempts >= max_attempts {
+                    error!("LEAP connection error: {}. Giving up after {} attempts", e, attempts);
+                    let _ = status_tx.send(BackendStatus::Failed {
+                        message: e.to_string(),
+                        attempts,
+                    });
+                    break;
+                }
+                let _ = status_tx.send(BackendStatus::Error {
+                    message: e.to_string(),
+                    attempts,
+                });
                 error!("LEAP connection error: {}. Reconnecting in {}s...", e, backoff);
-                tokio::time::sleep(tokio::time::Duration::from_secs(backoff)).await;
+                if !reconnect_delay(Duration::from_secs(backoff), &req_rx).await {
+                    info!("LEAP client stopped");
+                    let _ = status_tx.send(BackendStatus::Stopped);
+                    break;
+                }
                 backoff = (backoff * 2).min(max_backoff);
             }
         }
@@ -246,14 +423,102 @@ pub async fn run(
     Ok(())
 }
 
+/// Wait for the response carrying `tag` in its `ClientTag` header. Subscribe
+/// `events` before sending the request so the response can't be missed.
+pub async fn wait_for_client_tag(
+    events: &mut broadcast::Receiver<LeapEvent>,
+    tag: &str,
+) -> Option<LeapEvent> {
+    loop {
+        match events.recv().await {
+            Ok(event) if event.header.client_tag.as_deref() == Some(tag) => return Some(event),
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                warn!("Missed {} LEAP events waiting for ClientTag {}", n, tag);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// How a LEAP session ended, when it wasn't an error.
+#[derive(Debug, PartialEq)]
+enum SessionEnd {
+    /// The request channel closed: the bridge is stopping.
+    Stopped,
+    /// The processor closed the connection cleanly, e.g. while restarting.
+    RemoteClosed,
+}
+
+fn ping_timer(processor: &ProcessorConfig) -> tokio::time::Interval {
+    tokio::time::interval(Duration::from_secs(processor.ping_interval_secs.max(1)))
+}
+
+/// Keepalive read; any response to it, even an error status, shows the
+/// link is alive.
+const PING_URL: &str = "/server/1/status/ping";
+
+fn is_ping_response(event: &LeapEvent) -> bool {
+    event.header.url == PING_URL
+}
+
+/// `ClientTag` on `/zone/status` polls, so their responses can be told
+/// apart from everything else the processor sends.
+const POLL_CLIENT_TAG: &str = "ra-bridge-zone-poll";
+
+/// Tick `timer`, or wait forever when there isn't one.
+async fn tick(timer: &mut Option<tokio::time::Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Split a polled `MultipleZoneStatus` into one `ZoneStatus` event per zone,
+/// shaped like the subscription's, keeping only zones whose level changed
+/// since the last poll (`last`).
+fn zone_status_changes(response: &LeapEvent, last: &mut HashMap<String, f64>) -> Vec<LeapEvent> {
+    let Some(statuses) = response.body.get("ZoneStatuses").and_then(|s| s.as_array()) else {
+        warn!(
+            "Zone status poll failed: {}",
+            response.header.status_code.as_deref().unwrap_or("no ZoneStatuses in response")
+        );
+        return Vec::new();
+    };
+    statuses
+        .iter()
+        .filter_map(|status| {
+            let href = status.pointer("/Zone/href")?.as_str()?;
+            let level = status.get("Level")?.as_f64()?;
+            if last.insert(href.to_string(), level) == Some(level) {
+                return None;
+            }
+            Some(LeapEvent {
+                communique_type: "ReadResponse".to_string(),
+                header: LeapEventHeader {
+                    url: format!("{}/status", href),
+                    status_code: response.header.status_code.clone(),
+                    client_tag: None,
+                    extra: serde_json::Map::new(),
+                },
+                body: serde_json::json!({ "ZoneStatus": status }),
+            })
+        })
+        .collect()
+}
+
 async fn connect_and_run(
-    host: &str,
-    port: u16,
+    processor: &ProcessorConfig,
     certs_dir: &Path,
+    subscriptions: &[String],
     req_rx: &mut mpsc::Receiver<LeapRequest>,
     event_tx: &broadcast::Sender<LeapEvent>,
-) -> Result<()> {
-    let connector = build_leap_tls_connector(certs_dir)?;
+    status_tx: &watch::Sender<BackendStatus>,
+) -> Result<SessionEnd> {
+    let (host, port) = (processor.host.as_str(), processor.leap_port);
+    let connector = build_leap_tls_connector(certs_dir, &processor.ca_files)?;
     let tcp = TcpStream::connect((host, port)).await?;
     let server_name = rustls::pki_types::ServerName::try_from(host.to_string())
         .unwrap_or_else(|_| {
@@ -269,58 +534,640 @@ async fn connect_and_run(
     let (reader, mut writer) = tokio::io::split(tls);
     let mut reader = tokio::io::BufReader::new(reader);
 
-    // Subscribe to zone status events
-    let subscribe = serde_json::json!({
-        "CommuniqueType": "SubscribeRequest",
-        "Header": {"Url": "/zone/status"}
-    });
-    let mut msg = serde_json::to_string(&subscribe)?;
-    msg.push_str("\r\n");
-    writer.write_all(msg.as_bytes()).await?;
-    info!("Subscribed to zone status events");
-
-    let mut line = String::new();
-    let ping_interval = tokio::time::Duration::from_secs(15);
-    let mut ping_timer = tokio::time::interval(ping_interval);
+    // Subscribe to zone status events, unless they're polled for, then any
+    // extra (keypad LED) URLs
+    let zone_status = (!processor.use_polling).then_some("/zone/status");
+    for url in zone_status.into_iter().chain(subscriptions.iter().map(|s| s.as_str())) {
+        let subscribe = serde_json::json!({
+            "CommuniqueType": "SubscribeRequest",
+            "Header": {"Url": url}
+        });
+        let mut msg = serde_json::to_string(&subscribe)?;
+        protocol_log!(debug, "LEAP TX: {}", msg);
+        msg.push_str("\r\n");
+        writer.write_all(msg.as_bytes()).await?;
+    }
+    if processor.use_polling {
+        info!(
+            "Polling zone status every {}s ({} subscriptions)",
+            processor.poll_interval_secs.max(1),
+            subscriptions.len()
+        );
+    } else {
+        info!(
+            "Subscribed to zone status events ({} extra subscriptions)",
+            subscriptions.len()
+        );
+    }
+    let _ = status_tx.send(BackendStatus::Connected);
+
+    let mut messages = MessageReader::default();
+    let mut ping_timer = ping_timer(processor);
     ping_timer.tick().await; // consume the immediate first tick
+    let mut awaiting_pong = false;
+    // The first poll goes out straight away and reports every zone
+    let mut poll_timer = processor
+        .use_polling
+        .then(|| tokio::time::interval(Duration::from_secs(processor.poll_interval_secs.max(1))));
+    let mut polled_levels = HashMap::new();
 
     loop {
         tokio::select! {
             // Read events from processor
-            result = reader.read_line(&mut line) => {
-                let n = result?;
-                if n == 0 {
-                    return Err(anyhow::anyhow!("LEAP connection closed"));
-                }
-                let trimmed = line.trim();
-                if !trimmed.is_empty() {
-                    match serde_json::from_str::<LeapEvent>(trimmed) {
-                        Ok(event) => {
-                            let _ = event_tx.send(event);
+            result = messages.next(&mut reader) => {
+                // A clean TLS close reads as EOF; a dropped link errors out
+                let Some(message) = result? else {
+                    return Ok(SessionEnd::RemoteClosed);
+                };
+                protocol_log!(trace, "LEAP RX: {}", message);
+                match serde_json::from_str::<LeapEvent>(&message) {
+                    Ok(event) => {
+                        if is_ping_response(&event) {
+                            awaiting_pong = false;
                         }
-                        Err(e) => {
-                            warn!("Failed to parse LEAP event: {} — line: {}", e, trimmed);
+                        if event.header.client_tag.as_deref() == Some(POLL_CLIENT_TAG) {
+                            for change in zone_status_changes(&event, &mut polled_levels) {
+                                let _ = event_tx.send(change);
+                            }
+                            continue;
                         }
+                        let _ = event_tx.send(event);
+                    }
+                    Err(e) => {
+                        warn!("Failed to parse LEAP event: {} — message: {}", e, message);
                     }
                 }
-                line.clear();
             }
-            // Send requests to processor
-            Some(req) = req_rx.recv() => {
+            // Send requests to processor; a closed channel means the bridge stopped
+            req = req_rx.recv() => {
+                let Some(req) = req else { return Ok(SessionEnd::Stopped) };
                 let mut msg = serde_json::to_string(&req)?;
+                protocol_log!(debug, "LEAP TX: {}", msg);
                 msg.push_str("\r\n");
                 writer.write_all(msg.as_bytes()).await?;
             }
-            // Keepalive ping every 15s
+            // Keepalive ping; a dead link shows up as a missing response
+            // long before the read side errors out
             _ = ping_timer.tick() => {
+                if awaiting_pong {
+                    return Err(anyhow::anyhow!(
+                        "No LEAP ping response within {}s",
+                        processor.ping_interval_secs
+                    ));
+                }
+                awaiting_pong = true;
                 let ping = serde_json::json!({
                     "CommuniqueType": "ReadRequest",
-                    "Header": {"Url": "/server/1/status/ping"}
+                    "Header": {"Url": PING_URL}
                 });
                 let mut msg = serde_json::to_string(&ping)?;
+                protocol_log!(trace, "LEAP TX: {}", msg);
+                msg.push_str("\r\n");
+                writer.write_all(msg.as_bytes()).await?;
+            }
+            _ = tick(&mut poll_timer) => {
+                let read = serde_json::json!({
+                    "CommuniqueType": "ReadRequest",
+                    "Header": {"Url": "/zone/status", "ClientTag": POLL_CLIENT_TAG}
+                });
+                let mut msg = serde_json::to_string(&read)?;
+                protocol_log!(trace, "LEAP TX: {}", msg);
                 msg.push_str("\r\n");
                 writer.write_all(msg.as_bytes()).await?;
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leap_mock::{MockLeapServer, MockZone};
+    use tokio::time::timeout;
+
+    fn mock_zones() -> Vec<MockZone> {
+        vec![MockZone {
+            area: "Kitchen",
+            href: "/zone/5",
+            name: "Main",
+            control_type: "Dimmed",
+            category: "Light",
+            level: 0.0,
+        }]
+    }
+
+    fn mock_processor(server: &MockLeapServer) -> ProcessorConfig {
+        ProcessorConfig::new("127.0.0.1".to_string(), server.port)
+    }
+
+    async fn wait_until(mut cond: impl FnMut() -> bool) {
+        timeout(Duration::from_secs(5), async {
+            while !cond() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("condition not met in time");
+    }
+
+    fn subscribe_count(server: &MockLeapServer, url: &str) -> usize {
+        server
+            .received()
+            .iter()
+            .filter(|r| r["CommuniqueType"] == "SubscribeRequest" && r["Header"]["Url"] == url)
+            .count()
+    }
+
+    #[tokio::test]
+    async fn subscribes_and_relays_commands() {
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+        let (req_tx, req_rx) = mpsc::channel(8);
+        let (event_tx, mut event_rx) = broadcast::channel(16);
+        tokio::spawn(run(
+            mock_processor(&server),
+            server.certs_dir.clone(),
+            Vec::new(),
+            req_rx,
+            event_tx,
+            watch::channel(BackendStatus::NotConfigured).0,
+            0,
+        ));
+
+        req_tx
+            .send(LeapRequest {
+                communique_type: "CreateRequest".to_string(),
+                header: LeapHeader {
+                    url: "/zone/5/commandprocessor".to_string(),
+                    client_tag: None,
+                    extra: serde_json::Map::new(),
+                },
+                body: Some(serde_json::json!({
+                    "Command": {
+                        "CommandType": "GoToLevel",
+                        "Parameter": [{"Type": "Level", "Value": 40.0}]
+                    }
+                })),
+            })
+            .await
+            .unwrap();
+
+        let level = timeout(Duration::from_secs(5), async {
+            loop {
+                let event = event_rx.recv().await.unwrap();
+                if let Some(level) = event.body.pointer("/ZoneStatus/Level") {
+                    return level.as_f64().unwrap();
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(level, 40.0);
+        assert_eq!(server.level("/zone/5"), Some(40.0));
+        assert_eq!(server.received()[0]["CommuniqueType"], "SubscribeRequest");
+    }
+
+    #[tokio::test]
+    async fn polling_reads_zone_status_instead_of_subscribing() {
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+        let (_req_tx, req_rx) = mpsc::channel(8);
+        let (event_tx, mut event_rx) = broadcast::channel(16);
+        let mut processor = mock_processor(&server);
+        processor.use_polling = true;
+        processor.poll_interval_secs = 1;
+        tokio::spawn(run(
+            processor,
+            server.certs_dir.clone(),
+            vec!["/led/7/status".to_string()],
+            req_rx,
+            event_tx,
+            watch::channel(BackendStatus::NotConfigured).0,
+            0,
+        ));
+
+        async fn next_status(event_rx: &mut broadcast::Receiver<LeapEvent>) -> LeapEvent {
+            timeout(Duration::from_secs(5), async {
+                loop {
+                    let event = event_rx.recv().await.unwrap();
+                    if event.body.get("ZoneStatus").is_some() {
+                        return event;
+                    }
+                }
+            })
+            .await
+            .expect("no zone status event")
+        }
+        let first = next_status(&mut event_rx).await;
+        assert_eq!(first.header.url, "/zone/5/status");
+        assert_eq!(first.body.pointer("/ZoneStatus/Level"), Some(&serde_json::json!(0.0)));
+
+        // A change made at the wall shows up on a later poll, with no
+        // repeats of the unchanged level in between
+        server.set_level("/zone/5", 60.0);
+        let changed = next_status(&mut event_rx).await;
+        assert_eq!(changed.body.pointer("/ZoneStatus/Level"), Some(&serde_json::json!(60.0)));
+
+        assert_eq!(subscribe_count(&server, "/zone/status"), 0);
+        assert_eq!(subscribe_count(&server, "/led/7/status"), 1);
+    }
+
+    #[test]
+    fn polled_statuses_only_report_changes() {
+        let response = |levels: &[(&str, f64)]| LeapEvent {
+            communique_type: "ReadResponse".to_string(),
+            header: LeapEventHeader {
+                url: "/zone/status".to_string(),
+                status_code: Some("200 OK".to_string()),
+                client_tag: Some(POLL_CLIENT_TAG.to_string()),
+                extra: serde_json::Map::new(),
+            },
+            body: serde_json::json!({
+                "ZoneStatuses": levels
+                    .iter()
+                    .map(|(href, level)| serde_json::json!({ "Level": level, "Zone": { "href": href } }))
+                    .collect::<Vec<_>>()
+            }),
+        };
+        let mut last = HashMap::new();
+        let urls = |events: Vec<LeapEvent>| events.into_iter().map(|e| e.header.url).collect::<Vec<_>>();
+
+        assert_eq!(
+            urls(zone_status_changes(&response(&[("/zone/5", 0.0), ("/zone/6", 30.0)]), &mut last)),
+            ["/zone/5/status", "/zone/6/status"]
+        );
+        assert!(zone_status_changes(&response(&[("/zone/5", 0.0), ("/zone/6", 30.0)]), &mut last).is_empty());
+        assert_eq!(
+            urls(zone_status_changes(&response(&[("/zone/5", 0.0), ("/zone/6", 45.0)]), &mut last)),
+            ["/zone/6/status"]
+        );
+
+        let mut failed = response(&[]);
+        failed.body = serde_json::Value::Null;
+        assert!(zone_status_changes(&failed, &mut last).is_empty());
+    }
+
+    #[tokio::test]
+    async fn reconnects_and_resubscribes_after_drop() {
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+        let (_req_tx, req_rx) = mpsc::channel(8);
+        let (event_tx, _event_rx) = broadcast::channel(16);
+        tokio::spawn(run(
+            mock_processor(&server),
+            server.certs_dir.clone(),
+            vec!["/led/7/status".to_string()],
+            req_rx,
+            event_tx,
+            watch::channel(BackendStatus::NotConfigured).0,
+            0,
+        ));
+
+        wait_until(|| subscribe_count(&server, "/zone/status") == 1).await;
+        server.kick();
+        wait_until(|| {
+            server.connections() == 2
+                && subscribe_count(&server, "/zone/status") == 2
+                && subscribe_count(&server, "/led/7/status") == 2
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn responses_are_matched_by_client_tag() {
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+        let (req_tx, req_rx) = mpsc::channel(8);
+        let (event_tx, mut event_rx) = broadcast::channel(16);
+        tokio::spawn(run(
+            mock_processor(&server),
+            server.certs_dir.clone(),
+            Vec::new(),
+            req_rx,
+            event_tx,
+            watch::channel(BackendStatus::NotConfigured).0,
+            0,
+        ));
+
+        for (url, tag) in [("/area", None), ("/zone/5/status", Some("raw-1"))] {
+            req_tx
+                .send(LeapRequest {
+                    communique_type: "ReadRequest".to_string(),
+                    header: LeapHeader {
+                        url: url.to_string(),
+                        client_tag: tag.map(|t| t.to_string()),
+                        extra: serde_json::Map::new(),
+                    },
+                    body: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let event = timeout(Duration::from_secs(5), wait_for_client_tag(&mut event_rx, "raw-1"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.header.url, "/zone/5/status");
+        assert_eq!(event.body["ZoneStatus"]["Level"], 0.0);
+    }
+
+    #[tokio::test]
+    async fn remote_close_reconnects_without_error() {
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+        let (_req_tx, req_rx) = mpsc::channel(8);
+        let (event_tx, _event_rx) = broadcast::channel(16);
+        let (status_tx, mut status_rx) = watch::channel(BackendStatus::NotConfigured);
+        let errors = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let seen_errors = errors.clone();
+        tokio::spawn(async move {
+            while status_rx.changed().await.is_ok() {
+                if matches!(*status_rx.borrow_and_update(), BackendStatus::Error { .. }) {
+                    seen_errors.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                }
+            }
+        });
+        tokio::spawn(run(
+            mock_processor(&server),
+            server.certs_dir.clone(),
+            Vec::new(),
+            req_rx,
+            event_tx,
+            status_tx,
+            0,
+        ));
+
+        wait_until(|| subscribe_count(&server, "/zone/status") == 1).await;
+        server.close();
+        wait_until(|| subscribe_count(&server, "/zone/status") == 2).await;
+        assert_eq!(server.connections(), 2);
+        assert_eq!(errors.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn healthy_sessions_reset_the_attempt_count() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        // Forwards to the mock, or drops connections while `refuse` is set
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+        let refuse = Arc::new(AtomicBool::new(true));
+        let proxy = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_port = proxy.local_addr().unwrap().port();
+        let (upstream, proxy_refuse) = (server.port, refuse.clone());
+        tokio::spawn(async move {
+            while let Ok((mut client, _)) = proxy.accept().await {
+                if proxy_refuse.load(Ordering::SeqCst) {
+                    continue;
+                }
+                tokio::spawn(async move {
+                    let mut mock = TcpStream::connect(("127.0.0.1", upstream)).await.unwrap();
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut mock).await;
+                });
+            }
+        });
+
+        let (_req_tx, req_rx) = mpsc::channel(8);
+        let (event_tx, _) = broadcast::channel(16);
+        let (status_tx, mut status_rx) = watch::channel(BackendStatus::NotConfigured);
+        let processor = ProcessorConfig::new("127.0.0.1".to_string(), proxy_port);
+        tokio::spawn(run(processor, server.certs_dir.clone(), Vec::new(), req_rx, event_tx, status_tx, 2));
+
+        // One failure, then a healthy session the processor closes
+        status_rx.wait_for(|s| matches!(s, BackendStatus::Error { attempts: 1, .. })).await.unwrap();
+        refuse.store(false, Ordering::SeqCst);
+        status_rx.wait_for(|s| *s == BackendStatus::Connected).await.unwrap();
+        refuse.store(true, Ordering::SeqCst);
+        server.close();
+
+        // The next failure is the first since that session, not the second
+        let status = timeout(
+            Duration::from_secs(5),
+            status_rx.wait_for(|s| matches!(s, BackendStatus::Error { .. } | BackendStatus::Failed { .. })),
+        )
+        .await
+        .expect("no reconnect attempt")
+        .unwrap()
+        .clone();
+        assert!(matches!(status, BackendStatus::Error { attempts: 1, .. }), "{:?}", status);
+    }
+
+    #[tokio::test]
+    async fn stopping_during_backoff_reports_stopped() {
+        // Nothing listens on the port, so every attempt fails
+        let port = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let (req_tx, req_rx) = mpsc::channel(8);
+        let (event_tx, _) = broadcast::channel(16);
+        let (status_tx, mut status_rx) = watch::channel(BackendStatus::NotConfigured);
+        let processor = ProcessorConfig::new("127.0.0.1".to_string(), port);
+        let client = tokio::spawn(run(processor, PathBuf::from("/nonexistent"), Vec::new(), req_rx, event_tx, status_tx, 0));
+
+        status_rx.wait_for(|s| matches!(s, BackendStatus::Error { .. })).await.unwrap();
+        drop(req_tx);
+        timeout(Duration::from_secs(2), client).await.expect("client kept retrying").unwrap().unwrap();
+        assert_eq!(*status_rx.borrow(), BackendStatus::Stopped);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_reconnect_attempts() {
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+        // Nothing listens on a port we just released, so every connect is refused
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let processor = ProcessorConfig::new("127.0.0.1".to_string(), port);
+        let (_req_tx, req_rx) = mpsc::channel(8);
+        let (event_tx, _) = broadcast::channel(16);
+        let (status_tx, status_rx) = watch::channel(BackendStatus::NotConfigured);
+
+        timeout(
+            Duration::from_secs(10),
+            run(processor, server.certs_dir.clone(), Vec::new(), req_rx, event_tx, status_tx, 2),
+        )
+        .await
+        .expect("client kept retrying")
+        .unwrap();
+        assert!(
+            matches!(&*status_rx.borrow(), BackendStatus::Failed { attempts: 2, .. }),
+            "{:?}",
+            *status_rx.borrow()
+        );
+    }
+
+    #[tokio::test]
+    async fn ping_interval_comes_from_config() {
+        let mut processor = ProcessorConfig::new("127.0.0.1".to_string(), 8081);
+        assert_eq!(ping_timer(&processor).period(), Duration::from_secs(15));
+        processor.ping_interval_secs = 3;
+        assert_eq!(ping_timer(&processor).period(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn any_ping_response_counts() {
+        let event = |json: serde_json::Value| serde_json::from_value::<LeapEvent>(json).unwrap();
+        assert!(is_ping_response(&event(serde_json::json!({
+            "CommuniqueType": "ReadResponse",
+            "Header": {"Url": "/server/1/status/ping", "StatusCode": "200 OK"},
+            "Body": {"PingResponse": {"LEAPVersion": 1.115}}
+        }))));
+        assert!(is_ping_response(&event(serde_json::json!({
+            "CommuniqueType": "ExceptionResponse",
+            "Header": {"Url": "/server/1/status/ping", "StatusCode": "503 Service Unavailable"}
+        }))));
+        assert!(!is_ping_response(&event(serde_json::json!({
+            "CommuniqueType": "ReadResponse",
+            "Header": {"Url": "/zone/5/status", "StatusCode": "200 OK"},
+            "Body": {"PingResponse": {}}
+        }))));
+    }
+
+    #[tokio::test]
+    async fn unanswered_ping_forces_reconnect() {
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+        let mut processor = mock_processor(&server);
+        processor.ping_interval_secs = 1;
+        let (_req_tx, req_rx) = mpsc::channel(8);
+        let (event_tx, _) = broadcast::channel(16);
+        tokio::spawn(run(
+            processor,
+            server.certs_dir.clone(),
+            Vec::new(),
+            req_rx,
+            event_tx,
+            watch::channel(BackendStatus::NotConfigured).0,
+            0,
+        ));
+
+        wait_until(|| subscribe_count(&server, "/zone/status") == 1).await;
+        server.set_silent(true);
+        wait_until(|| server.connections() == 2).await;
+    }
+
+    #[tokio::test]
+    async fn session_request_reads_response() {
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+        let req = LeapRequest {
+            communique_type: "ReadRequest".to_string(),
+            header: LeapHeader {
+                url: "/zone/5/status".to_string(),
+                client_tag: None,
+                extra: serde_json::Map::new(),
+            },
+            body: None,
+        };
+        let mut session =
+            LeapSession::connect("127.0.0.1", server.port, &server.certs_dir, &[], DEFAULT_REQUEST_TIMEOUT)
+                .await
+                .unwrap();
+        let resp = session.request(&req, DEFAULT_REQUEST_TIMEOUT).await.unwrap();
+        assert_eq!(resp.header.url, "/zone/5/status");
+        assert_eq!(resp.body["ZoneStatus"]["Level"], 0.0);
+    }
+
+    #[tokio::test]
+    async fn session_reuses_one_connection() {
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+        let read = |url: &str| LeapRequest {
+            communique_type: "ReadRequest".to_string(),
+            header: LeapHeader {
+                url: url.to_string(),
+                client_tag: None,
+                extra: serde_json::Map::new(),
+            },
+            body: None,
+        };
+        let mut session =
+            LeapSession::connect("127.0.0.1", server.port, &server.certs_dir, &[], DEFAULT_REQUEST_TIMEOUT)
+                .await
+                .unwrap();
+        for url in ["/area", "/zone/5/status", "/zone/5/status"] {
+            let resp = session.request(&read(url), DEFAULT_REQUEST_TIMEOUT).await.unwrap();
+            assert_eq!(resp.header.url, url);
+        }
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn pretty_printed_messages_are_reassembled() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let mut reader = tokio::io::BufReader::new(client);
+        let mut messages = MessageReader::default();
+
+        let pretty = "{\r\n  \"CommuniqueType\": \"ReadResponse\",\r\n  \"Header\": {\"Url\": \"/zone/5/status\"},\r\n  \"Body\": {\"ZoneStatus\": {\"Level\": 40}}\r\n}\r\n";
+        let writer = tokio::spawn(async move {
+            for chunk in pretty.as_bytes().chunks(7) {
+                server.write_all(chunk).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+            server.write_all(b"\r\n{\"CommuniqueType\":\"ReadResponse\",\"Header\":{\"Url\":\"/a\"}}\r\n{oops\r\n").await.unwrap();
+        });
+
+        let first = messages.next(&mut reader).await.unwrap().unwrap();
+        let event: LeapEvent = serde_json::from_str(&first).unwrap();
+        assert_eq!(event.header.url, "/zone/5/status");
+        assert_eq!(event.body["ZoneStatus"]["Level"], 40);
+
+        // Blank lines are skipped; one-line messages still work
+        let second: LeapEvent = serde_json::from_str(&messages.next(&mut reader).await.unwrap().unwrap()).unwrap();
+        assert_eq!(second.header.url, "/a");
+
+        // Garbage is handed back rather than swallowing what follows
+        assert_eq!(messages.next(&mut reader).await.unwrap().as_deref(), Some("{oops"));
+        writer.await.unwrap();
+        assert_eq!(messages.next(&mut reader).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn session_request_times_out_when_processor_is_silent() {
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+        server.set_silent(true);
+        let req = LeapRequest {
+            communique_type: "ReadRequest".to_string(),
+            header: LeapHeader {
+                url: "/area".to_string(),
+                client_tag: None,
+                extra: serde_json::Map::new(),
+            },
+            body: None,
+        };
+        let mut session =
+            LeapSession::connect("127.0.0.1", server.port, &server.certs_dir, &[], DEFAULT_REQUEST_TIMEOUT)
+                .await
+                .unwrap();
+        let err = session.request(&req, Duration::from_millis(300)).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+        assert_eq!(server.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn extra_ca_file_is_trusted() {
+        let server = MockLeapServer::start(mock_zones()).await.unwrap();
+
+        // Same client cert, but the paired ca.crt is an unrelated CA, so only
+        // the extra CA file can vouch for the server.
+        let dir = std::env::temp_dir().join(format!("ra-bridge-ca-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["ra-bridge.crt", "ra-bridge.key"] {
+            std::fs::copy(server.certs_dir.join(name), dir.join(name)).unwrap();
+        }
+        let mut other_ca = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        other_ca.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let other_ca = other_ca.self_signed(&rcgen::KeyPair::generate().unwrap()).unwrap();
+        std::fs::write(dir.join("ca.crt"), other_ca.pem()).unwrap();
+
+        let req = LeapRequest {
+            communique_type: "ReadRequest".to_string(),
+            header: LeapHeader {
+                url: "/zone/5/status".to_string(),
+                client_tag: None,
+                extra: serde_json::Map::new(),
+            },
+            body: None,
+        };
+        let untrusted = LeapSession::connect("127.0.0.1", server.port, &dir, &[], DEFAULT_REQUEST_TIMEOUT).await;
+        assert!(untrusted.is_err());
+
+        let ca_files = vec![server.certs_dir.join("ca.crt")];
+        let mut session =
+            LeapSession::connect("127.0.0.1", server.port, &dir, &ca_files, DEFAULT_REQUEST_TIMEOUT)
+                .await
+                .unwrap();
+        let resp = session.request(&req, DEFAULT_REQUEST_TIMEOUT).await.unwrap();
+        assert_eq!(resp.body["ZoneStatus"]["Level"], 0.0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
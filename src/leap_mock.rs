@@ -0,0 +1,307 @@
+//! In-process mock of a LEAP processor for tests. Speaks the same
+//! line-delimited JSON over TLS as a real RA3 processor and answers the
+//! requests the bridge makes: area/zone reads, zone status (one zone or
+//! all of them), subscribe, GoToLevel commands and pings.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio_rustls::TlsAcceptor;
+
+/// A zone the mock processor reports.
+#[derive(Debug, Clone)]
+pub struct MockZone {
+    pub area: &'static str,
+    pub href: &'static str,
+    pub name: &'static str,
+    /// LEAP `ControlType`, e.g. "Dimmed" or "Shade".
+    pub control_type: &'static str,
+    /// LEAP `Category.Type`, e.g. "Light" or "ExhaustFan".
+    pub category: &'static str,
+    pub level: f64,
+}
+
+struct MockState {
+    zones: Vec<MockZone>,
+    levels: Mutex<HashMap<String, f64>>,
+    received: Mutex<Vec<Value>>,
+    connections: AtomicUsize,
+    silent: AtomicBool,
+}
+
+pub struct MockLeapServer {
+    pub port: u16,
+    /// Directory holding `ca.crt`, `ra-bridge.crt` and `ra-bridge.key` that
+    /// `build_leap_tls_connector` accepts for this server.
+    pub certs_dir: PathBuf,
+    state: Arc<MockState>,
+    /// `true` closes cleanly, `false` just drops the connection.
+    kick_tx: broadcast::Sender<bool>,
+}
+
+impl MockLeapServer {
+    pub async fn start(zones: Vec<MockZone>) -> Result<Self> {
+        let certs_dir = std::env::temp_dir().join(format!("ra-bridge-mock-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&certs_dir)?;
+
+        // CA → server cert (for 127.0.0.1) and client cert
+        let ca_key = KeyPair::generate()?;
+        let mut ca_params = CertificateParams::new(Vec::<String>::new())?;
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca_cert = ca_params.self_signed(&ca_key)?;
+
+        let server_key = KeyPair::generate()?;
+        let server_cert = CertificateParams::new(vec!["127.0.0.1".to_string()])?
+            .signed_by(&server_key, &ca_cert, &ca_key)?;
+
+        let client_key = KeyPair::generate()?;
+        let client_cert = CertificateParams::new(vec!["ra-bridge".to_string()])?
+            .signed_by(&client_key, &ca_cert, &ca_key)?;
+
+        std::fs::write(certs_dir.join("ca.crt"), ca_cert.pem())?;
+        std::fs::write(certs_dir.join("ra-bridge.crt"), client_cert.pem())?;
+        std::fs::write(certs_dir.join("ra-bridge.key"), client_key.serialize_pem())?;
+
+        let tls_config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![server_cert.der().clone()],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(server_key.serialize_der())),
+            )?;
+        let acceptor = TlsAcceptor::from(Arc::new(tls_config));
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let port = listener.local_addr()?.port();
+
+        let levels = zones.iter().map(|z| (z.href.to_string(), z.level)).collect();
+        let state = Arc::new(MockState {
+            zones,
+            levels: Mutex::new(levels),
+            received: Mutex::new(Vec::new()),
+            connections: AtomicUsize::new(0),
+            silent: AtomicBool::new(false),
+        });
+        let (kick_tx, _) = broadcast::channel(4);
+
+        let accept_state = state.clone();
+        let accept_kick = kick_tx.clone();
+        tokio::spawn(async move {
+            while let Ok((tcp, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                let state = accept_state.clone();
+                let kick_rx = accept_kick.subscribe();
+                tokio::spawn(async move {
+                    if let Ok(tls) = acceptor.accept(tcp).await {
+                        state.connections.fetch_add(1, Ordering::SeqCst);
+                        let _ = handle_conn(tls, state, kick_rx).await;
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            port,
+            certs_dir,
+            state,
+            kick_tx,
+        })
+    }
+
+    /// Number of TLS sessions accepted so far.
+    pub fn connections(&self) -> usize {
+        self.state.connections.load(Ordering::SeqCst)
+    }
+
+    /// Every request the server has received, in order.
+    pub fn received(&self) -> Vec<Value> {
+        self.state.received.lock().unwrap().clone()
+    }
+
+    pub fn level(&self, href: &str) -> Option<f64> {
+        self.state.levels.lock().unwrap().get(href).copied()
+    }
+
+    /// Change a zone's level as a wall control would, without telling
+    /// anyone; only reads see it.
+    pub fn set_level(&self, href: &str, level: f64) {
+        self.state.levels.lock().unwrap().insert(href.to_string(), level);
+    }
+
+    /// Keep accepting and recording requests but never answer them, like a
+    /// processor that has wedged after the TLS handshake.
+    pub fn set_silent(&self, silent: bool) {
+        self.state.silent.store(silent, Ordering::SeqCst);
+    }
+
+    /// Drop every open connection, as a processor reboot would.
+    pub fn kick(&self) {
+        let _ = self.kick_tx.send(false);
+    }
+
+    /// Close every open connection cleanly (TLS close_notify), as a processor
+    /// shutting its LEAP service down would.
+    pub fn close(&self) {
+        let _ = self.kick_tx.send(true);
+    }
+}
+
+impl Drop for MockLeapServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.certs_dir);
+    }
+}
+
+async fn handle_conn(
+    tls: tokio_rustls::server::TlsStream<tokio::net::TcpStream>,
+    state: Arc<MockState>,
+    mut kick_rx: broadcast::Receiver<bool>,
+) -> Result<()> {
+    let (reader, mut writer) = tokio::io::split(tls);
+    let mut reader = tokio::io::BufReader::new(reader);
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        tokio::select! {
+            n = reader.read_line(&mut line) => {
+                if n? == 0 {
+                    return Ok(());
+                }
+                let Ok(req) = serde_json::from_str::<Value>(line.trim()) else {
+                    continue;
+                };
+                state.received.lock().unwrap().push(req.clone());
+                if state.silent.load(Ordering::SeqCst) {
+                    continue;
+                }
+                for resp in respond(&req, &state) {
+                    let mut msg = serde_json::to_string(&resp)?;
+                    msg.push_str("\r\n");
+                    writer.write_all(msg.as_bytes()).await?;
+                }
+            }
+            graceful = kick_rx.recv() => {
+                if graceful.unwrap_or(false) {
+                    writer.shutdown().await?;
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn respond(req: &Value, state: &MockState) -> Vec<Value> {
+    let kind = req["CommuniqueType"].as_str().unwrap_or_default();
+    let url = req["Header"]["Url"].as_str().unwrap_or_default();
+    let mut header = json!({ "Url": url, "StatusCode": "200 OK" });
+    if let Some(tag) = req["Header"].get("ClientTag") {
+        header["ClientTag"] = tag.clone();
+    }
+
+    let areas = area_names(&state.zones);
+    let zone_status = |href: &str| {
+        let level = state.levels.lock().unwrap().get(href).copied().unwrap_or(0.0);
+        json!({ "ZoneStatus": { "Level": level, "Zone": { "href": href } } })
+    };
+
+    match (kind, url) {
+        ("SubscribeRequest", _) => {
+            vec![json!({ "CommuniqueType": "SubscribeResponse", "Header": header })]
+        }
+        ("ReadRequest", "/server/1/status/ping") => vec![json!({
+            "CommuniqueType": "ReadResponse",
+            "Header": header,
+            "Body": { "PingResponse": { "LEAPVersion": 1.0 } }
+        })],
+        ("ReadRequest", "/area") => {
+            let body: Vec<Value> = areas
+                .iter()
+                .enumerate()
+                .map(|(i, name)| json!({ "href": format!("/area/{}", i + 1), "Name": name }))
+                .collect();
+            vec![json!({ "CommuniqueType": "ReadResponse", "Header": header, "Body": { "Areas": body } })]
+        }
+        ("ReadRequest", u) if u.ends_with("/associatedzone") => {
+            let idx: usize = u
+                .trim_start_matches("/area/")
+                .trim_end_matches("/associatedzone")
+                .parse()
+                .unwrap_or(0);
+            let area = idx.checked_sub(1).and_then(|i| areas.get(i));
+            let body: Vec<Value> = state
+                .zones
+                .iter()
+                .filter(|z| Some(&z.area) == area)
+                .map(|z| {
+                    json!({ "href": z.href, "Name": z.name, "ControlType": z.control_type, "Category": { "Type": z.category } })
+                })
+                .collect();
+            vec![json!({ "CommuniqueType": "ReadResponse", "Header": header, "Body": { "Zones": body } })]
+        }
+        ("ReadRequest", u) if state.zones.iter().any(|z| z.href == u) => {
+            let zone = state.zones.iter().find(|z| z.href == u).unwrap();
+            let body = json!({ "Zone": { "href": zone.href, "Name": zone.name, "ControlType": zone.control_type } });
+            vec![json!({ "CommuniqueType": "ReadResponse", "Header": header, "Body": body })]
+        }
+        ("ReadRequest", "/zone/status") => {
+            let statuses: Vec<Value> = state
+                .zones
+                .iter()
+                .map(|z| {
+                    let mut status = zone_status(z.href)["ZoneStatus"].take();
+                    status["href"] = json!(format!("{}/status", z.href));
+                    status
+                })
+                .collect();
+            header["MessageBodyType"] = json!("MultipleZoneStatus");
+            vec![json!({ "CommuniqueType": "ReadResponse", "Header": header, "Body": { "ZoneStatuses": statuses } })]
+        }
+        ("ReadRequest", u) if u.ends_with("/status") => {
+            let href = u.trim_end_matches("/status");
+            vec![json!({ "CommuniqueType": "ReadResponse", "Header": header, "Body": zone_status(href) })]
+        }
+        ("CreateRequest", u) if u.ends_with("/commandprocessor") => {
+            let href = u.trim_end_matches("/commandprocessor");
+            let cmd = &req["Body"]["Command"];
+            let level = cmd["Parameter"][0]["Value"]
+                .as_f64()
+                .or_else(|| cmd["DimmedLevelParameters"]["Level"].as_f64());
+            if let Some(level) = level {
+                state.levels.lock().unwrap().insert(href.to_string(), level);
+            }
+            // A real processor acks the command, then publishes the new status
+            vec![
+                json!({ "CommuniqueType": "CreateResponse", "Header": header }),
+                json!({
+                    "CommuniqueType": "ReadResponse",
+                    "Header": { "Url": format!("{}/status", href), "StatusCode": "200 OK" },
+                    "Body": zone_status(href)
+                }),
+            ]
+        }
+        _ => {
+            header["StatusCode"] = json!("404 Not Found");
+            vec![json!({ "CommuniqueType": "ExceptionResponse", "Header": header })]
+        }
+    }
+}
+
+/// Unique area names in first-seen order; area N has href `/area/N`.
+fn area_names(zones: &[MockZone]) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    for z in zones {
+        if !names.contains(&z.area) {
+            names.push(z.area);
+        }
+    }
+    names
+}
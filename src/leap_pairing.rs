@@ -1,5 +1,5 @@
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::{bail, Context, Result};
@@ -8,11 +8,14 @@ use rsa::pkcs8::EncodePrivateKey;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio_rustls::TlsConnector;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
-const PAIRING_PORT: u16 = 8083;
+/// Where RA3 processors accept pairing; overridable for port-forwarded setups.
+pub const DEFAULT_PAIRING_PORT: u16 = 8083;
 const LEAP_PORT: u16 = 8081;
 const BUTTON_TIMEOUT_SECS: u64 = 180;
+/// Minimum gap between the end of one web pairing attempt and the next.
+pub const PAIR_COOLDOWN: std::time::Duration = std::time::Duration::from_secs(15);
 
 // Lutron LAP CA certificate (Caseta Local Access Protocol Cert Authority)
 pub(crate) const LAP_CA_PEM: &str = r#"-----BEGIN CERTIFICATE-----
@@ -150,6 +153,7 @@ lJ+cvdbUdsU=
 /// Used by the web UI to stream pairing status via SSE.
 pub async fn pair_with_progress(
     host: &str,
+    pairing_port: u16,
     certs_dir: &Path,
     config_path: &Path,
     leap_port: u16,
@@ -157,7 +161,8 @@ pub async fn pair_with_progress(
 ) -> Result<()> {
     use crate::state::PairingStatus;
 
-    std::fs::create_dir_all(certs_dir)?;
+    tokio::fs::create_dir_all(certs_dir).await?;
+    let settings = crate::discover::discovery_settings(config_path).await?;
 
     let _ = status_tx.send(PairingStatus::GeneratingKeys);
     info!("Generating RSA-2048 key pair...");
@@ -180,13 +185,13 @@ pub async fn pair_with_progress(
     let csr_pem = csr.pem().context("Failed to encode CSR as PEM")?;
 
     let key_path = certs_dir.join("ra-bridge.key");
-    std::fs::write(&key_path, key_pair.serialize_pem())?;
+    crate::config::write_private(&key_path, key_pair.serialize_pem()).await?;
 
     let _ = status_tx.send(PairingStatus::ConnectingToProcessor);
-    info!("Connecting to {}:{} for pairing...", host, PAIRING_PORT);
+    info!("Connecting to {}:{} for pairing...", host, pairing_port);
 
     let tls_connector = build_pairing_tls_connector()?;
-    let tcp = TcpStream::connect((host, PAIRING_PORT)).await?;
+    let tcp = TcpStream::connect((host, pairing_port)).await?;
     let server_name = rustls::pki_types::ServerName::try_from(host.to_string())
         .unwrap_or_else(|_| rustls::pki_types::ServerName::IpAddress(
             host.parse::<std::net::IpAddr>()
@@ -198,69 +203,18 @@ pub async fn pair_with_progress(
 
     info!("Connected! Waiting for button press...");
 
-    let timeout_secs = BUTTON_TIMEOUT_SECS;
-    let timeout = tokio::time::Duration::from_secs(timeout_secs);
     let (reader, mut writer) = tokio::io::split(tls);
     let mut reader = tokio::io::BufReader::new(reader);
 
-    // Countdown loop with progress updates
-    let status_tx_clone = status_tx.clone();
-    let start_time = tokio::time::Instant::now();
-    let got_access = tokio::time::timeout(timeout, async {
-        let mut line = String::new();
-        let mut tick = tokio::time::interval(tokio::time::Duration::from_secs(1));
-        tick.tick().await; // consume first tick
-
-        loop {
-            tokio::select! {
-                result = reader.read_line(&mut line) => {
-                    let n = result?;
-                    if n == 0 {
-                        bail!("Connection closed before receiving PhysicalAccess");
-                    }
-                    let trimmed = line.trim();
-                    if !trimmed.is_empty() {
-                        if let Ok(v) = serde_json::from_str::<serde_json::Value>(trimmed) {
-                            if let Some(perms) = v.pointer("/Body/Status/Permissions") {
-                                if let Some(arr) = perms.as_array() {
-                                    for p in arr {
-                                        if p.as_str() == Some("PhysicalAccess") {
-                                            return Ok(());
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    line.clear();
-                }
-                _ = tick.tick() => {
-                    let elapsed = start_time.elapsed().as_secs();
-                    let _ = status_tx_clone.send(PairingStatus::WaitingForButtonPress {
-                        elapsed,
-                        timeout: timeout_secs,
-                    });
-                }
-            }
-        }
-    })
-    .await;
-
-    match got_access {
-        Ok(Ok(())) => {
-            let _ = status_tx.send(PairingStatus::ButtonPressed);
-            info!("Physical access granted!");
-        }
-        Ok(Err(e)) => {
-            let _ = status_tx.send(PairingStatus::Failed { message: e.to_string() });
-            bail!("Error waiting for physical access: {}", e);
-        }
-        Err(_) => {
-            let msg = format!("Timeout waiting for button press ({} seconds)", timeout_secs);
-            let _ = status_tx.send(PairingStatus::Failed { message: msg.clone() });
-            bail!("{}", msg);
-        }
+    if let Err(e) = wait_for_button_press(&mut reader, Some(&status_tx)).await {
+        // Close the pairing session cleanly so the processor doesn't
+        // keep a half-open one around for the next attempt
+        let _ = writer.shutdown().await;
+        let _ = status_tx.send(PairingStatus::Failed { message: e.to_string() });
+        return Err(e);
     }
+    let _ = status_tx.send(PairingStatus::ButtonPressed);
+    info!("Physical access granted!");
 
     let _ = status_tx.send(PairingStatus::ReceivingCertificate);
 
@@ -307,9 +261,11 @@ pub async fn pair_with_progress(
             }
         },
     )
-    .await
-    .context("Timeout waiting for certificate response")?
-    .context("Error reading certificate response")?;
+    .await;
+    let _ = writer.shutdown().await;
+    let cert_response = cert_response
+        .context("Timeout waiting for certificate response")?
+        .context("Error reading certificate response")?;
 
     let signed_cert = cert_response
         .pointer("/Body/SigningResult/Certificate")
@@ -320,29 +276,32 @@ pub async fn pair_with_progress(
         .and_then(|v| v.as_str());
 
     let cert_path = certs_dir.join("ra-bridge.crt");
-    std::fs::write(&cert_path, signed_cert)?;
-
-    let ca_path = certs_dir.join("ca.crt");
-    let ca_pem = if let Some(rc) = root_cert {
-        rc.to_string()
-    } else {
-        LUTRON_ROOT_CA_PEM.to_string()
-    };
-    std::fs::write(&ca_path, &ca_pem)?;
+    crate::config::write_atomic(&cert_path, signed_cert).await?;
 
-    // Verify pairing
+    // Verify pairing, keeping whichever CA the processor actually validates
     let _ = status_tx.send(PairingStatus::VerifyingPairing);
-    if let Err(e) = verify_pairing(host, certs_dir).await {
+    let candidates = ca_candidates(root_cert);
+    if let Err(e) = verify_with_ca_candidates(host, leap_port, certs_dir, &settings.ca_files, &candidates).await {
         warn!("Verification failed: {}. You may need to re-pair.", e);
     }
 
     // Discover zones
     let _ = status_tx.send(PairingStatus::DiscoveringZones);
     info!("Discovering zones...");
-    let zones = crate::discover::discover_zones(host, leap_port, certs_dir).await?;
+    let types = crate::discover::parse_zone_types(crate::discover::DEFAULT_ZONE_TYPES);
+    let zones = crate::discover::discover_zones(
+        host,
+        leap_port,
+        certs_dir,
+        &settings.ca_files,
+        &types,
+        settings.ids,
+        settings.separator,
+    )
+    .await?;
     info!("Found {} zones", zones.len());
 
-    crate::discover::write_config(config_path, host, leap_port, &zones)?;
+    crate::discover::write_config(config_path, host, leap_port, &zones).await?;
     info!("Wrote {}", config_path.display());
 
     let _ = status_tx.send(PairingStatus::Complete { zone_count: zones.len() });
@@ -350,9 +309,61 @@ pub async fn pair_with_progress(
     Ok(())
 }
 
-/// Run the one-time pairing flow with a Lutron RA3 processor.
-pub async fn pair(host: &str, certs_dir: &Path) -> Result<()> {
-    std::fs::create_dir_all(certs_dir)?;
+/// Read the pairing session until the processor grants `PhysicalAccess`,
+/// i.e. someone pressed its button, giving up after `BUTTON_TIMEOUT_SECS`.
+/// With `status_tx`, a `WaitingForButtonPress` countdown goes out every second.
+async fn wait_for_button_press<R>(
+    reader: &mut R,
+    status_tx: Option<&tokio::sync::watch::Sender<crate::state::PairingStatus>>,
+) -> Result<()>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+{
+    let start_time = tokio::time::Instant::now();
+    let wait = async {
+        let mut line = String::new();
+        let mut tick = tokio::time::interval(tokio::time::Duration::from_secs(1));
+        tick.tick().await; // consume first tick
+
+        loop {
+            tokio::select! {
+                result = reader.read_line(&mut line) => {
+                    if result? == 0 {
+                        bail!("Connection closed before receiving PhysicalAccess");
+                    }
+                    let granted = serde_json::from_str::<serde_json::Value>(line.trim())
+                        .ok()
+                        .and_then(|v| v.pointer("/Body/Status/Permissions").cloned())
+                        .and_then(|perms| perms.as_array().cloned())
+                        .is_some_and(|perms| perms.iter().any(|p| p.as_str() == Some("PhysicalAccess")));
+                    if granted {
+                        return Ok(());
+                    }
+                    line.clear();
+                }
+                _ = tick.tick() => {
+                    if let Some(status_tx) = status_tx {
+                        let _ = status_tx.send(crate::state::PairingStatus::WaitingForButtonPress {
+                            elapsed: start_time.elapsed().as_secs(),
+                            timeout: BUTTON_TIMEOUT_SECS,
+                        });
+                    }
+                }
+            }
+        }
+    };
+
+    match tokio::time::timeout(tokio::time::Duration::from_secs(BUTTON_TIMEOUT_SECS), wait).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => bail!("Error waiting for physical access: {}", e),
+        Err(_) => bail!("Timeout waiting for button press ({} seconds)", BUTTON_TIMEOUT_SECS),
+    }
+}
+
+/// Run the one-time pairing flow with a Lutron RA3 processor. `ca_files`
+/// are trusted alongside `ca.crt` when verifying, as `[processor] ca_files`.
+pub async fn pair(host: &str, pairing_port: u16, certs_dir: &Path, ca_files: &[PathBuf]) -> Result<()> {
+    tokio::fs::create_dir_all(certs_dir).await?;
 
     // Generate RSA-2048 key pair
     info!("Generating RSA-2048 key pair...");
@@ -377,18 +388,18 @@ pub async fn pair(host: &str, certs_dir: &Path) -> Result<()> {
 
     // Save the private key now
     let key_path = certs_dir.join("ra-bridge.key");
-    std::fs::write(&key_path, key_pair.serialize_pem())?;
+    crate::config::write_private(&key_path, key_pair.serialize_pem()).await?;
     info!("Private key saved to {}", key_path.display());
 
     // Phase 1: Connect to pairing port with LAP credentials
     info!(
         "Connecting to {}:{} for pairing...",
-        host, PAIRING_PORT
+        host, pairing_port
     );
     info!("Trying RA3 (Lutron Root CA) first...");
 
     let tls_connector = build_pairing_tls_connector()?;
-    let tcp = TcpStream::connect((host, PAIRING_PORT)).await?;
+    let tcp = TcpStream::connect((host, pairing_port)).await?;
     let server_name = rustls::pki_types::ServerName::try_from(host.to_string())
         .unwrap_or_else(|_| rustls::pki_types::ServerName::IpAddress(
             host.parse::<std::net::IpAddr>()
@@ -400,43 +411,11 @@ pub async fn pair(host: &str, certs_dir: &Path) -> Result<()> {
 
     info!("Connected! Press the pairing button on the processor within {} seconds...", BUTTON_TIMEOUT_SECS);
 
-    // Read lines until we get PhysicalAccess permission
-    let timeout = tokio::time::Duration::from_secs(BUTTON_TIMEOUT_SECS);
     let (reader, mut writer) = tokio::io::split(tls);
     let mut reader = tokio::io::BufReader::new(reader);
 
-    let got_access = tokio::time::timeout(timeout, async {
-        let mut line = String::new();
-        loop {
-            line.clear();
-            let n = reader.read_line(&mut line).await?;
-            if n == 0 {
-                bail!("Connection closed before receiving PhysicalAccess");
-            }
-            let trimmed = line.trim();
-            if trimmed.is_empty() {
-                continue;
-            }
-            if let Ok(v) = serde_json::from_str::<serde_json::Value>(trimmed) {
-                if let Some(perms) = v.pointer("/Body/Status/Permissions") {
-                    if let Some(arr) = perms.as_array() {
-                        for p in arr {
-                            if p.as_str() == Some("PhysicalAccess") {
-                                return Ok(());
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    })
-    .await;
-
-    match got_access {
-        Ok(Ok(())) => info!("Physical access granted!"),
-        Ok(Err(e)) => bail!("Error waiting for physical access: {}", e),
-        Err(_) => bail!("Timeout waiting for button press ({} seconds)", BUTTON_TIMEOUT_SECS),
-    }
+    wait_for_button_press(&mut reader, None).await?;
+    info!("Physical access granted!");
 
     // Send CSR
     let pair_request = serde_json::json!({
@@ -496,31 +475,46 @@ pub async fn pair(host: &str, certs_dir: &Path) -> Result<()> {
 
     // Save signed certificate
     let cert_path = certs_dir.join("ra-bridge.crt");
-    std::fs::write(&cert_path, signed_cert)?;
+    crate::config::write_atomic(&cert_path, signed_cert).await?;
     info!("Signed certificate saved to {}", cert_path.display());
 
-    // For RA3, use the Lutron Root CA; for Caseta, use the returned root cert
-    let ca_path = certs_dir.join("ca.crt");
-    let ca_pem = if let Some(rc) = root_cert {
-        // Check if it's the Caseta CA or Lutron root; for RA3 we prefer the Lutron root
-        rc.to_string()
-    } else {
-        LUTRON_ROOT_CA_PEM.to_string()
-    };
-    std::fs::write(&ca_path, &ca_pem)?;
-    info!("CA certificate saved to {}", ca_path.display());
-
-    // Phase 2: Verify by connecting to LEAP port
+    // Phase 2: Verify by connecting to LEAP port. Caseta validates against
+    // the returned root cert and RA3 against the Lutron root, so try both
+    // and keep the one that works.
     info!("Verifying pairing by connecting to port {}...", LEAP_PORT);
-    if let Err(e) = verify_pairing(host, certs_dir).await {
-        warn!("Verification failed: {}. You may need to re-pair.", e);
-    } else {
-        info!("Pairing verified successfully!");
+    let candidates = ca_candidates(root_cert);
+    match verify_with_ca_candidates(host, LEAP_PORT, certs_dir, ca_files, &candidates).await {
+        Ok(label) => info!(
+            "Pairing verified successfully! {} saved to {}",
+            label,
+            certs_dir.join("ca.crt").display()
+        ),
+        Err(e) => warn!("Verification failed: {}. You may need to re-pair.", e),
     }
 
     Ok(())
 }
 
+/// Files pairing writes into the certs dir.
+pub const PAIRING_FILES: [&str; 3] = ["ra-bridge.crt", "ra-bridge.key", "ca.crt"];
+
+/// Delete the pairing certs from `certs_dir`, returning the ones that were
+/// there. Files already gone are skipped, so unpairing twice is harmless.
+pub async fn unpair(certs_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for name in PAIRING_FILES {
+        let path = certs_dir.join(name);
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => removed.push(path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+            }
+        }
+    }
+    Ok(removed)
+}
+
 fn build_pairing_tls_connector() -> Result<TlsConnector> {
     let mut root_store = rustls::RootCertStore::empty();
 
@@ -560,9 +554,69 @@ fn build_pairing_tls_connector() -> Result<TlsConnector> {
     Ok(TlsConnector::from(Arc::new(config)))
 }
 
-async fn verify_pairing(host: &str, certs_dir: &Path) -> Result<()> {
-    let connector = crate::leap_client::build_leap_tls_connector(certs_dir)?;
-    let tcp = TcpStream::connect((host, LEAP_PORT)).await?;
+/// CA certificates worth trying for `ca.crt`, most likely first: the root
+/// cert the processor returned with the signed cert (if any), then the
+/// Lutron root. A returned root that isn't a PEM certificate is skipped,
+/// and one that is the Lutron root, however it's wrapped, isn't tried twice.
+fn ca_candidates(root_cert: Option<&str>) -> Vec<(&'static str, String)> {
+    let mut candidates = Vec::new();
+    if let Some(rc) = root_cert {
+        match pem_certs(rc) {
+            Some(certs) if certs == pem_certs(LUTRON_ROOT_CA_PEM).unwrap_or_default() => {
+                debug!("Processor returned the Lutron root CA");
+            }
+            Some(_) => candidates.push(("returned root certificate", rc.to_string())),
+            None => warn!("Ignoring the processor's root certificate: not a PEM certificate"),
+        }
+    }
+    candidates.push(("Lutron root CA", LUTRON_ROOT_CA_PEM.to_string()));
+    candidates
+}
+
+/// The DER certificates in `pem`, or None if it holds none or doesn't parse.
+fn pem_certs(pem: &str) -> Option<Vec<Vec<u8>>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(pem.as_bytes()))
+        .map(|cert| cert.map(|cert| cert.to_vec()))
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    (!certs.is_empty()).then_some(certs)
+}
+
+/// Write each candidate to `ca.crt` in turn and keep the first one the
+/// processor validates against, returning its label. If none work, the
+/// first candidate is left in place and an error listing every failure is
+/// returned.
+async fn verify_with_ca_candidates(
+    host: &str,
+    port: u16,
+    certs_dir: &Path,
+    ca_files: &[PathBuf],
+    candidates: &[(&'static str, String)],
+) -> Result<&'static str> {
+    let ca_path = certs_dir.join("ca.crt");
+    let mut failures = Vec::new();
+    for (label, pem) in candidates {
+        crate::config::write_atomic(&ca_path, pem).await?;
+        match verify_pairing(host, port, certs_dir, ca_files).await {
+            Ok(()) => {
+                info!("Pairing verified using the {}", label);
+                return Ok(label);
+            }
+            Err(e) => {
+                warn!("Verification with the {} failed: {}", label, e);
+                failures.push(format!("{}: {}", label, e));
+            }
+        }
+    }
+    if let Some((_, pem)) = candidates.first() {
+        crate::config::write_atomic(&ca_path, pem).await?;
+    }
+    bail!("no CA certificate validated ({})", failures.join("; "))
+}
+
+async fn verify_pairing(host: &str, port: u16, certs_dir: &Path, ca_files: &[PathBuf]) -> Result<()> {
+    let connector = crate::leap_client::build_leap_tls_connector(certs_dir, ca_files)?;
+    let tcp = TcpStream::connect((host, port)).await?;
     let server_name = rustls::pki_types::ServerName::try_from(host.to_string())
         .unwrap_or_else(|_| rustls::pki_types::ServerName::IpAddress(
             host.parse::<std::net::IpAddr>()
@@ -594,3 +648,113 @@ async fn verify_pairing(host: &str, certs_dir: &Path) -> Result<()> {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leap_mock::MockLeapServer;
+    use crate::state::PairingStatus;
+    use tokio::time::{Duration, Instant};
+
+    #[tokio::test(start_paused = true)]
+    async fn button_wait_counts_down_then_times_out() {
+        let (client, _processor) = tokio::io::duplex(1024);
+        let mut reader = tokio::io::BufReader::new(client);
+        let (status_tx, mut status_rx) = tokio::sync::watch::channel(PairingStatus::Idle);
+
+        let started = Instant::now();
+        let wait = tokio::spawn(async move { wait_for_button_press(&mut reader, Some(&status_tx)).await });
+        status_rx.changed().await.unwrap();
+        assert!(matches!(
+            *status_rx.borrow(),
+            PairingStatus::WaitingForButtonPress { elapsed: 1, timeout: BUTTON_TIMEOUT_SECS }
+        ));
+
+        let err = wait.await.unwrap().unwrap_err();
+        assert_eq!(started.elapsed(), Duration::from_secs(BUTTON_TIMEOUT_SECS));
+        assert!(err.to_string().contains("Timeout waiting for button press"), "{}", err);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn button_wait_ends_on_physical_access() {
+        let (client, mut processor) = tokio::io::duplex(1024);
+        let mut reader = tokio::io::BufReader::new(client);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            processor.write_all(b"{\"Body\":{\"Status\":{\"Permissions\":[\"Public\"]}}}\r\n").await.unwrap();
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            let granted = b"{\"Body\":{\"Status\":{\"Permissions\":[\"Public\",\"PhysicalAccess\"]}}}\r\n";
+            processor.write_all(granted).await.unwrap();
+            // Keep the session open; the wait must end on the message alone
+            std::future::pending::<()>().await;
+        });
+
+        let started = Instant::now();
+        wait_for_button_press(&mut reader, None).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn verification_falls_back_to_a_working_ca() {
+        let server = MockLeapServer::start(Vec::new()).await.unwrap();
+        let mock_ca = std::fs::read_to_string(server.certs_dir.join("ca.crt")).unwrap();
+
+        let candidates = vec![
+            ("returned root certificate", LAP_CA_PEM.to_string()),
+            ("mock CA", mock_ca.clone()),
+        ];
+        let label = verify_with_ca_candidates("127.0.0.1", server.port, &server.certs_dir, &[], &candidates)
+            .await
+            .unwrap();
+        assert_eq!(label, "mock CA");
+        assert_eq!(std::fs::read_to_string(server.certs_dir.join("ca.crt")).unwrap(), mock_ca);
+
+        let bad = vec![
+            ("returned root certificate", LAP_CA_PEM.to_string()),
+            ("Lutron root CA", LUTRON_ROOT_CA_PEM.to_string()),
+        ];
+        assert!(verify_with_ca_candidates("127.0.0.1", server.port, &server.certs_dir, &[], &bad).await.is_err());
+        assert_eq!(std::fs::read_to_string(server.certs_dir.join("ca.crt")).unwrap(), LAP_CA_PEM);
+
+        // A CA from `[processor] ca_files` is trusted whatever the candidates
+        let extra_ca = server.certs_dir.join("extra-ca.crt");
+        std::fs::write(&extra_ca, &mock_ca).unwrap();
+        let label = verify_with_ca_candidates("127.0.0.1", server.port, &server.certs_dir, &[extra_ca], &bad)
+            .await
+            .unwrap();
+        assert_eq!(label, "returned root certificate");
+    }
+
+    #[tokio::test]
+    async fn unpair_removes_only_pairing_files() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-unpair-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        for name in ["ra-bridge.crt", "ra-bridge.key", "ca.crt", "extra-ca.pem"] {
+            tokio::fs::write(dir.join(name), "").await.unwrap();
+        }
+
+        let removed = unpair(&dir).await.unwrap();
+        assert_eq!(removed.len(), 3);
+        assert!(!crate::state::has_certs(&dir));
+        assert!(dir.join("extra-ca.pem").exists());
+        assert!(unpair(&dir).await.unwrap().is_empty());
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[test]
+    fn lutron_root_is_not_tried_twice() {
+        assert_eq!(ca_candidates(Some(LUTRON_ROOT_CA_PEM)).len(), 1);
+        let rewrapped = LUTRON_ROOT_CA_PEM.replace('\n', "\r\n");
+        assert_eq!(ca_candidates(Some(&rewrapped)).len(), 1);
+        let caseta = ca_candidates(Some(LAP_CA_PEM));
+        assert_eq!(caseta.iter().map(|c| c.0).collect::<Vec<_>>(), ["returned root certificate", "Lutron root CA"]);
+        assert_eq!(ca_candidates(None)[0].0, "Lutron root CA");
+    }
+
+    #[test]
+    fn unparseable_root_certs_are_skipped() {
+        assert_eq!(ca_candidates(Some("other")).len(), 1);
+        assert_eq!(ca_candidates(Some("-----BEGIN CERTIFICATE-----\n!!\n-----END CERTIFICATE-----\n")).len(), 1);
+    }
+}
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+
+/// Mapping between the perceptual RA2 level (0–100) and the level a backend
+/// is sent. Applied RA2→backend on commands and inverted backend→RA2 on
+/// events so a level read back matches the level that was set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LevelCurve {
+    /// Pass levels through unchanged.
+    #[default]
+    Linear,
+    /// backend = ra2² / 100
+    Square,
+    /// Piecewise-linear `[ra2, backend]` points, both columns increasing.
+    Table(Vec<[f64; 2]>),
+}
+
+impl LevelCurve {
+    pub fn is_linear(&self) -> bool {
+        matches!(self, LevelCurve::Linear)
+    }
+
+    /// Convert an RA2 level into the backend's level.
+    pub fn to_backend(&self, level: f64) -> f64 {
+        let level = level.clamp(0.0, 100.0);
+        match self {
+            LevelCurve::Linear => level,
+            LevelCurve::Square => level * level / 100.0,
+            LevelCurve::Table(points) => interpolate(points.iter().map(|p| (p[0], p[1])), level),
+        }
+    }
+
+    /// Convert a backend level back into an RA2 level.
+    pub fn to_ra2(&self, level: f64) -> f64 {
+        let level = level.clamp(0.0, 100.0);
+        match self {
+            LevelCurve::Linear => level,
+            LevelCurve::Square => (level * 100.0).sqrt(),
+            LevelCurve::Table(points) => interpolate(points.iter().map(|p| (p[1], p[0])), level),
+        }
+    }
+
+    /// A table needs at least two points and must be strictly increasing in
+    /// both columns, otherwise it can't be inverted.
+    pub fn validate(&self) -> Result<(), String> {
+        let LevelCurve::Table(points) = self else {
+            return Ok(());
+        };
+        if points.len() < 2 {
+            return Err("level_curve table needs at least two points".to_string());
+        }
+        for w in points.windows(2) {
+            if w[1][0] <= w[0][0] || w[1][1] <= w[0][1] {
+                return Err(format!(
+                    "level_curve table must be strictly increasing ({:?} → {:?})",
+                    w[0], w[1]
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The scale a level is given in over the API. Levels are 0–100 everywhere
+/// inside the bridge; `Byte` is the 0–255 brightness many automation
+/// platforms use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LevelScale {
+    #[default]
+    Percent,
+    Byte,
+}
+
+impl LevelScale {
+    /// Convert a level in this scale to an RA2 level (0–100).
+    pub fn to_ra2(self, value: f64) -> f64 {
+        match self {
+            LevelScale::Percent => value.clamp(0.0, 100.0),
+            LevelScale::Byte => brightness_to_level(value),
+        }
+    }
+
+    /// Convert an RA2 level back to this scale, for reporting.
+    pub fn report(self, level: f64) -> serde_json::Value {
+        match self {
+            LevelScale::Percent => level.into(),
+            LevelScale::Byte => level_to_brightness(level).into(),
+        }
+    }
+}
+
+/// Brightness (0–255) to an RA2 level (0–100), to two decimal places.
+pub fn brightness_to_level(brightness: f64) -> f64 {
+    (brightness.clamp(0.0, 255.0) * 100.0 / 255.0 * 100.0).round() / 100.0
+}
+
+/// RA2 level (0–100) to brightness (0–255).
+pub fn level_to_brightness(level: f64) -> u8 {
+    (level.clamp(0.0, 100.0) * 255.0 / 100.0).round() as u8
+}
+
+fn interpolate(points: impl Iterator<Item = (f64, f64)>, x: f64) -> f64 {
+    let points: Vec<(f64, f64)> = points.collect();
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(f), Some(l)) => (*f, *l),
+        _ => return x,
+    };
+    if x <= first.0 {
+        return first.1;
+    }
+    if x >= last.0 {
+        return last.1;
+    }
+    for w in points.windows(2) {
+        let ((x0, y0), (x1, y1)) = (w[0], w[1]);
+        if x <= x1 {
+            return y0 + (x - x0) * (y1 - y0) / (x1 - x0);
+        }
+    }
+    last.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn linear_is_identity() {
+        assert_close(LevelCurve::Linear.to_backend(42.0), 42.0);
+        assert_close(LevelCurve::Linear.to_ra2(42.0), 42.0);
+    }
+
+    #[test]
+    fn square_round_trip() {
+        let curve = LevelCurve::Square;
+        assert_close(curve.to_backend(50.0), 25.0);
+        for level in [0.0, 1.0, 33.3, 50.0, 99.0, 100.0] {
+            assert_close(curve.to_ra2(curve.to_backend(level)), level);
+        }
+    }
+
+    #[test]
+    fn table_round_trip() {
+        let curve = LevelCurve::Table(vec![[0.0, 0.0], [50.0, 20.0], [100.0, 100.0]]);
+        assert!(curve.validate().is_ok());
+        assert_close(curve.to_backend(25.0), 10.0);
+        assert_close(curve.to_backend(75.0), 60.0);
+        for level in [0.0, 10.0, 50.0, 80.0, 100.0] {
+            assert_close(curve.to_ra2(curve.to_backend(level)), level);
+        }
+    }
+
+    #[test]
+    fn table_must_increase() {
+        let curve = LevelCurve::Table(vec![[0.0, 0.0], [50.0, 60.0], [100.0, 50.0]]);
+        assert!(curve.validate().is_err());
+        assert!(LevelCurve::Table(vec![[0.0, 0.0]]).validate().is_err());
+    }
+
+    #[test]
+    fn byte_scale_boundaries() {
+        let byte = LevelScale::Byte;
+        assert_eq!(byte.to_ra2(0.0), 0.0);
+        assert_eq!(byte.to_ra2(128.0), 50.2);
+        assert_eq!(byte.to_ra2(255.0), 100.0);
+        assert_eq!(byte.to_ra2(300.0), 100.0);
+        assert_eq!(byte.report(0.0), 0);
+        assert_eq!(byte.report(50.2), 128);
+        assert_eq!(byte.report(100.0), 255);
+        assert_eq!(LevelScale::Percent.to_ra2(128.0), 100.0);
+        assert_eq!(LevelScale::Percent.report(50.2), 50.2);
+    }
+}
@@ -1,9 +1,20 @@
 mod bridge;
 mod config;
+mod config_audit;
+mod config_diff;
 mod discover;
+mod dump_levels;
 mod id_map;
+mod init_config;
+mod http_client;
 mod leap_client;
+#[cfg(test)]
+mod leap_mock;
 mod leap_pairing;
+mod level_curve;
+mod mqtt;
+mod notify;
+mod protocol_log;
 mod ra2_protocol;
 mod savant_client;
 mod savant_discover;
@@ -15,20 +26,75 @@ mod telnet_server;
 mod translator;
 mod web;
 mod web_log_layer;
+mod zone_attributes;
 
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use tracing::info;
 
 #[derive(Parser)]
 #[command(name = "ra-bridge", about = "RadioRA 3 → RadioRA 2 protocol relay")]
 struct Cli {
+    /// More log output: -v for debug, -vv for trace (RUST_LOG takes precedence)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// Only log warnings and errors (RUST_LOG takes precedence)
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Also log to this file, rotated daily (as <file>.YYYY-MM-DD)
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+    /// Rotated log files to keep alongside the current one
+    #[arg(long, global = true, default_value_t = 7, requires = "log_file")]
+    log_keep: usize,
     #[command(subcommand)]
     command: Commands,
 }
 
+impl Cli {
+    /// Default tracing filter when RUST_LOG isn't set.
+    fn log_level(&self) -> &'static str {
+        match (self.quiet, self.verbose) {
+            (true, _) => "warn",
+            (false, 0) => "info",
+            (false, 1) => "debug",
+            (false, _) => "trace",
+        }
+    }
+
+    fn env_filter(&self) -> tracing_subscriber::EnvFilter {
+        tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(self.log_level()))
+    }
+
+    /// Daily-rotated writer for `--log-file`. The guard flushes buffered
+    /// lines when dropped, so it must live until exit.
+    fn log_file_writer(
+        &self,
+    ) -> Result<Option<(tracing_appender::non_blocking::NonBlocking, tracing_appender::non_blocking::WorkerGuard)>>
+    {
+        let Some(path) = &self.log_file else {
+            return Ok(None);
+        };
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("--log-file {} has no file name", path.display()))?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => std::path::Path::new("."),
+        };
+        let appender = tracing_appender::rolling::Builder::new()
+            .rotation(tracing_appender::rolling::Rotation::DAILY)
+            .filename_prefix(file_name.to_string_lossy())
+            .max_log_files(self.log_keep + 1)
+            .build(dir)
+            .with_context(|| format!("Failed to open log file in {}", dir.display()))?;
+        Ok(Some(tracing_appender::non_blocking(appender)))
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Pair with a Lutron RadioRA 3 processor, then discover zones
@@ -45,6 +111,26 @@ enum Commands {
         /// LEAP port on the processor
         #[arg(long, default_value_t = 8081)]
         leap_port: u16,
+        /// Pairing port on the processor
+        #[arg(long, default_value_t = leap_pairing::DEFAULT_PAIRING_PORT)]
+        pairing_port: u16,
+        /// Zone control types to discover (dimmed, switched, shade, cco, ...)
+        #[arg(long, value_delimiter = ',', default_value = discover::DEFAULT_ZONE_TYPES)]
+        types: Vec<String>,
+        /// Print the discovered zones as JSON instead of writing config.
+        /// Pairing is skipped if certs_dir is already paired.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Delete the pairing certs and clear the processor from config so the
+    /// site can be paired again. Stop any running bridge first.
+    Unpair {
+        /// Directory containing pairing certificates
+        #[arg(long, default_value = "certs")]
+        certs_dir: PathBuf,
+        /// Path to config.toml; left alone if it doesn't exist
+        #[arg(long, default_value = "config.toml")]
+        config: PathBuf,
     },
     /// Run the RA2↔RA3 bridge relay
     Run {
@@ -66,6 +152,15 @@ enum Commands {
         /// Web server port
         #[arg(long, default_value_t = 8080)]
         web_port: u16,
+        /// Refuse config, pairing, bridge-control and site changes from the web UI
+        #[arg(long)]
+        read_only: bool,
+        /// Serve the web UI from this directory instead of the built-in page
+        #[arg(long)]
+        static_dir: Option<PathBuf>,
+        /// Directory of alternative config files the web UI may switch to
+        #[arg(long)]
+        profiles_dir: Option<PathBuf>,
     },
     /// Multi-site dev server for managing multiple RA3 site profiles
     Dev {
@@ -75,6 +170,36 @@ enum Commands {
         /// Web server port
         #[arg(long, default_value_t = 8080)]
         web_port: u16,
+        /// Refuse config, pairing, bridge-control and site changes from the web UI
+        #[arg(long)]
+        read_only: bool,
+        /// Serve the web UI from this directory instead of the built-in page
+        #[arg(long)]
+        static_dir: Option<PathBuf>,
+    },
+    /// Write a commented sample config.toml to start from
+    InitConfig {
+        /// Where to write it
+        #[arg(long, default_value = "config.toml")]
+        output: PathBuf,
+        /// Overwrite the file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Rewrite an older config.toml as the current config version
+    MigrateConfig {
+        /// Path to config.toml; the original is kept as config.toml.v<N>.bak
+        #[arg(long, default_value = "config.toml")]
+        config: PathBuf,
+    },
+    /// Print current zone levels from a running bridge's web API
+    DumpLevels {
+        /// Base URL of the bridge's web server
+        #[arg(long, default_value = "http://localhost:8080")]
+        web: String,
+        /// Print raw JSON instead of a table
+        #[arg(long)]
+        json: bool,
     },
     /// Discover Savant devices and add them to config
     SavantDiscover {
@@ -84,48 +209,93 @@ enum Commands {
         /// Savant WebSocket port
         #[arg(long, default_value_t = 8480)]
         port: u16,
-        /// Starting RA2 ID for Savant zones
+        /// Starting RA2 ID for Savant zones; ignored when config sets [id_ranges]
         #[arg(long, default_value_t = 200)]
         start_id: u32,
         /// Path to config.toml
         #[arg(long, default_value = "config.toml")]
         config: PathBuf,
+        /// Add the host as another [[savant]] entry instead of replacing the
+        /// first one. Rediscovering a host already in config always replaces it.
+        #[arg(long)]
+        add: bool,
     },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let (log_tx, _) = tokio::sync::broadcast::channel::<String>(256);
+    let log_history = web_log_layer::LogHistory::default();
 
     use tracing_subscriber::layer::SubscriberExt;
     use tracing_subscriber::util::SubscriberInitExt;
     use tracing_subscriber::Layer;
 
-    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
-    let fmt_layer = tracing_subscriber::fmt::layer().with_filter(env_filter);
-    let web_layer = web_log_layer::WebLogLayer::new(log_tx.clone());
+    let cli = Cli::parse();
+
+    let fmt_layer = tracing_subscriber::fmt::layer().with_filter(cli.env_filter());
+    let web_layer = web_log_layer::WebLogLayer::new(log_tx.clone(), log_history.clone());
+    let (file_layer, _log_guard) = match cli.log_file_writer()? {
+        Some((writer, guard)) => {
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer)
+                .with_filter(cli.env_filter());
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
 
     tracing_subscriber::registry()
         .with(fmt_layer)
+        .with(file_layer)
         .with(web_layer)
         .init();
 
-    let cli = Cli::parse();
-
     match cli.command {
-        Commands::Pair { host, certs_dir, config: config_path, leap_port } => {
-            leap_pairing::pair(&host, &certs_dir).await?;
+        Commands::Pair { host, certs_dir, config: config_path, leap_port, pairing_port, types, dry_run } => {
+            let settings = discover::discovery_settings(&config_path).await?;
+            if dry_run && state::has_certs(&certs_dir) {
+                info!("Already paired, skipping pairing");
+            } else {
+                leap_pairing::pair(&host, pairing_port, &certs_dir, &settings.ca_files).await?;
+            }
 
             info!("Discovering zones...");
-            let zones = discover::discover_zones(&host, leap_port, &certs_dir).await?;
+            let zones = discover::discover_zones(
+                &host,
+                leap_port,
+                &certs_dir,
+                &settings.ca_files,
+                &types,
+                settings.ids,
+                settings.separator,
+            )
+            .await?;
             info!("Found {} zones", zones.len());
 
-            discover::write_config(&config_path, &host, leap_port, &zones)?;
+            if dry_run {
+                let preview = serde_json::json!({ "zone_count": zones.len(), "zones": zones });
+                println!("{}", serde_json::to_string_pretty(&preview)?);
+                return Ok(());
+            }
+
+            discover::write_config(&config_path, &host, leap_port, &zones).await?;
             info!("Wrote {}", config_path.display());
         }
+        Commands::Unpair { certs_dir, config: config_path } => {
+            for path in leap_pairing::unpair(&certs_dir).await? {
+                info!("Removed {}", path.display());
+            }
+            if config_path.exists() {
+                let mut cfg = config::Config::load(&config_path).await?;
+                cfg.processor.host.clear();
+                cfg.save(&config_path).await?;
+                info!("Cleared processor from {}", config_path.display());
+            }
+        }
         Commands::Run { config: config_path, certs_dir } => {
-            let cfg = config::Config::load(&config_path)?;
+            let cfg = config::Config::load(&config_path).await?;
             tracing::info!(
                 "Loaded config: {} LEAP zones, {} Savant zones, LEAP at {}:{}",
                 cfg.zones.len(),
@@ -135,42 +305,100 @@ async fn main() -> Result<()> {
             );
             bridge::run(cfg, certs_dir).await?;
         }
-        Commands::Serve { config: config_path, certs_dir, web_port } => {
-            serve::serve(config_path, certs_dir, web_port, log_tx).await?;
+        Commands::Serve { config: config_path, certs_dir, web_port, read_only, static_dir, profiles_dir } => {
+            serve::serve(config_path, certs_dir, profiles_dir, web_port, read_only, static_dir, log_tx, log_history)
+                .await?;
         }
-        Commands::Dev { sites_dir, web_port } => {
-            serve::serve_dev(sites_dir, web_port, log_tx).await?;
+        Commands::Dev { sites_dir, web_port, read_only, static_dir } => {
+            serve::serve_dev(sites_dir, web_port, read_only, static_dir, log_tx, log_history).await?;
         }
-        Commands::SavantDiscover { host, port, start_id, config: config_path } => {
-            info!("Discovering Savant devices at {}:{}...", host, port);
-            let (savant_config, savant_zones) = savant_discover::discover_zones(&host, port, start_id).await?;
-            info!("Found {} Savant zones", savant_zones.len());
-
+        Commands::InitConfig { output, force } => {
+            init_config::run(&output, force).await?;
+            info!("Wrote {}", output.display());
+        }
+        Commands::MigrateConfig { config: config_path } => {
+            if config::Config::migrate_file(&config_path).await?.is_none() {
+                info!("{} is already config version {}", config_path.display(), config::CONFIG_VERSION);
+            }
+        }
+        Commands::DumpLevels { web, json } => {
+            dump_levels::run(&web, json).await?;
+        }
+        Commands::SavantDiscover { host, port, start_id, config: config_path, add } => {
             // Merge into existing config or create new one
             let mut cfg = if config_path.exists() {
-                config::Config::load(&config_path)?
+                config::Config::load(&config_path).await?
             } else {
                 config::Config {
-                    processor: config::ProcessorConfig {
-                        host: String::new(),
-                        leap_port: 8081,
-                    },
+                    version: config::CONFIG_VERSION,
+                    level_curve: Default::default(),
+                    processor: config::ProcessorConfig::new(String::new(), 8081),
                     telnet: Default::default(),
                     web: Default::default(),
                     zones: vec![],
-                    savant: None,
+                    savant: vec![],
                     savant_zones: vec![],
+                    buttons: vec![],
+                    keypads: vec![],
+                    bridge: Default::default(),
+                    notifications: Default::default(),
+                    logging: Default::default(),
+                    mqtt: None,
+                    id_ranges: None,
                 }
             };
 
-            cfg.savant = Some(savant_config);
-            cfg.savant_zones = savant_zones;
+            let index = cfg
+                .savant
+                .iter()
+                .position(|s| s.host == host)
+                .unwrap_or(if add { cfg.savant.len() } else { 0 });
+            let (device, max_zones) = cfg
+                .savant
+                .get(index)
+                .map(|s| (s.device.clone(), s.max_discovered_zones))
+                .unwrap_or_else(|| (Default::default(), config::default_max_discovered_zones()));
+            info!("Discovering Savant devices at {}:{}...", host, port);
+            let (savant_config, savant_zones) = savant_discover::discover_zones(
+                &host,
+                port,
+                cfg.savant_ids(start_id),
+                &device,
+                max_zones,
+                cfg.web.area_separator,
+            )
+            .await?;
+            info!("Found {} Savant zones", savant_zones.len());
+
+            // Other hosts keep their zones; this host's get ids clear of them
+            // and whatever names and limits they had before
+            let attributes = zone_attributes::ZoneAttributeStore::capture(&cfg);
+            let others: Vec<_> = cfg.savant_zones.drain(..).filter(|z| z.host != index).collect();
+            let reserved = cfg
+                .zones
+                .iter()
+                .map(|z| z.ra2_id)
+                .chain(others.iter().map(|z| z.ra2_id))
+                .collect();
+            let merged = savant_discover::merge_zones(&[], &savant_zones, &reserved, cfg.savant_ids(start_id))?;
+            cfg.savant_zones = others;
+            cfg.savant_zones.extend(
+                merged
+                    .zones
+                    .into_iter()
+                    .map(|z| config::SavantZoneMapping { host: index, ..z }),
+            );
+            attributes.restore(&mut cfg);
+            match cfg.savant.get_mut(index) {
+                Some(existing) => *existing = savant_config,
+                None => cfg.savant.push(savant_config),
+            }
 
             if let Err(e) = cfg.validate() {
                 anyhow::bail!("Config validation failed: {}", e);
             }
 
-            cfg.save(&config_path)?;
+            cfg.save(&config_path).await?;
             info!("Wrote {}", config_path.display());
         }
     }
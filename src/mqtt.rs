@@ -0,0 +1,317 @@
+//! Home Assistant over MQTT: each zone is announced as a light through MQTT
+//! discovery, its level is published whenever it changes, and commands on
+//! its command topic are fed into the bridge like any telnet `#OUTPUT`.
+//! Levels come from the same `zone_levels` cache the web UI's SSE stream
+//! watches.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Outgoing, Packet, QoS};
+use serde_json::Value;
+use tokio::sync::{watch, Notify};
+use tracing::{debug, info, warn};
+
+use crate::bridge::{BridgeCommand, BridgeMaps, CommandQueue, CommandSource};
+use crate::config::MqttConfig;
+use crate::level_curve::{brightness_to_level, level_to_brightness};
+use crate::ra2_protocol::Ra2Command;
+use crate::state::ZoneLevels;
+
+/// How often `zone_levels` is checked for changes, matching the SSE stream.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Connect to the broker and run until the bridge drops its command
+/// receiver. Connection errors are logged and retried.
+pub fn spawn(
+    mqtt: MqttConfig,
+    device_name: String,
+    maps: watch::Receiver<Arc<BridgeMaps>>,
+    zone_levels: ZoneLevels,
+    cmd_tx: CommandQueue,
+) {
+    let topics = Arc::new(Topics {
+        prefix: mqtt.topic_prefix.clone(),
+        discovery_prefix: mqtt.discovery_prefix.clone(),
+    });
+
+    let mut options = MqttOptions::new(mqtt.topic_prefix.clone(), mqtt.broker.clone(), mqtt.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(topics.availability(), "offline", QoS::AtLeastOnce, true));
+    if let Some(username) = &mqtt.username {
+        options.set_credentials(username, mqtt.password.clone().unwrap_or_default());
+    }
+    let (client, mut eventloop) = AsyncClient::new(options, 64);
+    let connected = Arc::new(Notify::new());
+
+    // Event loop: (re)subscribe on every connect and turn commands into
+    // set-levels. Announcing is left to the publisher so this never blocks.
+    {
+        let client = client.clone();
+        let topics = topics.clone();
+        let connected = connected.clone();
+        let maps = maps.clone();
+        let cmd_tx = cmd_tx.clone();
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    event = eventloop.poll() => event,
+                    _ = cmd_tx.closed() => break,
+                };
+                match event {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("MQTT connected to {}:{}", mqtt.broker, mqtt.port);
+                        let _ = client.try_subscribe(topics.command_filter(), QoS::AtLeastOnce);
+                        connected.notify_one();
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        let Some(id) = topics.command_id(&publish.topic) else { continue };
+                        if !maps.borrow().zone_names.contains_key(&id) {
+                            warn!("MQTT command for unknown zone {}", id);
+                            continue;
+                        }
+                        match parse_command(id, &publish.payload) {
+                            Some(cmd) => {
+                                info!("MQTT → bridge: {:?}", cmd);
+                                let _ = cmd_tx.submit(BridgeCommand { source: CommandSource::Mqtt, cmd });
+                            }
+                            None => warn!(
+                                "MQTT: unparsed command for zone {}: {}",
+                                id,
+                                String::from_utf8_lossy(&publish.payload)
+                            ),
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("MQTT connection error: {}. Reconnecting in {:?}...", e, RECONNECT_DELAY);
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                    }
+                }
+            }
+
+            // Bridge stopped: mark the lights unavailable and leave cleanly
+            let _ = client.try_publish(topics.availability(), QoS::AtLeastOnce, true, "offline");
+            let _ = client.try_disconnect();
+            let _ = tokio::time::timeout(Duration::from_secs(2), async {
+                while let Ok(event) = eventloop.poll().await {
+                    if matches!(event, Event::Outgoing(Outgoing::Disconnect)) {
+                        break;
+                    }
+                }
+            })
+            .await;
+            info!("MQTT client stopped");
+        });
+    }
+
+    tokio::spawn(publish_loop(client, topics, device_name, maps, zone_levels, connected, cmd_tx));
+}
+
+/// Announce every zone after each connect, follow zone edits, and publish
+/// levels as they change.
+async fn publish_loop(
+    client: AsyncClient,
+    topics: Arc<Topics>,
+    device_name: String,
+    mut maps: watch::Receiver<Arc<BridgeMaps>>,
+    zone_levels: ZoneLevels,
+    connected: Arc<Notify>,
+    cmd_tx: CommandQueue,
+) {
+    let mut announced: BTreeMap<u32, String> = BTreeMap::new();
+    let mut published: HashMap<u32, f64> = HashMap::new();
+    // Nothing is queued until the broker has answered once, so an
+    // unreachable broker can't back up the client's request channel
+    let mut online = false;
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = cmd_tx.closed() => break,
+            _ = connected.notified() => {
+                // Retained topics may be stale from before a restart
+                online = true;
+                announced.clear();
+                published.clear();
+                let _ = client.publish(topics.availability(), QoS::AtLeastOnce, true, "online").await;
+            }
+            changed = maps.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+            }
+            _ = interval.tick() => {}
+        }
+        if !online {
+            continue;
+        }
+
+        let names = maps.borrow_and_update().zone_names.clone();
+        for (id, name) in &names {
+            if announced.get(id) != Some(name) {
+                let payload = discovery_payload(&topics, *id, name, &device_name);
+                let _ = client
+                    .publish(topics.discovery(*id), QoS::AtLeastOnce, true, payload.to_string())
+                    .await;
+            }
+        }
+        for id in announced.keys().filter(|id| !names.contains_key(id)) {
+            debug!("MQTT: removing zone {}", id);
+            let _ = client.publish(topics.discovery(*id), QoS::AtLeastOnce, true, "").await;
+            published.remove(id);
+        }
+        announced = names;
+
+        let levels = zone_levels.snapshot().await;
+        for id in announced.keys() {
+            let Some(&level) = levels.get(id) else { continue };
+            if published.get(id) == Some(&level) {
+                continue;
+            }
+            let payload = state_payload(level).to_string();
+            if client
+                .publish(topics.state(*id), QoS::AtLeastOnce, true, payload)
+                .await
+                .is_ok()
+            {
+                published.insert(*id, level);
+            }
+        }
+    }
+}
+
+struct Topics {
+    prefix: String,
+    discovery_prefix: String,
+}
+
+impl Topics {
+    fn availability(&self) -> String {
+        format!("{}/status", self.prefix)
+    }
+
+    fn state(&self, id: u32) -> String {
+        format!("{}/light/{}/state", self.prefix, id)
+    }
+
+    fn command(&self, id: u32) -> String {
+        format!("{}/light/{}/set", self.prefix, id)
+    }
+
+    fn command_filter(&self) -> String {
+        format!("{}/light/+/set", self.prefix)
+    }
+
+    fn command_id(&self, topic: &str) -> Option<u32> {
+        topic
+            .strip_prefix(&self.prefix)?
+            .strip_prefix("/light/")?
+            .strip_suffix("/set")?
+            .parse()
+            .ok()
+    }
+
+    fn discovery(&self, id: u32) -> String {
+        format!("{}/light/{}/zone_{}/config", self.discovery_prefix, self.prefix, id)
+    }
+}
+
+/// MQTT discovery config for one zone, using the JSON light schema.
+fn discovery_payload(topics: &Topics, id: u32, name: &str, device_name: &str) -> Value {
+    serde_json::json!({
+        "name": name,
+        "unique_id": format!("{}_zone_{}", topics.prefix, id),
+        "schema": "json",
+        "command_topic": topics.command(id),
+        "state_topic": topics.state(id),
+        "availability_topic": topics.availability(),
+        "supported_color_modes": ["brightness"],
+        "brightness": true,
+        "brightness_scale": 255,
+        "device": {
+            "identifiers": [topics.prefix],
+            "name": device_name,
+            "manufacturer": "ra-bridge",
+        },
+    })
+}
+
+fn state_payload(level: f64) -> Value {
+    if level > 0.0 {
+        serde_json::json!({
+            "state": "ON",
+            "color_mode": "brightness",
+            "brightness": level_to_brightness(level),
+        })
+    } else {
+        serde_json::json!({ "state": "OFF" })
+    }
+}
+
+/// A JSON-schema light command. `ON` without a brightness means full on;
+/// `transition` (seconds) becomes the fade.
+fn parse_command(id: u32, payload: &[u8]) -> Option<Ra2Command> {
+    let command: Value = serde_json::from_slice(payload).ok()?;
+    let level = match command["state"].as_str()? {
+        "OFF" => 0.0,
+        "ON" => command["brightness"].as_f64().map_or(100.0, brightness_to_level),
+        _ => return None,
+    };
+    Some(Ra2Command::SetOutput {
+        id,
+        level,
+        fade: command["transition"].as_f64(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topics() -> Topics {
+        Topics {
+            prefix: "ra-bridge".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+
+    #[test]
+    fn brightness_maps_to_ra2_levels() {
+        assert_eq!(brightness_to_level(255.0), 100.0);
+        assert_eq!(brightness_to_level(0.0), 0.0);
+        assert_eq!(brightness_to_level(128.0), 50.2);
+        assert_eq!(level_to_brightness(100.0), 255);
+        assert_eq!(level_to_brightness(50.0), 128);
+        assert_eq!(level_to_brightness(brightness_to_level(77.0)), 77);
+    }
+
+    #[test]
+    fn commands_become_set_levels() {
+        let level = |payload: &str| match parse_command(5, payload.as_bytes()) {
+            Some(Ra2Command::SetOutput { id: 5, level, fade }) => (level, fade),
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(level(r#"{"state":"OFF"}"#), (0.0, None));
+        assert_eq!(level(r#"{"state":"ON"}"#), (100.0, None));
+        assert_eq!(level(r#"{"state":"ON","brightness":51,"transition":2}"#), (20.0, Some(2.0)));
+        assert!(parse_command(5, b"ON").is_none());
+    }
+
+    #[test]
+    fn topics_round_trip() {
+        let topics = topics();
+        assert_eq!(topics.command_id(&topics.command(12)), Some(12));
+        assert_eq!(topics.command_id("ra-bridge/light/x/set"), None);
+        assert_eq!(topics.command_id("other/light/12/set"), None);
+        assert_eq!(topics.discovery(12), "homeassistant/light/ra-bridge/zone_12/config");
+
+        let config = discovery_payload(&topics, 12, "Kitchen", "Home");
+        assert_eq!(config["command_topic"], "ra-bridge/light/12/set");
+        assert_eq!(config["state_topic"], "ra-bridge/light/12/state");
+        assert_eq!(state_payload(50.0)["brightness"], 128);
+        assert_eq!(state_payload(0.0)["state"], "OFF");
+    }
+}
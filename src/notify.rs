@@ -0,0 +1,180 @@
+//! Outbound webhook on bridge and backend state changes, so operators can be
+//! paged when the processor or Savant host drops. Best-effort: payloads are
+//! queued and POSTed by a background task, and dropped after a few retries.
+
+use std::mem::discriminant;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{mpsc, watch, RwLock};
+use tracing::{debug, warn};
+
+use crate::config::Config;
+use crate::state::{BackendHealth, BackendStatus, BridgeStatus};
+
+const ATTEMPTS: u32 = 3;
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Changes beyond this many unsent payloads are dropped rather than queued.
+const QUEUE: usize = 32;
+
+/// Watch the bridge and both backends and POST each state change to
+/// `[notifications] webhook_url`. The URL is read from `config` per send, so
+/// config edits and site switches apply without restarting anything.
+pub fn spawn(
+    config: Arc<RwLock<Option<Config>>>,
+    bridge_status: watch::Receiver<BridgeStatus>,
+    health: &BackendHealth,
+) {
+    let (tx, rx) = mpsc::channel(QUEUE);
+    watch_source("bridge", bridge_status, |_| false, tx.clone());
+    watch_source("leap", health.leap.subscribe(), is_connecting, tx.clone());
+    watch_source("savant", health.savant.subscribe(), is_connecting, tx);
+    tokio::spawn(deliver(config, rx));
+}
+
+/// Reconnect attempts flip a backend through `Connecting` every few seconds;
+/// the `Error` or `Connected` that follows is what's worth sending.
+fn is_connecting(status: &BackendStatus) -> bool {
+    *status == BackendStatus::Connecting
+}
+
+/// Queue a payload whenever `rx` moves to a different state. Repeats of the
+/// same state (e.g. `Error` with a higher attempt count) and states `skip`
+/// matches are not sent. The starting state is read before this returns, so
+/// no change made after it can be missed.
+fn watch_source<T>(
+    source: &'static str,
+    mut rx: watch::Receiver<T>,
+    skip: fn(&T) -> bool,
+    tx: mpsc::Sender<Value>,
+) where
+    T: Clone + Serialize + Send + Sync + 'static,
+{
+    let mut last = rx.borrow_and_update().clone();
+    tokio::spawn(async move {
+        while rx.changed().await.is_ok() {
+            let status = rx.borrow_and_update().clone();
+            if skip(&status) {
+                continue;
+            }
+            if discriminant(&status) != discriminant(&last) {
+                let payload = serde_json::json!({
+                    "source": source,
+                    "status": status,
+                    "previous": last,
+                    "timestamp": SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or_default(),
+                });
+                if tx.try_send(payload).is_err() {
+                    warn!("Webhook queue full, dropping {} state change", source);
+                }
+            }
+            last = status;
+        }
+    });
+}
+
+async fn deliver(config: Arc<RwLock<Option<Config>>>, mut rx: mpsc::Receiver<Value>) {
+    while let Some(payload) = rx.recv().await {
+        let url = config
+            .read()
+            .await
+            .as_ref()
+            .and_then(|c| c.notifications.webhook_url.clone());
+        let Some(url) = url else { continue };
+
+        let mut backoff = Duration::from_secs(1);
+        for attempt in 1..=ATTEMPTS {
+            let result = tokio::time::timeout(
+                ATTEMPT_TIMEOUT,
+                crate::http_client::post_json(&url, &payload),
+            )
+            .await;
+            let error = match result {
+                Ok(Ok(resp)) if (200..300).contains(&resp.status) => {
+                    debug!("Webhook sent: {}", payload);
+                    break;
+                }
+                Ok(Ok(resp)) => format!("HTTP {}", resp.status),
+                Ok(Err(e)) => format!("{:#}", e),
+                Err(_) => "timed out".to_string(),
+            };
+            if attempt == ATTEMPTS {
+                warn!("Webhook to {} failed after {} attempts: {}", url, ATTEMPTS, error);
+            } else {
+                debug!("Webhook to {} failed ({}), retrying in {:?}", url, error, backoff);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Accept one request and return its body, answering with `status`.
+    async fn receive(listener: &TcpListener, status: u16) -> Value {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut raw = Vec::new();
+        let mut buf = [0u8; 1024];
+        let body = loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            raw.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&raw).into_owned();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let len: usize = head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("Content-Length: "))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                if body.len() >= len {
+                    break body.to_string();
+                }
+            }
+        };
+        let reply = format!("HTTP/1.1 {} X\r\nContent-Length: 0\r\n\r\n", status);
+        stream.write_all(reply.as_bytes()).await.unwrap();
+        serde_json::from_str(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn backend_drops_are_posted_with_retry() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config: Config = toml::from_str("[processor]\nhost = \"10.0.0.5\"").unwrap();
+        config.notifications.webhook_url =
+            Some(format!("http://{}/hook", listener.local_addr().unwrap()));
+        let health = BackendHealth::new();
+        let bridge_status = watch::channel(BridgeStatus::Stopped).0;
+        spawn(Arc::new(RwLock::new(Some(config))), bridge_status.subscribe(), &health);
+
+        let _ = health.leap.send(BackendStatus::Connected);
+        let first = receive(&listener, 200).await;
+        assert_eq!(first["source"], "leap");
+        assert_eq!(first["status"]["state"], "Connected");
+        assert_eq!(first["previous"]["state"], "NotConfigured");
+
+        // Connecting is skipped and repeated errors collapse into one
+        let _ = health.leap.send(BackendStatus::Connecting);
+        let _ = health.leap.send(BackendStatus::Error { message: "refused".to_string(), attempts: 1 });
+        let _ = health.leap.send(BackendStatus::Error { message: "refused".to_string(), attempts: 2 });
+
+        // A failed delivery is retried with the same payload
+        let dropped = receive(&listener, 500).await;
+        assert_eq!(dropped["status"]["state"], "Error");
+        assert_eq!(dropped["previous"]["state"], "Connected");
+        assert_eq!(receive(&listener, 200).await, dropped);
+
+        let _ = bridge_status.send(BridgeStatus::Running);
+        let next = receive(&listener, 200).await;
+        assert_eq!((next["source"].as_str(), next["status"]["state"].as_str()), (Some("bridge"), Some("Running")));
+    }
+}
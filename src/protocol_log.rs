@@ -0,0 +1,31 @@
+//! Per-message protocol logging for the backend clients. Lines logged with
+//! `protocol_log!` come out at info when `[logging] trace_protocol` is on
+//! and at the quieter level given otherwise, so a busy site doesn't flood
+//! the log (and the web log stream) with every TX and RX.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static TRACE_PROTOCOL: AtomicBool = AtomicBool::new(false);
+
+/// Applied when the bridge starts, from `[logging] trace_protocol`.
+pub fn set_enabled(enabled: bool) {
+    TRACE_PROTOCOL.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    TRACE_PROTOCOL.load(Ordering::Relaxed)
+}
+
+/// `protocol_log!(debug, "Savant TX: {}", msg)` logs at info with
+/// `trace_protocol` on, else at the named level.
+macro_rules! protocol_log {
+    ($quiet:ident, $($arg:tt)+) => {
+        if $crate::protocol_log::enabled() {
+            tracing::info!($($arg)+)
+        } else {
+            tracing::$quiet!($($arg)+)
+        }
+    };
+}
+
+pub(crate) use protocol_log;
@@ -12,12 +12,60 @@ pub enum Ra2Command {
     QueryOutput { id: u32 },
     /// #MONITORING,<type>,<action>  (action: 1=enable, 2=disable)
     Monitoring { mon_type: u32, enable: bool },
+    /// #RESET, #REBOOT, #DETAILS, ?DETAILS or ?SYSTEM, kept verbatim. The
+    /// telnet server answers these itself (see `system_reply`); nothing is
+    /// reset or rebooted.
+    System { raw: String },
+}
+
+impl Ra2Command {
+    /// The integration ID this command targets, if any.
+    pub fn id(&self) -> Option<u32> {
+        match self {
+            Ra2Command::SetOutput { id, .. } => Some(*id),
+            Ra2Command::QueryOutput { id } => Some(*id),
+            Ra2Command::Monitoring { .. } | Ra2Command::System { .. } => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Ra2Event {
     /// ~OUTPUT,<id>,1,<level>
     OutputLevel { id: u32, level: f64 },
+    /// ~DEVICE,<id>,<component>,9,<state>  (LED state: 0 = off, 1 = on)
+    DeviceButton { id: u32, component: u32, led_on: bool },
+    /// ~ERROR,<code>  (2 = object does not exist)
+    Error { code: u32 },
+}
+
+/// RA2 `#OUTPUT` / `~OUTPUT` action number for a zone level.
+pub const OUTPUT_ACTION_LEVEL: u32 = 1;
+
+/// RA2 `~DEVICE` action number for an LED state report.
+pub const DEVICE_ACTION_LED_STATE: u32 = 9;
+
+impl Ra2Event {
+    /// The action number this event is reported with. Each event type has
+    /// its own; `~ERROR` has none.
+    pub fn action(&self) -> Option<u32> {
+        match self {
+            Ra2Event::OutputLevel { .. } => Some(OUTPUT_ACTION_LEVEL),
+            Ra2Event::DeviceButton { .. } => Some(DEVICE_ACTION_LED_STATE),
+            Ra2Event::Error { .. } => None,
+        }
+    }
+}
+
+/// RA2 error code for an integration ID that doesn't exist.
+pub const ERROR_OBJECT_NOT_FOUND: u32 = 2;
+
+/// Whether `line` asks for the project database (`GETDBXML` or
+/// `?DBEXPORT`), which the telnet server answers itself with the same
+/// DbXmlInfo.xml the web server exports.
+pub fn is_db_export_request(line: &str) -> bool {
+    let line = line.trim();
+    line.eq_ignore_ascii_case("GETDBXML") || line.eq_ignore_ascii_case("?DBEXPORT")
 }
 
 /// Parse a line from a telnet client into an RA2 command.
@@ -31,6 +79,10 @@ pub fn parse_command(line: &str) -> Option<Ra2Command> {
     let rest = &line[1..];
     let parts: Vec<&str> = rest.split(',').collect();
 
+    if is_system_command(prefix, parts[0]) {
+        return Some(Ra2Command::System { raw: line.to_string() });
+    }
+
     match prefix {
         b'#' => parse_action(&parts),
         b'?' => parse_query(&parts),
@@ -38,6 +90,40 @@ pub fn parse_command(line: &str) -> Option<Ra2Command> {
     }
 }
 
+fn is_system_command(prefix: u8, name: &str) -> bool {
+    let name = name.trim().to_uppercase();
+    match prefix {
+        b'#' => matches!(name.as_str(), "RESET" | "REBOOT" | "DETAILS"),
+        b'?' => matches!(name.as_str(), "SYSTEM" | "DETAILS"),
+        _ => false,
+    }
+}
+
+/// The canned reply to a `System` command, given the current time as an
+/// RFC 3339 UTC timestamp. An empty reply means the command is acknowledged
+/// with just the `GNET> ` prompt, the way a main repeater answers a command
+/// it accepted:
+///
+/// - `?SYSTEM,1` → `~SYSTEM,1,<HH:MM:SS>` and `?SYSTEM,2` →
+///   `~SYSTEM,2,<MM/DD/YYYY>`, both in UTC
+/// - any other `?SYSTEM` action, `#RESET`, `#REBOOT`, `#DETAILS` and
+///   `?DETAILS` → prompt only
+pub fn system_reply(raw: &str, now: &str) -> String {
+    let parts: Vec<&str> = raw.trim().split(',').map(str::trim).collect();
+    if !parts[0].eq_ignore_ascii_case("?SYSTEM") {
+        return String::new();
+    }
+    let (date, time) = now.split_once('T').unwrap_or((now, ""));
+    match parts.get(1).copied() {
+        Some("1") if time.len() >= 8 => format!("~SYSTEM,1,{}", &time[..8]),
+        Some("2") => match date.split('-').collect::<Vec<_>>()[..] {
+            [year, month, day] => format!("~SYSTEM,2,{}/{}/{}", month, day, year),
+            _ => String::new(),
+        },
+        _ => String::new(),
+    }
+}
+
 fn parse_action(parts: &[&str]) -> Option<Ra2Command> {
     if parts.is_empty() {
         return None;
@@ -50,12 +136,11 @@ fn parse_action(parts: &[&str]) -> Option<Ra2Command> {
                 return None;
             }
             let id: u32 = parts[1].trim().parse().ok()?;
-            // parts[2] is action number (1 = set level)
             let action: u32 = parts[2].trim().parse().ok()?;
-            if action != 1 {
+            if action != OUTPUT_ACTION_LEVEL {
                 return None; // v1: only support action 1 (set level)
             }
-            let level: f64 = parts[3].trim().parse().ok()?;
+            let level = parse_level(parts[3])?;
             let fade = if parts.len() >= 5 {
                 parts[4].trim().parse().ok()
             } else {
@@ -79,6 +164,18 @@ fn parse_action(parts: &[&str]) -> Option<Ra2Command> {
     }
 }
 
+/// Parse a level field: numeric, or `ON`/`OFF` as sent by some older controllers.
+fn parse_level(s: &str) -> Option<f64> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("ON") {
+        Some(100.0)
+    } else if s.eq_ignore_ascii_case("OFF") {
+        Some(0.0)
+    } else {
+        s.parse().ok()
+    }
+}
+
 fn parse_query(parts: &[&str]) -> Option<Ra2Command> {
     if parts.is_empty() {
         return None;
@@ -97,12 +194,34 @@ fn parse_query(parts: &[&str]) -> Option<Ra2Command> {
     }
 }
 
-/// Format an RA2 event as a protocol line (without trailing \r\n).
-pub fn format_event(event: &Ra2Event) -> String {
+/// Decimal places in `~OUTPUT` levels unless `[telnet] level_decimals` says
+/// otherwise.
+pub const DEFAULT_LEVEL_DECIMALS: usize = 2;
+pub const MAX_LEVEL_DECIMALS: usize = 2;
+
+/// Format an RA2 event as a protocol line (without trailing \r\n), with
+/// the event's own action number (see `Ra2Event::action`). Output levels
+/// are clamped to 0–100 and rounded half away from zero to `level_decimals`
+/// places.
+pub fn format_event(event: &Ra2Event, level_decimals: usize) -> String {
     match event {
         Ra2Event::OutputLevel { id, level } => {
-            format!("~OUTPUT,{},1,{:.2}", id, level)
+            let action = event.action().unwrap_or_default();
+            let decimals = level_decimals.min(MAX_LEVEL_DECIMALS);
+            let scale = 10f64.powi(decimals as i32);
+            // `+ 0.0` turns a rounded -0.0 into 0.0
+            let level = (level.clamp(0.0, 100.0) * scale).round() / scale + 0.0;
+            format!("~OUTPUT,{},{},{:.*}", id, action, decimals, level)
+        }
+        Ra2Event::DeviceButton {
+            id,
+            component,
+            led_on,
+        } => {
+            let action = event.action().unwrap_or_default();
+            format!("~DEVICE,{},{},{},{}", id, component, action, u8::from(*led_on))
         }
+        Ra2Event::Error { code } => format!("~ERROR,{}", code),
     }
 }
 
@@ -134,6 +253,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_set_output_on_off() {
+        assert_eq!(
+            parse_command("#OUTPUT,5,1,ON"),
+            Some(Ra2Command::SetOutput {
+                id: 5,
+                level: 100.0,
+                fade: None,
+            })
+        );
+        assert_eq!(
+            parse_command("#OUTPUT,5,1,off,2"),
+            Some(Ra2Command::SetOutput {
+                id: 5,
+                level: 0.0,
+                fade: Some(2.0),
+            })
+        );
+        assert_eq!(parse_command("#OUTPUT,5,1,MAYBE"), None);
+    }
+
     #[test]
     fn parse_query_output() {
         assert_eq!(
@@ -142,6 +282,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn system_commands_get_canned_replies() {
+        let system = |line: &str| match parse_command(line) {
+            Some(Ra2Command::System { raw }) => raw,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(system(" #reset,0 "), "#reset,0");
+        assert_eq!(system("#REBOOT"), "#REBOOT");
+        assert_eq!(system("?DETAILS,5"), "?DETAILS,5");
+        assert_eq!(parse_command("?RESET,0"), None);
+
+        let now = "2026-03-07T14:05:09.123456Z";
+        assert_eq!(system_reply("?SYSTEM,1", now), "~SYSTEM,1,14:05:09");
+        assert_eq!(system_reply("?system, 2", now), "~SYSTEM,2,03/07/2026");
+        assert_eq!(system_reply("?SYSTEM,4", now), "");
+        assert_eq!(system_reply("#RESET,0", now), "");
+    }
+
     #[test]
     fn parse_monitoring() {
         assert_eq!(
@@ -159,6 +317,66 @@ mod tests {
             id: 1,
             level: 100.0,
         };
-        assert_eq!(format_event(&event), "~OUTPUT,1,1,100.00");
+        assert_eq!(format_event(&event, DEFAULT_LEVEL_DECIMALS), "~OUTPUT,1,1,100.00");
+    }
+
+    #[test]
+    fn format_output_level_precision() {
+        let event = Ra2Event::OutputLevel { id: 3, level: 50.456 };
+        assert_eq!(format_event(&event, 0), "~OUTPUT,3,1,50");
+        assert_eq!(format_event(&event, 1), "~OUTPUT,3,1,50.5");
+        assert_eq!(format_event(&event, 2), "~OUTPUT,3,1,50.46");
+
+        // Halves round away from zero at every precision
+        let half = Ra2Event::OutputLevel { id: 3, level: 12.5 };
+        assert_eq!(format_event(&half, 0), "~OUTPUT,3,1,13");
+        let half = Ra2Event::OutputLevel { id: 3, level: 0.25 };
+        assert_eq!(format_event(&half, 1), "~OUTPUT,3,1,0.3");
+
+        // Out-of-range levels and precisions are clamped
+        let over = Ra2Event::OutputLevel { id: 3, level: 100.4 };
+        assert_eq!(format_event(&over, 0), "~OUTPUT,3,1,100");
+        let under = Ra2Event::OutputLevel { id: 3, level: -0.001 };
+        assert_eq!(format_event(&under, 2), "~OUTPUT,3,1,0.00");
+        assert_eq!(format_event(&event, 5), "~OUTPUT,3,1,50.46");
+    }
+
+    #[test]
+    fn format_error() {
+        let event = Ra2Event::Error {
+            code: ERROR_OBJECT_NOT_FOUND,
+        };
+        assert_eq!(format_event(&event, DEFAULT_LEVEL_DECIMALS), "~ERROR,2");
+    }
+
+    #[test]
+    fn format_device_led() {
+        let on = Ra2Event::DeviceButton {
+            id: 20,
+            component: 81,
+            led_on: true,
+        };
+        assert_eq!(format_event(&on, DEFAULT_LEVEL_DECIMALS), "~DEVICE,20,81,9,1");
+        let off = Ra2Event::DeviceButton {
+            id: 20,
+            component: 82,
+            led_on: false,
+        };
+        assert_eq!(format_event(&off, DEFAULT_LEVEL_DECIMALS), "~DEVICE,20,82,9,0");
+    }
+
+    #[test]
+    fn each_event_type_reports_its_action() {
+        let level = Ra2Event::OutputLevel { id: 4, level: 25.0 };
+        assert_eq!(level.action(), Some(OUTPUT_ACTION_LEVEL));
+        assert_eq!(format_event(&level, 2), "~OUTPUT,4,1,25.00");
+
+        let led = Ra2Event::DeviceButton { id: 20, component: 81, led_on: true };
+        assert_eq!(led.action(), Some(DEVICE_ACTION_LED_STATE));
+        assert_eq!(format_event(&led, 2).split(',').nth(3), Some("9"));
+
+        let error = Ra2Event::Error { code: 2 };
+        assert_eq!(error.action(), None);
+        assert_eq!(format_event(&error, 2), "~ERROR,2");
     }
 }
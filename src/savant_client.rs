@@ -1,11 +1,18 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use anyhow::{Context, Result};
 use futures_util::{SinkExt, StreamExt};
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::{broadcast, mpsc, watch};
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use tokio_tungstenite::tungstenite::Message;
+use serde::Serialize;
 use tracing::{error, info, warn};
 
-use crate::config::SavantZoneMapping;
+use crate::config::{SavantConfig, SavantDevice, SavantZoneMapping};
+use crate::protocol_log::protocol_log;
+use crate::state::{reconnect_delay, BackendStatus};
 
 #[derive(Debug, Clone)]
 pub enum SavantRequest {
@@ -13,12 +20,14 @@ pub enum SavantRequest {
         address: String,
         load_offset: usize,
         level: f64,
+        /// Seconds; sent after the level as `"<level>%.<fade>"`.
+        fade: Option<f64>,
     },
-    #[allow(dead_code)]
-    QueryLoad {
-        address: String,
-        load_offset: usize,
-    },
+    /// Reads the whole module; each tracked load's level comes back as a
+    /// `LoadLevel` event, so callers pick out their load by offset there.
+    QueryLoad { address: String },
+    /// A complete `{URI, messages}` message, sent as-is.
+    Raw(serde_json::Value),
 }
 
 #[derive(Debug, Clone)]
@@ -28,45 +37,268 @@ pub enum SavantEvent {
         load_offset: usize,
         level: f64,
     },
+    /// Every message from the host, for raw passthrough captures.
+    Message(serde_json::Value),
+}
+
+/// Connection health counters for the Savant client, surfaced in `/api/status`.
+#[derive(Default)]
+pub struct SavantMetrics {
+    rejected_messages: AtomicU64,
+    reconnects: AtomicU64,
+    last_reject_reason: Mutex<Option<String>>,
+    last_state_update: Mutex<Option<SystemTime>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SavantMetricsSnapshot {
+    pub rejected_messages: u64,
+    pub reconnects: u64,
+    pub last_reject_reason: Option<String>,
+    /// Unix timestamp (seconds) of the last state message from the host.
+    pub last_state_update: Option<u64>,
+}
+
+impl SavantMetrics {
+    fn record_reject(&self, reason: &str) {
+        self.rejected_messages.fetch_add(1, Ordering::Relaxed);
+        *self.last_reject_reason.lock().unwrap() = Some(reason.to_string());
+    }
+
+    fn record_reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_state_update(&self) {
+        *self.last_state_update.lock().unwrap() = Some(SystemTime::now());
+    }
+
+    pub fn snapshot(&self) -> SavantMetricsSnapshot {
+        SavantMetricsSnapshot {
+            rejected_messages: self.rejected_messages.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            last_reject_reason: self.last_reject_reason.lock().unwrap().clone(),
+            last_state_update: self
+                .last_state_update
+                .lock()
+                .unwrap()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs()),
+        }
+    }
+}
+
+/// `SavantMetrics` for each `[[savant]]` host, so a host that has gone quiet
+/// isn't hidden by another that keeps updating.
+#[derive(Default)]
+pub struct SavantHostMetrics(Mutex<Vec<Arc<SavantMetrics>>>);
+
+impl SavantHostMetrics {
+    /// Metrics for hosts `0..count`, keeping the counters of hosts already
+    /// there and dropping any beyond `count`.
+    pub fn hosts(&self, count: usize) -> Vec<Arc<SavantMetrics>> {
+        let mut hosts = self.0.lock().unwrap();
+        hosts.resize_with(count, Default::default);
+        hosts.clone()
+    }
+
+    /// One snapshot per host, in `[[savant]]` order.
+    pub fn snapshot(&self) -> Vec<SavantMetricsSnapshot> {
+        self.0.lock().unwrap().iter().map(|m| m.snapshot()).collect()
+    }
+}
+
+/// Whether the host accepted the `state/register` push subscription this session.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RegisterSupport {
+    /// Registration sent, no verdict yet — keep polling until we hear back.
+    Pending,
+    /// Host pushed a `state/update`, so polling is unnecessary.
+    Supported,
+    /// Host answered with `messageReject` (older firmware) — poll instead.
+    Rejected,
+}
+
+impl RegisterSupport {
+    fn should_poll(self) -> bool {
+        self != RegisterSupport::Supported
+    }
+}
+
+/// Packing of a module address and load offset into the key used in
+/// `load.<hex>` state names: `key = (address << shift) | (offset & mask)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadKey {
+    pub shift: u32,
+    pub mask: u32,
+}
+
+impl Default for LoadKey {
+    fn default() -> Self {
+        Self {
+            shift: 16,
+            mask: 1023,
+        }
+    }
 }
 
-/// Run the Savant WebSocket client. Reconnects with exponential backoff.
+impl LoadKey {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.shift == 0 || self.shift >= 32 {
+            return Err(format!("savant load_key_shift must be 1-31, got {}", self.shift));
+        }
+        if self.mask == 0 || self.mask >> self.shift != 0 {
+            return Err(format!(
+                "savant load_offset_mask {:#x} must be non-zero and fit below the {}-bit shift",
+                self.mask, self.shift
+            ));
+        }
+        Ok(())
+    }
+
+    /// Hex key for a module address (itself hex, e.g. "01A") and load offset.
+    pub fn encode(&self, address: &str, load_offset: usize) -> String {
+        let int_address = u32::from_str_radix(address, 16).unwrap_or(0);
+        let load_key = (int_address << self.shift) | (load_offset as u32 & self.mask);
+        format!("{:x}", load_key)
+    }
+
+    /// Reverse of `encode`: module address (3-digit upper hex) and offset.
+    pub fn decode(&self, load_key: u32) -> (String, usize) {
+        let int_address = load_key >> self.shift;
+        let load_offset = (load_key & self.mask) as usize;
+        (format!("{:03X}", int_address), load_offset)
+    }
+}
+
+/// Run the Savant WebSocket client. Reconnects with exponential backoff,
+/// giving up after `max_attempts` consecutive failures (0 = never).
 pub async fn run(
-    host: String,
-    port: u16,
+    savant: SavantConfig,
     zones: Vec<SavantZoneMapping>,
     mut req_rx: mpsc::Receiver<SavantRequest>,
     event_tx: broadcast::Sender<SavantEvent>,
+    metrics: Arc<SavantMetrics>,
+    status_tx: watch::Sender<BackendStatus>,
+    max_attempts: u32,
 ) -> Result<()> {
     let mut backoff = 1u64;
     let max_backoff = 60u64;
+    let mut attempts = 0u32;
 
     loop {
-        match connect_and_run(&host, port, &zones, &mut req_rx, &event_tx).await {
+        let _ = status_tx.send(BackendStatus::Connecting);
+        let session = connect_and_run(&savant, &zones, &mut req_rx, &event_tx, &metrics, &status_tx).await;
+        // A session that got as far as Connected starts the count over
+        if *status_tx.borrow() == BackendStatus::Connected {
+            attempts = 0;
+            backoff = 1;
+        }
+        match session {
             Ok(()) => {
                 info!("Savant connection closed gracefully");
+                let _ = status_tx.send(BackendStatus::Stopped);
                 break;
             }
             Err(e) => {
+                attempts += 1;
+                if max_attempts > 0 && attempts >= max_attempts {
+                    error!("Savant connection error: {}. Giving up after {} attempts", e, attempts);
+                    let _ = status_tx.send(BackendStatus::Failed {
+                        message: e.to_string(),
+                        attempts,
+                    });
+                    break;
+                }
+                let _ = status_tx.send(BackendStatus::Error {
+                    message: e.to_string(),
+                    attempts,
+                });
                 error!(
                     "Savant connection error: {}. Reconnecting in {}s...",
                     e, backoff
                 );
-                tokio::time::sleep(tokio::time::Duration::from_secs(backoff)).await;
+                if !reconnect_delay(std::time::Duration::from_secs(backoff), &req_rx).await {
+                    info!("Savant client stopped");
+                    let _ = status_tx.send(BackendStatus::Stopped);
+                    break;
+                }
                 backoff = (backoff * 2).min(max_backoff);
+                metrics.record_reconnect();
             }
         }
     }
     Ok(())
 }
 
+/// How long the host has to answer `session/devicePresent`.
+pub const SESSION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Wait for `session/deviceRecognized`. Anything else the host sends first
+/// is skipped; if the ack doesn't arrive within `timeout` the error names
+/// the last URI seen, which usually points at the wrong port or protocol.
+pub async fn wait_for_session<S>(ws_rx: &mut S, timeout: std::time::Duration) -> Result<()>
+where
+    S: futures_core::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    let mut last_uri: Option<String> = None;
+    let wait = async {
+        loop {
+            match ws_rx.next().await {
+                Some(Ok(Message::Text(text))) => {
+                    let msg = serde_json::from_str::<serde_json::Value>(&text).unwrap_or_default();
+                    let uri = msg["URI"].as_str().unwrap_or_default();
+                    if uri.contains("deviceRecognized") {
+                        return Ok(());
+                    }
+                    warn!("Savant handshake: ignoring {:?} while waiting for deviceRecognized", uri);
+                    last_uri = Some(uri.to_string());
+                }
+                Some(Ok(Message::Close(_))) | None => {
+                    return Err(anyhow::anyhow!("Connection closed during handshake"));
+                }
+                Some(Err(e)) => return Err(e.into()),
+                Some(Ok(_)) => {}
+            }
+        }
+    };
+    match tokio::time::timeout(timeout, wait).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!(
+            "Savant did not acknowledge session within {:?}{} (check protocol/port)",
+            timeout,
+            last_uri.map(|u| format!(", last message: {:?}", u)).unwrap_or_default()
+        )),
+    }
+}
+
+/// The `session/devicePresent` handshake announcing `device` as `app`.
+pub fn device_present(device: &SavantDevice, app: &str) -> serde_json::Value {
+    serde_json::json!({
+        "messages": [{
+            "protocolVersion": "0.1",
+            "device": {
+                "name": device.name,
+                "version": "1.0",
+                "app": app,
+                "ip": device.ip,
+                "model": "ra-bridge"
+            }
+        }],
+        "URI": "session/devicePresent"
+    })
+}
+
 async fn connect_and_run(
-    host: &str,
-    port: u16,
+    savant: &SavantConfig,
     zones: &[SavantZoneMapping],
     req_rx: &mut mpsc::Receiver<SavantRequest>,
     event_tx: &broadcast::Sender<SavantEvent>,
+    metrics: &SavantMetrics,
+    status_tx: &watch::Sender<BackendStatus>,
 ) -> Result<()> {
+    let (host, port) = (savant.host.as_str(), savant.port);
+    let load_key = savant.load_key();
     let url = format!("ws://{}:{}", host, port);
     let mut request = url.as_str().into_client_request()?;
     request
@@ -81,43 +313,15 @@ async fn connect_and_run(
     let (mut ws_tx, mut ws_rx) = ws_stream.split();
 
     // Step 1: Send session/devicePresent
-    let device_present = serde_json::json!({
-        "messages": [{
-            "protocolVersion": "0.1",
-            "device": {
-                "name": "Linux",
-                "version": "1.0",
-                "app": "ra-bridge",
-                "ip": "0.0.0.0",
-                "model": "ra-bridge"
-            }
-        }],
-        "URI": "session/devicePresent"
-    });
+    let device_present = device_present(&savant.device, &savant.device.app);
     ws_tx
         .send(Message::Text(serde_json::to_string(&device_present)?.into()))
         .await?;
-    info!("Sent session/devicePresent");
+    protocol_log!(debug, "Sent session/devicePresent");
 
-    // Wait for session/deviceRecognized
-    loop {
-        match ws_rx.next().await {
-            Some(Ok(Message::Text(text))) => {
-                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&text) {
-                    let uri = msg["URI"].as_str().unwrap_or_default();
-                    if uri.contains("deviceRecognized") {
-                        info!("Savant session established");
-                        break;
-                    }
-                }
-            }
-            Some(Ok(Message::Close(_))) | None => {
-                return Err(anyhow::anyhow!("Connection closed during handshake"));
-            }
-            Some(Err(e)) => return Err(e.into()),
-            _ => {}
-        }
-    }
+    wait_for_session(&mut ws_rx, SESSION_TIMEOUT).await?;
+    info!("Savant session established");
+    let _ = status_tx.send(BackendStatus::Connected);
 
     // Step 2: Request initial state for each unique module address
     let mut seen_addresses = std::collections::HashSet::new();
@@ -132,13 +336,27 @@ async fn connect_and_run(
                 .await?;
         }
     }
-    info!(
+    protocol_log!(
+        debug,
         "Requested initial state for {} modules",
         seen_addresses.len()
     );
 
-    // Enter main loop — poll module state periodically (session/ping and
-    // state/register are rejected by this firmware, so we poll instead)
+    // Step 3: Try to register for pushed state updates. Older firmware
+    // rejects this via messageReject, in which case we keep polling.
+    for addr in &seen_addresses {
+        let register = serde_json::json!({
+            "messages": [{"state": format!("module.{}", addr)}],
+            "URI": "state/register"
+        });
+        ws_tx
+            .send(Message::Text(serde_json::to_string(&register)?.into()))
+            .await?;
+    }
+    let mut register_support = RegisterSupport::Pending;
+
+    // Enter main loop — poll module state periodically until the host
+    // confirms registration by pushing a state/update
     let poll_interval = tokio::time::Duration::from_secs(30);
     let mut poll_timer = tokio::time::interval(poll_interval);
     poll_timer.tick().await; // consume immediate tick
@@ -148,7 +366,7 @@ async fn connect_and_run(
             msg = ws_rx.next() => {
                 match msg {
                     Some(Ok(Message::Text(text))) => {
-                        handle_savant_message(&text, zones, event_tx);
+                        handle_savant_message(&text, zones, load_key, event_tx, &mut register_support, metrics);
                     }
                     Some(Ok(Message::Close(_))) | None => {
                         return Err(anyhow::anyhow!("Savant WebSocket closed"));
@@ -157,12 +375,17 @@ async fn connect_and_run(
                     _ => {}
                 }
             }
-            Some(req) = req_rx.recv() => {
-                let msg = encode_request(&req);
-                info!("Savant TX: {}", serde_json::to_string(&msg)?);
+            req = req_rx.recv() => {
+                // A closed channel means the bridge stopped
+                let Some(req) = req else { return Ok(()) };
+                let msg = encode_request(&req, load_key);
+                protocol_log!(debug, "Savant TX: {}", serde_json::to_string(&msg)?);
                 ws_tx.send(Message::Text(serde_json::to_string(&msg)?.into())).await?;
             }
             _ = poll_timer.tick() => {
+                if !register_support.should_poll() {
+                    continue;
+                }
                 // Poll all tracked modules for current state
                 for addr in &seen_addresses {
                     let get_state = serde_json::json!({
@@ -179,18 +402,24 @@ async fn connect_and_run(
 fn handle_savant_message(
     text: &str,
     zones: &[SavantZoneMapping],
+    load_key: LoadKey,
     event_tx: &broadcast::Sender<SavantEvent>,
+    register_support: &mut RegisterSupport,
+    metrics: &SavantMetrics,
 ) {
     let msg: serde_json::Value = match serde_json::from_str(text) {
         Ok(v) => v,
         Err(_) => return,
     };
 
+    let _ = event_tx.send(SavantEvent::Message(msg.clone()));
     let uri = msg["URI"].as_str().unwrap_or_default();
 
-    // Log all non-poll Savant messages for debugging (polls are noisy)
+    // Poll replies are noisier still, so only with -vv
     if uri == "state/set" || uri.contains("state/update") {
-        info!("Savant RX raw: {}", text);
+        protocol_log!(debug, "Savant RX raw: {}", text);
+    } else {
+        protocol_log!(trace, "Savant RX raw: {}", text);
     }
 
     // Log rejected messages for debugging
@@ -200,16 +429,29 @@ fn handle_savant_message(
                 let rejected_uri = body["URI"].as_str().unwrap_or("?");
                 let reason = body["RejectReason"].as_str().unwrap_or("?");
                 warn!("Savant rejected {}: {}", rejected_uri, reason);
+                metrics.record_reject(&format!("{}: {}", rejected_uri, reason));
+                if rejected_uri == "state/register"
+                    && *register_support != RegisterSupport::Rejected
+                {
+                    info!("Savant state/register unsupported, falling back to polling");
+                    *register_support = RegisterSupport::Rejected;
+                }
             }
         }
         return;
     }
 
+    if uri.contains("state/update") && *register_support == RegisterSupport::Pending {
+        info!("Savant state/register accepted, polling disabled");
+        *register_support = RegisterSupport::Supported;
+    }
+
     // Handle state/set echo, state/update, and state/module/*/get responses
     if uri == "state/set" || uri.contains("state/update") || uri.contains("state/module/") {
+        metrics.record_state_update();
         if let Some(messages) = msg.get("messages").and_then(|m| m.as_array()) {
             for body in messages {
-                parse_state_body(body, uri, zones, event_tx);
+                parse_state_body(body, uri, zones, load_key, event_tx);
             }
         }
     }
@@ -219,25 +461,20 @@ fn parse_state_body(
     body: &serde_json::Value,
     uri: &str,
     zones: &[SavantZoneMapping],
+    load_key: LoadKey,
     event_tx: &broadcast::Sender<SavantEvent>,
 ) {
     let state_str = body.get("state").and_then(|s| s.as_str()).unwrap_or("");
 
     // Handle "load.XXXX" format (set echo / set confirmation)
-    // Reverse the hex key: load_key = (address << 16) | offset
+    // Reverse the hex key: key = (address << shift) | (offset & mask)
     if let Some(hex_key) = state_str.strip_prefix("load.") {
-        if let Ok(load_key) = u32::from_str_radix(hex_key, 16) {
-            let int_address = load_key >> 16;
-            let load_offset = (load_key & 1023) as usize;
-            let address = format!("{:03X}", int_address);
+        if let Ok(key) = u32::from_str_radix(hex_key, 16) {
+            let (address, load_offset) = load_key.decode(key);
 
-            // Parse level from value: "100%.0" → 100.0, or just "100.0"
             if let Some(value_str) = body.get("value").and_then(|v| v.as_str()) {
-                let numeric = value_str.replace('%', "");
-                // Take the part before the dot-separated fade value
-                let level_str = numeric.split('.').next().unwrap_or(&numeric);
-                if let Ok(level) = level_str.trim().parse::<f64>() {
-                    info!("Savant set-echo: load.{} → addr={} offset={} level={:.1}%",
+                if let Some(level) = parse_load_value(value_str) {
+                    protocol_log!(debug, "Savant set-echo: load.{} → addr={} offset={} level={:.1}%",
                         hex_key, address, load_offset, level);
                     emit_if_tracked(&address, load_offset, level, zones, event_tx);
                 }
@@ -274,6 +511,17 @@ fn parse_state_body(
     }
 }
 
+/// Level from a set-echo value. `"50%.0"` is level 50 with fade 0 (the
+/// fade only ever follows a `%`); without a `%` the whole value is the
+/// level, so `"33.5"` is 33.5. A decimal comma (`"33,5"`) is accepted too.
+fn parse_load_value(value: &str) -> Option<f64> {
+    let level = match value.split_once('%') {
+        Some((level, _fade)) => level,
+        None => value,
+    };
+    level.trim().replace(',', ".").parse().ok()
+}
+
 fn emit_if_tracked(
     address: &str,
     load_offset: usize,
@@ -286,7 +534,8 @@ fn emit_if_tracked(
         .iter()
         .find(|z| z.address == address && z.load_offset == load_offset);
     if let Some(z) = tracked {
-        info!(
+        protocol_log!(
+            debug,
             "Savant RX: addr={} offset={} level={:.1}% (zone {} '{}')",
             address, load_offset, level, z.ra2_id, z.name
         );
@@ -298,40 +547,214 @@ fn emit_if_tracked(
     }
 }
 
-fn encode_request(req: &SavantRequest) -> serde_json::Value {
+fn encode_request(req: &SavantRequest, load_key: LoadKey) -> serde_json::Value {
     match req {
         SavantRequest::SetLoad {
             address,
             load_offset,
             level,
+            fade,
         } => {
-            // Savant load key: (address_int << 16 | load_offset).toString(16)
-            // Matches the web UI's getSetStateValue() formula
-            let int_address = u32::from_str_radix(address, 16).unwrap_or(0);
-            let load_key = (int_address << 16) | (*load_offset as u32 & 1023);
-            let hex_key = format!("{:x}", load_key);
+            // Savant load key: (address_int << shift | load_offset & mask).toString(16)
+            // The default 16/1023 matches the web UI's getSetStateValue() formula
+            let hex_key = load_key.encode(address, *load_offset);
 
             // Binary switch mode: any non-zero level → 100% (on), zero → 0% (off)
             // Savant switch relays only respond to 0% and 100% — intermediate
             // values update state but don't physically toggle the relay.
             // TODO: add per-zone dimmer/switch flag for proper dimmer support
             let savant_level = if *level > 0.0 { 100 } else { 0 };
+            let value = match fade {
+                Some(fade) => format!("{}%.{}", savant_level, fade.round() as u64),
+                None => format!("{}%", savant_level),
+            };
             serde_json::json!({
                 "messages": [{
                     "state": format!("load.{}", hex_key),
-                    "value": format!("{}%", savant_level)
+                    "value": value
                 }],
                 "URI": "state/set"
             })
         }
-        SavantRequest::QueryLoad {
-            address,
-            load_offset: _,
-        } => {
+        SavantRequest::QueryLoad { address } => {
             serde_json::json!({
                 "messages": [{}],
                 "URI": format!("state/module/{}/get", address)
             })
         }
+        SavantRequest::Raw(msg) => msg.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_reject_falls_back_to_polling() {
+        let (event_tx, _rx) = broadcast::channel(16);
+        let metrics = SavantMetrics::default();
+        let mut support = RegisterSupport::Pending;
+        let reject = r#"{"URI":"messageReject","messages":[{"URI":"state/register","RejectReason":"unknown URI"}]}"#;
+        handle_savant_message(reject, &[], LoadKey::default(), &event_tx, &mut support, &metrics);
+        assert_eq!(support, RegisterSupport::Rejected);
+        assert!(support.should_poll());
+        let snap = metrics.snapshot();
+        assert_eq!(snap.rejected_messages, 1);
+        assert_eq!(snap.last_reject_reason.as_deref(), Some("state/register: unknown URI"));
+
+        // A later update must not flip a rejected session back
+        let update = r#"{"URI":"state/update","messages":[]}"#;
+        handle_savant_message(update, &[], LoadKey::default(), &event_tx, &mut support, &metrics);
+        assert_eq!(support, RegisterSupport::Rejected);
+    }
+
+    #[test]
+    fn raw_messages_pass_through_both_ways() {
+        let msg = serde_json::json!({"URI": "state/module/001/get", "messages": [{}]});
+        assert_eq!(
+            encode_request(&SavantRequest::Raw(msg.clone()), LoadKey::default()),
+            msg
+        );
+
+        let (event_tx, mut event_rx) = broadcast::channel(16);
+        let metrics = SavantMetrics::default();
+        let mut support = RegisterSupport::Pending;
+        let reply = r#"{"URI":"session/undocumented","messages":[{"x":1}]}"#;
+        handle_savant_message(reply, &[], LoadKey::default(), &event_tx, &mut support, &metrics);
+        match event_rx.try_recv().unwrap() {
+            SavantEvent::Message(m) => assert_eq!(m["URI"], "session/undocumented"),
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn register_update_disables_polling() {
+        let (event_tx, _rx) = broadcast::channel(16);
+        let metrics = SavantMetrics::default();
+        let mut support = RegisterSupport::Pending;
+        assert!(support.should_poll());
+        let update = r#"{"URI":"state/update","messages":[]}"#;
+        handle_savant_message(update, &[], LoadKey::default(), &event_tx, &mut support, &metrics);
+        assert_eq!(support, RegisterSupport::Supported);
+        assert!(!support.should_poll());
+        assert!(metrics.snapshot().last_state_update.is_some());
+    }
+
+    #[test]
+    fn parse_set_echo_values() {
+        assert_eq!(parse_load_value("50%.0"), Some(50.0));
+        assert_eq!(parse_load_value("33.5"), Some(33.5));
+        assert_eq!(parse_load_value("100"), Some(100.0));
+        assert_eq!(parse_load_value("33.5%.2"), Some(33.5));
+        assert_eq!(parse_load_value(" 75 %"), Some(75.0));
+        assert_eq!(parse_load_value("33,5"), Some(33.5));
+        assert_eq!(parse_load_value("on"), None);
+    }
+
+    #[test]
+    fn load_key_round_trip() {
+        let default = LoadKey::default();
+        assert_eq!(default.encode("01A", 3), "1a0003");
+        assert_eq!(default.decode(0x1a0003), ("01A".to_string(), 3));
+
+        let narrow = LoadKey { shift: 8, mask: 0xff };
+        assert!(narrow.validate().is_ok());
+        for (address, offset) in [("001", 0), ("01A", 7), ("0FF", 255)] {
+            let key = u32::from_str_radix(&narrow.encode(address, offset), 16).unwrap();
+            assert_eq!(narrow.decode(key), (address.to_string(), offset));
+        }
+        assert!(LoadKey { shift: 8, mask: 1023 }.validate().is_err());
+    }
+
+    use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+
+    // The callback signature is tungstenite's
+    #[allow(clippy::result_large_err)]
+    fn accept_savant_protocol(_: &Request, mut resp: Response) -> Result<Response, ErrorResponse> {
+        resp.headers_mut().insert("Sec-WebSocket-Protocol", "savant_protocol".parse().unwrap());
+        Ok(resp)
+    }
+
+    /// A host that answers every message with `reply_uri` and never closes.
+    async fn mock_host(reply_uri: &'static str) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_hdr_async(stream, accept_savant_protocol).await.unwrap();
+            while let Some(Ok(_)) = ws.next().await {
+                let reply = serde_json::json!({ "URI": reply_uri, "messages": [{}] });
+                let _ = ws.send(Message::Text(reply.to_string().into())).await;
+            }
+        });
+        port
+    }
+
+    async fn handshake(port: u16) -> Result<()> {
+        let mut request = format!("ws://127.0.0.1:{}", port).into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", "savant_protocol".parse().unwrap());
+        let (ws, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+        let (mut tx, mut rx) = ws.split();
+        let present = device_present(&SavantDevice::default(), "ra-bridge");
+        tx.send(Message::Text(present.to_string().into())).await.unwrap();
+        wait_for_session(&mut rx, std::time::Duration::from_millis(300)).await
+    }
+
+    #[tokio::test]
+    async fn wrong_session_ack_times_out_with_hint() {
+        let err = handshake(mock_host("session/deviceUnknown").await).await.unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("did not acknowledge session"), "{}", message);
+        assert!(message.contains("session/deviceUnknown"), "{}", message);
+
+        assert!(handshake(mock_host("session/deviceRecognized").await).await.is_ok());
+    }
+
+    #[test]
+    fn device_block_comes_from_config() {
+        let default: SavantConfig = toml::from_str("host = \"10.0.0.9\"").unwrap();
+        let msg = device_present(&default.device, &default.device.app);
+        let device = &msg["messages"][0]["device"];
+        assert_eq!((device["name"].as_str(), device["app"].as_str()), (Some("Linux"), Some("ra-bridge")));
+        assert_eq!(device["ip"], "0.0.0.0");
+
+        let custom: SavantConfig =
+            toml::from_str("host = \"10.0.0.9\"\n[device]\nname = \"Rack\"\nip = \"10.0.0.20\"").unwrap();
+        let msg = device_present(&custom.device, "ra-bridge-discover");
+        let device = &msg["messages"][0]["device"];
+        assert_eq!((device["name"].as_str(), device["ip"].as_str()), (Some("Rack"), Some("10.0.0.20")));
+        assert_eq!(device["app"], "ra-bridge-discover");
+    }
+
+    #[test]
+    fn set_echo_uses_configured_load_key() {
+        let (event_tx, mut event_rx) = broadcast::channel(16);
+        let zones = vec![SavantZoneMapping {
+            ra2_id: 5,
+            address: "01A".to_string(),
+            load_offset: 2,
+            ..Default::default()
+        }];
+        let load_key = LoadKey { shift: 8, mask: 0xff };
+        let req = SavantRequest::SetLoad {
+            address: "01A".to_string(),
+            load_offset: 2,
+            level: 100.0,
+            fade: Some(1.0),
+        };
+        let msg = encode_request(&req, load_key);
+        assert_eq!(msg["messages"][0]["state"], "load.1a02");
+        assert_eq!(msg["messages"][0]["value"], "100%.1");
+
+        parse_state_body(&msg["messages"][0], "state/set", &zones, load_key, &event_tx);
+        match event_rx.try_recv().unwrap() {
+            SavantEvent::LoadLevel { address, load_offset, level } => {
+                assert_eq!((address.as_str(), load_offset, level), ("01A", 2, 100.0));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
     }
 }
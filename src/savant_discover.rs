@@ -1,16 +1,26 @@
+use std::collections::HashSet;
+
 use anyhow::{Context, Result};
 use futures_util::{SinkExt, StreamExt};
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use tokio_tungstenite::tungstenite::Message;
+use tokio::time::{Duration, Instant};
 use tracing::{info, warn};
 
-use crate::config::{SavantConfig, SavantZoneMapping};
+use crate::config::{join_area_name, IdRange, SavantConfig, SavantDevice, SavantZoneMapping};
 
-/// Discover Savant devices and loads via WebSocket, returning config and zone mappings.
+/// Discover Savant devices and loads via WebSocket, returning config and zone
+/// mappings numbered from the start of `ids`. `device` is reported in the
+/// handshake and kept in the config, as is `max_zones`, which caps how many
+/// loads are kept; `separator` goes between room and load in zone names.
+/// Fails if `ids` can't hold every load.
 pub async fn discover_zones(
     host: &str,
     port: u16,
-    start_id: u32,
+    ids: IdRange,
+    device: &SavantDevice,
+    max_zones: usize,
+    separator: char,
 ) -> Result<(SavantConfig, Vec<SavantZoneMapping>)> {
     let url = format!("ws://{}:{}", host, port);
     let mut request = url.as_str().into_client_request()?;
@@ -26,49 +36,13 @@ pub async fn discover_zones(
     let (mut ws_tx, mut ws_rx) = ws_stream.split();
 
     // Send session/devicePresent
-    let device_present = serde_json::json!({
-        "messages": [{
-            "protocolVersion": "0.1",
-            "device": {
-                "name": "Linux",
-                "version": "1.0",
-                "app": "ra-bridge-discover",
-                "ip": "0.0.0.0",
-                "model": "ra-bridge"
-            }
-        }],
-        "URI": "session/devicePresent"
-    });
+    let app = format!("{}-discover", device.app);
+    let device_present = crate::savant_client::device_present(device, &app);
     ws_tx
         .send(Message::Text(serde_json::to_string(&device_present)?.into()))
         .await?;
 
-    // Wait for session acknowledgment
-    let timeout = tokio::time::Duration::from_secs(10);
-    tokio::time::timeout(timeout, async {
-        loop {
-            match ws_rx.next().await {
-                Some(Ok(Message::Text(text))) => {
-                    if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&text) {
-                        let uri = msg["URI"].as_str().unwrap_or_default();
-                        if uri.contains("deviceRecognized") {
-                            break;
-                        }
-                    }
-                }
-                Some(Ok(Message::Close(_))) | None => {
-                    return Err(anyhow::anyhow!("Connection closed during handshake"));
-                }
-                Some(Err(e)) => return Err(e.into()),
-                _ => {}
-            }
-            Ok::<(), anyhow::Error>(())
-                .ok();
-        }
-        Ok(())
-    })
-    .await
-    .context("Timeout waiting for Savant session")??;
+    crate::savant_client::wait_for_session(&mut ws_rx, crate::savant_client::SESSION_TIMEOUT).await?;
 
     info!("Savant session established, requesting device config");
 
@@ -81,37 +55,9 @@ pub async fn discover_zones(
         .send(Message::Text(serde_json::to_string(&get_config)?.into()))
         .await?;
 
-    // Collect responses for a few seconds
     let mut zones = Vec::new();
-    let mut ra2_id = start_id;
-
-    let collect_timeout = tokio::time::Duration::from_secs(10);
-    let _ = tokio::time::timeout(collect_timeout, async {
-        loop {
-            match ws_rx.next().await {
-                Some(Ok(Message::Text(text))) => {
-                    if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&text) {
-                        let uri = msg["URI"].as_str().unwrap_or_default();
-                        if uri.contains("lighting/config") || uri.contains("device") {
-                            if let Some(messages) = msg.get("messages").and_then(|m| m.as_array()) {
-                                for body in messages {
-                                    parse_device_config(body, &mut zones, &mut ra2_id);
-                                }
-                            }
-                            // Got a config response, we can finish
-                            if !zones.is_empty() {
-                                break;
-                            }
-                        }
-                    }
-                }
-                Some(Ok(Message::Close(_))) | None => break,
-                Some(Err(_)) => break,
-                _ => {}
-            }
-        }
-    })
-    .await;
+    let mut ra2_id = ids.start;
+    collect_device_config(&mut ws_rx, CONFIG_QUIET_PERIOD, &mut zones, &mut ra2_id, separator).await;
 
     // If we didn't get config via the config endpoint, try getting state from known modules
     if zones.is_empty() {
@@ -138,7 +84,7 @@ pub async fn discover_zones(
                             if uri.contains("state/module/") {
                                 if let Some(messages) = msg.get("messages").and_then(|m| m.as_array()) {
                                     for body in messages {
-                                        parse_state_discovery(uri, body, &mut zones, &mut ra2_id);
+                                        parse_state_discovery(uri, body, &mut zones, &mut ra2_id, separator);
                                     }
                                 }
                             }
@@ -156,35 +102,180 @@ pub async fn discover_zones(
     // Close connection
     let _ = ws_tx.send(Message::Close(None)).await;
 
-    let savant_config = SavantConfig {
-        host: host.to_string(),
-        port,
-    };
+    let mut savant_config = SavantConfig::new(host.to_string(), port);
+    savant_config.device = device.clone();
+    savant_config.max_discovered_zones = max_zones;
+    cap_zones(&mut zones, max_zones);
+
+    if zones.iter().any(|z| !ids.contains(z.ra2_id)) {
+        anyhow::bail!(
+            "Savant id range {} can't hold all {} loads; widen id_ranges.savant",
+            ids,
+            zones.len()
+        );
+    }
 
     info!("Savant discovery complete: {} zones found", zones.len());
     Ok((savant_config, zones))
 }
 
+/// Keep the first `max` zones, warning about the rest.
+fn cap_zones(zones: &mut Vec<SavantZoneMapping>, max: usize) {
+    if zones.len() <= max {
+        return;
+    }
+    warn!(
+        "Savant discovery found {} loads, more than max_discovered_zones ({}); dropping {} from {} on",
+        zones.len(),
+        max,
+        zones.len() - max,
+        zones[max].name
+    );
+    zones.truncate(max);
+}
+
+/// Longest wait for the device config to start arriving.
+const CONFIG_TIMEOUT: Duration = Duration::from_secs(10);
+/// Large systems send the device list over several messages; once loads
+/// have arrived, collection ends after this long without another one.
+const CONFIG_QUIET_PERIOD: Duration = Duration::from_secs(2);
+
+/// Parse `lighting/config` responses into `zones` until the host goes quiet
+/// for `quiet` after the first loads, `CONFIG_TIMEOUT` runs out, or the
+/// connection closes.
+async fn collect_device_config<S>(
+    ws_rx: &mut S,
+    quiet: Duration,
+    zones: &mut Vec<SavantZoneMapping>,
+    ra2_id: &mut u32,
+    separator: char,
+) where
+    S: futures_core::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    let deadline = Instant::now() + CONFIG_TIMEOUT;
+    let mut messages = 0;
+    loop {
+        let wait_until = if zones.is_empty() {
+            deadline
+        } else {
+            deadline.min(Instant::now() + quiet)
+        };
+        let Ok(next) = tokio::time::timeout_at(wait_until, ws_rx.next()).await else {
+            break;
+        };
+        match next {
+            Some(Ok(Message::Text(text))) => {
+                if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&text) {
+                    let uri = msg["URI"].as_str().unwrap_or_default();
+                    if uri.contains("lighting/config") || uri.contains("device") {
+                        if let Some(bodies) = msg.get("messages").and_then(|m| m.as_array()) {
+                            for body in bodies {
+                                parse_device_config(body, zones, ra2_id, separator);
+                            }
+                        }
+                        messages += 1;
+                    }
+                }
+            }
+            Some(Ok(Message::Close(_))) | None => break,
+            Some(Err(_)) => break,
+            _ => {}
+        }
+    }
+    if messages > 1 {
+        info!("Savant device config arrived in {} messages", messages);
+    }
+}
+
+/// Result of merging rediscovered loads into the configured Savant zones.
+pub struct MergedSavantZones {
+    pub zones: Vec<SavantZoneMapping>,
+    /// ra2_ids of loads that were not configured before.
+    pub added: Vec<u32>,
+    /// Configured loads the host no longer reports. They are kept so their
+    /// ids and names survive; the user can delete them by hand.
+    pub missing: Vec<u32>,
+}
+
+/// Merge `discovered` into `existing` by address and load offset. Matched
+/// loads keep their ra2_id, custom name and overrides; new loads get ids in
+/// `ids` after the highest one in use there (including `reserved`, e.g. LEAP
+/// ids). Fails if the range runs out.
+pub fn merge_zones(
+    existing: &[SavantZoneMapping],
+    discovered: &[SavantZoneMapping],
+    reserved: &HashSet<u32>,
+    ids: IdRange,
+) -> Result<MergedSavantZones> {
+    let same_load = |a: &SavantZoneMapping, b: &SavantZoneMapping| {
+        a.address.eq_ignore_ascii_case(&b.address) && a.load_offset == b.load_offset
+    };
+    let mut next_id = existing
+        .iter()
+        .map(|z| z.ra2_id)
+        .chain(reserved.iter().copied())
+        .filter(|id| ids.contains(*id))
+        .max()
+        .map_or(ids.start, |id| id.saturating_add(1));
+
+    let mut zones = existing.to_vec();
+    let mut added = Vec::new();
+    for z in discovered {
+        if let Some(e) = zones.iter_mut().find(|e| same_load(e, z)) {
+            // The room comes from the host, so refresh it
+            e.room = z.room.clone();
+            continue;
+        }
+        if !ids.contains(next_id) {
+            anyhow::bail!("Savant id range {} is full; widen id_ranges.savant", ids);
+        }
+        info!("  new load [{}] {} (addr={}, load={})", next_id, z.name, z.address, z.load_offset);
+        zones.push(SavantZoneMapping {
+            ra2_id: next_id,
+            ..z.clone()
+        });
+        added.push(next_id);
+        next_id += 1;
+    }
+
+    let missing: Vec<u32> = existing
+        .iter()
+        .filter(|e| !discovered.iter().any(|z| same_load(e, z)))
+        .map(|e| e.ra2_id)
+        .collect();
+    for id in &missing {
+        warn!("Savant zone {} no longer reported by host — kept in config", id);
+    }
+
+    Ok(MergedSavantZones {
+        zones,
+        added,
+        missing,
+    })
+}
+
 fn parse_device_config(
     body: &serde_json::Value,
     zones: &mut Vec<SavantZoneMapping>,
     ra2_id: &mut u32,
+    separator: char,
 ) {
     // If body contains a "devices" array, process each device
     if let Some(devices) = body.get("devices").and_then(|d| d.as_array()) {
         for device in devices {
-            parse_single_device(device, zones, ra2_id);
+            parse_single_device(device, zones, ra2_id, separator);
         }
         return;
     }
     // Otherwise treat the body itself as a device object
-    parse_single_device(body, zones, ra2_id);
+    parse_single_device(body, zones, ra2_id, separator);
 }
 
 fn parse_single_device(
     device: &serde_json::Value,
     zones: &mut Vec<SavantZoneMapping>,
     ra2_id: &mut u32,
+    separator: char,
 ) {
     // Get module address: try explicit address fields, then convert id to 3-digit hex
     let address = if let Some(addr) = device["address"]
@@ -203,6 +294,10 @@ fn parse_single_device(
     } else {
         return;
     };
+    // A device repeated in a later config message is only added once
+    if zones.iter().any(|z| z.address.eq_ignore_ascii_case(&address)) {
+        return;
+    }
 
     let room = device["room"]
         .as_str()
@@ -225,43 +320,49 @@ fn parse_single_device(
                 .unwrap_or(&format!("Load {}", i + 1))
                 .to_string();
 
-            let name = format!("{} \u{2500} {}", room, load_name);
+            let name = join_area_name(&room, &load_name, separator);
             info!("  [{}] {} (addr={}, load={})", ra2_id, name, address, i);
 
             zones.push(SavantZoneMapping {
                 ra2_id: *ra2_id,
                 address: address.to_string(),
                 load_offset: i,
+                discovered_name: Some(name.clone()),
                 name,
                 room: room.clone(),
+                ..Default::default()
             });
             *ra2_id += 1;
         }
     } else {
         // Single-load device
-        let name = format!(
-            "{} \u{2500} {}",
-            room,
-            device["name"].as_str().unwrap_or("Light")
-        );
+        let name = join_area_name(&room, device["name"].as_str().unwrap_or("Light"), separator);
         info!("  [{}] {} (addr={}, load=0)", ra2_id, name, address);
 
         zones.push(SavantZoneMapping {
             ra2_id: *ra2_id,
             address: address.to_string(),
             load_offset: 0,
+            discovered_name: Some(name.clone()),
             name,
             room,
+            ..Default::default()
         });
         *ra2_id += 1;
     }
 }
 
+/// A state CSV slot that reports a level. Unused slots read -1 or are empty.
+fn is_used_slot(slot: &str) -> bool {
+    slot.parse::<f64>().is_ok_and(|level| level >= 0.0)
+}
+
 fn parse_state_discovery(
     url: &str,
     body: &serde_json::Value,
     zones: &mut Vec<SavantZoneMapping>,
     ra2_id: &mut u32,
+    separator: char,
 ) {
     // Extract address from URL: "state/module/001/get"
     let address = match url
@@ -272,17 +373,22 @@ fn parse_state_discovery(
         None => return,
     };
 
-    // Count loads from state CSV or loads array
-    let load_count = if let Some(state_str) = body.get("state").and_then(|s| s.as_str()) {
-        state_str.split(',').count()
-    } else if let Some(loads) = body.get("loads").and_then(|l| l.as_array()) {
-        loads.len()
+    // Which slots hold a load, from the state CSV or the loads array. Unused
+    // slots report -1 or nothing, and a named load needs a non-empty name
+    let loads = body.get("loads").and_then(|l| l.as_array());
+    let slots: Vec<bool> = if let Some(state_str) = body.get("state").and_then(|s| s.as_str()) {
+        state_str.split(',').map(|s| is_used_slot(s.trim())).collect()
+    } else if let Some(loads) = loads {
+        loads
+            .iter()
+            .map(|l| l.is_object() && l.get("name").is_none_or(|n| n.as_str().is_some_and(|n| !n.trim().is_empty())))
+            .collect()
     } else {
         return; // No state data, module probably doesn't exist
     };
-
-    if load_count == 0 {
-        return;
+    let skipped = slots.iter().filter(|used| !**used).count();
+    if skipped > 0 {
+        warn!("Savant module {}: skipping {} unused or unnamed load slots", address, skipped);
     }
 
     let room = body
@@ -292,18 +398,14 @@ fn parse_state_discovery(
         .unwrap_or("Unknown Room")
         .to_string();
 
-    for i in 0..load_count {
-        let load_name = if let Some(loads) = body.get("loads").and_then(|l| l.as_array()) {
-            loads
-                .get(i)
-                .and_then(|l| l.get("name").and_then(|n| n.as_str()))
-                .unwrap_or(&format!("Load {}", i + 1))
-                .to_string()
-        } else {
-            format!("Load {}", i + 1)
-        };
+    for i in slots.iter().enumerate().filter(|(_, used)| **used).map(|(i, _)| i) {
+        let load_name = loads
+            .and_then(|loads| loads.get(i))
+            .and_then(|l| l.get("name").and_then(|n| n.as_str()))
+            .filter(|n| !n.trim().is_empty())
+            .map_or_else(|| format!("Load {}", i + 1), str::to_string);
 
-        let name = format!("{} \u{2500} {}", room, load_name);
+        let name = join_area_name(&room, &load_name, separator);
         warn!(
             "  [{}] {} (addr={}, load={}) — discovered via state probe",
             ra2_id, name, address, i
@@ -313,9 +415,124 @@ fn parse_state_discovery(
             ra2_id: *ra2_id,
             address: address.clone(),
             load_offset: i,
+            discovered_name: Some(name.clone()),
             name,
             room: room.clone(),
+            ..Default::default()
         });
         *ra2_id += 1;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(ra2_id: u32, address: &str, load_offset: usize, name: &str) -> SavantZoneMapping {
+        SavantZoneMapping {
+            ra2_id,
+            address: address.to_string(),
+            load_offset,
+            name: name.to_string(),
+            room: "Den".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merge_keeps_existing_ids_and_names() {
+        let existing = vec![load(210, "01A", 0, "Reading Lamp"), load(211, "01A", 1, "Gone")];
+        let mut moved = load(200, "01a", 0, "Den \u{2500} Load 1");
+        moved.room = "Library".to_string();
+        let discovered = vec![moved, load(201, "02B", 0, "Den \u{2500} Sconce")];
+        let reserved: HashSet<u32> = [5, 300].into_iter().collect();
+
+        let merged = merge_zones(&existing, &discovered, &reserved, IdRange { start: 200, end: 299 }).unwrap();
+
+        assert_eq!(merged.zones.len(), 3);
+        let lamp = &merged.zones[0];
+        assert_eq!((lamp.ra2_id, lamp.name.as_str(), lamp.room.as_str()), (210, "Reading Lamp", "Library"));
+        assert_eq!(merged.zones[2].ra2_id, 212);
+        assert_eq!(merged.added, vec![212]);
+        assert_eq!(merged.missing, vec![211]);
+
+        let err = merge_zones(&existing, &discovered, &reserved, IdRange { start: 200, end: 211 })
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("200..211 is full"));
+    }
+
+    #[test]
+    fn malformed_state_responses_dont_make_phantom_zones() {
+        let mut zones = Vec::new();
+        let mut ra2_id = 200;
+        let probe = |zones: &mut Vec<_>, ra2_id: &mut u32, addr: &str, body: serde_json::Value| {
+            parse_state_discovery(&format!("state/module/{}/get", addr), &body, zones, ra2_id, '\u{2500}')
+        };
+
+        probe(&mut zones, &mut ra2_id, "001", serde_json::json!({ "state": "-1,,50,-1,x,0", "room": "Den" }));
+        probe(&mut zones, &mut ra2_id, "002", serde_json::json!({ "state": "-1,-1,-1" }));
+        probe(
+            &mut zones,
+            &mut ra2_id,
+            "003",
+            serde_json::json!({ "loads": [{ "name": "" }, null, { "name": "Sconce" }, {}], "room": "Hall" }),
+        );
+
+        let got: Vec<(&str, usize, &str)> =
+            zones.iter().map(|z| (z.address.as_str(), z.load_offset, z.name.as_str())).collect();
+        assert_eq!(
+            got,
+            [
+                ("001", 2, "Den \u{2500} Load 3"),
+                ("001", 5, "Den \u{2500} Load 6"),
+                ("003", 2, "Hall \u{2500} Sconce"),
+                ("003", 3, "Hall \u{2500} Load 4"),
+            ]
+        );
+        assert_eq!(ra2_id, 204);
+
+        // Twenty modules of 64 slots each would still only add `max`
+        for addr in 4..=20 {
+            let state = vec!["0"; 64].join(",");
+            probe(&mut zones, &mut ra2_id, &format!("{:03}", addr), serde_json::json!({ "state": state }));
+        }
+        cap_zones(&mut zones, 256);
+        assert_eq!(zones.len(), 256);
+        assert_eq!(zones[3].name, "Hall \u{2500} Load 4");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn device_config_split_across_messages_is_collected() {
+        let config = |devices: serde_json::Value| {
+            let msg = serde_json::json!({ "URI": "lighting/config/device/get", "messages": [{ "devices": devices }] });
+            Message::Text(msg.to_string().into())
+        };
+        let first = config(serde_json::json!([
+            { "address": "01A", "room": "Den", "load": [{ "name": "Lamp" }, { "name": "Sconce" }] },
+        ]));
+        let second = config(serde_json::json!([
+            { "address": "01A", "room": "Den", "load": [{ "name": "Lamp" }, { "name": "Sconce" }] },
+            { "address": "02B", "room": "Hall", "load": [{ "name": "Pendant" }] },
+        ]));
+        // The host keeps the socket open after the last message
+        let mut ws_rx = Box::pin(async_stream::stream! {
+            yield Ok(first);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            yield Ok(second);
+            std::future::pending::<()>().await;
+        });
+
+        let mut zones = Vec::new();
+        let mut ra2_id = 200;
+        let started = Instant::now();
+        collect_device_config(&mut ws_rx, CONFIG_QUIET_PERIOD, &mut zones, &mut ra2_id, '-').await;
+
+        assert_eq!(started.elapsed(), Duration::from_millis(100) + CONFIG_QUIET_PERIOD);
+        let found: Vec<_> = zones.iter().map(|z| (z.ra2_id, z.address.as_str(), z.name.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![(200, "01A", "Den - Lamp"), (201, "01A", "Den - Sconce"), (202, "02B", "Hall - Pendant")]
+        );
+    }
+}
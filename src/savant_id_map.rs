@@ -1,36 +1,55 @@
 use std::collections::HashMap;
 
 use crate::config::SavantZoneMapping;
+use crate::level_curve::LevelCurve;
 
-/// Bidirectional map between RA2 integer IDs and Savant (address, load_offset) pairs.
+/// Bidirectional map between RA2 integer IDs and Savant (address, load_offset)
+/// pairs. Loads are keyed by host too, since two hosts can reuse an address.
 pub struct SavantIdMap {
-    ra2_to_savant: HashMap<u32, (String, usize)>,
-    savant_to_ra2: HashMap<(String, usize), u32>,
+    ra2_to_savant: HashMap<u32, (usize, String, usize)>,
+    savant_to_ra2: HashMap<(usize, String, usize), u32>,
+    curves: HashMap<u32, LevelCurve>,
+    default_curve: LevelCurve,
 }
 
 impl SavantIdMap {
-    pub fn from_zones(zones: &[SavantZoneMapping]) -> Self {
+    pub fn from_zones(zones: &[SavantZoneMapping], default_curve: &LevelCurve) -> Self {
         let mut ra2_to_savant = HashMap::new();
         let mut savant_to_ra2 = HashMap::new();
+        let mut curves = HashMap::new();
         for z in zones {
-            ra2_to_savant.insert(z.ra2_id, (z.address.clone(), z.load_offset));
-            savant_to_ra2.insert((z.address.clone(), z.load_offset), z.ra2_id);
+            ra2_to_savant.insert(z.ra2_id, (z.host, z.address.clone(), z.load_offset));
+            savant_to_ra2.insert((z.host, z.address.clone(), z.load_offset), z.ra2_id);
+            if let Some(curve) = &z.level_curve {
+                curves.insert(z.ra2_id, curve.clone());
+            }
         }
         Self {
             ra2_to_savant,
             savant_to_ra2,
+            curves,
+            default_curve: default_curve.clone(),
         }
     }
 
+    pub fn curve(&self, id: u32) -> &LevelCurve {
+        self.curves.get(&id).unwrap_or(&self.default_curve)
+    }
+
     pub fn ra2_to_savant(&self, id: u32) -> Option<(&str, usize)> {
         self.ra2_to_savant
             .get(&id)
-            .map(|(addr, off)| (addr.as_str(), *off))
+            .map(|(_, addr, off)| (addr.as_str(), *off))
+    }
+
+    /// Index of the `[[savant]]` host that owns `id`.
+    pub fn host(&self, id: u32) -> Option<usize> {
+        self.ra2_to_savant.get(&id).map(|(host, _, _)| *host)
     }
 
-    pub fn savant_to_ra2(&self, address: &str, load_offset: usize) -> Option<u32> {
+    pub fn savant_to_ra2(&self, host: usize, address: &str, load_offset: usize) -> Option<u32> {
         self.savant_to_ra2
-            .get(&(address.to_string(), load_offset))
+            .get(&(host, address.to_string(), load_offset))
             .copied()
     }
 }
@@ -47,6 +66,7 @@ mod tests {
                 load_offset: 0,
                 name: "Kitchen Light".to_string(),
                 room: "Kitchen".to_string(),
+                ..Default::default()
             },
             SavantZoneMapping {
                 ra2_id: 201,
@@ -54,6 +74,7 @@ mod tests {
                 load_offset: 1,
                 name: "Kitchen Fan".to_string(),
                 room: "Kitchen".to_string(),
+                ..Default::default()
             },
             SavantZoneMapping {
                 ra2_id: 202,
@@ -61,13 +82,14 @@ mod tests {
                 load_offset: 0,
                 name: "Bedroom Light".to_string(),
                 room: "Bedroom".to_string(),
+                ..Default::default()
             },
         ]
     }
 
     #[test]
     fn ra2_to_savant_lookup() {
-        let map = SavantIdMap::from_zones(&test_zones());
+        let map = SavantIdMap::from_zones(&test_zones(), &LevelCurve::Linear);
         assert_eq!(map.ra2_to_savant(200), Some(("001", 0)));
         assert_eq!(map.ra2_to_savant(201), Some(("001", 1)));
         assert_eq!(map.ra2_to_savant(202), Some(("002", 0)));
@@ -76,10 +98,29 @@ mod tests {
 
     #[test]
     fn savant_to_ra2_lookup() {
-        let map = SavantIdMap::from_zones(&test_zones());
-        assert_eq!(map.savant_to_ra2("001", 0), Some(200));
-        assert_eq!(map.savant_to_ra2("001", 1), Some(201));
-        assert_eq!(map.savant_to_ra2("002", 0), Some(202));
-        assert_eq!(map.savant_to_ra2("003", 0), None);
+        let map = SavantIdMap::from_zones(&test_zones(), &LevelCurve::Linear);
+        assert_eq!(map.savant_to_ra2(0, "001", 0), Some(200));
+        assert_eq!(map.savant_to_ra2(0, "001", 1), Some(201));
+        assert_eq!(map.savant_to_ra2(0, "002", 0), Some(202));
+        assert_eq!(map.savant_to_ra2(0, "003", 0), None);
+    }
+
+    #[test]
+    fn hosts_can_share_addresses() {
+        let mut zones = test_zones();
+        zones.push(SavantZoneMapping {
+            ra2_id: 300,
+            host: 1,
+            address: "001".to_string(),
+            load_offset: 0,
+            name: "Garage Light".to_string(),
+            ..Default::default()
+        });
+        let map = SavantIdMap::from_zones(&zones, &LevelCurve::Linear);
+        assert_eq!((map.host(200), map.host(300), map.host(999)), (Some(0), Some(1), None));
+        assert_eq!(map.ra2_to_savant(300), Some(("001", 0)));
+        assert_eq!(map.savant_to_ra2(0, "001", 0), Some(200));
+        assert_eq!(map.savant_to_ra2(1, "001", 0), Some(300));
+        assert_eq!(map.savant_to_ra2(1, "001", 1), None);
     }
 }
@@ -2,42 +2,48 @@ use crate::ra2_protocol::{Ra2Command, Ra2Event};
 use crate::savant_client::{SavantEvent, SavantRequest};
 use crate::savant_id_map::SavantIdMap;
 
-/// Translate an RA2 command into a Savant request.
-pub fn ra2_to_savant(cmd: &Ra2Command, map: &SavantIdMap) -> Option<SavantRequest> {
+/// Translate an RA2 command into a Savant request. `default_fade` applies to
+/// set-level commands that don't carry their own fade.
+pub fn ra2_to_savant(
+    cmd: &Ra2Command,
+    map: &SavantIdMap,
+    default_fade: Option<f64>,
+) -> Option<SavantRequest> {
     match cmd {
-        Ra2Command::SetOutput { id, level, .. } => {
+        Ra2Command::SetOutput { id, level, fade } => {
             let (address, load_offset) = map.ra2_to_savant(*id)?;
             Some(SavantRequest::SetLoad {
                 address: address.to_string(),
                 load_offset,
-                level: *level,
+                level: map.curve(*id).to_backend(*level),
+                fade: fade.or(default_fade),
             })
         }
         Ra2Command::QueryOutput { id } => {
-            let (address, load_offset) = map.ra2_to_savant(*id)?;
+            let (address, _) = map.ra2_to_savant(*id)?;
             Some(SavantRequest::QueryLoad {
                 address: address.to_string(),
-                load_offset,
             })
         }
-        Ra2Command::Monitoring { .. } => None,
+        Ra2Command::Monitoring { .. } | Ra2Command::System { .. } => None,
     }
 }
 
-/// Translate a Savant event into an RA2 event.
-pub fn savant_to_ra2(event: &SavantEvent, map: &SavantIdMap) -> Option<Ra2Event> {
+/// Translate an event from the `host`th Savant host into an RA2 event.
+pub fn savant_to_ra2(event: &SavantEvent, map: &SavantIdMap, host: usize) -> Option<Ra2Event> {
     match event {
         SavantEvent::LoadLevel {
             address,
             load_offset,
             level,
         } => {
-            let ra2_id = map.savant_to_ra2(address, *load_offset)?;
+            let ra2_id = map.savant_to_ra2(host, address, *load_offset)?;
             Some(Ra2Event::OutputLevel {
                 id: ra2_id,
-                level: *level,
+                level: map.curve(ra2_id).to_ra2(*level),
             })
         }
+        SavantEvent::Message(_) => None,
     }
 }
 
@@ -45,6 +51,7 @@ pub fn savant_to_ra2(event: &SavantEvent, map: &SavantIdMap) -> Option<Ra2Event>
 mod tests {
     use super::*;
     use crate::config::SavantZoneMapping;
+    use crate::level_curve::LevelCurve;
 
     fn test_map() -> SavantIdMap {
         SavantIdMap::from_zones(&[
@@ -54,6 +61,7 @@ mod tests {
                 load_offset: 0,
                 name: "Kitchen Light".to_string(),
                 room: "Kitchen".to_string(),
+                ..Default::default()
             },
             SavantZoneMapping {
                 ra2_id: 201,
@@ -61,8 +69,9 @@ mod tests {
                 load_offset: 1,
                 name: "Bedroom Light".to_string(),
                 room: "Bedroom".to_string(),
+                ..Default::default()
             },
-        ])
+        ], &LevelCurve::Linear)
     }
 
     #[test]
@@ -73,34 +82,41 @@ mod tests {
             level: 75.0,
             fade: None,
         };
-        let req = ra2_to_savant(&cmd, &map).unwrap();
+        let req = ra2_to_savant(&cmd, &map, None).unwrap();
         match req {
             SavantRequest::SetLoad {
                 address,
                 load_offset,
                 level,
+                fade,
             } => {
                 assert_eq!(address, "001");
                 assert_eq!(load_offset, 0);
                 assert_eq!(level, 75.0);
+                assert_eq!(fade, None);
             }
             _ => panic!("Expected SetLoad"),
         }
     }
 
+    #[test]
+    fn default_fade_applies_unless_command_has_one() {
+        let map = test_map();
+        let fade_of = |fade| match ra2_to_savant(&Ra2Command::SetOutput { id: 200, level: 75.0, fade }, &map, Some(2.0)) {
+            Some(SavantRequest::SetLoad { fade, .. }) => fade,
+            _ => panic!("Expected SetLoad"),
+        };
+        assert_eq!(fade_of(None), Some(2.0));
+        assert_eq!(fade_of(Some(0.5)), Some(0.5));
+    }
+
     #[test]
     fn translate_query_output() {
         let map = test_map();
         let cmd = Ra2Command::QueryOutput { id: 201 };
-        let req = ra2_to_savant(&cmd, &map).unwrap();
+        let req = ra2_to_savant(&cmd, &map, None).unwrap();
         match req {
-            SavantRequest::QueryLoad {
-                address,
-                load_offset,
-            } => {
-                assert_eq!(address, "002");
-                assert_eq!(load_offset, 1);
-            }
+            SavantRequest::QueryLoad { address } => assert_eq!(address, "002"),
             _ => panic!("Expected QueryLoad"),
         }
     }
@@ -113,7 +129,7 @@ mod tests {
             load_offset: 0,
             level: 50.0,
         };
-        let ra2 = savant_to_ra2(&event, &map).unwrap();
+        let ra2 = savant_to_ra2(&event, &map, 0).unwrap();
         assert_eq!(
             ra2,
             Ra2Event::OutputLevel {
@@ -123,6 +139,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn level_curve_round_trip() {
+        let curve = LevelCurve::Table(vec![[0.0, 0.0], [50.0, 20.0], [100.0, 100.0]]);
+        let map = SavantIdMap::from_zones(
+            &[SavantZoneMapping {
+                ra2_id: 200,
+                address: "001".to_string(),
+                load_offset: 0,
+                ..Default::default()
+            }],
+            &curve,
+        );
+        let cmd = Ra2Command::SetOutput {
+            id: 200,
+            level: 75.0,
+            fade: None,
+        };
+        let sent = match ra2_to_savant(&cmd, &map, None).unwrap() {
+            SavantRequest::SetLoad { level, .. } => level,
+            _ => panic!("Expected SetLoad"),
+        };
+        assert_eq!(sent, 60.0);
+
+        let event = SavantEvent::LoadLevel {
+            address: "001".to_string(),
+            load_offset: 0,
+            level: sent,
+        };
+        assert_eq!(
+            savant_to_ra2(&event, &map, 0),
+            Some(Ra2Event::OutputLevel { id: 200, level: 75.0 })
+        );
+    }
+
     #[test]
     fn unknown_id_returns_none() {
         let map = test_map();
@@ -131,7 +181,7 @@ mod tests {
             level: 50.0,
             fade: None,
         };
-        assert!(ra2_to_savant(&cmd, &map).is_none());
+        assert!(ra2_to_savant(&cmd, &map, None).is_none());
     }
 
     #[test]
@@ -141,7 +191,7 @@ mod tests {
             mon_type: 5,
             enable: true,
         };
-        assert!(ra2_to_savant(&cmd, &map).is_none());
+        assert!(ra2_to_savant(&cmd, &map, None).is_none());
     }
 
     #[test]
@@ -152,6 +202,6 @@ mod tests {
             load_offset: 0,
             level: 100.0,
         };
-        assert!(savant_to_ra2(&event, &map).is_none());
+        assert!(savant_to_ra2(&event, &map, 0).is_none());
     }
 }
@@ -1,18 +1,39 @@
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::Result;
 use tracing::info;
 
+use tokio::net::TcpStream;
 use tokio::sync::broadcast;
 
 use crate::state::{AppState, BridgeStatus};
+use crate::web_log_layer::LogHistory;
 
-pub async fn serve(config_path: PathBuf, certs_dir: PathBuf, web_port: u16, log_tx: broadcast::Sender<String>) -> Result<()> {
-    let state = AppState::new(config_path.clone(), certs_dir.clone(), log_tx);
+#[allow(clippy::too_many_arguments)]
+pub async fn serve(
+    config_path: PathBuf,
+    certs_dir: PathBuf,
+    profiles_dir: Option<PathBuf>,
+    web_port: u16,
+    read_only: bool,
+    static_dir: Option<PathBuf>,
+    log_tx: broadcast::Sender<String>,
+    log_history: LogHistory,
+) -> Result<()> {
+    let state = AppState::new(
+        config_path.clone(),
+        certs_dir.clone(),
+        profiles_dir,
+        read_only,
+        log_tx,
+        log_history,
+    );
+    crate::notify::spawn(state.config.clone(), state.bridge_status.subscribe(), &state.backend_health);
 
     // Try loading existing config
     let has_config = if config_path.exists() {
-        match crate::config::Config::load(&config_path) {
+        match crate::config::Config::load(&config_path).await {
             Ok(cfg) => {
                 info!(
                     "Loaded config: {} zones, processor at {}",
@@ -22,6 +43,10 @@ pub async fn serve(config_path: PathBuf, certs_dir: PathBuf, web_port: u16, log_
                 *state.config.write().await = Some(cfg);
                 true
             }
+            Err(e) if e.is::<crate::config::EmptyConfig>() => {
+                info!("{}", e);
+                false
+            }
             Err(e) => {
                 tracing::warn!("Failed to load config: {}", e);
                 false
@@ -32,27 +57,39 @@ pub async fn serve(config_path: PathBuf, certs_dir: PathBuf, web_port: u16, log_
     };
 
     // Check if certs exist
-    let has_certs = certs_dir.join("ra-bridge.crt").exists()
-        && certs_dir.join("ra-bridge.key").exists()
-        && certs_dir.join("ca.crt").exists();
+    let has_certs = crate::state::has_certs(&certs_dir);
+
+    // Start web server first so the UI is up during any auto-start grace period
+    let app = crate::web::router(state.clone(), static_dir);
+    let addr = format!("0.0.0.0:{}", web_port);
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    info!("Web server listening on http://{}", addr);
+    let server = tokio::spawn(async move { axum::serve(listener, app).await });
 
     // Auto-start bridge if config + certs exist
     if has_config && has_certs {
         info!("Config and certs found, auto-starting bridge...");
         let config = state.config.read().await.clone().unwrap();
         let _ = state.bridge_status.send(BridgeStatus::Starting);
+        autostart_grace(&config).await;
 
         match crate::bridge::start(
             config,
             certs_dir.clone(),
             state.zone_levels.clone(),
             state.bridge_status.clone(),
+            state.savant_metrics.clone(),
+            state.command_metrics.clone(),
+            state.backend_health.clone(),
         )
         .await
         {
             Ok(handle) => {
                 *state.leap_req_tx.write().await = handle.leap_req_tx;
+                *state.leap_events.write().await = handle.leap_events;
                 *state.savant_req_tx.write().await = handle.savant_req_tx;
+                *state.savant_events.write().await = handle.savant_events;
+                *state.bridge_maps.write().await = Some(handle.maps_tx);
                 *state.bridge_shutdown.write().await = Some(handle.shutdown_tx);
                 *state.bridge_started_at.write().await = Some(tokio::time::Instant::now());
                 let _ = state.bridge_status.send(BridgeStatus::Running);
@@ -71,18 +108,25 @@ pub async fn serve(config_path: PathBuf, certs_dir: PathBuf, web_port: u16, log_
         if config.has_savant() && !config.has_leap() {
             info!("Savant-only config found, auto-starting bridge...");
             let _ = state.bridge_status.send(BridgeStatus::Starting);
+            autostart_grace(&config).await;
             // Use a dummy certs_dir; LEAP won't be started
             match crate::bridge::start(
                 config,
                 certs_dir.clone(),
                 state.zone_levels.clone(),
                 state.bridge_status.clone(),
+                state.savant_metrics.clone(),
+                state.command_metrics.clone(),
+                state.backend_health.clone(),
             )
             .await
             {
                 Ok(handle) => {
                     *state.leap_req_tx.write().await = handle.leap_req_tx;
+                    *state.leap_events.write().await = handle.leap_events;
                     *state.savant_req_tx.write().await = handle.savant_req_tx;
+                    *state.savant_events.write().await = handle.savant_events;
+                    *state.bridge_maps.write().await = Some(handle.maps_tx);
                     *state.bridge_shutdown.write().await = Some(handle.shutdown_tx);
                     *state.bridge_started_at.write().await = Some(tokio::time::Instant::now());
                     let _ = state.bridge_status.send(BridgeStatus::Running);
@@ -102,22 +146,71 @@ pub async fn serve(config_path: PathBuf, certs_dir: PathBuf, web_port: u16, log_
         info!("No config/certs found — web UI will show setup wizard");
     }
 
-    // Start web server
-    let app = crate::web::router(state);
-    let addr = format!("0.0.0.0:{}", web_port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    info!("Web server listening on http://{}", addr);
-
-    axum::serve(listener, app).await?;
+    server.await??;
 
     Ok(())
 }
 
-pub async fn serve_dev(sites_dir: PathBuf, web_port: u16, log_tx: broadcast::Sender<String>) -> Result<()> {
+/// Apply `[bridge] autostart_delay_secs` and `autostart_wait_secs` before an
+/// auto-start. Gives up quietly; the bridge's own reconnect takes over.
+async fn autostart_grace(config: &crate::config::Config) {
+    let bridge = &config.bridge;
+    if bridge.autostart_delay_secs > 0 {
+        info!("Waiting {}s before auto-start", bridge.autostart_delay_secs);
+        tokio::time::sleep(Duration::from_secs(bridge.autostart_delay_secs)).await;
+    }
+    if bridge.autostart_wait_secs == 0 {
+        return;
+    }
+
+    let (host, port) = if config.has_leap() {
+        (config.processor.host.as_str(), config.processor.leap_port)
+    } else if let Some(savant) = config.savant.first() {
+        (savant.host.as_str(), savant.port)
+    } else {
+        return;
+    };
+    if wait_until_reachable(host, port, Duration::from_secs(bridge.autostart_wait_secs)).await {
+        info!("{}:{} is reachable", host, port);
+    } else {
+        tracing::warn!(
+            "{}:{} still unreachable after {}s, starting anyway",
+            host,
+            port,
+            bridge.autostart_wait_secs
+        );
+    }
+}
+
+/// Retry a TCP connect to `host:port` once a second until it succeeds or
+/// `limit` runs out.
+async fn wait_until_reachable(host: &str, port: u16, limit: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + limit;
+    loop {
+        let attempt = tokio::time::timeout(Duration::from_secs(2), TcpStream::connect((host, port)));
+        if let Ok(Ok(_)) = attempt.await {
+            return true;
+        }
+        if tokio::time::Instant::now() + Duration::from_secs(1) >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+pub async fn serve_dev(
+    sites_dir: PathBuf,
+    web_port: u16,
+    read_only: bool,
+    static_dir: Option<PathBuf>,
+    log_tx: broadcast::Sender<String>,
+    log_history: LogHistory,
+) -> Result<()> {
     // Ensure sites directory exists
     std::fs::create_dir_all(&sites_dir)?;
 
-    let state = AppState::new_dev(sites_dir.clone(), log_tx);
+    let state = AppState::new_dev(sites_dir.clone(), read_only, log_tx, log_history);
+    crate::notify::spawn(state.config.clone(), state.bridge_status.subscribe(), &state.backend_health);
 
     // Auto-activate if exactly one site exists
     let sites = state.list_sites().await;
@@ -132,7 +225,7 @@ pub async fn serve_dev(sites_dir: PathBuf, web_port: u16, log_tx: broadcast::Sen
     }
 
     // Start web server
-    let app = crate::web::router(state);
+    let app = crate::web::router(state, static_dir);
     let addr = format!("0.0.0.0:{}", web_port);
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     info!("Dev server listening on http://{}", addr);
@@ -155,16 +248,9 @@ pub async fn activate_site(state: &AppState, site_name: &str) -> Result<()> {
     }
 
     // 1. Stop current bridge if running
-    {
-        let shutdown = state.bridge_shutdown.write().await.take();
-        if let Some(tx) = shutdown {
-            let _ = tx.send(()).await;
-            *state.leap_req_tx.write().await = None;
-            *state.savant_req_tx.write().await = None;
-            *state.bridge_started_at.write().await = None;
-            let _ = state.bridge_status.send(BridgeStatus::Stopped);
-            tokio::time::sleep(std::time::Duration::from_millis(300)).await;
-        }
+    if crate::web::routes::stop_bridge(state).await {
+        let _ = state.bridge_status.send(BridgeStatus::Stopped);
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
     }
 
     // 2. Swap paths
@@ -174,11 +260,11 @@ pub async fn activate_site(state: &AppState, site_name: &str) -> Result<()> {
     *state.certs_dir.write().await = certs_dir.clone();
 
     // 3. Clear zone levels (stale data from previous site)
-    state.zone_levels.write().await.clear();
+    state.zone_levels.clear().await;
 
     // 4. Load new config
     let has_config = if config_path.exists() {
-        match crate::config::Config::load(&config_path) {
+        match crate::config::Config::load(&config_path).await {
             Ok(cfg) => {
                 info!(
                     "Site '{}': loaded config — {} zones, processor at {}",
@@ -189,6 +275,11 @@ pub async fn activate_site(state: &AppState, site_name: &str) -> Result<()> {
                 *state.config.write().await = Some(cfg);
                 true
             }
+            Err(e) if e.is::<crate::config::EmptyConfig>() => {
+                info!("Site '{}': {}", site_name, e);
+                *state.config.write().await = None;
+                false
+            }
             Err(e) => {
                 tracing::warn!("Site '{}': failed to load config: {}", site_name, e);
                 *state.config.write().await = None;
@@ -200,6 +291,8 @@ pub async fn activate_site(state: &AppState, site_name: &str) -> Result<()> {
         false
     };
 
+    state.bump_config_generation();
+
     // 5. Set active site
     *state.active_site.write().await = Some(site_name.to_string());
 
@@ -211,30 +304,9 @@ pub async fn activate_site(state: &AppState, site_name: &str) -> Result<()> {
     if has_config && has_certs {
         info!("Site '{}': auto-starting bridge...", site_name);
         let config = state.config.read().await.clone().unwrap();
-        let _ = state.bridge_status.send(BridgeStatus::Starting);
-
-        match crate::bridge::start(
-            config,
-            certs_dir,
-            state.zone_levels.clone(),
-            state.bridge_status.clone(),
-        )
-        .await
-        {
-            Ok(handle) => {
-                *state.leap_req_tx.write().await = handle.leap_req_tx;
-                *state.savant_req_tx.write().await = handle.savant_req_tx;
-                *state.bridge_shutdown.write().await = Some(handle.shutdown_tx);
-                *state.bridge_started_at.write().await = Some(tokio::time::Instant::now());
-                let _ = state.bridge_status.send(BridgeStatus::Running);
-                info!("Site '{}': bridge started", site_name);
-            }
-            Err(e) => {
-                let _ = state
-                    .bridge_status
-                    .send(BridgeStatus::Error { message: e.to_string() });
-                tracing::error!("Site '{}': failed to start bridge: {}", site_name, e);
-            }
+        match crate::web::routes::start_bridge(state, config).await {
+            Ok(()) => info!("Site '{}': bridge started", site_name),
+            Err(e) => tracing::error!("Site '{}': failed to start bridge: {}", site_name, e),
         }
     } else {
         info!(
@@ -245,3 +317,145 @@ pub async fn activate_site(state: &AppState, site_name: &str) -> Result<()> {
 
     Ok(())
 }
+
+/// Config files in `--profiles-dir`, sorted by name.
+pub fn list_profiles(profiles_dir: &Path) -> Result<Vec<String>> {
+    let mut profiles: Vec<String> = std::fs::read_dir(profiles_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".toml") && !name.starts_with('.'))
+        .collect();
+    profiles.sort();
+    Ok(profiles)
+}
+
+/// Resolve a profile name from a request to a file directly inside
+/// `profiles_dir`. Anything but a bare `*.toml` file name is refused, so
+/// requests can't reach outside the directory.
+fn profile_path(profiles_dir: &Path, name: &str) -> Result<PathBuf> {
+    let mut components = Path::new(name).components();
+    let (Some(Component::Normal(file)), None) = (components.next(), components.next()) else {
+        anyhow::bail!("Invalid profile name: {}", name);
+    };
+    if name.starts_with('.') || Path::new(file).extension().is_none_or(|ext| ext != "toml") {
+        anyhow::bail!("Invalid profile name: {}", name);
+    }
+    let path = profiles_dir.join(file);
+    if !path.is_file() {
+        anyhow::bail!("Profile not found: {}", name);
+    }
+    Ok(path)
+}
+
+/// Switch to the profile `name` from `--profiles-dir`: the new config is
+/// loaded and validated first, then swapped in as the active config path.
+/// A running bridge is restarted on the new config. Profiles share the one
+/// certs dir, so a profile for a different processor than the current
+/// config's is refused. Returns whether the bridge was restarted.
+pub async fn load_profile(state: &AppState, name: &str) -> Result<bool> {
+    let profiles_dir = state
+        .profiles_dir
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Profiles not enabled"))?;
+    let config_path = profile_path(profiles_dir, name)?;
+    let config = crate::config::Config::load(&config_path).await?;
+    if let Err(e) = config.validate() {
+        anyhow::bail!("Invalid config in {}: {}", name, e);
+    }
+    if let Some(current) = state.config.read().await.as_ref() {
+        if current.processor.host != config.processor.host {
+            anyhow::bail!(
+                "Profile '{}' is for processor {}, but the certs in {} are for {}",
+                name,
+                config.processor.host,
+                state.certs_dir.read().await.display(),
+                current.processor.host,
+            );
+        }
+    }
+
+    // 1. Stop current bridge if running
+    let was_running = crate::web::routes::stop_bridge(state).await;
+    if was_running {
+        let _ = state.bridge_status.send(BridgeStatus::Stopped);
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    }
+
+    // 2. Swap config; levels from the old zones no longer apply
+    info!(
+        "Profile '{}': loaded config — {} zones, processor at {}",
+        name,
+        config.zones.len(),
+        config.processor.host,
+    );
+    *state.config_path.write().await = config_path;
+    state.zone_levels.clear().await;
+    *state.config.write().await = Some(config.clone());
+    state.bump_config_generation();
+
+    // 3. Bring the bridge back up on the new config
+    if !was_running {
+        return Ok(false);
+    }
+    crate::web::routes::start_bridge(state, config)
+        .await
+        .map_err(|e| e.context(format!("Profile '{}' loaded, but the bridge failed to restart", name)))?;
+    info!("Profile '{}': bridge restarted", name);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reachability_wait_sees_open_and_closed_ports() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(wait_until_reachable("127.0.0.1", port, Duration::from_secs(1)).await);
+
+        drop(listener);
+        assert!(!wait_until_reachable("127.0.0.1", port, Duration::from_secs(1)).await);
+    }
+
+    #[tokio::test]
+    async fn profiles_swap_config_and_stay_inside_their_directory() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-profiles-{}", uuid::Uuid::new_v4()));
+        let profiles_dir = dir.join("profiles");
+        std::fs::create_dir_all(&profiles_dir).unwrap();
+        std::fs::write(profiles_dir.join("normal.toml"), "[processor]\nhost = \"10.0.0.5\"\n").unwrap();
+        std::fs::write(profiles_dir.join("party.toml"), "[processor]\nhost = \"10.0.0.6\"\n").unwrap();
+        std::fs::write(profiles_dir.join("notes.txt"), "").unwrap();
+        std::fs::write(dir.join("secret.toml"), "[processor]\nhost = \"10.9.9.9\"\n").unwrap();
+
+        let state = AppState::new(
+            dir.join("config.toml"),
+            dir.join("certs"),
+            Some(profiles_dir.clone()),
+            false,
+            broadcast::channel(1).0,
+            LogHistory::default(),
+        );
+        assert_eq!(list_profiles(&profiles_dir).unwrap(), vec!["normal.toml", "party.toml"]);
+
+        assert!(!load_profile(&state, "party.toml").await.unwrap());
+        assert_eq!(*state.config_path.read().await, profiles_dir.join("party.toml"));
+        assert_eq!(state.config.read().await.as_ref().unwrap().processor.host, "10.0.0.6");
+        assert_eq!(state.config_generation(), 1);
+
+        // The certs now belong to 10.0.0.6, so only its profiles load
+        let late = "[processor]\nhost = \"10.0.0.6\"\n[bridge]\nskip_redundant_sets = true\n";
+        std::fs::write(profiles_dir.join("late.toml"), late).unwrap();
+        assert!(load_profile(&state, "normal.toml").await.is_err());
+        assert!(!load_profile(&state, "late.toml").await.unwrap());
+        assert_eq!(*state.config_path.read().await, profiles_dir.join("late.toml"));
+
+        for name in ["../secret.toml", "/etc/passwd", "notes.txt", "missing.toml", "", "sub/../party.toml"] {
+            assert!(load_profile(&state, name).await.is_err(), "{} was accepted", name);
+        }
+        assert_eq!(*state.config_path.read().await, profiles_dir.join("late.toml"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,14 +1,17 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use serde::Serialize;
-use tokio::sync::{broadcast, mpsc, watch, RwLock};
+use tokio::sync::{broadcast, mpsc, watch, Mutex, RwLock};
 use tokio::time::Instant;
 
+use crate::bridge::{BridgeMaps, CommandMetrics};
 use crate::config::Config;
-use crate::leap_client::LeapRequest;
-use crate::savant_client::SavantRequest;
+use crate::leap_client::{LeapEvent, LeapRequest};
+use crate::savant_client::{SavantEvent, SavantHostMetrics, SavantRequest};
+use crate::web_log_layer::LogHistory;
 
 #[derive(Debug, Clone, Serialize, PartialEq)]
 #[serde(tag = "state")]
@@ -19,6 +22,140 @@ pub enum BridgeStatus {
     Error { message: String },
 }
 
+/// Connection state of a single backend (LEAP or Savant).
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "state")]
+pub enum BackendStatus {
+    /// Backend has no zones/config, so it was never started.
+    NotConfigured,
+    Connecting,
+    Connected,
+    /// Last connection attempt failed; the client keeps retrying.
+    /// `attempts` counts consecutive failures.
+    Error { message: String, attempts: u32 },
+    /// `[bridge] max_reconnect_attempts` ran out; the client has stopped
+    /// retrying and the bridge must be restarted.
+    Failed { message: String, attempts: u32 },
+    Stopped,
+}
+
+/// Per-backend status channels, so the UI can show "LEAP up, Savant down".
+pub struct BackendHealth {
+    pub leap: watch::Sender<BackendStatus>,
+    pub savant: watch::Sender<BackendStatus>,
+}
+
+impl BackendHealth {
+    pub fn new() -> Self {
+        Self {
+            leap: watch::channel(BackendStatus::NotConfigured).0,
+            savant: watch::channel(BackendStatus::NotConfigured).0,
+        }
+    }
+}
+
+/// Wait out a backend's reconnect delay, returning false as soon as every
+/// sender of `req_rx` is gone, so a bridge stopped mid-backoff reports
+/// Stopped rather than the last Error. Queued requests are left queued.
+pub async fn reconnect_delay<T>(delay: std::time::Duration, req_rx: &mpsc::Receiver<T>) -> bool {
+    const CHECK_EVERY: std::time::Duration = std::time::Duration::from_millis(100);
+    let deadline = Instant::now() + delay;
+    loop {
+        if req_rx.is_closed() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        tokio::time::sleep(CHECK_EVERY.min(deadline - now)).await;
+    }
+}
+
+/// Levels closer than this are the same level.
+const LEVEL_EPSILON: f64 = 0.005;
+
+/// Last known level (0–100) of every zone, written by the bridge forwarders
+/// and the web routes and read by the UI, MQTT and telnet sync. Every method
+/// takes the lock once, so a read-modify-write such as `toggle` can't lose an
+/// event that lands in between.
+#[derive(Clone, Default)]
+pub struct ZoneLevels(Arc<RwLock<Levels>>);
+
+#[derive(Default)]
+struct Levels {
+    current: HashMap<u32, f64>,
+    /// The last level each backend reported and `update` passed on. The
+    /// deadband is measured from here, not from `current`: commands write
+    /// their target into `current`, and the backend confirming it must
+    /// still reach telnet clients, SSE and MQTT.
+    forwarded: HashMap<u32, f64>,
+}
+
+impl ZoneLevels {
+    pub async fn get_level(&self, id: u32) -> Option<f64> {
+        self.0.read().await.current.get(&id).copied()
+    }
+
+    pub async fn set_level(&self, id: u32, level: f64) {
+        self.0.write().await.current.insert(id, level);
+    }
+
+    /// Whether `id` is cached at `level`, give or take float noise.
+    pub async fn is_at(&self, id: u32, level: f64) -> bool {
+        self.get_level(id)
+            .await
+            .is_some_and(|current| (current - level).abs() < LEVEL_EPSILON)
+    }
+
+    /// Store a level reported by a backend unless it's within `deadband` of
+    /// the last report passed on. Turning on or off always counts. Returns
+    /// whether it was stored, i.e. whether the report should be passed on.
+    pub async fn update(&self, id: u32, level: f64, deadband: f64) -> bool {
+        let mut levels = self.0.write().await;
+        if let Some(&previous) = levels.forwarded.get(&id) {
+            let switched = (previous > 0.0) != (level > 0.0);
+            if !switched && (level - previous).abs() < deadband {
+                return false;
+            }
+        }
+        levels.forwarded.insert(id, level);
+        levels.current.insert(id, level);
+        true
+    }
+
+    /// Turn a zone off if it's on, otherwise set it to `on_level`. Returns
+    /// the previous level and the new one.
+    pub async fn toggle(&self, id: u32, on_level: f64) -> (Option<f64>, f64) {
+        let mut levels = self.0.write().await;
+        let previous = levels.current.get(&id).copied();
+        let level = if previous.is_some_and(|l| l > 0.0) { 0.0 } else { on_level };
+        levels.current.insert(id, level);
+        (previous, level)
+    }
+
+    pub async fn remove(&self, id: u32) {
+        let mut levels = self.0.write().await;
+        levels.current.remove(&id);
+        levels.forwarded.remove(&id);
+    }
+
+    pub async fn extend(&self, levels: impl IntoIterator<Item = (u32, f64)>) {
+        self.0.write().await.current.extend(levels);
+    }
+
+    pub async fn clear(&self) {
+        let mut levels = self.0.write().await;
+        levels.current.clear();
+        levels.forwarded.clear();
+    }
+
+    /// A copy of every cached level.
+    pub async fn snapshot(&self) -> HashMap<u32, f64> {
+        self.0.read().await.current.clone()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, PartialEq)]
 #[serde(tag = "state")]
 pub enum PairingStatus {
@@ -40,10 +177,30 @@ pub enum SavantDiscoveryStatus {
     Idle,
     Connecting,
     Enumerating { device_count: usize },
-    Complete { zone_count: usize },
+    /// `added` and `missing` are only set when merging into existing zones.
+    Complete {
+        zone_count: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        added: Option<Vec<u32>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        missing: Option<Vec<u32>>,
+        /// The running bridge needs a restart to pick up every change.
+        restart_required: bool,
+    },
     Failed { message: String },
 }
 
+/// What activating a site would do, computed without touching it.
+#[derive(Debug, Clone, Serialize)]
+pub struct SitePreview {
+    pub has_config: bool,
+    pub has_certs: bool,
+    pub will_autostart: bool,
+    pub zone_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config_error: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct SiteInfo {
     pub name: String,
@@ -54,112 +211,335 @@ pub struct SiteInfo {
 
 pub struct AppState {
     pub config: Arc<RwLock<Option<Config>>>,
+    /// Bumped on every config change so integrators can cheaply tell when to
+    /// re-download the XML export.
+    pub config_generation: Arc<AtomicU64>,
     pub bridge_status: watch::Sender<BridgeStatus>,
     pub pairing_status: watch::Sender<PairingStatus>,
     pub savant_discovery_status: watch::Sender<SavantDiscoveryStatus>,
-    pub zone_levels: Arc<RwLock<HashMap<u32, f64>>>,
+    pub zone_levels: ZoneLevels,
     pub bridge_started_at: RwLock<Option<Instant>>,
     pub leap_req_tx: RwLock<Option<mpsc::Sender<LeapRequest>>>,
-    pub savant_req_tx: RwLock<Option<mpsc::Sender<SavantRequest>>>,
+    pub leap_events: RwLock<Option<broadcast::Sender<LeapEvent>>>,
+    pub savant_req_tx: RwLock<Vec<mpsc::Sender<SavantRequest>>>,
+    pub savant_events: RwLock<Vec<broadcast::Sender<SavantEvent>>>,
+    pub bridge_maps: RwLock<Option<watch::Sender<Arc<BridgeMaps>>>>,
     pub bridge_shutdown: RwLock<Option<mpsc::Sender<()>>>,
+    pub savant_metrics: Arc<SavantHostMetrics>,
+    pub command_metrics: Arc<CommandMetrics>,
+    pub backend_health: Arc<BackendHealth>,
 
     // Swappable paths (RwLock for dev mode site switching)
     pub config_path: RwLock<PathBuf>,
     pub certs_dir: RwLock<PathBuf>,
 
+    /// `serve --profiles-dir`: alternative config files that can be swapped
+    /// in with `POST /api/config/load` (single mode only).
+    pub profiles_dir: Option<PathBuf>,
+
     // Multi-site (dev mode only)
     pub sites_dir: Option<PathBuf>,
     pub active_site: RwLock<Option<String>>,
     pub dev_mode: bool,
+    /// Started with `--read-only`: config, pairing, bridge control and site
+    /// changes are refused; the dashboard and level control still work.
+    pub read_only: bool,
+    /// Held by a running pairing and by site activate/delete/rename, so none
+    /// of them can swap or remove paths out from under another.
+    pub site_lock: Arc<Mutex<()>>,
+    /// No new pairing attempt until this instant (see `PAIR_COOLDOWN`).
+    pub pair_cooldown_until: Arc<RwLock<Option<Instant>>>,
+    /// Zones in the middle of an identify blink.
+    pub identifying: Mutex<HashSet<u32>>,
 
     // Log broadcast for web UI
     pub log_tx: broadcast::Sender<String>,
+    pub log_history: LogHistory,
 }
 
 impl AppState {
-    pub fn new(config_path: PathBuf, certs_dir: PathBuf, log_tx: broadcast::Sender<String>) -> Arc<Self> {
+    pub fn new(
+        config_path: PathBuf,
+        certs_dir: PathBuf,
+        profiles_dir: Option<PathBuf>,
+        read_only: bool,
+        log_tx: broadcast::Sender<String>,
+        log_history: LogHistory,
+    ) -> Arc<Self> {
         let (bridge_status, _) = watch::channel(BridgeStatus::Stopped);
         let (pairing_status, _) = watch::channel(PairingStatus::Idle);
         let (savant_discovery_status, _) = watch::channel(SavantDiscoveryStatus::Idle);
 
         Arc::new(Self {
             config: Arc::new(RwLock::new(None)),
+            config_generation: Arc::new(AtomicU64::new(0)),
             bridge_status,
             pairing_status,
             savant_discovery_status,
-            zone_levels: Arc::new(RwLock::new(HashMap::new())),
+            zone_levels: ZoneLevels::default(),
             bridge_started_at: RwLock::new(None),
             leap_req_tx: RwLock::new(None),
-            savant_req_tx: RwLock::new(None),
+            leap_events: RwLock::new(None),
+            savant_req_tx: RwLock::new(Vec::new()),
+            savant_events: RwLock::new(Vec::new()),
+            bridge_maps: RwLock::new(None),
             bridge_shutdown: RwLock::new(None),
+            savant_metrics: Arc::new(SavantHostMetrics::default()),
+            command_metrics: Arc::new(CommandMetrics::default()),
+            backend_health: Arc::new(BackendHealth::new()),
             config_path: RwLock::new(config_path),
             certs_dir: RwLock::new(certs_dir),
+            profiles_dir,
             sites_dir: None,
             active_site: RwLock::new(None),
             dev_mode: false,
+            read_only,
+            site_lock: Arc::new(Mutex::new(())),
+            pair_cooldown_until: Arc::new(RwLock::new(None)),
+            identifying: Mutex::new(HashSet::new()),
             log_tx,
+            log_history,
         })
     }
 
-    pub fn new_dev(sites_dir: PathBuf, log_tx: broadcast::Sender<String>) -> Arc<Self> {
+    pub fn new_dev(
+        sites_dir: PathBuf,
+        read_only: bool,
+        log_tx: broadcast::Sender<String>,
+        log_history: LogHistory,
+    ) -> Arc<Self> {
         let (bridge_status, _) = watch::channel(BridgeStatus::Stopped);
         let (pairing_status, _) = watch::channel(PairingStatus::Idle);
         let (savant_discovery_status, _) = watch::channel(SavantDiscoveryStatus::Idle);
 
         Arc::new(Self {
             config: Arc::new(RwLock::new(None)),
+            config_generation: Arc::new(AtomicU64::new(0)),
             bridge_status,
             pairing_status,
             savant_discovery_status,
-            zone_levels: Arc::new(RwLock::new(HashMap::new())),
+            zone_levels: ZoneLevels::default(),
             bridge_started_at: RwLock::new(None),
             leap_req_tx: RwLock::new(None),
-            savant_req_tx: RwLock::new(None),
+            leap_events: RwLock::new(None),
+            savant_req_tx: RwLock::new(Vec::new()),
+            savant_events: RwLock::new(Vec::new()),
+            bridge_maps: RwLock::new(None),
             bridge_shutdown: RwLock::new(None),
+            savant_metrics: Arc::new(SavantHostMetrics::default()),
+            command_metrics: Arc::new(CommandMetrics::default()),
+            backend_health: Arc::new(BackendHealth::new()),
             config_path: RwLock::new(PathBuf::from("config.toml")),
             certs_dir: RwLock::new(PathBuf::from("certs")),
+            profiles_dir: None,
             sites_dir: Some(sites_dir),
             active_site: RwLock::new(None),
             dev_mode: true,
+            read_only,
+            site_lock: Arc::new(Mutex::new(())),
+            pair_cooldown_until: Arc::new(RwLock::new(None)),
+            identifying: Mutex::new(HashSet::new()),
             log_tx,
+            log_history,
         })
     }
 
+    pub fn config_generation(&self) -> u64 {
+        self.config_generation.load(Ordering::Relaxed)
+    }
+
+    pub fn bump_config_generation(&self) {
+        self.config_generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// All sites, sorted by name. The directory scan runs on the blocking
+    /// pool so slow storage doesn't stall the runtime.
     pub async fn list_sites(&self) -> Vec<SiteInfo> {
         let sites_dir = match &self.sites_dir {
-            Some(d) => d,
+            Some(d) => d.clone(),
             None => return vec![],
         };
 
         let active = self.active_site.read().await.clone();
 
-        let mut sites = Vec::new();
-        let entries = match std::fs::read_dir(sites_dir) {
-            Ok(e) => e,
-            Err(_) => return vec![],
-        };
+        tokio::task::spawn_blocking(move || scan_sites(&sites_dir, active.as_deref()))
+            .await
+            .unwrap_or_default()
+    }
 
-        for entry in entries.flatten() {
-            if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
-                continue;
-            }
-            let name = entry.file_name().to_string_lossy().to_string();
-            let site_path = entry.path();
-            let has_config = site_path.join("config.toml").exists();
-            let has_certs = site_path.join("certs/ra-bridge.crt").exists()
-                && site_path.join("certs/ra-bridge.key").exists()
-                && site_path.join("certs/ca.crt").exists();
-            let is_active = active.as_deref() == Some(&name);
-
-            sites.push(SiteInfo {
-                name,
-                has_config,
-                has_certs,
-                active: is_active,
-            });
+    /// Preview `activate_site` for `name`; `None` if the site doesn't exist.
+    pub async fn preview_site(&self, name: &str) -> Option<SitePreview> {
+        let site_path = self.sites_dir.as_ref()?.join(name);
+        tokio::task::spawn_blocking(move || site_path.is_dir().then(|| preview_site(&site_path)))
+            .await
+            .ok()
+            .flatten()
+    }
+}
+
+/// Whether `certs_dir` holds everything the bridge needs to reach the processor.
+pub fn has_certs(certs_dir: &Path) -> bool {
+    certs_dir.join("ra-bridge.crt").exists()
+        && certs_dir.join("ra-bridge.key").exists()
+        && certs_dir.join("ca.crt").exists()
+}
+
+fn preview_site(site_path: &Path) -> SitePreview {
+    let config_path = site_path.join("config.toml");
+    let (config, config_error) = if config_path.exists() {
+        match Config::read(&config_path) {
+            Ok(cfg) => (Some(cfg), None),
+            Err(e) if e.is::<crate::config::EmptyConfig>() => (None, None),
+            Err(e) => (None, Some(e.to_string())),
+        }
+    } else {
+        (None, None)
+    };
+    let has_config = config.is_some();
+    let has_certs = has_certs(&site_path.join("certs"));
+    SitePreview {
+        has_config,
+        has_certs,
+        will_autostart: has_config && has_certs,
+        zone_count: config.map_or(0, |c| c.zones.len() + c.savant_zones.len()),
+        config_error,
+    }
+}
+
+fn scan_sites(sites_dir: &Path, active: Option<&str>) -> Vec<SiteInfo> {
+    let mut sites = Vec::new();
+    let entries = match std::fs::read_dir(sites_dir) {
+        Ok(e) => e,
+        Err(_) => return vec![],
+    };
+
+    for entry in entries.flatten() {
+        if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        let site_path = entry.path();
+        let has_config = site_path.join("config.toml").exists();
+        let has_certs = has_certs(&site_path.join("certs"));
+        let is_active = active == Some(name.as_str());
+
+        sites.push(SiteInfo {
+            name,
+            has_config,
+            has_certs,
+            active: is_active,
+        });
+    }
+
+    sites.sort_by(|a, b| a.name.cmp(&b.name));
+    sites
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn deadband_drops_jitter_but_not_real_changes() {
+        let levels = ZoneLevels::default();
+        assert!(levels.update(1, 50.0, 1.0).await);
+        assert!(!levels.update(1, 50.6, 1.0).await);
+        assert!(!levels.update(1, 49.2, 1.0).await);
+        assert_eq!(levels.get_level(1).await, Some(50.0));
+        assert!(levels.update(1, 52.0, 1.0).await);
+        assert_eq!(levels.get_level(1).await, Some(52.0));
+
+        // A command's target in the cache doesn't swallow its confirmation
+        levels.set_level(1, 80.0).await;
+        assert!(levels.update(1, 80.0, 1.0).await);
+        assert!(!levels.update(1, 80.3, 1.0).await);
+
+        // Off and on always go through, however small the step
+        levels.set_level(2, 0.5).await;
+        assert!(levels.update(2, 0.0, 1.0).await);
+        assert!(levels.update(2, 0.4, 1.0).await);
+        assert!(levels.update(2, 0.45, 0.0).await);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_level_updates_are_not_lost() {
+        let levels = ZoneLevels::default();
+        levels.set_level(1, 0.0).await;
+
+        // An even number of toggles racing with set-levels on other zones
+        // must leave zone 1 off again
+        let mut tasks = Vec::new();
+        for i in 0..200u32 {
+            let levels = levels.clone();
+            tasks.push(tokio::spawn(async move {
+                if i % 2 == 0 {
+                    levels.toggle(1, 100.0).await;
+                    levels.toggle(1, 100.0).await;
+                } else {
+                    levels.set_level(1000 + i, f64::from(i % 100)).await;
+                }
+            }));
         }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(levels.get_level(1).await, Some(0.0));
+        assert_eq!(levels.get_level(1199).await, Some(99.0));
+        assert_eq!(levels.snapshot().await.len(), 101);
+        assert_eq!(levels.toggle(1, 80.0).await, (Some(0.0), 80.0));
+        assert_eq!(levels.toggle(1, 80.0).await, (Some(80.0), 0.0));
+    }
+
+    #[tokio::test]
+    async fn list_sites_scans_every_site_dir() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-sites-{}", uuid::Uuid::new_v4()));
+        for i in 0..100 {
+            std::fs::create_dir_all(dir.join(format!("site-{:03}", i))).unwrap();
+        }
+        std::fs::write(dir.join("site-001").join("config.toml"), "").unwrap();
+        std::fs::write(dir.join("not-a-site.txt"), "").unwrap();
+
+        let (log_tx, _) = broadcast::channel(4);
+        let state = AppState::new_dev(dir.clone(), false, log_tx, LogHistory::default());
+        *state.active_site.write().await = Some("site-042".to_string());
+
+        let sites = state.list_sites().await;
+        assert_eq!(sites.len(), 100);
+        assert_eq!(sites[0].name, "site-000");
+        assert_eq!(sites[99].name, "site-099");
+        assert!(sites[1].has_config && !sites[0].has_config);
+        assert!(sites[42].active);
+        assert_eq!(sites.iter().filter(|s| s.active).count(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn preview_site_has_no_side_effects() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-sites-{}", uuid::Uuid::new_v4()));
+        let site = dir.join("home");
+        std::fs::create_dir_all(site.join("certs")).unwrap();
+        // Unversioned, so activating would migrate and rewrite it
+        let original = "[processor]\nhost = \"10.0.0.5\"\n\n[[zones]]\nra2_id = 1\nleap_href = \"/zone/5\"\n";
+        std::fs::write(site.join("config.toml"), original).unwrap();
+
+        let (log_tx, _) = broadcast::channel(4);
+        let state = AppState::new_dev(dir.clone(), false, log_tx, LogHistory::default());
+        let preview = state.preview_site("home").await.unwrap();
+        assert!(preview.has_config && !preview.has_certs && !preview.will_autostart);
+        assert_eq!(preview.zone_count, 1);
+
+        for f in ["ra-bridge.crt", "ra-bridge.key", "ca.crt"] {
+            std::fs::write(site.join("certs").join(f), "").unwrap();
+        }
+        assert!(state.preview_site("home").await.unwrap().will_autostart);
+        assert!(state.preview_site("missing").await.is_none());
+
+        assert_eq!(std::fs::read_to_string(site.join("config.toml")).unwrap(), original);
+        assert_eq!(std::fs::read_dir(&site).unwrap().count(), 2);
+        assert!(state.active_site.read().await.is_none());
 
-        sites.sort_by(|a, b| a.name.cmp(&b.name));
-        sites
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
@@ -1,54 +1,192 @@
-use anyhow::Result;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, mpsc};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc, watch};
 use tracing::{info, warn};
 
+use crate::bridge::{BridgeCommand, BridgeMaps, CommandQueue, CommandSource};
+use crate::config::TelnetConfig;
 use crate::ra2_protocol::{self, Ra2Command, Ra2Event};
 
-/// Start the telnet server. Incoming commands are sent on `cmd_tx`.
+/// Times a busy telnet port is tried, e.g. while a previous bridge is still
+/// letting go of it.
+const BIND_ATTEMPTS: u32 = 3;
+const BIND_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest one write to a client may block. A client that stops reading,
+/// or a half-open socket, fills the send buffer; without a limit the writer
+/// task, and the read loop queueing replies behind it, would hang forever.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A bound telnet listener, from `bind`.
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, PathBuf),
+}
+
+/// Bind `telnet.unix_socket` if set, otherwise the TCP port. A port that's
+/// in use is retried a few times before giving up.
+pub async fn bind(telnet: &TelnetConfig) -> Result<Listener> {
+    if let Some(path) = &telnet.unix_socket {
+        return bind_unix(path);
+    }
+
+    let mut attempt = 1;
+    loop {
+        match TcpListener::bind(("0.0.0.0", telnet.port)).await {
+            Ok(listener) => return Ok(Listener::Tcp(listener)),
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && attempt < BIND_ATTEMPTS => {
+                warn!(
+                    "Telnet port {} is in use, retrying in {:?} ({}/{})",
+                    telnet.port, BIND_RETRY_DELAY, attempt, BIND_ATTEMPTS
+                );
+                tokio::time::sleep(BIND_RETRY_DELAY).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to bind telnet port {}", telnet.port));
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &Path) -> Result<Listener> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // A socket left behind by a previous run would make bind fail, but
+    // anything else at the path is someone's file and stays put
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a socket", path.display());
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind {}", path.display()))?;
+    Ok(Listener::Unix(listener, path.to_path_buf()))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(path: &Path) -> Result<Listener> {
+    anyhow::bail!("Unix sockets are not supported on this platform ({})", path.display())
+}
+
+/// Start the telnet server on `listener`. Incoming commands are sent on `cmd_tx`.
 /// Events from LEAP are received on `event_rx` and forwarded to all clients.
-pub async fn run(
-    port: u16,
-    cmd_tx: mpsc::Sender<Ra2Command>,
+/// Commands for ids not in the current `maps` are answered with `~ERROR,2`
+/// instead of being forwarded. Returns once the bridge stops taking commands,
+/// dropping the listener so the next start can bind it again.
+pub async fn serve(
+    listener: Listener,
+    telnet: TelnetConfig,
+    cmd_tx: CommandQueue,
     event_tx: broadcast::Sender<Ra2Event>,
+    maps: watch::Receiver<Arc<BridgeMaps>>,
 ) -> Result<()> {
-    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
-    info!("RA2 telnet server listening on port {}", port);
+    let telnet = Arc::new(telnet);
+    let listener = match listener {
+        Listener::Tcp(listener) => listener,
+        #[cfg(unix)]
+        Listener::Unix(listener, path) => return serve_unix(listener, &path, telnet, cmd_tx, event_tx, maps).await,
+    };
+    info!("RA2 telnet server listening on port {}", telnet.port);
 
     loop {
-        let (stream, addr) = listener.accept().await?;
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = cmd_tx.closed() => {
+                info!("RA2 telnet server on port {} stopped", telnet.port);
+                return Ok(());
+            }
+        };
         info!("Telnet client connected: {}", addr);
 
         let cmd_tx = cmd_tx.clone();
         let event_rx = event_tx.subscribe();
+        let maps = maps.clone();
+        let telnet = telnet.clone();
 
         tokio::spawn(async move {
-            if let Err(e) = handle_client(stream, cmd_tx, event_rx).await {
+            let source = CommandSource::Telnet(addr.to_string());
+            if let Err(e) = handle_client(stream, source, telnet, cmd_tx, event_rx, maps).await {
                 warn!("Client {} disconnected: {}", addr, e);
             }
         });
     }
 }
 
-async fn handle_client(
-    stream: TcpStream,
-    cmd_tx: mpsc::Sender<Ra2Command>,
-    mut event_rx: broadcast::Receiver<Ra2Event>,
+#[cfg(unix)]
+async fn serve_unix(
+    listener: tokio::net::UnixListener,
+    path: &Path,
+    telnet: Arc<TelnetConfig>,
+    cmd_tx: CommandQueue,
+    event_tx: broadcast::Sender<Ra2Event>,
+    maps: watch::Receiver<Arc<BridgeMaps>>,
 ) -> Result<()> {
-    let (reader, mut writer) = stream.into_split();
+    info!("RA2 telnet server listening on {}", path.display());
+
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = cmd_tx.closed() => {
+                info!("RA2 telnet server on {} stopped", path.display());
+                let _ = std::fs::remove_file(path);
+                return Ok(());
+            }
+        };
+        info!("Telnet client connected on {}", path.display());
+
+        let cmd_tx = cmd_tx.clone();
+        let event_rx = event_tx.subscribe();
+        let maps = maps.clone();
+        let telnet = telnet.clone();
+        let source = CommandSource::Telnet(path.display().to_string());
+
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(stream, source, telnet, cmd_tx, event_rx, maps).await {
+                warn!("Unix socket client disconnected: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_client<S>(
+    stream: S,
+    source: CommandSource,
+    config: Arc<TelnetConfig>,
+    cmd_tx: CommandQueue,
+    mut event_rx: broadcast::Receiver<Ra2Event>,
+    maps: watch::Receiver<Arc<BridgeMaps>>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
     let mut reader = BufReader::new(reader);
 
     // Login sequence
-    if !login_flow(&mut reader, &mut writer).await? {
+    let mut telnet = TelnetFilter::default();
+    if !login_flow(&mut reader, &mut writer, &mut telnet, &config.banner).await? {
         return Ok(());
     }
+    let level_decimals = config.level_decimals;
 
     writer.write_all(b"GNET> ").await?;
 
-    // Spawn event writer task
-    let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
-    let write_handle = {
+    // Spawn event writer task. Replies meant only for this client (errors,
+    // the XML export, system commands) go through `reply_tx` already
+    // formatted; an empty reply writes just the prompt. Dropping it shuts
+    // the writer down.
+    let (reply_tx, mut reply_rx) = mpsc::channel::<String>(16);
+    let mut write_handle = {
         let mut writer = writer;
         tokio::spawn(async move {
             loop {
@@ -56,13 +194,10 @@ async fn handle_client(
                     event = event_rx.recv() => {
                         match event {
                             Ok(ev) => {
-                                let formatted = ra2_protocol::format_event(&ev);
+                                let formatted = ra2_protocol::format_event(&ev, level_decimals);
                                 info!("telnet → HA: {}", formatted);
-                                let line = format!("{}\r\n", formatted);
-                                if writer.write_all(line.as_bytes()).await.is_err() {
-                                    break;
-                                }
-                                if writer.write_all(b"GNET> ").await.is_err() {
+                                let line = format!("{}\r\nGNET> ", formatted);
+                                if !write_or_give_up(&mut writer, line.as_bytes()).await {
                                     break;
                                 }
                             }
@@ -72,50 +207,112 @@ async fn handle_client(
                             Err(broadcast::error::RecvError::Closed) => break,
                         }
                     }
-                    _ = shutdown_rx.recv() => break,
+                    reply = reply_rx.recv() => {
+                        let Some(reply) = reply else { break };
+                        let line = if reply.is_empty() {
+                            "GNET> ".to_string()
+                        } else {
+                            format!("{}\r\nGNET> ", reply)
+                        };
+                        if !write_or_give_up(&mut writer, line.as_bytes()).await {
+                            break;
+                        }
+                    }
                 }
             }
             writer
         })
     };
 
-    // Read commands from client
-    let mut line = String::new();
+    // Read commands from client. Negotiation after login is stripped but not
+    // answered; the writer belongs to the event task by now.
     loop {
-        line.clear();
-        let n = reader.read_line(&mut line).await?;
-        if n == 0 {
+        let line = tokio::select! {
+            line = telnet.read_line(&mut reader) => line?,
+            // The writer only stops first when the client stopped reading
+            _ = &mut write_handle => return Ok(()),
+        };
+        let Some(line) = line else {
             break; // Client disconnected
+        };
+        telnet.take_replies();
+
+        if ra2_protocol::is_db_export_request(&line) {
+            let xml = maps.borrow().db_xml.trim_end().replace('\n', "\r\n");
+            info!("HA → telnet: database export ({} bytes)", xml.len());
+            let _ = reply_tx.send(xml).await;
+            continue;
         }
 
         if let Some(cmd) = ra2_protocol::parse_command(&line) {
-            info!("HA → telnet: {:?}", cmd);
-            cmd_tx.send(cmd).await?;
+            info!("HA → telnet [{}]: {:?}", source, cmd);
+            if let Ra2Command::System { raw } = &cmd {
+                let reply = ra2_protocol::system_reply(raw, &crate::web_log_layer::timestamp());
+                let _ = reply_tx.send(reply).await;
+                continue;
+            }
+            if let Some(id) = cmd.id().filter(|id| !maps.borrow().known_ids.contains(id)) {
+                warn!("HA → telnet [{}]: unknown ra2_id {}", source, id);
+                let error = Ra2Event::Error {
+                    code: ra2_protocol::ERROR_OBJECT_NOT_FOUND,
+                };
+                let _ = reply_tx
+                    .send(ra2_protocol::format_event(&error, level_decimals))
+                    .await;
+                continue;
+            }
+            cmd_tx.submit(BridgeCommand {
+                source: source.clone(),
+                cmd,
+            })?;
         } else if !line.trim().is_empty() {
-            warn!("HA → telnet: unparsed line: {:?}", line.trim());
+            warn!("HA → telnet [{}]: unparsed line: {:?}", source, line.trim());
         }
     }
 
-    drop(shutdown_tx);
+    drop(reply_tx);
     let _ = write_handle.await;
     Ok(())
 }
 
-async fn login_flow(
-    reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
-    writer: &mut tokio::net::tcp::OwnedWriteHalf,
-) -> Result<bool> {
+/// Write `buf` within `WRITE_TIMEOUT`. False means the client is gone or
+/// has stopped reading.
+async fn write_or_give_up<W: AsyncWrite + Unpin>(writer: &mut W, buf: &[u8]) -> bool {
+    match tokio::time::timeout(WRITE_TIMEOUT, writer.write_all(buf)).await {
+        Ok(result) => result.is_ok(),
+        Err(_) => {
+            warn!("Telnet client hasn't read anything for {:?}, disconnecting it", WRITE_TIMEOUT);
+            false
+        }
+    }
+}
+
+async fn login_flow<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    telnet: &mut TelnetFilter,
+    banner: &str,
+) -> Result<bool>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    if !banner.is_empty() {
+        let banner = banner.trim_end_matches(['\r', '\n']).replace("\r\n", "\n").replace('\n', "\r\n");
+        writer.write_all(format!("{}\r\n", banner).as_bytes()).await?;
+    }
+
     // Send login prompt
     writer.write_all(b"login: ").await?;
 
-    let mut line = String::new();
-    reader.read_line(&mut line).await?;
-    let username = line.trim().to_string();
-    line.clear();
+    let username = telnet.read_line(reader).await?.unwrap_or_default();
+    let username = username.trim().to_string();
+    writer.write_all(&telnet.take_replies()).await?;
 
     writer.write_all(b"password: ").await?;
-    reader.read_line(&mut line).await?;
-    let password = line.trim().to_string();
+    let password = telnet.read_line(reader).await?.unwrap_or_default();
+    let password = password.trim().to_string();
+    writer.write_all(&telnet.take_replies()).await?;
 
     if username == "lutron" && password == "integration" {
         info!("Client authenticated successfully");
@@ -129,3 +326,307 @@ async fn login_flow(
         Ok(false)
     }
 }
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const OPT_SGA: u8 = 3;
+
+#[derive(Default, Clone, Copy)]
+enum IacState {
+    #[default]
+    Data,
+    Iac,
+    Verb(u8),
+    Sub,
+    SubIac,
+}
+
+/// Strips telnet IAC option negotiation out of the client's byte stream so
+/// standard telnet clients don't corrupt the login and command lines. We
+/// agree to suppress-go-ahead and refuse every other option (so the client
+/// keeps echoing locally).
+#[derive(Default)]
+struct TelnetFilter {
+    state: IacState,
+    replies: Vec<u8>,
+    /// The last line ended on `\r`, so a `\n` or NUL right after it is the
+    /// rest of that terminator rather than an empty line.
+    after_cr: bool,
+}
+
+impl TelnetFilter {
+    /// Read one line with IAC sequences removed, without its terminator.
+    /// Lines end at `\r`, `\n` or `\r\n`: RA2 controllers send CRLF, and some
+    /// separate several commands with bare CRs in one write. Bytes after the
+    /// terminator stay buffered for the next call. Returns `None` at EOF with
+    /// nothing buffered.
+    async fn read_line<R: AsyncBufRead + Unpin>(&mut self, reader: &mut R) -> Result<Option<String>> {
+        let mut line = Vec::new();
+        loop {
+            let buf = reader.fill_buf().await?;
+            if buf.is_empty() {
+                return Ok((!line.is_empty()).then(|| String::from_utf8_lossy(&line).into_owned()));
+            }
+            let mut used = 0;
+            let mut done = false;
+            for &b in buf {
+                used += 1;
+                let Some(b) = self.feed(b) else { continue };
+                let after_cr = std::mem::take(&mut self.after_cr);
+                match b {
+                    b'\r' => {
+                        self.after_cr = true;
+                        done = true;
+                        break;
+                    }
+                    b'\n' | b'\0' if after_cr => {}
+                    b'\n' => {
+                        done = true;
+                        break;
+                    }
+                    _ => line.push(b),
+                }
+            }
+            reader.consume(used);
+            if done {
+                return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+            }
+        }
+    }
+
+    /// Negotiation replies queued since the last call.
+    fn take_replies(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.replies)
+    }
+
+    /// Advance the IAC state machine; returns the byte if it is line data.
+    fn feed(&mut self, b: u8) -> Option<u8> {
+        match self.state {
+            IacState::Data if b == IAC => self.state = IacState::Iac,
+            IacState::Data => return Some(b),
+            IacState::Iac => {
+                self.state = match b {
+                    IAC => {
+                        self.state = IacState::Data;
+                        return Some(IAC);
+                    }
+                    WILL | WONT | DO | DONT => IacState::Verb(b),
+                    SB => IacState::Sub,
+                    _ => IacState::Data, // NOP, GA, AYT, ... carry no option
+                }
+            }
+            IacState::Verb(verb) => {
+                self.state = IacState::Data;
+                self.reply(verb, b);
+            }
+            IacState::Sub if b == IAC => self.state = IacState::SubIac,
+            IacState::Sub => {}
+            IacState::SubIac => {
+                self.state = if b == SE { IacState::Data } else { IacState::Sub };
+            }
+        }
+        None
+    }
+
+    fn reply(&mut self, verb: u8, option: u8) {
+        let answer = match (verb, option) {
+            (DO, OPT_SGA) => WILL,
+            (WILL, OPT_SGA) => DO,
+            (DO, _) => WONT,
+            (WILL, _) => DONT,
+            // Every option but SGA is already off, so only SGA needs an answer
+            (WONT, OPT_SGA) => DONT,
+            (DONT, OPT_SGA) => WONT,
+            _ => return,
+        };
+        self.replies.extend_from_slice(&[IAC, answer, option]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPT_ECHO: u8 = 1;
+    const OPT_TTYPE: u8 = 24;
+
+    #[tokio::test]
+    async fn strips_negotiation_before_login() {
+        let mut input = vec![
+            IAC, DO, OPT_SGA, IAC, WILL, OPT_TTYPE, IAC, DO, OPT_ECHO,
+            IAC, SB, OPT_TTYPE, 1, IAC, SE,
+        ];
+        input.extend_from_slice(b"lutron\r\n");
+        input.extend_from_slice(&[IAC, WONT, OPT_ECHO]);
+        input.extend_from_slice(b"integration\r\n");
+
+        let mut reader = input.as_slice();
+        let mut writer = Vec::new();
+        let mut telnet = TelnetFilter::default();
+        assert!(login_flow(&mut reader, &mut writer, &mut telnet, "").await.unwrap());
+
+        let mut expected = b"login: ".to_vec();
+        expected.extend_from_slice(&[IAC, WILL, OPT_SGA, IAC, DONT, OPT_TTYPE, IAC, WONT, OPT_ECHO]);
+        expected.extend_from_slice(b"password: ");
+        assert_eq!(writer, expected);
+    }
+
+    #[tokio::test]
+    async fn configured_banner_is_sent_first() {
+        let mut reader = &b"lutron\r\nintegration\r\n"[..];
+        let mut writer = Vec::new();
+        let mut telnet = TelnetFilter::default();
+        let banner = "Lutron RadioRA 2\nMain Repeater\n";
+        assert!(login_flow(&mut reader, &mut writer, &mut telnet, banner).await.unwrap());
+        assert_eq!(writer, b"Lutron RadioRA 2\r\nMain Repeater\r\nlogin: password: ");
+    }
+
+    #[tokio::test]
+    async fn escaped_iac_and_split_reads() {
+        let mut telnet = TelnetFilter::default();
+        let mut reader = BufReader::with_capacity(2, &[b'#', IAC, IAC, b'x', IAC, DO][..]);
+        let line = telnet.read_line(&mut reader).await.unwrap().unwrap();
+        assert_eq!(line.as_bytes(), "#\u{FFFD}x".as_bytes());
+
+        // A sequence split across reads still resolves
+        let mut reader = &[OPT_SGA, b'o', b'k', b'\n'][..];
+        assert_eq!(telnet.read_line(&mut reader).await.unwrap().as_deref(), Some("ok"));
+        assert_eq!(telnet.take_replies(), vec![IAC, WILL, OPT_SGA]);
+    }
+
+    #[tokio::test]
+    async fn several_commands_in_one_read() {
+        let mut telnet = TelnetFilter::default();
+        let mut reader = &b"#OUTPUT,5,1,50\r\n?OUTPUT,5,1\r\n"[..];
+        let mut commands = Vec::new();
+        while let Some(line) = telnet.read_line(&mut reader).await.unwrap() {
+            commands.extend(ra2_protocol::parse_command(&line));
+        }
+        assert_eq!(commands.len(), 2);
+        assert!(matches!(commands[0], Ra2Command::SetOutput { id: 5, .. }));
+        assert!(matches!(commands[1], Ra2Command::QueryOutput { id: 5 }));
+
+        // Bare CRs, bare LFs, telnet CR NUL and blank lines all split cleanly
+        let mut reader = &b"a\rb\nc\r\0\r\n\nd"[..];
+        let mut lines = Vec::new();
+        while let Some(line) = telnet.read_line(&mut reader).await.unwrap() {
+            lines.push(line);
+        }
+        assert_eq!(lines, vec!["a", "b", "c", "", "", "d"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn client_that_stops_reading_is_dropped() {
+        let config: crate::config::Config = toml::from_str(
+            "[processor]\nhost = \"10.0.0.5\"\n[[zones]]\nra2_id = 1\nleap_href = \"/zone/5\"\nname = \"Kitchen\"",
+        )
+        .unwrap();
+        let (_maps_tx, maps) = watch::channel(Arc::new(BridgeMaps::from_config(&config)));
+        let (cmd_tx, _cmd_rx) = CommandQueue::new(8, Arc::default());
+        let (event_tx, event_rx) = broadcast::channel(16);
+
+        // Room for the login prompts and not much more; the client never
+        // reads, so the writer soon blocks on a full buffer
+        let (mut client, server) = tokio::io::duplex(64);
+        client.write_all(b"lutron\r\nintegration\r\n").await.unwrap();
+        let started = tokio::time::Instant::now();
+        let handle = tokio::spawn(handle_client(
+            server,
+            CommandSource::Telnet("stalled".to_string()),
+            Arc::new(TelnetConfig::default()),
+            cmd_tx,
+            event_rx,
+            maps,
+        ));
+        for level in 0..10 {
+            event_tx.send(Ra2Event::OutputLevel { id: 1, level: f64::from(level) }).unwrap();
+        }
+
+        tokio::time::timeout(WRITE_TIMEOUT * 2, handle)
+            .await
+            .expect("handle_client still waiting on a stalled writer")
+            .unwrap()
+            .unwrap();
+        assert!(started.elapsed() >= WRITE_TIMEOUT);
+        drop(client);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn serves_clients_on_unix_socket() {
+        let path = std::env::temp_dir().join(format!("ra-bridge-{}.sock", uuid::Uuid::new_v4()));
+        // A socket left behind by a previous run must not block the bind
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let config: crate::config::Config = toml::from_str(
+            "[processor]\nhost = \"10.0.0.5\"\n[[zones]]\nra2_id = 1\nleap_href = \"/zone/5\"\nname = \"Kitchen\"",
+        )
+        .unwrap();
+        let (_maps_tx, maps) = watch::channel(Arc::new(BridgeMaps::from_config(&config)));
+        let (cmd_tx, mut cmd_rx) = CommandQueue::new(8, Arc::default());
+        let (event_tx, _) = broadcast::channel(8);
+        let telnet = TelnetConfig {
+            unix_socket: Some(path.clone()),
+            ..Default::default()
+        };
+        let listener = bind(&telnet).await.unwrap();
+        tokio::spawn(serve(listener, telnet, cmd_tx, event_tx, maps));
+
+        let stream = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                if let Ok(s) = tokio::net::UnixStream::connect(&path).await {
+                    return s;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let (reader, mut writer) = stream.into_split();
+        writer.write_all(b"lutron\r\nintegration\r\n#OUTPUT,1,1,50\r\n").await.unwrap();
+        let cmd = tokio::time::timeout(std::time::Duration::from_secs(5), cmd_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cmd.cmd.id(), Some(1));
+        assert_eq!(cmd.source, CommandSource::Telnet(path.display().to_string()));
+
+        writer.write_all(b"GETDBXML\r\n").await.unwrap();
+        let mut reader = BufReader::new(reader);
+        let mut received = String::new();
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while !received.contains("</Project>\r\n") {
+                reader.read_line(&mut received).await.unwrap();
+            }
+        })
+        .await
+        .unwrap();
+        let xml = &received[received.find("<?xml").unwrap()..];
+        assert!(xml.contains("IntegrationID=\"1\""), "{}", xml);
+        assert!(!xml.replace("\r\n", "").contains('\n'));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_socket_never_replaces_a_regular_file() {
+        let path = std::env::temp_dir().join(format!("ra-bridge-{}.sock", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "keep me").unwrap();
+
+        let telnet = TelnetConfig {
+            unix_socket: Some(path.clone()),
+            ..Default::default()
+        };
+        assert!(bind(&telnet).await.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -2,46 +2,16 @@ use crate::id_map::IdMap;
 use crate::leap_client::{LeapEvent, LeapHeader, LeapRequest};
 use crate::ra2_protocol::{Ra2Command, Ra2Event};
 
-/// Translate an RA2 command into a LEAP request.
-pub fn ra2_to_leap(cmd: &Ra2Command, map: &IdMap) -> Option<LeapRequest> {
+/// Translate an RA2 command into a LEAP request. `default_fade` applies to
+/// set-level commands that don't carry their own fade; switched zones are
+/// never faded.
+pub fn ra2_to_leap(cmd: &Ra2Command, map: &IdMap, default_fade: Option<f64>) -> Option<LeapRequest> {
     match cmd {
         Ra2Command::SetOutput { id, level, fade } => {
             let href = map.ra2_to_leap(*id)?;
-            let url = format!("{}/commandprocessor", href);
-
-            let body = if let Some(fade_time) = fade {
-                let fade_str = format!("{:02}:{:02}:{:02}",
-                    (*fade_time as u64) / 3600,
-                    ((*fade_time as u64) % 3600) / 60,
-                    (*fade_time as u64) % 60,
-                );
-                serde_json::json!({
-                    "Command": {
-                        "CommandType": "GoToDimmedLevel",
-                        "DimmedLevelParameters": {
-                            "Level": level,
-                            "FadeTime": fade_str,
-                        }
-                    }
-                })
-            } else {
-                serde_json::json!({
-                    "Command": {
-                        "CommandType": "GoToLevel",
-                        "Parameter": [{"Type": "Level", "Value": level}]
-                    }
-                })
-            };
-
-            Some(LeapRequest {
-                communique_type: "CreateRequest".to_string(),
-                header: LeapHeader {
-                    url,
-                    client_tag: None,
-                    extra: serde_json::Map::new(),
-                },
-                body: Some(body),
-            })
+            let level = map.curve(*id).to_backend(*level);
+            let fade = fade.or(default_fade).filter(|_| map.accepts_fade(*id));
+            Some(set_level_request(href, level, fade))
         }
         Ra2Command::QueryOutput { id } => {
             let href = map.ra2_to_leap(*id)?;
@@ -61,11 +31,51 @@ pub fn ra2_to_leap(cmd: &Ra2Command, map: &IdMap) -> Option<LeapRequest> {
             // Monitoring commands are handled locally (we already subscribe to all zone events)
             None
         }
+        // Answered by the telnet server
+        Ra2Command::System { .. } => None,
+    }
+}
+
+/// A LEAP command setting `href` to a backend `level`; with a fade it's a
+/// `GoToDimmedLevel`, otherwise a plain `GoToLevel`.
+pub fn set_level_request(href: &str, level: f64, fade: Option<f64>) -> LeapRequest {
+    let body = if let Some(fade_time) = fade {
+        let secs = fade_time.round() as u64;
+        let fade_str = format!("{:02}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60);
+        serde_json::json!({
+            "Command": {
+                "CommandType": "GoToDimmedLevel",
+                "DimmedLevelParameters": {
+                    "Level": level,
+                    "FadeTime": fade_str,
+                }
+            }
+        })
+    } else {
+        serde_json::json!({
+            "Command": {
+                "CommandType": "GoToLevel",
+                "Parameter": [{"Type": "Level", "Value": level}]
+            }
+        })
+    };
+
+    LeapRequest {
+        communique_type: "CreateRequest".to_string(),
+        header: LeapHeader {
+            url: format!("{}/commandprocessor", href),
+            client_tag: None,
+            extra: serde_json::Map::new(),
+        },
+        body: Some(body),
     }
 }
 
 /// Translate a LEAP event into an RA2 event.
 pub fn leap_to_ra2(event: &LeapEvent, map: &IdMap) -> Option<Ra2Event> {
+    if let Some(led_status) = event.body.get("LEDStatus") {
+        return led_to_ra2(led_status, map);
+    }
     let zone_status = event.body.get("ZoneStatus")?;
     let level = zone_status.get("Level")?.as_f64()?;
 
@@ -93,7 +103,19 @@ pub fn leap_to_ra2(event: &LeapEvent, map: &IdMap) -> Option<Ra2Event> {
 
     Some(Ra2Event::OutputLevel {
         id: ra2_id,
-        level,
+        level: map.curve(ra2_id).to_ra2(level),
+    })
+}
+
+/// `{"LED": {"href": "/led/412"}, "State": "On"}` → `~DEVICE` LED state.
+fn led_to_ra2(led_status: &serde_json::Value, map: &IdMap) -> Option<Ra2Event> {
+    let href = led_status.get("LED")?.get("href")?.as_str()?;
+    let state = led_status.get("State")?.as_str()?;
+    let (id, component) = map.led_to_ra2(href)?;
+    Some(Ra2Event::DeviceButton {
+        id,
+        component,
+        led_on: state.eq_ignore_ascii_case("On"),
     })
 }
 
@@ -101,6 +123,7 @@ pub fn leap_to_ra2(event: &LeapEvent, map: &IdMap) -> Option<Ra2Event> {
 mod tests {
     use super::*;
     use crate::config::ZoneMapping;
+    use crate::level_curve::LevelCurve;
 
     fn test_map() -> IdMap {
         IdMap::from_zones(&[
@@ -108,13 +131,22 @@ mod tests {
                 ra2_id: 1,
                 leap_href: "/zone/5".to_string(),
                 name: "Kitchen".to_string(),
+                ..Default::default()
             },
             ZoneMapping {
                 ra2_id: 2,
                 leap_href: "/zone/8".to_string(),
                 name: "Living Room".to_string(),
+                ..Default::default()
             },
-        ])
+            ZoneMapping {
+                ra2_id: 3,
+                leap_href: "/zone/9".to_string(),
+                name: "Exhaust Fan".to_string(),
+                control_type: Some("Switched".to_string()),
+                ..Default::default()
+            },
+        ], &LevelCurve::Linear)
     }
 
     #[test]
@@ -125,7 +157,7 @@ mod tests {
             level: 75.0,
             fade: None,
         };
-        let req = ra2_to_leap(&cmd, &map).unwrap();
+        let req = ra2_to_leap(&cmd, &map, None).unwrap();
         assert_eq!(req.communique_type, "CreateRequest");
         assert_eq!(req.header.url, "/zone/5/commandprocessor");
         let body = req.body.unwrap();
@@ -133,11 +165,51 @@ mod tests {
         assert_eq!(body["Command"]["Parameter"][0]["Value"], 75.0);
     }
 
+    #[test]
+    fn default_fade_applies_unless_command_has_one() {
+        let map = test_map();
+        let cmd = Ra2Command::SetOutput {
+            id: 1,
+            level: 50.0,
+            fade: None,
+        };
+        let body = ra2_to_leap(&cmd, &map, Some(1.0)).unwrap().body.unwrap();
+        assert_eq!(body["Command"]["CommandType"], "GoToDimmedLevel");
+        assert_eq!(body["Command"]["DimmedLevelParameters"]["FadeTime"], "00:00:01");
+
+        let cmd = Ra2Command::SetOutput {
+            id: 1,
+            level: 50.0,
+            fade: Some(90.0),
+        };
+        let body = ra2_to_leap(&cmd, &map, Some(1.0)).unwrap().body.unwrap();
+        assert_eq!(body["Command"]["DimmedLevelParameters"]["FadeTime"], "00:01:30");
+
+        // Rounded to the nearest second rather than cut short
+        let cmd = Ra2Command::SetOutput {
+            id: 1,
+            level: 50.0,
+            fade: Some(1.6),
+        };
+        let body = ra2_to_leap(&cmd, &map, None).unwrap().body.unwrap();
+        assert_eq!(body["Command"]["DimmedLevelParameters"]["FadeTime"], "00:00:02");
+    }
+
+    #[test]
+    fn switched_zones_are_never_faded() {
+        let map = test_map();
+        for fade in [None, Some(2.0)] {
+            let cmd = Ra2Command::SetOutput { id: 3, level: 100.0, fade };
+            let body = ra2_to_leap(&cmd, &map, Some(1.0)).unwrap().body.unwrap();
+            assert_eq!(body["Command"]["CommandType"], "GoToLevel");
+        }
+    }
+
     #[test]
     fn translate_query_output() {
         let map = test_map();
         let cmd = Ra2Command::QueryOutput { id: 2 };
-        let req = ra2_to_leap(&cmd, &map).unwrap();
+        let req = ra2_to_leap(&cmd, &map, None).unwrap();
         assert_eq!(req.communique_type, "ReadRequest");
         assert_eq!(req.header.url, "/zone/8/status");
     }
@@ -150,6 +222,7 @@ mod tests {
             header: crate::leap_client::LeapEventHeader {
                 url: "/zone/5/status".to_string(),
                 status_code: Some("200".to_string()),
+                client_tag: None,
                 extra: serde_json::Map::new(),
             },
             body: serde_json::json!({
@@ -169,6 +242,78 @@ mod tests {
         );
     }
 
+    #[test]
+    fn level_curve_round_trip() {
+        let map = IdMap::from_zones(
+            &[ZoneMapping {
+                ra2_id: 1,
+                leap_href: "/zone/5".to_string(),
+                name: "Kitchen".to_string(),
+                level_curve: Some(LevelCurve::Square),
+                ..Default::default()
+            }],
+            &LevelCurve::Linear,
+        );
+        let cmd = Ra2Command::SetOutput {
+            id: 1,
+            level: 50.0,
+            fade: None,
+        };
+        let req = ra2_to_leap(&cmd, &map, None).unwrap();
+        let sent = req.body.unwrap()["Command"]["Parameter"][0]["Value"]
+            .as_f64()
+            .unwrap();
+        assert_eq!(sent, 25.0);
+
+        let event = LeapEvent {
+            communique_type: "ReadResponse".to_string(),
+            header: crate::leap_client::LeapEventHeader {
+                url: "/zone/5/status".to_string(),
+                status_code: Some("200".to_string()),
+                client_tag: None,
+                extra: serde_json::Map::new(),
+            },
+            body: serde_json::json!({
+                "ZoneStatus": { "Level": sent, "Zone": {"href": "/zone/5"} }
+            }),
+        };
+        assert_eq!(
+            leap_to_ra2(&event, &map),
+            Some(Ra2Event::OutputLevel { id: 1, level: 50.0 })
+        );
+    }
+
+    #[test]
+    fn translate_led_status_event() {
+        let map = test_map().with_buttons(&[crate::config::ButtonMapping {
+            ra2_id: 20,
+            component: 81,
+            led_href: "/led/412".to_string(),
+            name: "Entry Scene".to_string(),
+        }]);
+        let led_event = |href: &str, state: &str| LeapEvent {
+            communique_type: "ReadResponse".to_string(),
+            header: crate::leap_client::LeapEventHeader {
+                url: format!("{}/status", href),
+                status_code: Some("200 OK".to_string()),
+                client_tag: None,
+                extra: serde_json::Map::new(),
+            },
+            body: serde_json::json!({
+                "LEDStatus": { "LED": {"href": href}, "State": state }
+            }),
+        };
+        assert_eq!(
+            leap_to_ra2(&led_event("/led/412", "On"), &map),
+            Some(Ra2Event::DeviceButton { id: 20, component: 81, led_on: true })
+        );
+        assert_eq!(
+            leap_to_ra2(&led_event("/led/412", "Off"), &map),
+            Some(Ra2Event::DeviceButton { id: 20, component: 81, led_on: false })
+        );
+        assert_eq!(leap_to_ra2(&led_event("/led/999", "On"), &map), None);
+    }
+
     #[test]
     fn unknown_id_returns_none() {
         let map = test_map();
@@ -177,6 +322,6 @@ mod tests {
             level: 50.0,
             fade: None,
         };
-        assert!(ra2_to_leap(&cmd, &map).is_none());
+        assert!(ra2_to_leap(&cmd, &map, None).is_none());
     }
 }
@@ -0,0 +1,105 @@
+//! The API's error body: `{"error": {"code": "...", "message": "..."}}`.
+//! Clients branch on `code`, which is stable and fixes the HTTP status;
+//! `message` is for people and may change.
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// The request itself is malformed, e.g. a bad site name.
+    InvalidRequest,
+    /// A config (or CSV, or profile) failed to parse or validate.
+    InvalidConfig,
+    /// No config is loaded yet.
+    NoConfig,
+    /// The processor hasn't been paired, so there's nothing to work from.
+    NotPaired,
+    BridgeRunning,
+    BridgeNotRunning,
+    ZoneNotFound,
+    /// A zone name matched more than one zone.
+    AmbiguousZone,
+    /// Any other missing resource: a site, or a feature that's switched off.
+    NotFound,
+    AlreadyExists,
+    /// Pairing, discovery or another exclusive operation is under way.
+    Busy,
+    RateLimited,
+    ReadOnly,
+    /// The endpoint needs `[web] api_token`, which isn't set.
+    ApiDisabled,
+    Unauthorized,
+    /// The processor or Savant host failed or dropped the connection.
+    BackendError,
+    /// The processor or Savant host didn't answer in time.
+    BackendTimeout,
+    SaveFailed,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidRequest | ErrorCode::InvalidConfig => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::ReadOnly | ErrorCode::ApiDisabled => StatusCode::FORBIDDEN,
+            ErrorCode::ZoneNotFound | ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::NoConfig
+            | ErrorCode::NotPaired
+            | ErrorCode::BridgeRunning
+            | ErrorCode::BridgeNotRunning
+            | ErrorCode::AmbiguousZone
+            | ErrorCode::AlreadyExists
+            | ErrorCode::Busy => StatusCode::CONFLICT,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::BackendError => StatusCode::BAD_GATEWAY,
+            ErrorCode::BackendTimeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::SaveFailed | ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// An error response with `code`'s status.
+pub fn api_error(code: ErrorCode, message: impl std::fmt::Display) -> Response {
+    api_error_with(code, message, serde_json::json!({}))
+}
+
+/// `api_error` with the fields of `extra` next to `error`, e.g. the zones an
+/// ambiguous name matched.
+pub fn api_error_with(code: ErrorCode, message: impl std::fmt::Display, extra: serde_json::Value) -> Response {
+    let mut body = serde_json::json!({
+        "error": { "code": code, "message": message.to_string() },
+    });
+    if let (Some(body), serde_json::Value::Object(extra)) = (body.as_object_mut(), extra) {
+        body.extend(extra);
+    }
+    (code.status(), Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn errors_carry_code_message_and_extras() {
+        let resp = api_error_with(
+            ErrorCode::AmbiguousZone,
+            "'hall' matches 2 zones",
+            serde_json::json!({ "matches": [1, 2] }),
+        );
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "error": { "code": "AMBIGUOUS_ZONE", "message": "'hall' matches 2 zones" },
+                "matches": [1, 2],
+            })
+        );
+        assert_eq!(api_error(ErrorCode::NotPaired, "pair first").status(), StatusCode::CONFLICT);
+    }
+}
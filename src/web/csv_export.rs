@@ -0,0 +1,299 @@
+//! Zone mappings as CSV, for installers who keep their id plan in a
+//! spreadsheet. Rows are matched back to configured zones by LEAP href or
+//! Savant address + load offset; import only changes ra2_ids and names.
+
+use std::collections::HashMap;
+
+use crate::config::Config;
+
+use super::xml_export::{leap_area, savant_area};
+
+pub const HEADER: [&str; 6] = ["ra2_id", "backend", "href_or_address", "load_offset", "area", "name"];
+
+/// The zone a CSV row refers to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ZoneKey {
+    Leap { href: String },
+    Savant { address: String, load_offset: usize },
+}
+
+impl std::fmt::Display for ZoneKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZoneKey::Leap { href } => write!(f, "LEAP zone {}", href),
+            ZoneKey::Savant { address, load_offset } => write!(f, "Savant load {}:{}", address, load_offset),
+        }
+    }
+}
+
+/// One data row of an imported CSV. `line` is 1-based, counting the header.
+#[derive(Debug, PartialEq)]
+pub struct CsvRow {
+    pub line: usize,
+    pub ra2_id: u32,
+    pub zone: ZoneKey,
+    pub name: String,
+}
+
+/// Every LEAP then Savant zone, one per row. `area` is informational: it's
+/// derived from the name (or Savant room) and ignored on import.
+pub fn generate_csv(config: &Config) -> String {
+    let mut out = HEADER.join(",");
+    out.push_str("\r\n");
+    for z in &config.zones {
+        let (area, _) = leap_area(z, config.web.area_separator);
+        push_row(&mut out, &[&z.ra2_id.to_string(), "leap", &z.leap_href, "", &area, &z.name]);
+    }
+    for z in &config.savant_zones {
+        let (area, _) = savant_area(z, config.web.area_separator);
+        let offset = z.load_offset.to_string();
+        push_row(&mut out, &[&z.ra2_id.to_string(), "savant", &z.address, &offset, &area, &z.name]);
+    }
+    out
+}
+
+fn push_row(out: &mut String, fields: &[&str]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\r', '\n']) || field.trim() != *field {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+/// Split one line into fields. Quoted fields may hold commas and `""`, but
+/// not line breaks.
+fn split_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (false, ',') => fields.push(std::mem::take(&mut field)),
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quote".to_string());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+/// Parse an uploaded CSV. The header must name the `HEADER` columns in
+/// order; blank lines are skipped. Every malformed row is reported, each
+/// prefixed with its line number.
+pub fn parse_csv(text: &str) -> Result<Vec<CsvRow>, Vec<String>> {
+    let mut lines = text.lines().enumerate().map(|(i, l)| (i + 1, l));
+    let header = lines
+        .by_ref()
+        .find(|(_, l)| !l.trim().is_empty())
+        .map(|(n, l)| (n, split_line(l.trim_start_matches('\u{feff}'))));
+    match header {
+        Some((_, Ok(fields)))
+            if fields.iter().map(|f| f.trim().to_ascii_lowercase()).eq(HEADER.iter().map(|h| h.to_string())) => {}
+        Some((n, _)) => return Err(vec![format!("line {}: header must be {}", n, HEADER.join(","))]),
+        None => return Err(vec!["CSV is empty".to_string()]),
+    }
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for (line, text) in lines.filter(|(_, l)| !l.trim().is_empty()) {
+        match parse_row(text) {
+            Ok((ra2_id, zone, name)) => rows.push(CsvRow { line, ra2_id, zone, name }),
+            Err(e) => errors.push(format!("line {}: {}", line, e)),
+        }
+    }
+    if errors.is_empty() {
+        Ok(rows)
+    } else {
+        Err(errors)
+    }
+}
+
+fn parse_row(line: &str) -> Result<(u32, ZoneKey, String), String> {
+    let fields = split_line(line)?;
+    if fields.len() != HEADER.len() {
+        return Err(format!("expected {} columns, found {}", HEADER.len(), fields.len()));
+    }
+    let field = |i: usize| fields[i].trim();
+    let ra2_id: u32 = field(0)
+        .parse()
+        .map_err(|_| format!("ra2_id '{}' is not a number", field(0)))?;
+    if ra2_id == 0 {
+        return Err("ra2_id must be at least 1".to_string());
+    }
+    if field(2).is_empty() {
+        return Err("href_or_address is empty".to_string());
+    }
+    let zone = match field(1).to_ascii_lowercase().as_str() {
+        "leap" => ZoneKey::Leap {
+            href: field(2).to_string(),
+        },
+        "savant" => ZoneKey::Savant {
+            address: field(2).to_string(),
+            load_offset: field(3)
+                .parse()
+                .map_err(|_| format!("load_offset '{}' is not a number", field(3)))?,
+        },
+        other => return Err(format!("backend '{}' must be leap or savant", other)),
+    };
+    Ok((ra2_id, zone, field(5).to_string()))
+}
+
+/// Apply imported rows to `config`, returning how many zones changed. Rows
+/// for zones that aren't configured, a zone listed twice, or an ra2_id used
+/// by two rows are all rejected with their line numbers; the config is left
+/// untouched unless every row applies.
+pub fn apply_csv(config: &mut Config, rows: &[CsvRow]) -> Result<usize, Vec<String>> {
+    let mut errors = Vec::new();
+    let mut seen_zones: HashMap<&ZoneKey, usize> = HashMap::new();
+    let mut seen_ids: HashMap<u32, usize> = HashMap::new();
+    for row in rows {
+        if let Some(first) = seen_zones.insert(&row.zone, row.line) {
+            errors.push(format!("line {}: same zone as line {}", row.line, first));
+        }
+        if let Some(first) = seen_ids.insert(row.ra2_id, row.line) {
+            errors.push(format!("line {}: ra2_id {} is also used on line {}", row.line, row.ra2_id, first));
+        }
+    }
+
+    let mut updated = config.clone();
+    let mut changed = 0;
+    for row in rows {
+        let target = match &row.zone {
+            ZoneKey::Leap { href } => updated
+                .zones
+                .iter_mut()
+                .find(|z| &z.leap_href == href)
+                .map(|z| (&mut z.ra2_id, &mut z.name)),
+            ZoneKey::Savant { address, load_offset } => updated
+                .savant_zones
+                .iter_mut()
+                .find(|z| z.address.eq_ignore_ascii_case(address) && z.load_offset == *load_offset)
+                .map(|z| (&mut z.ra2_id, &mut z.name)),
+        };
+        let Some((ra2_id, name)) = target else {
+            errors.push(format!("line {}: no configured {}", row.line, row.zone));
+            continue;
+        };
+        if *ra2_id != row.ra2_id || *name != row.name {
+            *ra2_id = row.ra2_id;
+            *name = row.name.clone();
+            changed += 1;
+        }
+    }
+
+    if errors.is_empty() {
+        // Ids from the CSV may still collide with zones it didn't list
+        if let Err(e) = updated.validate() {
+            errors.push(e);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    *config = updated;
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SavantZoneMapping, ZoneMapping};
+
+    fn config() -> Config {
+        let mut config: Config = toml::from_str("[processor]\nhost = \"10.0.0.5\"").unwrap();
+        config.zones = vec![
+            ZoneMapping {
+                ra2_id: 1,
+                leap_href: "/zone/5".to_string(),
+                name: "Kitchen \u{2500} Main, North".to_string(),
+                ..Default::default()
+            },
+            ZoneMapping {
+                ra2_id: 2,
+                leap_href: "/zone/6".to_string(),
+                name: "Hall".to_string(),
+                ..Default::default()
+            },
+        ];
+        config.savant_zones = vec![SavantZoneMapping {
+            ra2_id: 200,
+            address: "01A".to_string(),
+            load_offset: 3,
+            name: "Den \"Lamp\"".to_string(),
+            room: "Den".to_string(),
+            ..Default::default()
+        }];
+        config
+    }
+
+    #[test]
+    fn export_round_trips_through_import() {
+        let mut config = config();
+        let csv = generate_csv(&config);
+        assert!(csv.contains("1,leap,/zone/5,,Kitchen,\"Kitchen \u{2500} Main, North\"\r\n"));
+        assert!(csv.contains("200,savant,01A,3,Den,\"Den \"\"Lamp\"\"\"\r\n"));
+
+        let rows = parse_csv(&csv).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(apply_csv(&mut config, &rows), Ok(0));
+
+        let edited = csv.replace("2,leap,/zone/6,,Ungrouped,Hall", "7,leap,/zone/6,,,Hallway");
+        assert_eq!(apply_csv(&mut config, &parse_csv(&edited).unwrap()), Ok(1));
+        assert_eq!((config.zones[1].ra2_id, config.zones[1].name.as_str()), (7, "Hallway"));
+    }
+
+    #[test]
+    fn bad_rows_are_reported_by_line() {
+        let header = HEADER.join(",");
+        let errors = parse_csv(&format!("{}\n1,leap,/zone/5,,,A\n\nx,leap,/zone/6,,,B\n3,knx,1,,,C\n4,savant,01A\n", header))
+            .unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("line 4: ra2_id 'x'"));
+        assert!(errors[1].starts_with("line 5: backend 'knx'"));
+        assert!(errors[2].starts_with("line 6: expected 6 columns"));
+        assert!(parse_csv("id,name\n1,A").unwrap_err()[0].starts_with("line 1: header"));
+
+        let mut config = config();
+        let rows = parse_csv(&format!(
+            "{}\n5,leap,/zone/5,,,A\n5,leap,/zone/6,,,B\n9,leap,/zone/6,,,C\n9,leap,/zone/99,,,D\n",
+            header
+        ))
+        .unwrap();
+        let errors = apply_csv(&mut config, &rows).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "line 3: ra2_id 5 is also used on line 2",
+                "line 4: same zone as line 3",
+                "line 5: ra2_id 9 is also used on line 4",
+                "line 5: no configured LEAP zone /zone/99",
+            ]
+        );
+
+        // Colliding with a zone the CSV didn't mention fails validation
+        let rows = parse_csv(&format!("{}\n200,leap,/zone/5,,,A\n", header)).unwrap();
+        assert!(apply_csv(&mut config, &rows).unwrap_err()[0].contains("200"));
+        assert_eq!(config.zones[0].ra2_id, 1);
+    }
+}
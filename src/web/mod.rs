@@ -1,44 +1,89 @@
+pub mod api_error;
+pub mod csv_export;
 pub mod routes;
 pub mod sse;
 pub mod xml_export;
 
+use std::path::PathBuf;
 use std::sync::Arc;
 
+use axum::middleware;
 use axum::routing::{delete, get, post, put};
 use axum::Router;
+use tower_http::compression::CompressionLayer;
+use tower_http::services::{ServeDir, ServeFile};
+use tracing::{info, warn};
 
 use crate::state::AppState;
 
-pub fn router(state: Arc<AppState>) -> Router {
-    Router::new()
-        .route("/", get(routes::index))
+/// The API plus the web UI: the embedded page, or with `static_dir` any file
+/// under that directory, falling back to its `index.html` for unknown paths
+/// so a frontend can be iterated on without rebuilding.
+pub fn router(state: Arc<AppState>, static_dir: Option<PathBuf>) -> Router {
+    let rw = middleware::from_fn_with_state(state.clone(), routes::require_writable);
+    let api = Router::new()
         .route("/api/status", get(routes::status))
+        .route("/api/version", get(routes::version))
         .route("/api/zones", get(routes::zones))
         .route("/api/config", get(routes::get_config))
-        .route("/api/config", put(routes::put_config))
-        .route("/api/pair", post(routes::start_pair))
+        .route("/api/config", put(routes::put_config).layer(rw.clone()))
+        .route("/api/config/issues", get(routes::config_issues))
+        .route("/api/config/profiles", get(routes::list_profiles))
+        .route("/api/config/load", post(routes::load_profile).layer(rw.clone()))
+        .route("/api/pair", post(routes::start_pair).layer(rw.clone()))
         .route("/api/pair/status", get(sse::pair_status_stream))
-        .route("/api/discover", post(routes::discover))
-        .route("/api/bridge/start", post(routes::bridge_start))
-        .route("/api/bridge/stop", post(routes::bridge_stop))
-        .route("/api/bridge/restart", post(routes::bridge_restart))
+        .route("/api/unpair", post(routes::unpair).layer(rw.clone()))
+        .route("/api/discover", post(routes::discover).layer(rw.clone()))
+        .route("/api/bridge/start", post(routes::bridge_start).layer(rw.clone()))
+        .route("/api/bridge/stop", post(routes::bridge_stop).layer(rw.clone()))
+        .route("/api/bridge/restart", post(routes::bridge_restart).layer(rw.clone()))
+        .route("/api/bridge/status/stream", get(sse::bridge_status_stream))
+        .route("/api/zones/{id}", delete(routes::delete_zone).layer(rw.clone()))
+        .route("/api/zones/by-name", post(routes::set_zone_level_by_name))
         .route("/api/zones/{id}/level", post(routes::set_zone_level))
+        .route("/api/zones/{id}/refresh", post(routes::refresh_zone))
+        .route("/api/zones/{id}/identify", post(routes::identify_zone))
+        .route("/api/zones/{id}/toggle", post(routes::toggle_zone))
+        .route("/api/zones/{id}/route", get(routes::zone_route))
+        .route("/api/leap/raw", post(routes::leap_raw))
+        .route("/api/leap/zones/raw", get(routes::leap_zones_raw))
         .route("/api/export/xml", get(routes::export_xml))
+        .route("/api/export/json", get(routes::export_json))
+        .route("/api/export/csv", get(routes::export_csv))
+        .route("/api/import/csv", post(routes::import_csv).layer(rw.clone()))
         .route("/DbXmlInfo.xml", get(routes::export_xml))
         .route("/api/events", get(sse::zone_events_stream))
         .route("/api/logs", get(sse::log_stream))
+        .route("/api/logs/download", get(routes::download_logs))
         // Savant discovery
-        .route("/api/savant/discover", post(routes::savant_discover))
+        .route("/api/savant/discover", post(routes::savant_discover).layer(rw.clone()))
         .route(
             "/api/savant/discover/status",
             get(sse::savant_discovery_status_stream),
         )
-        .route("/api/savant/remove", post(routes::savant_remove))
+        .route("/api/savant/remove", post(routes::savant_remove).layer(rw.clone()))
+        .route("/api/savant/raw", post(routes::savant_raw))
         // Site management (dev mode)
         .route("/api/sites", get(routes::list_sites))
-        .route("/api/sites", post(routes::create_site))
-        .route("/api/sites/{name}", delete(routes::delete_site))
-        .route("/api/sites/{name}/activate", post(routes::activate_site))
-        .route("/api/sites/{name}/rename", post(routes::rename_site))
-        .with_state(state)
+        .route("/api/sites", post(routes::create_site).layer(rw.clone()))
+        .route("/api/sites/{name}", delete(routes::delete_site).layer(rw.clone()))
+        .route("/api/sites/{name}/activate", post(routes::activate_site).layer(rw.clone()))
+        .route("/api/sites/{name}/preview", get(routes::preview_site))
+        .route("/api/sites/{name}/rename", post(routes::rename_site).layer(rw.clone()))
+        .with_state(state);
+
+    let app = match static_dir {
+        Some(dir) => {
+            let index = dir.join("index.html");
+            if !index.is_file() {
+                warn!("{} not found; unknown paths will 404", index.display());
+            }
+            info!("Serving web UI from {}", dir.display());
+            api.fallback_service(ServeDir::new(&dir).fallback(ServeFile::new(index)))
+        }
+        None => api.route("/", get(routes::index)),
+    };
+    // gzip/deflate when the client asks for it; the default predicate
+    // leaves SSE streams and tiny responses alone
+    app.layer(CompressionLayer::new())
 }
@@ -1,21 +1,39 @@
 use std::sync::Arc;
 
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
 use axum::response::{Html, IntoResponse, Json, Response};
 use serde::Deserialize;
 use tracing::info;
 
-use crate::leap_client::{LeapHeader, LeapRequest};
-use crate::savant_client::SavantRequest;
+use crate::bridge::CommandSource;
+use crate::leap_client::LeapRequest;
+use crate::level_curve::LevelScale;
+use crate::savant_client::{SavantEvent, SavantRequest};
 use crate::state::{AppState, BridgeStatus, PairingStatus, SavantDiscoveryStatus};
 
+use super::api_error::{api_error, api_error_with, ErrorCode};
+
 static INDEX_HTML: &str = include_str!("../../templates/index.html");
 
 pub async fn index() -> Html<&'static str> {
     Html(INDEX_HTML)
 }
 
+/// Crate version plus the git hash and build time captured by build.rs.
+fn version_info() -> serde_json::Value {
+    serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_hash": env!("RA_BRIDGE_GIT_HASH"),
+        "built_at": env!("RA_BRIDGE_BUILT_AT"),
+    })
+}
+
+pub async fn version() -> Json<serde_json::Value> {
+    Json(version_info())
+}
+
 pub async fn status(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
     // Derive running status from actual state — the watch channel is unreliable
     // due to races between old/new bridge instances on restart.
@@ -30,7 +48,23 @@ pub async fn status(State(state): State<Arc<AppState>>) -> Json<serde_json::Valu
     let processor_host = config.as_ref().map(|c| c.processor.host.clone());
     let savant_host = config
         .as_ref()
-        .and_then(|c| c.savant.as_ref().map(|s| s.host.clone()));
+        .and_then(|c| c.savant.first().map(|s| s.host.clone()));
+    // Only configured zones count, so levels cached for since-deleted zones
+    // don't show up as "on".
+    let zones_on = {
+        let levels = state.zone_levels.snapshot().await;
+        config
+            .as_ref()
+            .map(|c| {
+                c.zones
+                    .iter()
+                    .map(|z| z.ra2_id)
+                    .chain(c.savant_zones.iter().map(|z| z.ra2_id))
+                    .filter(|id| levels.get(id).is_some_and(|&l| l > 0.0))
+                    .count()
+            })
+            .unwrap_or(0)
+    };
     drop(config);
 
     let uptime_secs = {
@@ -41,21 +75,34 @@ pub async fn status(State(state): State<Arc<AppState>>) -> Json<serde_json::Valu
     let active_site = state.active_site.read().await.clone();
 
     Json(serde_json::json!({
+        "version": version_info(),
         "bridge": bridge_status,
         "zone_count": zone_count,
         "savant_zone_count": savant_zone_count,
+        "levels": {
+            "on": zones_on,
+            "total": zone_count + savant_zone_count,
+        },
         "processor_host": processor_host,
         "savant_host": savant_host,
+        "savant": state.savant_metrics.snapshot(),
+        "commands": state.command_metrics.snapshot(),
+        "backends": {
+            "leap": state.backend_health.leap.borrow().clone(),
+            "savant": state.backend_health.savant.borrow().clone(),
+        },
         "uptime_secs": uptime_secs,
         "has_config": state.config.read().await.is_some(),
+        "config_generation": state.config_generation(),
         "active_site": active_site,
         "dev_mode": state.dev_mode,
+        "read_only": state.read_only,
     }))
 }
 
 pub async fn zones(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
     let config = state.config.read().await;
-    let levels = state.zone_levels.read().await;
+    let levels = state.zone_levels.snapshot().await;
 
     let mut zones: Vec<serde_json::Value> = Vec::new();
 
@@ -90,11 +137,75 @@ pub async fn zones(State(state): State<Arc<AppState>>) -> Json<serde_json::Value
 #[derive(Deserialize)]
 pub struct SetLevelRequest {
     level: f64,
+    /// Seconds; defaults to `[bridge] default_fade_secs`.
+    #[serde(default)]
+    fade: Option<f64>,
+    /// What `level` is given in; the response reports it the same way.
+    #[serde(default)]
+    scale: LevelScale,
 }
 
 enum ZoneTarget {
     Leap { href: String },
-    Savant { address: String, load_offset: usize },
+    Savant { host: usize, address: String, load_offset: usize },
+}
+
+/// Resolve which backend owns `id` and where commands for it are sent.
+fn zone_target(config: &crate::config::Config, id: u32) -> Option<ZoneTarget> {
+    if let Some(zone) = config.zones.iter().find(|z| z.ra2_id == id) {
+        return Some(ZoneTarget::Leap {
+            href: zone.leap_href.clone(),
+        });
+    }
+    config
+        .savant_zones
+        .iter()
+        .find(|z| z.ra2_id == id)
+        .map(|zone| ZoneTarget::Savant {
+            host: zone.host,
+            address: zone.address.clone(),
+            load_offset: zone.load_offset,
+        })
+}
+
+pub async fn zone_route(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> Response {
+    let target = {
+        let config = state.config.read().await;
+        match config.as_ref() {
+            Some(c) => zone_target(c, id),
+            None => {
+                return api_error(ErrorCode::NoConfig, "No config loaded");
+            }
+        }
+    };
+
+    match target {
+        Some(ZoneTarget::Leap { href }) => {
+            let running = state.leap_req_tx.read().await.is_some();
+            Json(serde_json::json!({
+                "ra2_id": id,
+                "backend": "leap",
+                "target": { "leap_href": href },
+                "backend_running": running,
+            }))
+            .into_response()
+        }
+        Some(ZoneTarget::Savant {
+            host,
+            address,
+            load_offset,
+        }) => {
+            let running = state.savant_req_tx.read().await.get(host).is_some();
+            Json(serde_json::json!({
+                "ra2_id": id,
+                "backend": "savant",
+                "target": { "host": host, "address": address, "load_offset": load_offset },
+                "backend_running": running,
+            }))
+            .into_response()
+        }
+        None => api_error(ErrorCode::ZoneNotFound, format!("No backend owns ra2_id {}", id)),
+    }
 }
 
 pub async fn set_zone_level(
@@ -102,120 +213,742 @@ pub async fn set_zone_level(
     Path(id): Path<u32>,
     Json(payload): Json<SetLevelRequest>,
 ) -> Response {
-    let level = payload.level.clamp(0.0, 100.0);
+    set_level(&state, id, &payload).await
+}
 
+#[derive(Deserialize)]
+pub struct SetLevelByNameRequest {
+    name: String,
+    #[serde(flatten)]
+    set: SetLevelRequest,
+}
+
+/// Set a zone's level by its name, for scripts that don't know ra2_ids.
+/// Names match as in `Config::zones_matching_name`; more than one match is
+/// refused with the candidates listed.
+pub async fn set_zone_level_by_name(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<SetLevelByNameRequest>,
+) -> Response {
+    let matches: Vec<(u32, String)> = match state.config.read().await.as_ref() {
+        Some(config) => config
+            .zones_matching_name(&payload.name)
+            .into_iter()
+            .map(|(id, name)| (id, name.to_string()))
+            .collect(),
+        None => {
+            return api_error(ErrorCode::NoConfig, "No config loaded");
+        }
+    };
+
+    match matches.as_slice() {
+        [(id, name)] => {
+            info!("SetLevel by name '{}' → zone {} ({})", payload.name, id, name);
+            set_level(&state, *id, &payload.set).await
+        }
+        [] => api_error(ErrorCode::ZoneNotFound, format!("No zone matches '{}'", payload.name)),
+        _ => api_error_with(
+            ErrorCode::AmbiguousZone,
+            format!("'{}' matches {} zones", payload.name, matches.len()),
+            serde_json::json!({
+                "matches": matches
+                    .iter()
+                    .map(|(id, name)| serde_json::json!({ "ra2_id": id, "name": name }))
+                    .collect::<Vec<_>>(),
+            }),
+        ),
+    }
+}
+
+/// Success without a backend command, for a set to the level `id` is
+/// already at under `[bridge] skip_redundant_sets`.
+fn redundant_set(id: u32, level: f64, scale: LevelScale) -> Response {
+    info!("bridge: ra2_id={} already at {} (from {}), not sent", id, level, CommandSource::Web);
+    Json(serde_json::json!({
+        "ok": true,
+        "level": scale.report(level),
+        "scale": scale,
+        "skipped": true,
+    }))
+    .into_response()
+}
+
+fn set_level_response(level: f64, scale: LevelScale) -> Response {
+    Json(serde_json::json!({ "ok": true, "level": scale.report(level), "scale": scale })).into_response()
+}
+
+/// Clamp, curve and send a set-level to whichever backend owns `id`.
+async fn set_level(state: &AppState, id: u32, payload: &SetLevelRequest) -> Response {
+    send_level(state, id, payload, true).await
+}
+
+/// `set_level`, but `may_skip` false sends even under `[bridge]
+/// skip_redundant_sets`, for callers that already put the target level in
+/// the cache.
+async fn send_level(state: &AppState, id: u32, payload: &SetLevelRequest, may_skip: bool) -> Response {
     // Look up zone target while holding config lock, then release it
-    let target = {
+    let (target, level, backend_level, fade, skip_redundant) = {
         let config_guard = state.config.read().await;
         let config = match config_guard.as_ref() {
             Some(c) => c,
             None => {
-                return (
-                    StatusCode::NOT_FOUND,
-                    Json(serde_json::json!({ "error": "No config loaded" })),
-                )
-                    .into_response();
+                return api_error(ErrorCode::NoConfig, "No config loaded");
             }
         };
 
-        if let Some(zone) = config.zones.iter().find(|z| z.ra2_id == id) {
-            Some(ZoneTarget::Leap {
-                href: zone.leap_href.clone(),
-            })
-        } else if let Some(zone) = config.savant_zones.iter().find(|z| z.ra2_id == id) {
-            Some(ZoneTarget::Savant {
-                address: zone.address.clone(),
-                load_offset: zone.load_offset,
-            })
-        } else {
-            None
-        }
+        let level = config.level_limits_for(id).apply(payload.scale.to_ra2(payload.level));
+        (
+            zone_target(config, id),
+            level,
+            config.level_curve_for(id).to_backend(level),
+            payload
+                .fade
+                .map(|f| f.max(0.0))
+                .or(config.bridge.default_fade())
+                .filter(|_| config.zones.iter().find(|z| z.ra2_id == id).is_none_or(crate::config::ZoneMapping::accepts_fade)),
+            config.bridge.skip_redundant_sets && may_skip,
+        )
     };
 
     match target {
         Some(ZoneTarget::Leap { href }) => {
-            info!("SetLevel zone={} level={} backend=LEAP href={}", id, level, href);
+            info!("bridge → LEAP: ra2_id={} level={} href={} (from {})", id, level, href, CommandSource::Web);
             let tx = state.leap_req_tx.read().await;
             let tx = match tx.as_ref() {
                 Some(tx) => tx.clone(),
                 None => {
-                    return (
-                        StatusCode::BAD_REQUEST,
-                        Json(serde_json::json!({ "error": "Bridge not running" })),
-                    )
-                        .into_response();
+                    return api_error(ErrorCode::BridgeNotRunning, "Bridge not running");
                 }
             };
 
-            let req = LeapRequest {
-                communique_type: "CreateRequest".to_string(),
-                header: LeapHeader {
-                    url: format!("{}/commandprocessor", href),
-                    client_tag: None,
-                    extra: serde_json::Map::new(),
-                },
-                body: Some(serde_json::json!({
-                    "Command": {
-                        "CommandType": "GoToLevel",
-                        "Parameter": [{"Type": "Level", "Value": level}]
-                    }
-                })),
-            };
-
+            if skip_redundant && state.zone_levels.is_at(id, level).await {
+                return redundant_set(id, level, payload.scale);
+            }
+            let req = crate::translator::set_level_request(&href, backend_level, fade);
             let _ = tx.send(req).await;
-            state.zone_levels.write().await.insert(id, level);
-            Json(serde_json::json!({ "ok": true })).into_response()
+            state.zone_levels.set_level(id, level).await;
+            set_level_response(level, payload.scale)
         }
         Some(ZoneTarget::Savant {
+            host,
             address,
             load_offset,
         }) => {
-            info!("SetLevel zone={} level={} backend=Savant addr={} offset={}", id, level, address, load_offset);
+            info!(
+                "bridge → Savant: ra2_id={} level={} host={} addr={} offset={} (from {})",
+                id, level, host, address, load_offset, CommandSource::Web
+            );
             let tx = state.savant_req_tx.read().await;
-            let tx = match tx.as_ref() {
+            let tx = match tx.get(host) {
                 Some(tx) => tx.clone(),
                 None => {
-                    return (
-                        StatusCode::BAD_REQUEST,
-                        Json(serde_json::json!({ "error": "Bridge not running" })),
-                    )
-                        .into_response();
+                    return api_error(ErrorCode::BridgeNotRunning, "Bridge not running");
                 }
             };
 
+            if skip_redundant && state.zone_levels.is_at(id, level).await {
+                return redundant_set(id, level, payload.scale);
+            }
             let _ = tx
                 .send(SavantRequest::SetLoad {
                     address,
                     load_offset,
-                    level,
+                    level: backend_level,
+                    fade,
                 })
                 .await;
-            state.zone_levels.write().await.insert(id, level);
-            Json(serde_json::json!({ "ok": true })).into_response()
+            state.zone_levels.set_level(id, level).await;
+            set_level_response(level, payload.scale)
         }
-        None => (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": format!("Zone {} not found", id) })),
-        )
-            .into_response(),
+        None => api_error(ErrorCode::ZoneNotFound, format!("Zone {} not found", id)),
+    }
+}
+
+/// Middleware for the routes that change config, pairing, the bridge or
+/// sites: refused when the server runs with `--read-only`.
+pub async fn require_writable(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    if state.read_only {
+        return api_error(ErrorCode::ReadOnly, "Server is in read-only mode");
     }
+    next.run(req).await
+}
+
+/// Full-on/off blinks in an identify sequence, and how long each half lasts.
+const IDENTIFY_BLINKS: usize = 3;
+const IDENTIFY_STEP: std::time::Duration = std::time::Duration::from_millis(800);
+
+/// Blink a zone between 100% and off a few times so an installer can see
+/// which load an ra2_id drives, then put it back at its previous level. The
+/// sequence runs in the background; the response comes after the first step.
+pub async fn identify_zone(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> Response {
+    if !state.identifying.lock().await.insert(id) {
+        return api_error(ErrorCode::Busy, format!("Zone {} is already identifying", id));
+    }
+    let original = state.zone_levels.get_level(id).await.unwrap_or(0.0);
+    let step = |level| SetLevelRequest {
+        level,
+        fade: Some(0.0),
+        scale: LevelScale::Percent,
+    };
+
+    let first = set_level(&state, id, &step(100.0)).await;
+    if !first.status().is_success() {
+        state.identifying.lock().await.remove(&id);
+        return first;
+    }
+
+    info!("Identifying zone {} (restoring {} afterwards)", id, original);
+    let task_state = state.clone();
+    tokio::spawn(async move {
+        let state = task_state;
+        for blink in 0..IDENTIFY_BLINKS {
+            if blink > 0 {
+                set_level(&state, id, &step(100.0)).await;
+            }
+            tokio::time::sleep(IDENTIFY_STEP).await;
+            set_level(&state, id, &step(0.0)).await;
+            tokio::time::sleep(IDENTIFY_STEP).await;
+        }
+        set_level(&state, id, &step(original)).await;
+        state.identifying.lock().await.remove(&id);
+    });
+
+    Json(serde_json::json!({ "ok": true, "restore_level": original })).into_response()
+}
+
+/// Turn a zone off if it's on, otherwise full on. The cached level is
+/// flipped under one lock, so two quick toggles can't both see "off".
+pub async fn toggle_zone(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> Response {
+    let (previous, level) = state.zone_levels.toggle(id, 100.0).await;
+    let request = SetLevelRequest {
+        level,
+        fade: None,
+        scale: LevelScale::Percent,
+    };
+    // The cache already holds the new level, so it mustn't count as redundant
+    let response = send_level(&state, id, &request, false).await;
+    if !response.status().is_success() {
+        // Nothing was sent; put the cache back
+        match previous {
+            Some(previous) => state.zone_levels.set_level(id, previous).await,
+            None => state.zone_levels.remove(id).await,
+        }
+    }
+    response
+}
+
+/// How long `refresh_zone` waits for the backend to report the level.
+const REFRESH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Read a zone's current level from its backend now, instead of waiting for
+/// the next event or Savant poll, and return it.
+pub async fn refresh_zone(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> Response {
+    let (target, curve) = {
+        let config_guard = state.config.read().await;
+        let Some(config) = config_guard.as_ref() else {
+            return api_error(ErrorCode::NoConfig, "No config loaded");
+        };
+        (zone_target(config, id), config.level_curve_for(id).clone())
+    };
+    let not_running = || api_error(ErrorCode::BridgeNotRunning, "Bridge not running");
+
+    let backend_level = match target {
+        Some(ZoneTarget::Leap { href }) => {
+            let tx = state.leap_req_tx.read().await.clone();
+            let events = state.leap_events.read().await.as_ref().map(|e| e.subscribe());
+            let (Some(tx), Some(mut events)) = (tx, events) else {
+                return not_running();
+            };
+            let tag = format!("refresh-{}", uuid::Uuid::new_v4());
+            let req = LeapRequest {
+                communique_type: "ReadRequest".to_string(),
+                header: crate::leap_client::LeapHeader {
+                    url: format!("{}/status", href),
+                    client_tag: Some(tag.clone()),
+                    extra: Default::default(),
+                },
+                body: None,
+            };
+            if tx.send(req).await.is_err() {
+                return not_running();
+            }
+            tokio::time::timeout(
+                REFRESH_TIMEOUT,
+                crate::leap_client::wait_for_client_tag(&mut events, &tag),
+            )
+            .await
+            .ok()
+            .flatten()
+            .and_then(|event| event.body["ZoneStatus"]["Level"].as_f64())
+        }
+        Some(ZoneTarget::Savant {
+            host,
+            address,
+            load_offset,
+        }) => {
+            let tx = state.savant_req_tx.read().await.get(host).cloned();
+            let events = state.savant_events.read().await.get(host).map(|e| e.subscribe());
+            let (Some(tx), Some(mut events)) = (tx, events) else {
+                return not_running();
+            };
+            let req = SavantRequest::QueryLoad {
+                address: address.clone(),
+            };
+            if tx.send(req).await.is_err() {
+                return not_running();
+            }
+            tokio::time::timeout(REFRESH_TIMEOUT, async {
+                loop {
+                    match events.recv().await {
+                        Ok(SavantEvent::LoadLevel {
+                            address: a,
+                            load_offset: o,
+                            level,
+                        }) if a == address && o == load_offset => return Some(level),
+                        Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            })
+            .await
+            .ok()
+            .flatten()
+        }
+        None => {
+            return api_error(ErrorCode::ZoneNotFound, format!("Zone {} not found", id));
+        }
+    };
+
+    match backend_level {
+        Some(backend_level) => {
+            let level = curve.to_ra2(backend_level);
+            state.zone_levels.set_level(id, level).await;
+            Json(serde_json::json!({ "ok": true, "level": level })).into_response()
+        }
+        None => api_error(ErrorCode::BackendTimeout, format!("Zone {} did not report a level", id)),
+    }
+}
+
+/// Check the `Authorization: Bearer` header against `[web] api_token`.
+/// Without a configured token the guarded endpoints are disabled outright.
+async fn require_api_token(state: &AppState, headers: &HeaderMap) -> Result<(), Response> {
+    let expected = state
+        .config
+        .read()
+        .await
+        .as_ref()
+        .and_then(|c| c.web.api_token.clone());
+    let Some(expected) = expected.filter(|t| !t.is_empty()) else {
+        return Err(api_error(ErrorCode::ApiDisabled, "Set [web] api_token to enable this endpoint"));
+    };
+    let given = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if !given.is_some_and(|given| constant_time_eq(given.as_bytes(), expected.as_bytes())) {
+        return Err(api_error(ErrorCode::Unauthorized, "Missing or invalid API token"));
+    }
+    Ok(())
+}
+
+/// Compare without stopping at the first differing byte, so response times
+/// don't give the token away a byte at a time.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Send an arbitrary LEAP request over the bridge's connection and return
+/// the processor's response, matched by `ClientTag` (one is generated if
+/// the request has none).
+pub async fn leap_raw(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(mut req): Json<LeapRequest>,
+) -> Response {
+    if let Err(resp) = require_api_token(&state, &headers).await {
+        return resp;
+    }
+
+    let tx = state.leap_req_tx.read().await.clone();
+    let events = state.leap_events.read().await.as_ref().map(|e| e.subscribe());
+    let (Some(tx), Some(mut events)) = (tx, events) else {
+        return api_error(ErrorCode::BridgeNotRunning, "LEAP bridge not running");
+    };
+
+    let tag = req
+        .header
+        .client_tag
+        .get_or_insert_with(|| format!("raw-{}", uuid::Uuid::new_v4()))
+        .clone();
+    info!(
+        "Raw LEAP {} {} (ClientTag {})",
+        req.communique_type, req.header.url, tag
+    );
+    if tx.send(req).await.is_err() {
+        return api_error(ErrorCode::BridgeNotRunning, "LEAP bridge not running");
+    }
+
+    match tokio::time::timeout(
+        crate::leap_client::DEFAULT_REQUEST_TIMEOUT,
+        crate::leap_client::wait_for_client_tag(&mut events, &tag),
+    )
+    .await
+    {
+        Ok(Some(event)) => Json(event).into_response(),
+        Ok(None) => api_error(ErrorCode::BackendError, "LEAP connection closed before responding"),
+        Err(_) => api_error(ErrorCode::BackendTimeout, format!("No response with ClientTag {}", tag)),
+    }
+}
+
+/// Where `leap_zones_raw` sends its reads: the running bridge's connection,
+/// or one opened for the whole walk when the bridge is stopped.
+enum RawLeapConnection {
+    Live(
+        tokio::sync::mpsc::Sender<LeapRequest>,
+        tokio::sync::broadcast::Receiver<crate::leap_client::LeapEvent>,
+    ),
+    OneShot(crate::leap_client::LeapSession),
+}
+
+impl RawLeapConnection {
+    fn source(&self) -> &'static str {
+        match self {
+            RawLeapConnection::Live(..) => "live",
+            RawLeapConnection::OneShot(_) => "one_shot",
+        }
+    }
+
+    /// Read `url`, failing with the error code and message to report.
+    async fn read(&mut self, url: &str) -> Result<crate::leap_client::LeapEvent, (ErrorCode, String)> {
+        let mut req = LeapRequest {
+            communique_type: "ReadRequest".into(),
+            header: crate::leap_client::LeapHeader {
+                url: url.to_string(),
+                client_tag: None,
+                extra: Default::default(),
+            },
+            body: None,
+        };
+        match self {
+            RawLeapConnection::Live(tx, events) => {
+                let tag = format!("raw-{}", uuid::Uuid::new_v4());
+                req.header.client_tag = Some(tag.clone());
+                if tx.send(req).await.is_err() {
+                    return Err((ErrorCode::BridgeNotRunning, "LEAP bridge not running".to_string()));
+                }
+                match tokio::time::timeout(
+                    crate::leap_client::DEFAULT_REQUEST_TIMEOUT,
+                    crate::leap_client::wait_for_client_tag(events, &tag),
+                )
+                .await
+                {
+                    Ok(Some(event)) => Ok(event),
+                    Ok(None) => Err((
+                        ErrorCode::BackendError,
+                        "LEAP connection closed before responding".to_string(),
+                    )),
+                    Err(_) => Err((
+                        ErrorCode::BackendTimeout,
+                        format!("No response to {} (ClientTag {})", url, tag),
+                    )),
+                }
+            }
+            RawLeapConnection::OneShot(session) => session
+                .request(&req, crate::leap_client::DEFAULT_REQUEST_TIMEOUT)
+                .await
+                .map_err(|e| (ErrorCode::BackendError, format!("Failed to read {}: {:#}", url, e))),
+        }
+    }
+}
+
+/// The processor's unparsed `/area` response and each area's
+/// `associatedzone` response, for working out why discovery skipped a zone
+/// (a missing href, an unexpected ControlType). Uses the bridge's connection
+/// when it's running, otherwise connects just for these reads.
+pub async fn leap_zones_raw(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if let Err(resp) = require_api_token(&state, &headers).await {
+        return resp;
+    }
+
+    let tx = state.leap_req_tx.read().await.clone();
+    let events = state.leap_events.read().await.as_ref().map(|e| e.subscribe());
+    let mut conn = match (tx, events) {
+        (Some(tx), Some(events)) => RawLeapConnection::Live(tx, events),
+        _ => {
+            let Some(processor) = state.config.read().await.as_ref().map(|c| c.processor.clone()) else {
+                return api_error(ErrorCode::NotPaired, "Processor not paired");
+            };
+            let certs_dir = state.certs_dir.read().await.clone();
+            match crate::leap_client::LeapSession::connect(
+                &processor.host,
+                processor.leap_port,
+                &certs_dir,
+                &processor.ca_files,
+                crate::leap_client::DEFAULT_REQUEST_TIMEOUT,
+            )
+            .await
+            {
+                Ok(session) => RawLeapConnection::OneShot(session),
+                Err(e) => return api_error(ErrorCode::BackendError, format!("Failed to connect: {:#}", e)),
+            }
+        }
+    };
+    info!("Reading raw LEAP areas and zones ({})", conn.source());
+
+    let area = match conn.read("/area").await {
+        Ok(event) => event,
+        Err((code, msg)) => return api_error(code, msg),
+    };
+    let mut associated = Vec::new();
+    let hrefs: Vec<String> = area.body["Areas"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|a| a["href"].as_str())
+        .filter(|href| !href.is_empty())
+        .map(str::to_string)
+        .collect();
+    for href in hrefs {
+        let response = match conn.read(&format!("{}/associatedzone", href)).await {
+            // A LEAP error status is still a response; a failed read means
+            // the connection is gone, so stop rather than time out per area
+            Ok(event) => serde_json::to_value(event).unwrap_or_default(),
+            Err((code, msg)) => return api_error(code, msg),
+        };
+        associated.push(serde_json::json!({ "area": href, "response": response }));
+    }
+
+    Json(serde_json::json!({
+        "source": conn.source(),
+        "area": area,
+        "associatedzones": associated,
+    }))
+    .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct SavantRawQuery {
+    /// How long to collect host messages after sending, in milliseconds.
+    #[serde(default = "default_savant_raw_window_ms")]
+    pub window_ms: u64,
+    /// Which `[[savant]]` host to talk to, by index.
+    #[serde(default)]
+    pub host: usize,
+}
+
+fn default_savant_raw_window_ms() -> u64 {
+    2000
+}
+
+/// Longest capture window a raw Savant request may ask for.
+const MAX_SAVANT_RAW_WINDOW_MS: u64 = 10_000;
+
+/// Send an arbitrary `{URI, messages}` message over the bridge's Savant
+/// connection. Savant has no request ids, so everything the host sends
+/// during the capture window is returned.
+pub async fn savant_raw(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SavantRawQuery>,
+    headers: HeaderMap,
+    Json(msg): Json<serde_json::Value>,
+) -> Response {
+    if let Err(resp) = require_api_token(&state, &headers).await {
+        return resp;
+    }
+    if msg["URI"].as_str().is_none() {
+        return api_error(ErrorCode::InvalidRequest, "Message needs a string \"URI\"");
+    }
+
+    let tx = state.savant_req_tx.read().await.get(query.host).cloned();
+    let events = state.savant_events.read().await.get(query.host).map(|e| e.subscribe());
+    let (Some(tx), Some(mut events)) = (tx, events) else {
+        return api_error(ErrorCode::BridgeNotRunning, "Savant bridge not running");
+    };
+
+    info!("Savant raw TX: {}", msg);
+    if tx.send(SavantRequest::Raw(msg)).await.is_err() {
+        return api_error(ErrorCode::BridgeNotRunning, "Savant bridge not running");
+    }
+
+    let window = std::time::Duration::from_millis(query.window_ms.min(MAX_SAVANT_RAW_WINDOW_MS));
+    let deadline = tokio::time::Instant::now() + window;
+    let mut captured = Vec::new();
+    loop {
+        match tokio::time::timeout_at(deadline, events.recv()).await {
+            Ok(Ok(SavantEvent::Message(msg))) => {
+                info!("Savant raw RX: {}", msg);
+                captured.push(msg);
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(tokio::sync::broadcast::error::RecvError::Lagged(n))) => {
+                tracing::warn!("Savant raw capture missed {} messages", n);
+            }
+            Ok(Err(tokio::sync::broadcast::error::RecvError::Closed)) | Err(_) => break,
+        }
+    }
+
+    Json(serde_json::json!({ "messages": captured })).into_response()
+}
+
+/// Remove a single LEAP or Savant zone from the config. A running bridge
+/// has its id maps rebuilt at once; deleting a backend's last zone also
+/// needs a restart, reported as `restart_required`.
+pub async fn delete_zone(State(state): State<Arc<AppState>>, Path(id): Path<u32>) -> Response {
+    let config_path = state.config_path.read().await.clone();
+    let Some(mut config) = state.config.read().await.clone() else {
+        return api_error(ErrorCode::NoConfig, "No config loaded");
+    };
+
+    let (backend, name) = if let Some(pos) = config.zones.iter().position(|z| z.ra2_id == id) {
+        ("leap", config.zones.remove(pos).name)
+    } else if let Some(pos) = config.savant_zones.iter().position(|z| z.ra2_id == id) {
+        ("savant", config.savant_zones.remove(pos).name)
+    } else {
+        return api_error(ErrorCode::ZoneNotFound, format!("Zone {} not found", id));
+    };
+
+    if let Err(e) = config.validate() {
+        return api_error(ErrorCode::InvalidConfig, format!("Invalid config: {}", e));
+    }
+    if let Err(e) = config.save(&config_path).await {
+        return api_error(ErrorCode::SaveFailed, format!("Failed to save: {}", e));
+    }
+    let Installed { restart_required, diff } = match install_config(&state, config).await {
+        Ok(installed) => installed,
+        Err(e) => return api_error(ErrorCode::InvalidConfig, format!("Invalid config: {}", e)),
+    };
+    state.zone_levels.remove(id).await;
+
+    info!("Deleted {} zone {} '{}'", backend, id, name);
+    Json(serde_json::json!({
+        "ok": true,
+        "id": id,
+        "name": name,
+        "backend": backend,
+        "restart_required": restart_required,
+        "diff": diff,
+    }))
+    .into_response()
+}
+
+/// What `install_config` changed.
+struct Installed {
+    /// The running bridge needs a restart for the change to take full
+    /// effect (see `bridge::restart_required`).
+    restart_required: bool,
+    /// Zones added, removed and changed relative to the config replaced.
+    diff: crate::config_diff::ZoneDiff,
+}
+
+/// Swap a new config into state. A running bridge has its id maps rebuilt in
+/// place. The diff is taken under the same lock as the swap, so a concurrent
+/// save can't slip in between. An invalid config is refused, so no caller
+/// can put conflicting id maps into the live bridge.
+async fn install_config(state: &AppState, new_config: crate::config::Config) -> Result<Installed, String> {
+    new_config.validate()?;
+    let mut config_guard = state.config.write().await;
+    let diff = crate::config_diff::diff_zones(config_guard.as_ref(), &new_config);
+    let restart_required = match state.bridge_maps.read().await.as_ref() {
+        Some(maps_tx) => {
+            maps_tx.send_replace(Arc::new(crate::bridge::BridgeMaps::from_config(&new_config)));
+            config_guard
+                .as_ref()
+                .is_none_or(|old| crate::bridge::restart_required(old, &new_config))
+        }
+        None => false,
+    };
+    *config_guard = Some(new_config);
+    state.bump_config_generation();
+    Ok(Installed { restart_required, diff })
 }
 
 pub async fn get_config(State(state): State<Arc<AppState>>) -> Response {
     let config = state.config.read().await;
     match config.as_ref() {
-        Some(cfg) => match toml::to_string_pretty(cfg) {
+        Some(cfg) => match toml::to_string_pretty(&cfg.redacted()) {
             Ok(toml_str) => Json(serde_json::json!({ "config": toml_str })).into_response(),
-            Err(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-                .into_response(),
+            Err(e) => api_error(ErrorCode::Internal, e.to_string()),
         },
         None => Json(serde_json::json!({ "config": null })).into_response(),
     }
 }
 
+#[derive(Deserialize)]
+pub struct IssuesQuery {
+    /// Also read every href from the processor and enumerate Savant loads.
+    #[serde(default)]
+    live: bool,
+}
+
+/// Validate the running config, optionally checking it against the live
+/// processor and Savant host.
+pub async fn config_issues(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<IssuesQuery>,
+) -> Response {
+    let Some(config) = state.config.read().await.clone() else {
+        return api_error(ErrorCode::NoConfig, "No config loaded");
+    };
+
+    let mut issues = crate::config_audit::static_issues(&config);
+    if query.live {
+        let certs_dir = state.certs_dir.read().await.clone();
+        if config.has_leap() {
+            issues.extend(crate::config_audit::leap_issues(&config, &certs_dir).await);
+        }
+        issues.extend(crate::config_audit::savant_issues(&config).await);
+    }
+
+    Json(serde_json::json!({ "live": query.live, "issues": issues })).into_response()
+}
+
+fn profiles_disabled() -> Response {
+    api_error(ErrorCode::NotFound, "Profiles not enabled (start with --profiles-dir)")
+}
+
+/// Config files in `--profiles-dir`, and which of them is active.
+pub async fn list_profiles(State(state): State<Arc<AppState>>) -> Response {
+    let Some(profiles_dir) = state.profiles_dir.clone() else {
+        return profiles_disabled();
+    };
+    let config_path = state.config_path.read().await.clone();
+    let active = (config_path.parent() == Some(profiles_dir.as_path()))
+        .then(|| config_path.file_name().map(|f| f.to_string_lossy().into_owned()))
+        .flatten();
+    match tokio::task::spawn_blocking(move || crate::serve::list_profiles(&profiles_dir)).await {
+        Ok(Ok(profiles)) => Json(serde_json::json!({ "profiles": profiles, "active": active })).into_response(),
+        Ok(Err(e)) => api_error(ErrorCode::Internal, format!("Failed to list profiles: {}", e)),
+        Err(e) => api_error(ErrorCode::Internal, e.to_string()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoadProfileQuery {
+    /// File name inside `--profiles-dir`, e.g. `party.toml`.
+    path: String,
+}
+
+/// Make a profile the active config, restarting the bridge if it was running.
+pub async fn load_profile(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LoadProfileQuery>,
+) -> Response {
+    if state.profiles_dir.is_none() {
+        return profiles_disabled();
+    }
+    let Ok(_site_guard) = state.site_lock.try_lock() else {
+        return site_busy();
+    };
+
+    match crate::serve::load_profile(&state, &query.path).await {
+        Ok(restarted) => {
+            info!("Loaded profile: {}", query.path);
+            Json(serde_json::json!({ "ok": true, "active": query.path, "restarted": restarted })).into_response()
+        }
+        Err(e) => api_error(ErrorCode::InvalidConfig, format!("{:#}", e)),
+    }
+}
+
 #[derive(Deserialize)]
 pub struct ConfigUpdate {
     config: String,
@@ -227,22 +960,23 @@ pub async fn put_config(
 ) -> Response {
     let config_path = state.config_path.read().await.clone();
     match toml::from_str::<crate::config::Config>(&payload.config) {
-        Ok(new_config) => {
-            if let Err(e) = new_config.save(&config_path) {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({ "error": format!("Failed to save: {}", e) })),
-                )
-                    .into_response();
+        Ok(mut new_config) => {
+            new_config.keep_secrets(state.config.read().await.as_ref());
+            if let Err(e) = new_config.validate() {
+                return api_error(ErrorCode::InvalidConfig, format!("Invalid config: {}", e));
             }
-            *state.config.write().await = Some(new_config);
-            Json(serde_json::json!({ "ok": true })).into_response()
+            if let Err(e) = new_config.save(&config_path).await {
+                return api_error(ErrorCode::SaveFailed, format!("Failed to save: {}", e));
+            }
+            let Installed { restart_required, diff } = match install_config(&state, new_config).await {
+                Ok(installed) => installed,
+                Err(e) => return api_error(ErrorCode::InvalidConfig, format!("Invalid config: {}", e)),
+            };
+            info!("Config saved from the web UI: {}", diff.summary());
+            Json(serde_json::json!({ "ok": true, "restart_required": restart_required, "diff": diff }))
+                .into_response()
         }
-        Err(e) => (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({ "error": format!("Invalid TOML: {}", e) })),
-        )
-            .into_response(),
+        Err(e) => api_error(ErrorCode::InvalidConfig, format!("Invalid TOML: {}", e)),
     }
 }
 
@@ -251,6 +985,8 @@ pub struct PairRequest {
     host: String,
     #[serde(default = "default_leap_port")]
     leap_port: u16,
+    #[serde(default = "default_pairing_port")]
+    pairing_port: u16,
     site_name: Option<String>,
 }
 
@@ -258,6 +994,10 @@ fn default_leap_port() -> u16 {
     8081
 }
 
+fn default_pairing_port() -> u16 {
+    crate::leap_pairing::DEFAULT_PAIRING_PORT
+}
+
 pub async fn start_pair(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<PairRequest>,
@@ -268,15 +1008,31 @@ pub async fn start_pair(
         match current {
             PairingStatus::Idle | PairingStatus::Complete { .. } | PairingStatus::Failed { .. } => {}
             _ => {
-                return (
-                    StatusCode::CONFLICT,
-                    Json(serde_json::json!({ "error": "Pairing already in progress" })),
-                )
-                    .into_response();
+                return api_error(ErrorCode::Busy, "Pairing already in progress");
             }
         }
     }
 
+    // Each attempt holds the pairing port for up to 3 minutes, so space
+    // them out rather than letting a stuck client pile them up
+    if let Some(until) = *state.pair_cooldown_until.read().await {
+        let remaining = until.saturating_duration_since(tokio::time::Instant::now());
+        if !remaining.is_zero() {
+            let secs = remaining.as_secs() + 1;
+            return (
+                [(axum::http::header::RETRY_AFTER, secs.to_string())],
+                api_error(ErrorCode::RateLimited, format!("Please wait {}s before pairing again", secs)),
+            )
+                .into_response();
+        }
+    }
+
+    // Held until the pairing task finishes so sites can't be switched or
+    // removed while it still writes certs and config to the old paths
+    let Ok(site_guard) = state.site_lock.clone().try_lock_owned() else {
+        return site_busy();
+    };
+
     // In dev mode with site_name, resolve paths from sites_dir
     let (certs_dir, config_path) = if state.dev_mode {
         if let Some(ref site_name) = payload.site_name {
@@ -300,23 +1056,43 @@ pub async fn start_pair(
 
     let host = payload.host.clone();
     let leap_port = payload.leap_port;
+    let pairing_port = payload.pairing_port;
     let status_tx = state.pairing_status.clone();
     let config_store = state.config.clone();
+    let config_generation = state.config_generation.clone();
+    let zone_levels = state.zone_levels.clone();
+    let pair_cooldown_until = state.pair_cooldown_until.clone();
 
     tokio::spawn(async move {
-        match crate::leap_pairing::pair_with_progress(
+        let _site_guard = site_guard;
+        let result = crate::leap_pairing::pair_with_progress(
             &host,
+            pairing_port,
             &certs_dir,
             &config_path,
             leap_port,
             status_tx.clone(),
         )
-        .await
-        {
+        .await;
+        // The pairing connection is closed by now; start the cooldown from here
+        *pair_cooldown_until.write().await =
+            Some(tokio::time::Instant::now() + crate::leap_pairing::PAIR_COOLDOWN);
+        match result {
             Ok(()) => {
                 // Reload config after successful pairing
-                if let Ok(cfg) = crate::config::Config::load(&config_path) {
+                if let Ok(cfg) = crate::config::Config::load(&config_path).await {
+                    let levels = crate::discover::read_levels(
+                        &host,
+                        leap_port,
+                        &certs_dir,
+                        &cfg.processor.ca_files,
+                        &cfg.zones,
+                        &cfg.level_curve,
+                    )
+                    .await;
+                    zone_levels.extend(levels).await;
                     *config_store.write().await = Some(cfg);
+                    config_generation.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                 }
                 info!("Pairing completed successfully via web UI");
             }
@@ -332,58 +1108,143 @@ pub async fn start_pair(
     (StatusCode::ACCEPTED, Json(serde_json::json!({ "ok": true }))).into_response()
 }
 
-pub async fn discover(State(state): State<Arc<AppState>>) -> Response {
+#[derive(Deserialize)]
+pub struct DiscoverQuery {
+    /// Keep existing zones' ids/names and only add new ones.
+    #[serde(default)]
+    merge: bool,
+    /// Comma-separated zone control types to keep (default dimmed,switched).
+    types: Option<String>,
+    /// Return the zones (with their proposed ra2_ids) without writing config.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+pub async fn discover(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DiscoverQuery>,
+) -> Response {
     let config = state.config.read().await;
-    let (host, leap_port) = match config.as_ref() {
-        Some(cfg) => (cfg.processor.host.clone(), cfg.processor.leap_port),
+    let (host, leap_port, ca_files, existing_zones, savant_ids, ids, separator) = match config.as_ref() {
+        Some(cfg) => (
+            cfg.processor.host.clone(),
+            cfg.processor.leap_port,
+            cfg.processor.ca_files.clone(),
+            cfg.zones.clone(),
+            cfg.savant_zones.iter().map(|z| z.ra2_id).collect::<std::collections::HashSet<u32>>(),
+            cfg.leap_ids(),
+            cfg.web.area_separator,
+        ),
         None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "No config loaded — pair first" })),
-            )
-                .into_response();
+            return api_error(ErrorCode::NotPaired, "No config loaded — pair first");
         }
     };
     drop(config);
 
     let certs_dir = state.certs_dir.read().await.clone();
     let config_path = state.config_path.read().await.clone();
-    let config_store = state.config.clone();
 
-    match crate::discover::discover_zones(&host, leap_port, &certs_dir).await {
-        Ok(zones) => {
+    let types = crate::discover::parse_zone_types(
+        query.types.as_deref().unwrap_or(crate::discover::DEFAULT_ZONE_TYPES),
+    );
+    match crate::discover::discover_zones(&host, leap_port, &certs_dir, &ca_files, &types, ids, separator).await {
+        Ok(discovered) => {
+            let (zones, added, missing) = if query.merge {
+                match crate::discover::merge_zones(&existing_zones, &discovered, &savant_ids, ids) {
+                    Ok(merged) => (merged.zones, Some(merged.added), Some(merged.missing)),
+                    Err(e) => {
+                        return api_error(ErrorCode::InvalidConfig, format!("Merge failed: {}", e));
+                    }
+                }
+            } else {
+                (discovered, None, None)
+            };
             let zone_count = zones.len();
-            if let Err(e) = crate::discover::write_config(&config_path, &host, leap_port, &zones) {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({ "error": format!("Failed to write config: {}", e) })),
-                )
-                    .into_response();
+            if query.dry_run {
+                return Json(serde_json::json!({
+                    "ok": true,
+                    "dry_run": true,
+                    "zone_count": zone_count,
+                    "added": added,
+                    "missing": missing,
+                    "zones": zones,
+                }))
+                .into_response();
+            }
+            if let Err(e) = crate::discover::write_config(&config_path, &host, leap_port, &zones).await {
+                return api_error(ErrorCode::SaveFailed, format!("Failed to write config: {:#}", e));
             }
             // Reload
-            if let Ok(cfg) = crate::config::Config::load(&config_path) {
-                *config_store.write().await = Some(cfg);
+            let restart_required = match crate::config::Config::load(&config_path).await {
+                Ok(cfg) => {
+                    let levels = crate::discover::read_levels(
+                        &host,
+                        leap_port,
+                        &certs_dir,
+                        &ca_files,
+                        &cfg.zones,
+                        &cfg.level_curve,
+                    )
+                    .await;
+                    state.zone_levels.extend(levels).await;
+                    match install_config(&state, cfg).await {
+                        Ok(installed) => installed.restart_required,
+                        Err(e) => return api_error(ErrorCode::InvalidConfig, format!("Invalid config: {}", e)),
+                    }
+                }
+                Err(_) => false,
+            };
+            Json(serde_json::json!({
+                "ok": true,
+                "zone_count": zone_count,
+                "added": added,
+                "missing": missing,
+                "restart_required": restart_required,
+            }))
+            .into_response()
+        }
+        Err(e) => api_error(ErrorCode::BackendError, format!("Discovery failed: {}", e)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BridgeStartQuery {
+    /// Answer once every started backend has connected (or failed to),
+    /// instead of as soon as the bridge tasks are spawned.
+    #[serde(default)]
+    pub wait: bool,
+}
+
+/// How long `?wait=true` waits for the backends to connect.
+const BRIDGE_START_WAIT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// The first connection outcome of a backend that was just started. `rx`
+/// must be subscribed before the start, so a status left over from the last
+/// run counts as already seen.
+async fn first_connection(
+    rx: &mut tokio::sync::watch::Receiver<crate::state::BackendStatus>,
+) -> Result<(), String> {
+    use crate::state::BackendStatus;
+    loop {
+        if rx.changed().await.is_err() {
+            return Err("backend stopped".to_string());
+        }
+        match &*rx.borrow_and_update() {
+            BackendStatus::Connected => return Ok(()),
+            BackendStatus::Error { message, .. } | BackendStatus::Failed { message, .. } => {
+                return Err(message.clone())
             }
-            Json(serde_json::json!({ "ok": true, "zone_count": zone_count })).into_response()
+            _ => {}
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": format!("Discovery failed: {}", e) })),
-        )
-            .into_response(),
     }
 }
 
-pub async fn bridge_start(State(state): State<Arc<AppState>>) -> Response {
+pub async fn bridge_start(State(state): State<Arc<AppState>>, Query(query): Query<BridgeStartQuery>) -> Response {
     // Check current status
     {
         let current = state.bridge_status.borrow().clone();
         if matches!(current, BridgeStatus::Running | BridgeStatus::Starting) {
-            return (
-                StatusCode::CONFLICT,
-                Json(serde_json::json!({ "error": "Bridge already running" })),
-            )
-                .into_response();
+            return api_error(ErrorCode::BridgeRunning, "Bridge already running");
         }
     }
 
@@ -391,103 +1252,293 @@ pub async fn bridge_start(State(state): State<Arc<AppState>>) -> Response {
     let config = match config {
         Some(c) => c,
         None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "No config loaded" })),
-            )
-                .into_response();
+            return api_error(ErrorCode::NoConfig, "No config loaded");
         }
     };
+    if let Err(e) = config.validate() {
+        return api_error(ErrorCode::InvalidConfig, format!("Invalid config: {}", e));
+    }
 
-    let _ = state.bridge_status.send(BridgeStatus::Starting);
+    let mut leap_status = state.backend_health.leap.subscribe();
+    let mut savant_status = state.backend_health.savant.subscribe();
 
-    let certs_dir = state.certs_dir.read().await.clone();
-    let zone_levels = state.zone_levels.clone();
-    let bridge_status_tx = state.bridge_status.clone();
+    match start_bridge(&state, config).await {
+        Ok(()) => {
+            let mut started = Vec::new();
+            if state.leap_req_tx.read().await.is_some() {
+                started.push(("LEAP", &mut leap_status));
+            }
+            if !state.savant_req_tx.read().await.is_empty() {
+                started.push(("Savant", &mut savant_status));
+            }
+            if !query.wait {
+                return Json(serde_json::json!({ "ok": true })).into_response();
+            }
+
+            // The bridge stays up either way; a backend that failed keeps
+            // retrying, so this only reports how the first attempt went. All
+            // backends share the one wait, however many there are.
+            let deadline = tokio::time::Instant::now() + BRIDGE_START_WAIT;
+            let mut outcome = Ok(());
+            for (name, rx) in started {
+                outcome = match tokio::time::timeout_at(deadline, first_connection(rx)).await {
+                    Ok(Ok(())) => continue,
+                    Ok(Err(e)) => Err((ErrorCode::BackendError, format!("{} connection failed: {}", name, e))),
+                    Err(_) => Err((
+                        ErrorCode::BackendTimeout,
+                        format!("{} not connected after {}s", name, BRIDGE_START_WAIT.as_secs()),
+                    )),
+                };
+                break;
+            }
+            let backends = serde_json::json!({
+                "leap": state.backend_health.leap.borrow().clone(),
+                "savant": state.backend_health.savant.borrow().clone(),
+            });
+            match outcome {
+                Ok(()) => Json(serde_json::json!({ "ok": true, "backends": backends })).into_response(),
+                Err((code, error)) => api_error_with(code, error, serde_json::json!({ "backends": backends })),
+            }
+        }
+        Err(e) => api_error(ErrorCode::Internal, format!("Failed to start: {}", e)),
+    }
+}
 
-    match crate::bridge::start(config, certs_dir, zone_levels, bridge_status_tx).await {
+/// Start the bridge on `config` with the current certs dir and put its
+/// handles in state, reporting Starting, then Running or the error, on
+/// `bridge_status`.
+pub(crate) async fn start_bridge(state: &AppState, config: crate::config::Config) -> anyhow::Result<()> {
+    let _ = state.bridge_status.send(BridgeStatus::Starting);
+    let certs_dir = state.certs_dir.read().await.clone();
+    match crate::bridge::start(
+        config,
+        certs_dir,
+        state.zone_levels.clone(),
+        state.bridge_status.clone(),
+        state.savant_metrics.clone(),
+        state.command_metrics.clone(),
+        state.backend_health.clone(),
+    )
+    .await
+    {
         Ok(handle) => {
             *state.leap_req_tx.write().await = handle.leap_req_tx;
+            *state.leap_events.write().await = handle.leap_events;
             *state.savant_req_tx.write().await = handle.savant_req_tx;
+            *state.savant_events.write().await = handle.savant_events;
+            *state.bridge_maps.write().await = Some(handle.maps_tx);
             *state.bridge_shutdown.write().await = Some(handle.shutdown_tx);
             *state.bridge_started_at.write().await = Some(tokio::time::Instant::now());
             let _ = state.bridge_status.send(BridgeStatus::Running);
-            Json(serde_json::json!({ "ok": true })).into_response()
+            Ok(())
         }
         Err(e) => {
             let _ = state
                 .bridge_status
                 .send(BridgeStatus::Error { message: e.to_string() });
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": format!("Failed to start: {}", e) })),
-            )
-                .into_response()
+            Err(e)
         }
     }
 }
 
+/// Signal the running bridge to shut down and drop its handles from state.
+/// Returns false if no bridge was running.
+pub(crate) async fn stop_bridge(state: &AppState) -> bool {
+    let Some(tx) = state.bridge_shutdown.write().await.take() else {
+        return false;
+    };
+    let _ = tx.send(()).await;
+    *state.leap_req_tx.write().await = None;
+    *state.leap_events.write().await = None;
+    state.savant_req_tx.write().await.clear();
+    state.savant_events.write().await.clear();
+    *state.bridge_maps.write().await = None;
+    *state.bridge_started_at.write().await = None;
+    true
+}
+
 pub async fn bridge_stop(State(state): State<Arc<AppState>>) -> Response {
-    let shutdown = state.bridge_shutdown.write().await.take();
-    match shutdown {
-        Some(tx) => {
-            let _ = tx.send(()).await;
-            *state.leap_req_tx.write().await = None;
-            *state.savant_req_tx.write().await = None;
-            *state.bridge_started_at.write().await = None;
-            Json(serde_json::json!({ "ok": true })).into_response()
-        }
-        None => (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({ "error": "Bridge not running" })),
-        )
-            .into_response(),
+    if stop_bridge(&state).await {
+        Json(serde_json::json!({ "ok": true })).into_response()
+    } else {
+        api_error(ErrorCode::BridgeNotRunning, "Bridge not running")
     }
 }
 
-pub async fn bridge_restart(State(state): State<Arc<AppState>>) -> Response {
+pub async fn bridge_restart(State(state): State<Arc<AppState>>, query: Query<BridgeStartQuery>) -> Response {
     // Stop first
-    {
-        let shutdown = state.bridge_shutdown.write().await.take();
-        if let Some(tx) = shutdown {
-            let _ = tx.send(()).await;
-            *state.leap_req_tx.write().await = None;
-            *state.savant_req_tx.write().await = None;
-            *state.bridge_started_at.write().await = None;
-        }
-    }
+    stop_bridge(&state).await;
 
     // Small delay to let things clean up
     tokio::time::sleep(std::time::Duration::from_millis(500)).await;
 
     // Start again
-    bridge_start(State(state)).await
+    bridge_start(State(state), query).await
 }
 
-pub async fn export_xml(State(state): State<Arc<AppState>>) -> Response {
+pub async fn export_xml(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+) -> Response {
     let config = state.config.read().await;
     match config.as_ref() {
         Some(cfg) => {
-            let xml = super::xml_export::generate_xml(&cfg.zones, &cfg.savant_zones);
+            let xml = super::xml_export::generate_xml(
+                &cfg.web.project_name,
+                cfg.web.area_separator,
+                &cfg.zones,
+                &cfg.savant_zones,
+                &cfg.keypads,
+            );
+            let etag = super::xml_export::etag(&xml);
+            let generation = (
+                axum::http::HeaderName::from_static("x-config-generation"),
+                state.config_generation().to_string(),
+            );
+
+            // Controllers poll this file; let them skip the body when unchanged
+            let not_modified = headers
+                .get(axum::http::header::IF_NONE_MATCH)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| etag_matches(v, &etag));
+            if not_modified {
+                return (
+                    StatusCode::NOT_MODIFIED,
+                    [(axum::http::header::ETAG, etag), generation],
+                )
+                    .into_response();
+            }
+
             (
                 [(
                     axum::http::header::CONTENT_TYPE,
-                    "application/xml; charset=utf-8",
+                    "application/xml; charset=utf-8".to_string(),
                 ),
                 (
                     axum::http::header::CONTENT_DISPOSITION,
-                    "attachment; filename=\"DbXmlInfo.xml\"",
-                )],
+                    "attachment; filename=\"DbXmlInfo.xml\"".to_string(),
+                ),
+                (axum::http::header::ETAG, etag),
+                generation],
                 xml,
             )
                 .into_response()
         }
-        None => (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({ "error": "No config loaded" })),
+        None => api_error(ErrorCode::NoConfig, "No config loaded"),
+    }
+}
+
+/// `If-None-Match` is `*` or a comma-separated list of (possibly weak) tags.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|t| t.trim())
+        .any(|t| t == "*" || t.trim_start_matches("W/") == etag)
+}
+
+/// Zone mappings as a spreadsheet-friendly CSV (see `csv_export`).
+pub async fn export_csv(State(state): State<Arc<AppState>>) -> Response {
+    let config = state.config.read().await;
+    match config.as_ref() {
+        Some(cfg) => (
+            [
+                (axum::http::header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (axum::http::header::CONTENT_DISPOSITION, "attachment; filename=\"zones.csv\""),
+            ],
+            super::csv_export::generate_csv(cfg),
         )
             .into_response(),
+        None => api_error(ErrorCode::NoConfig, "No config loaded"),
+    }
+}
+
+/// Update zone ra2_ids and names from an edited `export_csv` file. Nothing
+/// is saved unless every row applies; `errors` lists each rejected row.
+pub async fn import_csv(State(state): State<Arc<AppState>>, body: String) -> Response {
+    let rejected = |errors: Vec<String>| {
+        api_error_with(
+            ErrorCode::InvalidConfig,
+            format!("CSV rejected: {}", errors.join("; ")),
+            serde_json::json!({ "errors": errors }),
+        )
+    };
+    let rows = match super::csv_export::parse_csv(&body) {
+        Ok(rows) => rows,
+        Err(errors) => return rejected(errors),
+    };
+    let Some(mut config) = state.config.read().await.clone() else {
+        return api_error(ErrorCode::NoConfig, "No config loaded");
+    };
+    let changed = match super::csv_export::apply_csv(&mut config, &rows) {
+        Ok(changed) => changed,
+        Err(errors) => return rejected(errors),
+    };
+
+    let config_path = state.config_path.read().await.clone();
+    if let Err(e) = config.save(&config_path).await {
+        return api_error(ErrorCode::SaveFailed, format!("Failed to save: {}", e));
+    }
+    info!("Imported zone CSV: {} of {} zones changed", changed, rows.len());
+    let restart_required = match install_config(&state, config).await {
+        Ok(installed) => installed.restart_required,
+        Err(e) => return api_error(ErrorCode::InvalidConfig, format!("Invalid config: {}", e)),
+    };
+    Json(serde_json::json!({ "ok": true, "changed": changed, "restart_required": restart_required }))
+        .into_response()
+}
+
+/// Machine-readable zone dump for home-automation platforms. Every zone has
+/// the same keys; fields that don't apply to its backend are null.
+pub async fn export_json(State(state): State<Arc<AppState>>) -> Response {
+    use super::xml_export::{guess_output_type, leap_area, output_type, savant_area};
+
+    let config = state.config.read().await;
+    let cfg = match config.as_ref() {
+        Some(c) => c,
+        None => {
+            return api_error(ErrorCode::NoConfig, "No config loaded");
+        }
+    };
+    let levels = state.zone_levels.snapshot().await;
+
+    let mut zones: Vec<serde_json::Value> = Vec::new();
+    for z in &cfg.zones {
+        let (area, output_name) = leap_area(z, cfg.web.area_separator);
+        zones.push(serde_json::json!({
+            "ra2_id": z.ra2_id,
+            "name": z.name,
+            "area": area,
+            "output_name": output_name,
+            "output_type": output_type(z.control_type.as_deref(), z.category.as_deref(), &output_name),
+            "backend": "leap",
+            "leap_href": z.leap_href,
+            "address": null,
+            "load_offset": null,
+            "level": levels.get(&z.ra2_id).copied(),
+        }));
+    }
+    for z in &cfg.savant_zones {
+        let (area, output_name) = savant_area(z, cfg.web.area_separator);
+        zones.push(serde_json::json!({
+            "ra2_id": z.ra2_id,
+            "name": z.name,
+            "area": area,
+            "output_name": output_name,
+            "output_type": guess_output_type(&output_name),
+            "backend": "savant",
+            "leap_href": null,
+            "address": z.address,
+            "load_offset": z.load_offset,
+            "level": levels.get(&z.ra2_id).copied(),
+        }));
     }
+
+    Json(serde_json::json!({
+        "schema_version": 1,
+        "project_name": cfg.web.project_name,
+        "zones": zones,
+    }))
+    .into_response()
 }
 
 // --- Savant discovery endpoints ---
@@ -499,6 +1550,9 @@ pub struct SavantDiscoverRequest {
     port: u16,
     #[serde(default = "default_savant_start_id")]
     start_id: u32,
+    /// Keep ra2_ids and names of loads that are already configured.
+    #[serde(default)]
+    merge: bool,
 }
 
 fn default_savant_port() -> u16 {
@@ -509,6 +1563,37 @@ fn default_savant_start_id() -> u32 {
     200
 }
 
+/// The buffered web UI log as a text file for support tickets, headed by
+/// the active site and the time it was taken.
+pub async fn download_logs(State(state): State<Arc<AppState>>) -> Response {
+    let site = state.active_site.read().await.clone();
+    let mut text = format!(
+        "# ra-bridge log, site: {}, downloaded {}\n",
+        site.as_deref().unwrap_or("-"),
+        crate::web_log_layer::timestamp()
+    );
+    for line in state.log_history.lines() {
+        text.push_str(&line);
+        text.push('\n');
+    }
+
+    let filename = match &site {
+        Some(site) => format!("ra-bridge-{}-log.txt", site),
+        None => "ra-bridge-log.txt".to_string(),
+    };
+    (
+        [
+            (axum::http::header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        text,
+    )
+        .into_response()
+}
+
 pub async fn savant_discover(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<SavantDiscoverRequest>,
@@ -518,11 +1603,7 @@ pub async fn savant_discover(
         let current = state.savant_discovery_status.borrow().clone();
         match current {
             SavantDiscoveryStatus::Connecting | SavantDiscoveryStatus::Enumerating { .. } => {
-                return (
-                    StatusCode::CONFLICT,
-                    Json(serde_json::json!({ "error": "Savant discovery already in progress" })),
-                )
-                    .into_response();
+                return api_error(ErrorCode::Busy, "Savant discovery already in progress");
             }
             _ => {}
         }
@@ -530,38 +1611,92 @@ pub async fn savant_discover(
 
     let host = payload.host.clone();
     let port = payload.port;
-    let start_id = payload.start_id;
     let status_tx = state.savant_discovery_status.clone();
-    let config_store = state.config.clone();
     let config_path = state.config_path.read().await.clone();
+    let (ids, device, max_zones, separator) = match state.config.read().await.as_ref() {
+        Some(c) => (
+            c.savant_ids(payload.start_id),
+            c.savant.first().map(|s| s.device.clone()).unwrap_or_default(),
+            c.savant.first().map_or_else(crate::config::default_max_discovered_zones, |s| s.max_discovered_zones),
+            c.web.area_separator,
+        ),
+        None => (
+            crate::config::IdRange::starting_at(payload.start_id),
+            Default::default(),
+            crate::config::default_max_discovered_zones(),
+            crate::config::default_area_separator(),
+        ),
+    };
 
     tokio::spawn(async move {
         let _ = status_tx.send(SavantDiscoveryStatus::Connecting);
 
-        match crate::savant_discover::discover_zones(&host, port, start_id).await {
-            Ok((savant_config, savant_zones)) => {
-                let zone_count = savant_zones.len();
+        match crate::savant_discover::discover_zones(&host, port, ids, &device, max_zones, separator).await {
+            Ok((savant_config, discovered)) => {
                 let _ = status_tx.send(SavantDiscoveryStatus::Enumerating {
-                    device_count: zone_count,
+                    device_count: discovered.len(),
                 });
-                let mut config = config_store
+                let mut config = state
+                    .config
                     .read()
                     .await
                     .clone()
                     .unwrap_or_else(|| crate::config::Config {
-                        processor: crate::config::ProcessorConfig {
-                            host: String::new(),
-                            leap_port: 8081,
-                        },
+                        version: crate::config::CONFIG_VERSION,
+                        level_curve: Default::default(),
+                        processor: crate::config::ProcessorConfig::new(String::new(), 8081),
                         telnet: Default::default(),
                         web: Default::default(),
                         zones: vec![],
-                        savant: None,
+                        savant: vec![],
                         savant_zones: vec![],
+                        buttons: vec![],
+                        keypads: vec![],
+                        bridge: Default::default(),
+                        notifications: Default::default(),
+                        logging: Default::default(),
+                        mqtt: None,
+                        id_ranges: None,
                     });
 
-                config.savant = Some(savant_config);
+                // The UI manages the first [[savant]] host; zones on any
+                // other host are kept as they are
+                let (own, others): (Vec<_>, Vec<_>) = config.savant_zones.iter().cloned().partition(|z| z.host == 0);
+                let (savant_zones, added, missing) = if payload.merge {
+                    let reserved = config
+                        .zones
+                        .iter()
+                        .map(|z| z.ra2_id)
+                        .chain(others.iter().map(|z| z.ra2_id))
+                        .collect();
+                    match crate::savant_discover::merge_zones(&own, &discovered, &reserved, ids) {
+                        Ok(merged) => (merged.zones, Some(merged.added), Some(merged.missing)),
+                        Err(e) => {
+                            let _ = status_tx.send(SavantDiscoveryStatus::Failed { message: e.to_string() });
+                            return;
+                        }
+                    }
+                } else {
+                    (discovered, None, None)
+                };
+                let zone_count = savant_zones.len();
+                // A merge keeps the rest of [savant], e.g. load key settings
+                let savant_config = match config.savant.first() {
+                    Some(old) if payload.merge => crate::config::SavantConfig {
+                        host: savant_config.host,
+                        port: savant_config.port,
+                        ..old.clone()
+                    },
+                    _ => savant_config,
+                };
+                match config.savant.first_mut() {
+                    Some(first) => *first = savant_config,
+                    None => config.savant.push(savant_config),
+                }
+                let attributes = crate::zone_attributes::ZoneAttributeStore::capture(&config);
                 config.savant_zones = savant_zones;
+                config.savant_zones.extend(others);
+                attributes.restore(&mut config);
 
                 // Validate no ID conflicts
                 if let Err(e) = config.validate() {
@@ -570,15 +1705,28 @@ pub async fn savant_discover(
                 }
 
                 // Save
-                if let Err(e) = config.save(&config_path) {
+                if let Err(e) = config.save(&config_path).await {
                     let _ = status_tx.send(SavantDiscoveryStatus::Failed {
                         message: e.to_string(),
                     });
                     return;
                 }
 
-                *config_store.write().await = Some(config);
-                let _ = status_tx.send(SavantDiscoveryStatus::Complete { zone_count });
+                // Rebuilds the running bridge's maps, so new zones can be
+                // controlled straight away
+                let restart_required = match install_config(&state, config).await {
+                    Ok(installed) => installed.restart_required,
+                    Err(message) => {
+                        let _ = status_tx.send(SavantDiscoveryStatus::Failed { message });
+                        return;
+                    }
+                };
+                let _ = status_tx.send(SavantDiscoveryStatus::Complete {
+                    zone_count,
+                    added,
+                    missing,
+                    restart_required,
+                });
                 info!(
                     "Savant discovery complete: {} zones saved to config",
                     zone_count
@@ -596,46 +1744,65 @@ pub async fn savant_discover(
     (StatusCode::ACCEPTED, Json(serde_json::json!({ "ok": true }))).into_response()
 }
 
+/// Remove the first `[[savant]]` host, the one the UI manages, and its
+/// zones. Zones on later hosts move down an index with their host.
 pub async fn savant_remove(State(state): State<Arc<AppState>>) -> Response {
     let config_path = state.config_path.read().await.clone();
-    let mut config_guard = state.config.write().await;
-
-    match config_guard.as_mut() {
-        Some(config) => {
-            config.savant = None;
-            config.savant_zones.clear();
+    let Some(mut config) = state.config.read().await.clone() else {
+        return api_error(ErrorCode::NoConfig, "No config loaded");
+    };
 
-            if let Err(e) = config.save(&config_path) {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({ "error": format!("Failed to save: {}", e) })),
-                )
-                    .into_response();
-            }
+    if !config.savant.is_empty() {
+        config.savant.remove(0);
+    }
+    let (removed, kept): (Vec<_>, Vec<_>) = config.savant_zones.into_iter().partition(|z| z.host == 0);
+    config.savant_zones = kept;
+    for z in &mut config.savant_zones {
+        z.host -= 1;
+    }
 
-            Json(serde_json::json!({ "ok": true })).into_response()
-        }
-        None => (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({ "error": "No config loaded" })),
-        )
-            .into_response(),
+    if let Err(e) = config.validate() {
+        return api_error(ErrorCode::InvalidConfig, format!("Invalid config: {}", e));
     }
+    if let Err(e) = config.save(&config_path).await {
+        return api_error(ErrorCode::SaveFailed, format!("Failed to save: {}", e));
+    }
+    let restart_required = match install_config(&state, config).await {
+        Ok(installed) => installed.restart_required,
+        Err(e) => return api_error(ErrorCode::InvalidConfig, format!("Invalid config: {}", e)),
+    };
+    for z in &removed {
+        state.zone_levels.remove(z.ra2_id).await;
+    }
+
+    Json(serde_json::json!({ "ok": true, "restart_required": restart_required })).into_response()
 }
 
 // --- Site management endpoints (dev mode) ---
 
-pub async fn list_sites(State(state): State<Arc<AppState>>) -> Response {
+#[derive(Deserialize)]
+pub struct ListSitesQuery {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+pub async fn list_sites(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListSitesQuery>,
+) -> Response {
     if !state.dev_mode {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Not in dev mode" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::NotFound, "Not in dev mode");
     }
     let sites = state.list_sites().await;
+    let total = sites.len();
+    let page: Vec<_> = sites
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
     let active = state.active_site.read().await.clone();
-    Json(serde_json::json!({ "sites": sites, "active": active })).into_response()
+    Json(serde_json::json!({ "sites": page, "total": total, "active": active })).into_response()
 }
 
 #[derive(Deserialize)]
@@ -648,112 +1815,151 @@ pub async fn create_site(
     Json(payload): Json<CreateSiteRequest>,
 ) -> Response {
     if !state.dev_mode {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Not in dev mode" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::NotFound, "Not in dev mode");
     }
 
     let name = payload.name.trim().to_string();
     if name.is_empty() || name.contains('/') || name.contains('\\') || name.starts_with('.') {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({ "error": "Invalid site name" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::InvalidRequest, "Invalid site name");
     }
 
     let sites_dir = state.sites_dir.as_ref().unwrap();
     let site_path = sites_dir.join(&name);
 
     if site_path.exists() {
-        return (
-            StatusCode::CONFLICT,
-            Json(serde_json::json!({ "error": "Site already exists" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::AlreadyExists, "Site already exists");
     }
 
     if let Err(e) = std::fs::create_dir_all(site_path.join("certs")) {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": format!("Failed to create site: {}", e) })),
-        )
-            .into_response();
+        return api_error(ErrorCode::Internal, format!("Failed to create site: {}", e));
     }
 
     info!("Created site: {}", name);
     Json(serde_json::json!({ "ok": true, "name": name })).into_response()
 }
 
+/// Forget the processor: stop the bridge, delete the pairing certs and clear
+/// `[processor] host` so the site shows as unpaired and can be paired again,
+/// e.g. after swapping processors. Zones are kept until the next discovery.
+pub async fn unpair(State(state): State<Arc<AppState>>) -> Response {
+    {
+        let current = state.pairing_status.borrow().clone();
+        match current {
+            PairingStatus::Idle | PairingStatus::Complete { .. } | PairingStatus::Failed { .. } => {}
+            _ => {
+                return api_error(ErrorCode::Busy, "Pairing already in progress");
+            }
+        }
+    }
+    let Ok(_site_guard) = state.site_lock.clone().try_lock_owned() else {
+        return site_busy();
+    };
+
+    let bridge_stopped = stop_bridge(&state).await;
+    if bridge_stopped {
+        let _ = state.bridge_status.send(BridgeStatus::Stopped);
+    }
+
+    let certs_dir = state.certs_dir.read().await.clone();
+    let removed = match crate::leap_pairing::unpair(&certs_dir).await {
+        Ok(removed) => removed,
+        Err(e) => {
+            return api_error(ErrorCode::Internal, format!("{:#}", e));
+        }
+    };
+
+    let config_path = state.config_path.read().await.clone();
+    if let Some(config) = state.config.write().await.as_mut() {
+        config.processor.host.clear();
+        if let Err(e) = config.save(&config_path).await {
+            return api_error(ErrorCode::SaveFailed, format!("Failed to save: {}", e));
+        }
+    }
+    state.bump_config_generation();
+    let _ = state.pairing_status.send(PairingStatus::Idle);
+
+    info!("Unpaired: removed {} cert file(s) from {}", removed.len(), certs_dir.display());
+    let removed: Vec<String> = removed.iter().map(|p| p.display().to_string()).collect();
+    Json(serde_json::json!({ "ok": true, "removed": removed, "bridge_stopped": bridge_stopped }))
+        .into_response()
+}
+
+/// Pairing and site activate/delete/rename all hold `site_lock`.
+fn site_busy() -> Response {
+    api_error(ErrorCode::Busy, "Pairing or another site operation is in progress")
+}
+
 pub async fn delete_site(
     State(state): State<Arc<AppState>>,
     Path(name): Path<String>,
 ) -> Response {
     if !state.dev_mode {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Not in dev mode" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::NotFound, "Not in dev mode");
     }
 
+    let Ok(_site_guard) = state.site_lock.try_lock() else {
+        return site_busy();
+    };
+
     // Can't delete active site
     let active = state.active_site.read().await.clone();
     if active.as_deref() == Some(&name) {
-        return (
-            StatusCode::CONFLICT,
-            Json(serde_json::json!({ "error": "Cannot delete active site — switch to another first" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::Busy, "Cannot delete active site — switch to another first");
     }
 
     let sites_dir = state.sites_dir.as_ref().unwrap();
     let site_path = sites_dir.join(&name);
 
     if !site_path.exists() {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Site not found" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::NotFound, "Site not found");
     }
 
     if let Err(e) = std::fs::remove_dir_all(&site_path) {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": format!("Failed to delete: {}", e) })),
-        )
-            .into_response();
+        return api_error(ErrorCode::Internal, format!("Failed to delete: {}", e));
     }
 
     info!("Deleted site: {}", name);
     Json(serde_json::json!({ "ok": true })).into_response()
 }
 
+/// What activating a site would do (auto-start or "needs pairing"),
+/// without stopping the bridge or touching the site.
+pub async fn preview_site(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Response {
+    if !state.dev_mode {
+        return api_error(ErrorCode::NotFound, "Not in dev mode");
+    }
+
+    if name.contains('/') || name.contains('\\') || name.starts_with('.') {
+        return api_error(ErrorCode::InvalidRequest, "Invalid site name");
+    }
+
+    match state.preview_site(&name).await {
+        Some(preview) => Json(preview).into_response(),
+        None => api_error(ErrorCode::NotFound, "Site not found"),
+    }
+}
+
 pub async fn activate_site(
     State(state): State<Arc<AppState>>,
     Path(name): Path<String>,
 ) -> Response {
     if !state.dev_mode {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Not in dev mode" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::NotFound, "Not in dev mode");
     }
 
+    let Ok(_site_guard) = state.site_lock.try_lock() else {
+        return site_busy();
+    };
+
     match crate::serve::activate_site(&state, &name).await {
         Ok(()) => {
             info!("Activated site: {}", name);
             Json(serde_json::json!({ "ok": true, "active": name })).into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        )
-            .into_response(),
+        Err(e) => api_error(ErrorCode::Internal, e.to_string()),
     }
 }
 
@@ -768,20 +1974,16 @@ pub async fn rename_site(
     Json(payload): Json<RenameSiteRequest>,
 ) -> Response {
     if !state.dev_mode {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Not in dev mode" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::NotFound, "Not in dev mode");
     }
 
+    let Ok(_site_guard) = state.site_lock.try_lock() else {
+        return site_busy();
+    };
+
     let new_name = payload.new_name.trim().to_string();
     if new_name.is_empty() || new_name.contains('/') || new_name.contains('\\') || new_name.starts_with('.') {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({ "error": "Invalid site name" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::InvalidRequest, "Invalid site name");
     }
 
     let sites_dir = state.sites_dir.as_ref().unwrap();
@@ -789,27 +1991,15 @@ pub async fn rename_site(
     let new_path = sites_dir.join(&new_name);
 
     if !old_path.exists() {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "error": "Site not found" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::NotFound, "Site not found");
     }
 
     if new_path.exists() {
-        return (
-            StatusCode::CONFLICT,
-            Json(serde_json::json!({ "error": "A site with that name already exists" })),
-        )
-            .into_response();
+        return api_error(ErrorCode::AlreadyExists, "A site with that name already exists");
     }
 
     if let Err(e) = std::fs::rename(&old_path, &new_path) {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": format!("Failed to rename: {}", e) })),
-        )
-            .into_response();
+        return api_error(ErrorCode::Internal, format!("Failed to rename: {}", e));
     }
 
     // If this was the active site, update paths
@@ -824,3 +2014,146 @@ pub async fn rename_site(
     info!("Renamed site: {} → {}", name, new_name);
     Json(serde_json::json!({ "ok": true, "new_name": new_name })).into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn toggle_sends_even_when_skipping_redundant_sets() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-routes-{}", uuid::Uuid::new_v4()));
+        let state = AppState::new(
+            dir.join("config.toml"),
+            dir.join("certs"),
+            None,
+            false,
+            tokio::sync::broadcast::channel(1).0,
+            crate::web_log_layer::LogHistory::default(),
+        );
+        let config: crate::config::Config = toml::from_str(
+            "[processor]\nhost = \"10.0.0.5\"\n[bridge]\nskip_redundant_sets = true\n\
+             [[zones]]\nra2_id = 1\nleap_href = \"/zone/5\"",
+        )
+        .unwrap();
+        *state.config.write().await = Some(config);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        *state.leap_req_tx.write().await = Some(tx);
+
+        for expected in [100.0, 0.0] {
+            let response = toggle_zone(State(state.clone()), Path(1)).await;
+            assert!(response.status().is_success());
+            let req = rx.try_recv().expect("toggle was skipped as redundant");
+            assert_eq!(req.header.url, "/zone/5/commandprocessor");
+            assert_eq!(state.zone_levels.get_level(1).await, Some(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn put_config_refuses_an_invalid_config() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-routes-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = AppState::new(
+            dir.join("config.toml"),
+            dir.join("certs"),
+            None,
+            false,
+            tokio::sync::broadcast::channel(1).0,
+            crate::web_log_layer::LogHistory::default(),
+        );
+        let duplicated = "[processor]\nhost = \"10.0.0.5\"\n\
+                          [[zones]]\nra2_id = 1\nleap_href = \"/zone/5\"\n\
+                          [[zones]]\nra2_id = 1\nleap_href = \"/zone/6\"";
+        let update = ConfigUpdate { config: duplicated.to_string() };
+
+        let response = put_config(State(state.clone()), Json(update)).await;
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        assert!(!dir.join("config.toml").exists());
+        assert!(state.config.read().await.is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn delete_zone_removes_one_zone_or_404s() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-routes-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = AppState::new(
+            dir.join("config.toml"),
+            dir.join("certs"),
+            None,
+            false,
+            tokio::sync::broadcast::channel(1).0,
+            crate::web_log_layer::LogHistory::default(),
+        );
+        let config: crate::config::Config = toml::from_str(
+            "[processor]\nhost = \"10.0.0.5\"\n\
+             [[zones]]\nra2_id = 1\nleap_href = \"/zone/5\"\nname = \"Kitchen\"\n\
+             [[zones]]\nra2_id = 2\nleap_href = \"/zone/6\"\nname = \"Hall\"",
+        )
+        .unwrap();
+        let (maps_tx, maps_rx) = tokio::sync::watch::channel(Arc::new(crate::bridge::BridgeMaps::from_config(&config)));
+        *state.bridge_maps.write().await = Some(maps_tx);
+        *state.config.write().await = Some(config);
+        state.zone_levels.set_level(1, 50.0).await;
+
+        let response = delete_zone(State(state.clone()), Path(9)).await;
+        assert_eq!(response.status(), axum::http::StatusCode::NOT_FOUND);
+        assert!(!dir.join("config.toml").exists());
+
+        let response = delete_zone(State(state.clone()), Path(1)).await;
+        assert!(response.status().is_success());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["name"], "Kitchen");
+        assert_eq!(body["restart_required"], false);
+        assert!(!maps_rx.borrow().known_ids.contains(&1));
+        assert_eq!(state.zone_levels.get_level(1).await, None);
+        let saved = crate::config::Config::read(&dir.join("config.toml")).unwrap();
+        assert_eq!(saved.zones.iter().map(|z| z.ra2_id).collect::<Vec<_>>(), vec![2]);
+
+        // The last LEAP zone takes the LEAP backend with it
+        let response = delete_zone(State(state.clone()), Path(2)).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["restart_required"], true);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn savant_remove_keeps_other_hosts() {
+        let dir = std::env::temp_dir().join(format!("ra-bridge-routes-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = AppState::new(
+            dir.join("config.toml"),
+            dir.join("certs"),
+            None,
+            false,
+            tokio::sync::broadcast::channel(1).0,
+            crate::web_log_layer::LogHistory::default(),
+        );
+        let config: crate::config::Config = toml::from_str(
+            "[processor]\nhost = \"10.0.0.5\"\n\
+             [[savant]]\nhost = \"10.0.0.7\"\n[[savant]]\nhost = \"10.0.0.8\"\n\
+             [[savant_zones]]\nra2_id = 200\naddress = \"001\"\nload_offset = 0\n\
+             [[savant_zones]]\nra2_id = 201\naddress = \"002\"\nload_offset = 0\nhost = 1",
+        )
+        .unwrap();
+        let (maps_tx, maps_rx) = tokio::sync::watch::channel(Arc::new(crate::bridge::BridgeMaps::from_config(&config)));
+        *state.bridge_maps.write().await = Some(maps_tx);
+        *state.config.write().await = Some(config);
+
+        assert!(savant_remove(State(state.clone())).await.status().is_success());
+        // The running bridge routes by the new host indices straight away
+        assert!(!maps_rx.borrow().known_ids.contains(&200));
+        assert_eq!(maps_rx.borrow().savant.host(201), Some(0));
+        let config = state.config.read().await.clone().unwrap();
+        assert_eq!(config.savant.len(), 1);
+        assert_eq!(config.savant[0].host, "10.0.0.8");
+        assert_eq!(config.savant_zones.len(), 1);
+        assert_eq!((config.savant_zones[0].ra2_id, config.savant_zones[0].host), (201, 0));
+        assert!(config.validate().is_ok());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -20,15 +20,38 @@ pub async fn pair_status_stream(
         }
 
         // Then stream changes
-        loop {
-            match rx.changed().await {
-                Ok(()) => {
-                    let status = rx.borrow().clone();
-                    if let Ok(json) = serde_json::to_string(&status) {
-                        yield Ok(Event::default().data(json));
-                    }
-                }
-                Err(_) => break,
+        while rx.changed().await.is_ok() {
+            let status = rx.borrow().clone();
+            if let Ok(json) = serde_json::to_string(&status) {
+                yield Ok(Event::default().data(json));
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(std::time::Duration::from_secs(15))
+            .text("ping"),
+    )
+}
+
+pub async fn bridge_status_stream(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut rx = state.bridge_status.subscribe();
+
+    let stream = async_stream::stream! {
+        // Send current status immediately
+        let current = rx.borrow().clone();
+        if let Ok(json) = serde_json::to_string(&current) {
+            yield Ok(Event::default().data(json));
+        }
+
+        // Then stream changes
+        while rx.changed().await.is_ok() {
+            let status = rx.borrow().clone();
+            if let Ok(json) = serde_json::to_string(&status) {
+                yield Ok(Event::default().data(json));
             }
         }
     };
@@ -53,15 +76,10 @@ pub async fn savant_discovery_status_stream(
         }
 
         // Then stream changes
-        loop {
-            match rx.changed().await {
-                Ok(()) => {
-                    let status = rx.borrow().clone();
-                    if let Ok(json) = serde_json::to_string(&status) {
-                        yield Ok(Event::default().data(json));
-                    }
-                }
-                Err(_) => break,
+        while rx.changed().await.is_ok() {
+            let status = rx.borrow().clone();
+            if let Ok(json) = serde_json::to_string(&status) {
+                yield Ok(Event::default().data(json));
             }
         }
     };
@@ -110,7 +128,7 @@ pub async fn zone_events_stream(
         loop {
             tokio::time::sleep(std::time::Duration::from_millis(250)).await;
 
-            let current = zone_levels.read().await.clone();
+            let current = zone_levels.snapshot().await;
             let mut changes = Vec::new();
 
             for (id, level) in &current {
@@ -1,10 +1,12 @@
+use std::collections::HashSet;
+
 use indexmap::IndexMap;
 use uuid::Uuid;
 
-use crate::config::{SavantZoneMapping, ZoneMapping};
+use crate::config::{split_area_name, SavantZoneMapping, VirtualKeypad, ZoneMapping};
 
 /// Guess RA2 OutputType from zone name.
-fn guess_output_type(name: &str) -> &'static str {
+pub fn guess_output_type(name: &str) -> &'static str {
     let lower = name.to_lowercase();
     if lower.contains("fan") {
         return "NON_DIM";
@@ -18,24 +20,110 @@ fn guess_output_type(name: &str) -> &'static str {
     "INC"
 }
 
+/// RA2 OutputType for a zone: from its discovered LEAP `ControlType` when
+/// known, then its `Category`, otherwise guessed from the name.
+pub fn output_type(control_type: Option<&str>, category: Option<&str>, name: &str) -> &'static str {
+    match control_type.map(|c| c.to_ascii_lowercase()).as_deref() {
+        Some("dimmed") => "INC",
+        Some("switched") => "NON_DIM",
+        Some("shade") => "SYSTEM_SHADE",
+        Some("cco") => "CCO_MAINTAINED",
+        Some("fanspeed") => "CEILING_FAN_TYPE",
+        _ => category
+            .and_then(category_output_type)
+            .unwrap_or_else(|| guess_output_type(name)),
+    }
+}
+
+/// RA2 OutputType implied by a LEAP `Category.Type`, ignoring case and spaces.
+fn category_output_type(category: &str) -> Option<&'static str> {
+    let category: String = category
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    match category.as_str() {
+        "light" => Some("INC"),
+        "fan" | "exhaustfan" | "heater" | "receptacle" => Some("NON_DIM"),
+        "shade" | "blind" | "drape" | "drapery" => Some("SYSTEM_SHADE"),
+        _ => None,
+    }
+}
+
+/// Area IntegrationIDs are hashed from the area name into this range so an
+/// area keeps its ID across exports regardless of which other areas exist.
+const AREA_ID_BASE: u32 = 10_000;
+const AREA_ID_SPAN: u32 = 50_000;
+
+/// Stable IntegrationID for an area name (FNV-1a, so it doesn't depend on the
+/// std hasher, which may change between Rust releases).
+fn stable_area_id(name: &str) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for b in name.as_bytes() {
+        hash ^= *b as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    AREA_ID_BASE + hash % AREA_ID_SPAN
+}
+
+/// 128-bit FNV-1a, used to derive UUIDs and the ETag from config values.
+fn fnv1a_128(bytes: &[u8]) -> u128 {
+    let mut hash: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+    for b in bytes {
+        hash ^= *b as u128;
+        hash = hash.wrapping_mul(0x0000_0000_0100_0000_0000_0000_0000_013b);
+    }
+    hash
+}
+
+/// Name-based UUID so the same config always exports the same IDs;
+/// controllers treat a changed GUID or output UUID as a changed project.
+fn stable_uuid(name: &str) -> Uuid {
+    uuid::Builder::from_custom_bytes(fnv1a_128(name.as_bytes()).to_be_bytes()).into_uuid()
+}
+
+/// Strong ETag for an export. The XML is a pure function of the config, so
+/// hashing it is a hash of everything in the config that affects the export.
+pub fn etag(xml: &str) -> String {
+    format!("\"{:032x}\"", fnv1a_128(xml.as_bytes()))
+}
+
 struct AreaOutput {
     ra2_id: u32,
     output_name: String,
+    output_type: &'static str,
+}
+
+/// (area, output) names for a LEAP zone, split at `separator` first.
+pub fn leap_area(z: &ZoneMapping, separator: char) -> (String, String) {
+    split_area_name(&z.name, separator).unwrap_or_else(|| ("Ungrouped".to_string(), z.name.clone()))
+}
+
+/// (area, output) names for a Savant zone — falls back to its room.
+pub fn savant_area(z: &SavantZoneMapping, separator: char) -> (String, String) {
+    if let Some(split) = split_area_name(&z.name, separator) {
+        split
+    } else if !z.room.is_empty() {
+        (z.room.clone(), z.name.clone())
+    } else {
+        ("Savant".to_string(), z.name.clone())
+    }
 }
 
-/// Generate Lutron RadioRA 2 DbXmlInfo.xml from zone mappings (LEAP + Savant).
-pub fn generate_xml(zones: &[ZoneMapping], savant_zones: &[SavantZoneMapping]) -> String {
-    // Group zones by area (text before " ─ ")
+/// Generate Lutron RadioRA 2 DbXmlInfo.xml from zone mappings (LEAP + Savant)
+/// and virtual keypads, grouped into areas at `separator` first.
+pub fn generate_xml(
+    project_name: &str,
+    separator: char,
+    zones: &[ZoneMapping],
+    savant_zones: &[SavantZoneMapping],
+    keypads: &[VirtualKeypad],
+) -> String {
+    // Group zones by area (text before " ─ ", or another separator)
     let mut areas: IndexMap<String, Vec<AreaOutput>> = IndexMap::new();
     for z in zones {
-        let (area_name, output_name) = if let Some(pos) = z.name.find(" \u{2500} ") {
-            (
-                z.name[..pos].trim().to_string(),
-                z.name[pos + " \u{2500} ".len()..].trim().to_string(),
-            )
-        } else {
-            ("Ungrouped".to_string(), z.name.clone())
-        };
+        let (area_name, output_name) = leap_area(z, separator);
+        let output_type = output_type(z.control_type.as_deref(), z.category.as_deref(), &output_name);
 
         areas
             .entry(area_name)
@@ -43,21 +131,14 @@ pub fn generate_xml(zones: &[ZoneMapping], savant_zones: &[SavantZoneMapping]) -
             .push(AreaOutput {
                 ra2_id: z.ra2_id,
                 output_name,
+                output_type,
             });
     }
 
     // Add Savant zones — use room as area name
     for z in savant_zones {
-        let (area_name, output_name) = if let Some(pos) = z.name.find(" \u{2500} ") {
-            (
-                z.name[..pos].trim().to_string(),
-                z.name[pos + " \u{2500} ".len()..].trim().to_string(),
-            )
-        } else if !z.room.is_empty() {
-            (z.room.clone(), z.name.clone())
-        } else {
-            ("Savant".to_string(), z.name.clone())
-        };
+        let (area_name, output_name) = savant_area(z, separator);
+        let output_type = guess_output_type(&output_name);
 
         areas
             .entry(area_name)
@@ -65,18 +146,34 @@ pub fn generate_xml(zones: &[ZoneMapping], savant_zones: &[SavantZoneMapping]) -
             .push(AreaOutput {
                 ra2_id: z.ra2_id,
                 output_name,
+                output_type,
             });
     }
 
+    // Keypads go in their area's DeviceGroups, creating the area if needed
+    let mut devices: IndexMap<String, Vec<(String, &VirtualKeypad)>> = IndexMap::new();
+    for k in keypads {
+        let (area_name, device_name) =
+            split_area_name(&k.name, separator).unwrap_or_else(|| ("Ungrouped".to_string(), k.name.clone()));
+        areas.entry(area_name.clone()).or_default();
+        devices.entry(area_name).or_default().push((device_name, k));
+    }
+
     let mut xml = String::new();
     xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" ?>\n");
     xml.push_str("<Project>\n");
 
     // GUID
-    xml.push_str(&format!("  <GUID>{}</GUID>\n", Uuid::new_v4()));
+    xml.push_str(&format!(
+        "  <GUID>{}</GUID>\n",
+        stable_uuid(&format!("project:{}", project_name))
+    ));
 
     // ProjectName
-    xml.push_str("  <ProjectName ProjectName=\"RA3 Bridge Import\" />\n");
+    xml.push_str(&format!(
+        "  <ProjectName ProjectName=\"{}\" />\n",
+        xml_escape(project_name)
+    ));
 
     // Empty required elements
     xml.push_str("  <Timeclocks />\n");
@@ -88,29 +185,43 @@ pub fn generate_xml(zones: &[ZoneMapping], savant_zones: &[SavantZoneMapping]) -
     xml.push_str("    <Area Name=\"Root\" IntegrationID=\"1\" IsLeaf=\"false\">\n");
     xml.push_str("      <Areas>\n");
 
-    let mut area_id: u32 = 100;
+    // Root area and every output ID are taken; probe past them on collision
+    let mut used_ids: HashSet<u32> = areas
+        .values()
+        .flatten()
+        .map(|o| o.ra2_id)
+        .chain(keypads.iter().map(|k| k.ra2_id))
+        .chain(std::iter::once(1))
+        .collect();
+
     for (area_name, outputs) in &areas {
+        let mut area_id = stable_area_id(area_name);
+        while !used_ids.insert(area_id) {
+            area_id = AREA_ID_BASE + (area_id - AREA_ID_BASE + 1) % AREA_ID_SPAN;
+        }
+
         xml.push_str(&format!(
             "        <Area Name=\"{}\" IntegrationID=\"{}\" IsLeaf=\"true\">\n",
             xml_escape(area_name),
             area_id,
         ));
-        area_id += 1;
 
         xml.push_str("          <Outputs>\n");
         for out in outputs {
-            let output_type = guess_output_type(&out.output_name);
             xml.push_str(&format!(
                 "            <Output Name=\"{}\" IntegrationID=\"{}\" OutputType=\"{}\" Wattage=\"0\" UUID=\"{}\" />\n",
                 xml_escape(&out.output_name),
                 out.ra2_id,
-                output_type,
-                Uuid::new_v4(),
+                out.output_type,
+                stable_uuid(&format!("output:{}:{}:{}", out.ra2_id, area_name, out.output_name)),
             ));
         }
         xml.push_str("          </Outputs>\n");
 
-        xml.push_str("          <DeviceGroups />\n");
+        match devices.get(area_name) {
+            Some(keypads) => push_device_group(&mut xml, area_name, keypads),
+            None => xml.push_str("          <DeviceGroups />\n"),
+        }
         xml.push_str("          <Scenes />\n");
         xml.push_str("          <ShadeGroups />\n");
         xml.push_str("        </Area>\n");
@@ -124,6 +235,44 @@ pub fn generate_xml(zones: &[ZoneMapping], savant_zones: &[SavantZoneMapping]) -
     xml
 }
 
+/// An area's keypads as one `DeviceGroup` of seeTouch keypads with a
+/// single-action button per configured component.
+fn push_device_group(xml: &mut String, area_name: &str, keypads: &[(String, &VirtualKeypad)]) {
+    xml.push_str("          <DeviceGroups>\n");
+    xml.push_str(&format!(
+        "            <DeviceGroup Name=\"{}\">\n",
+        xml_escape(area_name)
+    ));
+    xml.push_str("              <Devices>\n");
+    for (device_name, keypad) in keypads {
+        xml.push_str(&format!(
+            "                <Device Name=\"{}\" IntegrationID=\"{}\" DeviceType=\"SEETOUCH_KEYPAD\" UUID=\"{}\">\n",
+            xml_escape(device_name),
+            keypad.ra2_id,
+            stable_uuid(&format!("keypad:{}:{}:{}", keypad.ra2_id, area_name, device_name)),
+        ));
+        xml.push_str("                  <Components>\n");
+        for button in &keypad.buttons {
+            xml.push_str(&format!(
+                "                    <Component ComponentNumber=\"{}\" ComponentType=\"BUTTON\">\n",
+                button.component
+            ));
+            xml.push_str(&format!(
+                "                      <Button Name=\"Button {}\" Engraving=\"{}\" ButtonType=\"SingleAction\" UUID=\"{}\" />\n",
+                button.component,
+                xml_escape(&button.name),
+                stable_uuid(&format!("button:{}:{}", keypad.ra2_id, button.component)),
+            ));
+            xml.push_str("                    </Component>\n");
+        }
+        xml.push_str("                  </Components>\n");
+        xml.push_str("                </Device>\n");
+    }
+    xml.push_str("              </Devices>\n");
+    xml.push_str("            </DeviceGroup>\n");
+    xml.push_str("          </DeviceGroups>\n");
+}
+
 fn xml_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -145,6 +294,20 @@ mod tests {
         assert_eq!(guess_output_type("SCONCE"), "INC");
     }
 
+    #[test]
+    fn test_output_type_prefers_control_type() {
+        assert_eq!(output_type(Some("Switched"), None, "KITCHEN LIGHTS"), "NON_DIM");
+        assert_eq!(output_type(Some("Shade"), None, "BLINDS"), "SYSTEM_SHADE");
+        assert_eq!(output_type(Some("Dimmed"), Some("ExhaustFan"), "CEILING FAN"), "INC");
+        assert_eq!(output_type(None, None, "CEILING FAN"), "NON_DIM");
+
+        // Category settles what the name would guess wrong
+        assert_eq!(output_type(None, Some("Light"), "HOT TUB LIGHT"), "INC");
+        assert_eq!(output_type(Some("WhiteTune"), Some("Exhaust Fan"), "BATH"), "NON_DIM");
+        assert_eq!(output_type(None, Some("Drapery"), "LIVING ROOM"), "SYSTEM_SHADE");
+        assert_eq!(output_type(None, Some("Other"), "POOL HEATER"), "NON_DIM");
+    }
+
     #[test]
     fn test_generate_xml_basic() {
         let zones = vec![
@@ -152,20 +315,30 @@ mod tests {
                 ra2_id: 1,
                 leap_href: "/zone/100".to_string(),
                 name: "KITCHEN \u{2500} CEILING LIGHTS".to_string(),
+                ..Default::default()
             },
             ZoneMapping {
                 ra2_id: 2,
                 leap_href: "/zone/101".to_string(),
                 name: "KITCHEN \u{2500} EXHAUST FAN".to_string(),
+                ..Default::default()
             },
             ZoneMapping {
                 ra2_id: 3,
                 leap_href: "/zone/200".to_string(),
                 name: "BEDROOM \u{2500} SCONCE".to_string(),
+                ..Default::default()
+            },
+            ZoneMapping {
+                ra2_id: 4,
+                leap_href: "/zone/201".to_string(),
+                name: "BATH \u{2500} HOT TUB LIGHT".to_string(),
+                category: Some("Light".to_string()),
+                ..Default::default()
             },
         ];
 
-        let xml = generate_xml(&zones, &[]);
+        let xml = generate_xml("RA3 Bridge Import", '\u{2500}', &zones, &[], &[]);
 
         // Check structure
         assert!(xml.contains("<?xml version=\"1.0\""));
@@ -189,6 +362,9 @@ mod tests {
 
         assert!(xml.contains("Name=\"SCONCE\""));
         assert!(xml.contains("IntegrationID=\"3\""));
+
+        // The name alone would say NON_DIM
+        assert!(xml.contains("Name=\"HOT TUB LIGHT\" IntegrationID=\"4\" OutputType=\"INC\""));
     }
 
     #[test]
@@ -197,9 +373,10 @@ mod tests {
             ra2_id: 1,
             leap_href: "/zone/100".to_string(),
             name: "STANDALONE LIGHT".to_string(),
+            ..Default::default()
         }];
 
-        let xml = generate_xml(&zones, &[]);
+        let xml = generate_xml("RA3 Bridge Import", '\u{2500}', &zones, &[], &[]);
         assert!(xml.contains("Name=\"Ungrouped\""));
         assert!(xml.contains("Name=\"STANDALONE LIGHT\""));
     }
@@ -210,6 +387,7 @@ mod tests {
             ra2_id: 1,
             leap_href: "/zone/100".to_string(),
             name: "KITCHEN \u{2500} CEILING LIGHTS".to_string(),
+            ..Default::default()
         }];
         let savant_zones = vec![SavantZoneMapping {
             ra2_id: 200,
@@ -217,19 +395,175 @@ mod tests {
             load_offset: 0,
             name: "LIVING ROOM \u{2500} MAIN LIGHT".to_string(),
             room: "LIVING ROOM".to_string(),
+            ..Default::default()
         }];
 
-        let xml = generate_xml(&zones, &savant_zones);
+        let xml = generate_xml("RA3 Bridge Import", '\u{2500}', &zones, &savant_zones, &[]);
         assert!(xml.contains("Name=\"KITCHEN\""));
         assert!(xml.contains("Name=\"LIVING ROOM\""));
         assert!(xml.contains("IntegrationID=\"200\""));
         assert!(xml.contains("Name=\"MAIN LIGHT\""));
     }
 
+    #[test]
+    fn test_generate_xml_with_keypads() {
+        use crate::config::VirtualButton;
+
+        let zones = vec![ZoneMapping {
+            ra2_id: 1,
+            leap_href: "/zone/100".to_string(),
+            name: "KITCHEN \u{2500} CEILING LIGHTS".to_string(),
+            ..Default::default()
+        }];
+        let button = |component, name: &str| VirtualButton {
+            component,
+            name: name.to_string(),
+        };
+        let keypads = vec![
+            VirtualKeypad {
+                ra2_id: 300,
+                name: "KITCHEN \u{2500} SCENES".to_string(),
+                buttons: vec![button(1, "Dinner"), button(2, "Cook & Clean")],
+            },
+            VirtualKeypad {
+                ra2_id: 301,
+                name: "HALL \u{2500} ENTRY".to_string(),
+                buttons: vec![button(1, "All Off")],
+            },
+        ];
+
+        let xml = generate_xml("RA3 Bridge Import", '\u{2500}', &zones, &[], &keypads);
+        assert_eq!(xml.matches("Name=\"KITCHEN\" IntegrationID").count(), 1);
+        assert!(xml.contains("<Area Name=\"HALL\""));
+        assert_eq!(xml.matches("<DeviceGroup Name=").count(), 2);
+        assert!(xml.contains("<Device Name=\"SCENES\" IntegrationID=\"300\" DeviceType=\"SEETOUCH_KEYPAD\""));
+        assert!(xml.contains("<Component ComponentNumber=\"2\" ComponentType=\"BUTTON\">"));
+        assert!(xml.contains("Engraving=\"Cook &amp; Clean\""));
+        assert!(xml.contains("<Device Name=\"ENTRY\" IntegrationID=\"301\""));
+
+        // The kitchen keypad sits in the kitchen area, after its outputs
+        let kitchen = xml.find("Name=\"KITCHEN\"").unwrap();
+        let hall = xml.find("Name=\"HALL\"").unwrap();
+        let scenes = xml.find("Name=\"SCENES\"").unwrap();
+        assert!(kitchen < scenes && scenes < hall);
+        assert!(!generate_xml("RA3 Bridge Import", '\u{2500}', &zones, &[], &[]).contains("<DeviceGroup "));
+    }
+
+    #[test]
+    fn test_generate_xml_project_name() {
+        let zones = vec![ZoneMapping {
+            ra2_id: 1,
+            leap_href: "/zone/100".to_string(),
+            name: "KITCHEN \u{2500} CEILING LIGHTS".to_string(),
+            ..Default::default()
+        }];
+
+        let xml = generate_xml("Smith & Co Residence", '\u{2500}', &zones, &[], &[]);
+        assert!(xml.contains("ProjectName=\"Smith &amp; Co Residence\""));
+        assert!(!xml.contains("RA3 Bridge Import"));
+    }
+
+    fn area_ids(xml: &str) -> Vec<String> {
+        xml.lines()
+            .filter(|l| l.trim_start().starts_with("<Area "))
+            .map(|l| l.trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_area_ids_stable_across_exports() {
+        let zones = vec![
+            ZoneMapping {
+                ra2_id: 1,
+                leap_href: "/zone/100".to_string(),
+                name: "KITCHEN \u{2500} CEILING LIGHTS".to_string(),
+                ..Default::default()
+            },
+            ZoneMapping {
+                ra2_id: 2,
+                leap_href: "/zone/200".to_string(),
+                name: "BEDROOM \u{2500} SCONCE".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let first = generate_xml("RA3 Bridge Import", '\u{2500}', &zones, &[], &[]);
+        let second = generate_xml("RA3 Bridge Import", '\u{2500}', &zones, &[], &[]);
+        assert_eq!(area_ids(&first), area_ids(&second));
+
+        // Adding an area in front must not shift the existing areas' IDs
+        let mut grown = vec![ZoneMapping {
+            ra2_id: 3,
+            leap_href: "/zone/300".to_string(),
+            name: "ATTIC \u{2500} BULB".to_string(),
+            ..Default::default()
+        }];
+        grown.extend(zones.clone());
+        let third = generate_xml("RA3 Bridge Import", '\u{2500}', &grown, &[], &[]);
+        let third_ids = area_ids(&third);
+        for line in area_ids(&first) {
+            assert!(third_ids.contains(&line), "missing {}", line);
+        }
+    }
+
+    #[test]
+    fn test_savant_area_fallbacks() {
+        let mut z = SavantZoneMapping {
+            ra2_id: 200,
+            address: "001".to_string(),
+            load_offset: 0,
+            name: "PATIO".to_string(),
+            room: "OUTSIDE".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(savant_area(&z, '\u{2500}'), ("OUTSIDE".to_string(), "PATIO".to_string()));
+        z.room.clear();
+        assert_eq!(savant_area(&z, '\u{2500}'), ("Savant".to_string(), "PATIO".to_string()));
+    }
+
+    #[test]
+    fn test_area_separators() {
+        let area = |name: &str| {
+            leap_area(&ZoneMapping {
+                ra2_id: 1,
+                leap_href: "/zone/1".to_string(),
+                name: name.to_string(),
+                ..Default::default()
+            }, '\u{2500}')
+        };
+        let kitchen = ("KITCHEN".to_string(), "CEILING LIGHTS".to_string());
+        assert_eq!(area("KITCHEN \u{2500} CEILING LIGHTS"), kitchen);
+        assert_eq!(area("KITCHEN - CEILING LIGHTS"), kitchen);
+        assert_eq!(area("KITCHEN / CEILING LIGHTS"), kitchen);
+        // The first separator wins; unspaced ones are part of the name
+        assert_eq!(area("DEN - WALL-WASHER / LEFT").1, "WALL-WASHER / LEFT");
+        assert_eq!(area("WALL-WASHER").0, "Ungrouped");
+        // The configured separator wins wherever it is
+        assert_eq!(area("DEN - WALL \u{2500} LEFT"), ("DEN - WALL".to_string(), "LEFT".to_string()));
+    }
+
     #[test]
     fn test_xml_escape() {
         assert_eq!(xml_escape("A & B"), "A &amp; B");
         assert_eq!(xml_escape("<tag>"), "&lt;tag&gt;");
         assert_eq!(xml_escape("\"quoted\""), "&quot;quoted&quot;");
     }
+
+    #[test]
+    fn test_identical_config_exports_identical_xml() {
+        let zones = vec![ZoneMapping {
+            ra2_id: 1,
+            leap_href: "/zone/100".to_string(),
+            name: "KITCHEN \u{2500} CEILING LIGHTS".to_string(),
+            ..Default::default()
+        }];
+
+        let first = generate_xml("RA3 Bridge Import", '\u{2500}', &zones, &[], &[]);
+        let second = generate_xml("RA3 Bridge Import", '\u{2500}', &zones, &[], &[]);
+        assert_eq!(first, second);
+        assert_eq!(etag(&first), etag(&second));
+
+        let renamed = generate_xml("Other Project", '\u{2500}', &zones, &[], &[]);
+        assert_ne!(etag(&first), etag(&renamed));
+    }
 }
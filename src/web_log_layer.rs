@@ -1,17 +1,54 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
 use tokio::sync::broadcast;
 use tracing::field::{Field, Visit};
 use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::{FormatTime, SystemTime};
 use tracing_subscriber::layer::Context;
 use tracing_subscriber::Layer;
 
-/// A tracing layer that broadcasts formatted log lines to web UI clients.
+/// Lines kept for `GET /api/logs/download`.
+pub const LOG_HISTORY_LINES: usize = 2000;
+
+/// The most recent web UI log lines, timestamped, for downloading after the
+/// fact. Cheap to clone; every clone shares the same buffer.
+#[derive(Clone, Default)]
+pub struct LogHistory(Arc<Mutex<VecDeque<String>>>);
+
+impl LogHistory {
+    fn push(&self, line: String) {
+        let mut lines = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if lines.len() == LOG_HISTORY_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// Buffered lines, oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+}
+
+/// Current UTC time in RFC 3339, as the console log shows it.
+pub fn timestamp() -> String {
+    let mut s = String::new();
+    let _ = SystemTime.format_time(&mut Writer::new(&mut s));
+    s
+}
+
+/// A tracing layer that broadcasts formatted log lines to web UI clients and
+/// keeps them in a `LogHistory`.
 pub struct WebLogLayer {
     tx: broadcast::Sender<String>,
+    history: LogHistory,
 }
 
 impl WebLogLayer {
-    pub fn new(tx: broadcast::Sender<String>) -> Self {
-        Self { tx }
+    pub fn new(tx: broadcast::Sender<String>, history: LogHistory) -> Self {
+        Self { tx, history }
     }
 }
 
@@ -58,8 +95,26 @@ impl<S: Subscriber> Layer<S> for WebLogLayer {
 
         // Clean, pipeline-focused format for web UI
         let line = visitor.message.trim_matches('"').to_string();
+        self.history.push(format!("{} {:>5} {}", timestamp(), level, line));
 
         // Best-effort send — if no subscribers, just drop
         let _ = self.tx.send(line);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_keeps_the_newest_lines() {
+        let history = LogHistory::default();
+        for i in 0..LOG_HISTORY_LINES + 5 {
+            history.clone().push(i.to_string());
+        }
+        let lines = history.lines();
+        assert_eq!(lines.len(), LOG_HISTORY_LINES);
+        assert_eq!(lines[0], "5");
+        assert_eq!(lines.last().unwrap(), &(LOG_HISTORY_LINES + 4).to_string());
+    }
+}
@@ -0,0 +1,256 @@
+//! Per-zone settings the user chose: a custom name, a level curve and
+//! min/max limits. Discovery can rebuild the zone list from scratch, so these
+//! are captured by each zone's stable identity (LEAP href, or Savant host +
+//! address + load offset) before the list is replaced and put back after.
+//! Only what the user set is kept, so a zone renamed on the processor, or a
+//! new `area_separator`, still shows up on rediscovery.
+
+use std::collections::HashMap;
+
+use crate::config::{split_area_name, Config, SavantZoneMapping, ZoneMapping};
+use crate::level_curve::LevelCurve;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ZoneAttributes {
+    /// The zone's name, if it isn't the one discovery gave it.
+    pub name: Option<String>,
+    /// The zone predates `discovered_name`, so `name` may just be an older
+    /// discovered name.
+    pub name_unverified: bool,
+    pub level_curve: Option<LevelCurve>,
+    pub min_level: Option<f64>,
+    pub max_level: Option<f64>,
+}
+
+impl ZoneAttributes {
+    fn new(
+        name: &str,
+        discovered_name: Option<&str>,
+        level_curve: &Option<LevelCurve>,
+        min_level: Option<f64>,
+        max_level: Option<f64>,
+    ) -> Self {
+        Self {
+            name: (!name.is_empty() && discovered_name != Some(name)).then(|| name.to_string()),
+            name_unverified: discovered_name.is_none(),
+            level_curve: level_curve.clone(),
+            min_level,
+            max_level,
+        }
+    }
+}
+
+/// What makes a zone the same zone across edits and rediscovery, whatever
+/// its ra2_id or name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ZoneIdentity {
+    Leap(String),
+    Savant { host: usize, address: String, load_offset: usize },
+}
+
+impl ZoneIdentity {
+    pub fn leap(zone: &ZoneMapping) -> Self {
+        ZoneIdentity::Leap(zone.leap_href.clone())
+    }
+
+    pub fn savant(zone: &SavantZoneMapping) -> Self {
+        // Savant addresses are hex and compared case-insensitively elsewhere
+        ZoneIdentity::Savant {
+            host: zone.host,
+            address: zone.address.to_ascii_uppercase(),
+            load_offset: zone.load_offset,
+        }
+    }
+}
+
+/// The attributes of every zone in a config, by identity.
+#[derive(Debug, Default)]
+pub struct ZoneAttributeStore {
+    zones: HashMap<ZoneIdentity, ZoneAttributes>,
+    /// `web.area_separator` of the captured config.
+    separator: char,
+}
+
+impl ZoneAttributeStore {
+    pub fn capture(config: &Config) -> Self {
+        let leap = config.zones.iter().map(|z| {
+            let attrs = ZoneAttributes::new(
+                &z.name,
+                z.discovered_name.as_deref(),
+                &z.level_curve,
+                z.min_level,
+                z.max_level,
+            );
+            (ZoneIdentity::leap(z), attrs)
+        });
+        let savant = config.savant_zones.iter().map(|z| {
+            let attrs = ZoneAttributes::new(
+                &z.name,
+                z.discovered_name.as_deref(),
+                &z.level_curve,
+                z.min_level,
+                z.max_level,
+            );
+            (ZoneIdentity::savant(z), attrs)
+        });
+        Self {
+            zones: leap.chain(savant).collect(),
+            separator: config.web.area_separator,
+        }
+    }
+
+    /// Reapply captured attributes to the zones in `config` that have them,
+    /// returning how many zones changed. Settings that were never set leave
+    /// what's there.
+    pub fn restore(&self, config: &mut Config) -> usize {
+        let mut changed = 0;
+        for z in &mut config.zones {
+            if let Some(attrs) = self.zones.get(&ZoneIdentity::leap(z)) {
+                changed += usize::from(attrs.apply(self.separator, &mut z.name, &mut z.level_curve, &mut z.min_level, &mut z.max_level));
+            }
+        }
+        for z in &mut config.savant_zones {
+            if let Some(attrs) = self.zones.get(&ZoneIdentity::savant(z)) {
+                changed += usize::from(attrs.apply(self.separator, &mut z.name, &mut z.level_curve, &mut z.min_level, &mut z.max_level));
+            }
+        }
+        changed
+    }
+}
+
+impl ZoneAttributes {
+    fn apply(
+        &self,
+        separator: char,
+        name: &mut String,
+        level_curve: &mut Option<LevelCurve>,
+        min_level: &mut Option<f64>,
+        max_level: &mut Option<f64>,
+    ) -> bool {
+        let before = (name.clone(), level_curve.clone(), *min_level, *max_level);
+        if let Some(custom) = &self.name {
+            // Without a recorded discovered name, a name that only differs
+            // in its area separator is taken to be the old discovered one
+            let old_discovered = self.name_unverified
+                && split_area_name(custom, separator).is_some_and(|parts| split_area_name(name, separator) == Some(parts));
+            if !old_discovered {
+                name.clone_from(custom);
+            }
+        }
+        if self.level_curve.is_some() {
+            level_curve.clone_from(&self.level_curve);
+        }
+        if self.min_level.is_some() {
+            *min_level = self.min_level;
+        }
+        if self.max_level.is_some() {
+            *max_level = self.max_level;
+        }
+        before != (name.clone(), level_curve.clone(), *min_level, *max_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_follow_identity_not_position_or_id() {
+        let mut config: Config = toml::from_str("[processor]\nhost = \"10.0.0.5\"").unwrap();
+        config.zones = vec![ZoneMapping {
+            ra2_id: 10,
+            leap_href: "/zone/5".to_string(),
+            name: "Pendants".to_string(),
+            level_curve: Some(LevelCurve::Square),
+            min_level: Some(20.0),
+            ..Default::default()
+        }];
+        config.savant_zones = vec![SavantZoneMapping {
+            ra2_id: 200,
+            host: 1,
+            address: "01a".to_string(),
+            load_offset: 3,
+            name: "Den Lamp".to_string(),
+            max_level: Some(80.0),
+            ..Default::default()
+        }];
+        let store = ZoneAttributeStore::capture(&config);
+
+        // A fresh discovery renumbers and renames everything
+        config.zones = vec![
+            ZoneMapping {
+                ra2_id: 1,
+                leap_href: "/zone/7".to_string(),
+                name: "Hall".to_string(),
+                ..Default::default()
+            },
+            ZoneMapping {
+                ra2_id: 2,
+                leap_href: "/zone/5".to_string(),
+                name: "Kitchen Main".to_string(),
+                ..Default::default()
+            },
+        ];
+        config.savant_zones = vec![
+            SavantZoneMapping {
+                ra2_id: 201,
+                address: "01A".to_string(),
+                load_offset: 3,
+                name: "Load 3".to_string(),
+                ..Default::default()
+            },
+            SavantZoneMapping {
+                ra2_id: 202,
+                host: 1,
+                address: "01A".to_string(),
+                load_offset: 3,
+                name: "Load 3".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(store.restore(&mut config), 2);
+        let kitchen = &config.zones[1];
+        assert_eq!((kitchen.ra2_id, kitchen.name.as_str()), (2, "Pendants"));
+        assert_eq!(kitchen.level_curve, Some(LevelCurve::Square));
+        assert_eq!(kitchen.min_level, Some(20.0));
+        assert_eq!(config.zones[0].name, "Hall");
+        // Only the load on the same host gets them back
+        assert_eq!(config.savant_zones[0].name, "Load 3");
+        assert_eq!(config.savant_zones[1].name, "Den Lamp");
+        assert_eq!(config.savant_zones[1].max_level, Some(80.0));
+        assert_eq!(store.restore(&mut config), 0);
+    }
+
+    #[test]
+    fn only_names_the_user_chose_are_kept() {
+        let zone = |href: &str, name: &str, discovered: Option<&str>| ZoneMapping {
+            leap_href: href.to_string(),
+            name: name.to_string(),
+            discovered_name: discovered.map(str::to_string),
+            ..Default::default()
+        };
+        let mut config: Config = toml::from_str("[processor]\nhost = \"10.0.0.5\"").unwrap();
+        config.zones = vec![
+            zone("/zone/1", "Kitchen ─ Main", Some("Kitchen ─ Main")),
+            zone("/zone/2", "Island pendants", Some("Kitchen ─ Island")),
+            zone("/zone/3", "Hall ─ Cans", None),
+            zone("/zone/4", "Porch light", None),
+        ];
+        let store = ZoneAttributeStore::capture(&config);
+
+        // Renamed on the processor, and discovered with a new separator
+        config.zones = vec![
+            zone("/zone/1", "Kitchen / Downlights", Some("Kitchen / Downlights")),
+            zone("/zone/2", "Kitchen / Island", Some("Kitchen / Island")),
+            zone("/zone/3", "Hall / Cans", Some("Hall / Cans")),
+            zone("/zone/4", "Porch / Sconces", Some("Porch / Sconces")),
+        ];
+        config.zones[0].level_curve = Some(LevelCurve::Square);
+        store.restore(&mut config);
+        let names: Vec<&str> = config.zones.iter().map(|z| z.name.as_str()).collect();
+        assert_eq!(names, ["Kitchen / Downlights", "Island pendants", "Hall / Cans", "Porch light"]);
+        // Nothing captured, so nothing is cleared
+        assert_eq!(config.zones[0].level_curve, Some(LevelCurve::Square));
+    }
+}